@@ -0,0 +1,150 @@
+//! Time and peak heap memory of extracting representative CARs with the buffer and seek readers,
+//! and with `read_single_file_auto` choosing between them. Run with
+//! `cargo bench --bench backends`, the guidance of `read_single_file_auto` follows the results.
+//! Times are measured by criterion, the peak memory of a single read is printed before the
+//! timings of each reader.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::generate::{pseudo_random, CarSpec, Order};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use futures::{executor::block_on, io::Cursor};
+use rs_car_ipfs::single_file::{
+    read_single_file_auto, read_single_file_buffer_with_options,
+    read_single_file_seek_with_options, ReadSingleFileOptions, DEFAULT_REORDER_WINDOW,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Tracks the heap memory in use and its peak since the last reset
+struct PeakAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(current, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAllocator = PeakAllocator;
+
+/// Memory budget of `read_single_file_auto` in the benchmarks
+const AUTO_BUDGET: usize = 4 << 20;
+
+/// Read of a case into the output
+type Read<'a> = &'a dyn Fn(&mut Cursor<Vec<u8>>);
+
+struct Case {
+    name: &'static str,
+    car: Vec<u8>,
+    len: usize,
+}
+
+fn cases() -> Vec<Case> {
+    let spec = |name, len, chunk_size, fanout, order| {
+        let car = CarSpec {
+            data: pseudo_random(len, 1),
+            chunk_size,
+            fanout,
+            order,
+            ..Default::default()
+        }
+        .build()
+        .car;
+        Case { name, car, len }
+    };
+    vec![
+        spec("small file", 64 << 10, 256 << 10, 174, Order::Dfs),
+        spec("large file", 64 << 20, 256 << 10, 174, Order::Dfs),
+        spec("deep DAG", 4 << 20, 1 << 10, 2, Order::Dfs),
+        spec("wide DAG", 16 << 20, 4 << 10, 4096, Order::Dfs),
+        spec("reordered stream", 16 << 20, 256 << 10, 8, Order::Bfs),
+    ]
+}
+
+/// Peak heap memory of one read on top of the memory in use before it, which includes the CAR
+/// and the output
+fn peak_memory(read: impl FnOnce()) -> usize {
+    let before = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(before, Ordering::Relaxed);
+    read();
+    PEAK.load(Ordering::Relaxed) - before
+}
+
+fn backends(c: &mut Criterion) {
+    // The seek reader holds leaves arriving before their turn within `reorder_window`
+    let options = ReadSingleFileOptions {
+        reorder_window: Some(DEFAULT_REORDER_WINDOW),
+        ..Default::default()
+    };
+    for case in cases() {
+        let mut group = c.benchmark_group(case.name);
+        group.throughput(Throughput::Bytes(case.len as u64));
+        group.sample_size(10);
+        let readers: [(&str, Read); 3] = [
+            ("buffer", &|out| {
+                block_on(read_single_file_buffer_with_options(
+                    &mut case.car.as_slice(),
+                    out,
+                    None,
+                    &options,
+                ))
+                .unwrap();
+            }),
+            ("seek", &|out| {
+                block_on(read_single_file_seek_with_options(
+                    &mut case.car.as_slice(),
+                    out,
+                    None,
+                    &options,
+                ))
+                .unwrap();
+            }),
+            ("auto", &|out| {
+                block_on(read_single_file_auto(
+                    &mut case.car.as_slice(),
+                    out,
+                    None,
+                    AUTO_BUDGET,
+                    &options,
+                ))
+                .unwrap();
+            }),
+        ];
+        for (reader, read) in readers {
+            let mut out = Cursor::new(Vec::with_capacity(case.len));
+            let peak = peak_memory(|| read(&mut out));
+            assert_eq!(out.get_ref().len(), case.len);
+            println!(
+                "{}/{}: {} KiB file, peak memory {} KiB",
+                case.name,
+                reader,
+                case.len >> 10,
+                peak >> 10
+            );
+            group.bench_function(reader, |b| {
+                b.iter(|| {
+                    out.set_position(0);
+                    read(&mut out);
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, backends);
+criterion_main!(benches);
@@ -0,0 +1,77 @@
+//! Time of extracting a CAR into a file through a `memmap2::MmapMut` with `MappedOutput`, against
+//! writing the file with `async_std::fs::File`. Run with
+//! `cargo bench --bench mapped --features memmap2,async-std`, both outputs are sized to the file
+//! up front and neither is synced, so only the write path is compared.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::generate::{pseudo_random, CarSpec};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use futures::executor::block_on;
+use rs_car_ipfs::single_file::{read_single_file_seek_file, MappedOutput, ReadSingleFileOptions};
+use std::{fs, path::Path};
+
+/// Create the file at `path` extended to `len` bytes
+fn sized_file(path: &Path, len: usize) -> fs::File {
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+        .unwrap();
+    file.set_len(len as u64).unwrap();
+    file
+}
+
+fn mapped(c: &mut Criterion) {
+    let len = 64 << 20;
+    let car = CarSpec {
+        data: pseudo_random(len, 1),
+        chunk_size: 256 << 10,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    let path = std::env::temp_dir().join(format!("rs-car-ipfs-bench-{}", std::process::id()));
+    let options = ReadSingleFileOptions {
+        preallocate: true,
+        ..Default::default()
+    };
+
+    let mut group = c.benchmark_group("mapped");
+    group.throughput(Throughput::Bytes(len as u64));
+    group.sample_size(10);
+    group.bench_function("file", |b| {
+        b.iter(|| {
+            let mut out = async_std::fs::File::from(sized_file(&path, len));
+            block_on(read_single_file_seek_file(
+                &mut car.as_slice(),
+                &mut out,
+                None,
+                &options,
+            ))
+            .unwrap();
+        })
+    });
+    group.bench_function("mmap", |b| {
+        b.iter(|| {
+            let file = sized_file(&path, len);
+            let map = unsafe { memmap2::MmapMut::map_mut(&file) }.unwrap();
+            let mut out = MappedOutput::new(map);
+            block_on(read_single_file_seek_file(
+                &mut car.as_slice(),
+                &mut out,
+                None,
+                &options,
+            ))
+            .unwrap();
+        })
+    });
+    group.finish();
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, mapped);
+criterion_main!(benches);
@@ -0,0 +1,55 @@
+//! Time of extracting a CAR read from a file with the seek reader, for several
+//! `read_buffer_capacity`. Run with `cargo bench --bench read_buffer`, larger buffers pay off once
+//! reads of the input are the bottleneck, e.g. on fast disks.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::generate::{pseudo_random, CarSpec};
+use futures::{executor::block_on, io::Cursor};
+use rs_car_ipfs::single_file::{
+    read_single_file_seek_with_options, ReadSingleFileOptions, DEFAULT_READ_BUFFER_CAPACITY,
+};
+use std::time::Instant;
+
+const ITERATIONS: u32 = 8;
+
+fn main() {
+    let len = 64 << 20;
+    let car = CarSpec {
+        data: pseudo_random(len, 1),
+        chunk_size: 256 << 10,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    let path = std::env::temp_dir().join(format!("rs-car-ipfs-bench-{}.car", std::process::id()));
+    std::fs::write(&path, &car).unwrap();
+
+    println!("{:>12} {:>12} {:>12}", "capacity", "time", "throughput");
+    for capacity in [0, 8 << 10, DEFAULT_READ_BUFFER_CAPACITY, 1 << 20, 8 << 20] {
+        let options = ReadSingleFileOptions {
+            read_buffer_capacity: Some(capacity),
+            ..Default::default()
+        };
+        let mut out = Cursor::new(Vec::with_capacity(len));
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            out.set_position(0);
+            block_on(async {
+                let mut input = async_std::fs::File::open(&path).await.unwrap();
+                read_single_file_seek_with_options(&mut input, &mut out, None, &options)
+                    .await
+                    .unwrap();
+            });
+        }
+        let time = start.elapsed() / ITERATIONS;
+        println!(
+            "{:>8} KiB {:>9.2} ms {:>7.0} MiB/s",
+            capacity >> 10,
+            time.as_secs_f64() * 1000.0,
+            (len >> 20) as f64 / time.as_secs_f64()
+        );
+    }
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,19 @@
+//! Arbitrary bytes as a CAR stream: the buffer reader must return, with a file or an error, and
+//! never panic. Run with `cargo +nightly fuzz run read_single_file_buffer`, seeded with the
+//! fixtures of `tests/data`.
+
+#![no_main]
+
+use futures::{executor::block_on, io::Cursor};
+use libfuzzer_sys::fuzz_target;
+use rs_car_ipfs::single_file::read_single_file_buffer;
+
+fuzz_target!(|data: &[u8]| {
+    let mut out = Cursor::new(Vec::new());
+    let _ = block_on(read_single_file_buffer(
+        &mut Cursor::new(data),
+        &mut out,
+        None,
+        None,
+    ));
+});
@@ -0,0 +1,522 @@
+//! Chunk a file into a UnixFS DAG the same way `ipfs add` does, to compute the CID a file would
+//! have and check that an extracted file reproduces its root CID.
+//!
+//! Supports kubo's balanced layout, where leaves are grouped bottom-up into nodes of at most
+//! `max_links` children, with fixed size chunks or the chunks of a custom [`Chunker`].
+
+use futures::{AsyncRead, AsyncReadExt};
+use multihash::{Code, MultihashDigest};
+use quick_protobuf::{MessageWrite, Writer};
+use rs_car::Cid;
+use std::{borrow::Cow, collections::HashMap, fmt, sync::Arc};
+
+use crate::{
+    pb::{FlatUnixFs, PBLink, UnixFs, UnixFsType},
+    single_file::ReadSingleFileError,
+};
+
+pub(crate) const DAG_PB: u64 = 0x70;
+const RAW: u64 = 0x55;
+
+/// Chunker and layout settings matching a known `ipfs add` configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkerProfile {
+    /// kubo defaults: 256 KiB fixed chunks, balanced layout with 174 links per node, dag-pb
+    /// leaves and CIDv0. Same as `ipfs add`
+    KuboDefaultV0,
+    /// kubo defaults with CIDv1 and raw leaves. Same as `ipfs add --cid-version=1`
+    KuboDefaultV1,
+}
+
+impl ChunkerProfile {
+    pub(crate) fn file_adder(&self) -> FileAdder {
+        PackOptions::from(*self).file_adder()
+    }
+}
+
+/// Chunker and layout settings to pack files, the flags of `ipfs add` they match are named on each
+/// field. Defaults to [`ChunkerProfile::KuboDefaultV0`]
+///
+/// ```
+/// use rs_car_ipfs::adder::PackOptions;
+///
+/// // ipfs add --chunker=size-1048576 --raw-leaves --cid-version=1
+/// let options = PackOptions {
+///     chunk_size: 1048576,
+///     raw_leaves: true,
+///     cid_version: 1,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Length of the fixed size chunks, `--chunker=size-<chunk_size>`. Only used if
+    /// [`chunker`](Self::chunker) is `None`
+    pub chunk_size: usize,
+    /// Encode leaves as raw blocks instead of dag-pb UnixFS nodes, `--raw-leaves`
+    pub raw_leaves: bool,
+    /// Version of the CIDs of dag-pb nodes, 0 or 1, `--cid-version`. Raw leaves always have a
+    /// CIDv1
+    pub cid_version: u64,
+    /// Max count of links of a node, kubo's `helpers.DefaultLinksPerBlock` is 174
+    pub max_links: usize,
+    /// Split files with a custom chunker, e.g. a content-defined one, instead of fixed size chunks
+    pub chunker: Option<ChunkerHook>,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        ChunkerProfile::KuboDefaultV0.into()
+    }
+}
+
+impl From<ChunkerProfile> for PackOptions {
+    fn from(profile: ChunkerProfile) -> Self {
+        let (raw_leaves, cid_version) = match profile {
+            ChunkerProfile::KuboDefaultV0 => (false, 0),
+            ChunkerProfile::KuboDefaultV1 => (true, 1),
+        };
+        Self {
+            chunk_size: 262144,
+            raw_leaves,
+            cid_version,
+            max_links: 174,
+            chunker: None,
+        }
+    }
+}
+
+impl PackOptions {
+    pub(crate) fn file_adder(&self) -> FileAdder {
+        let chunker = match &self.chunker {
+            Some(hook) => hook.new_chunker(),
+            None => Box::new(FixedSize(self.chunk_size)),
+        };
+        FileAdder::new(chunker, self.max_links, self.raw_leaves, self.cid_version)
+    }
+}
+
+/// Splits the content of a file into the chunks encoded as leaves
+///
+/// ```
+/// use rs_car_ipfs::adder::Chunker;
+///
+/// /// Ends chunks after each new line
+/// struct Lines;
+///
+/// impl Chunker for Lines {
+///     fn next_chunk(&mut self, data: &[u8]) -> Option<usize> {
+///         data.iter().position(|byte| *byte == b'\n').map(|i| i + 1)
+///     }
+/// }
+/// ```
+pub trait Chunker: Send {
+    /// Length of the chunk at the start of `data`, the bytes of the file not chunked yet, or
+    /// `None` if more data is needed to find its end. Data after the last chunk found is the last
+    /// chunk of the file.
+    ///
+    /// Called again with the data after each chunk found, the length must be within
+    /// `1..=data.len()` or packing fails with an `InvalidData` error
+    fn next_chunk(&mut self, data: &[u8]) -> Option<usize>;
+}
+
+/// Chunks of `self.0` bytes, `--chunker=size-<n>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedSize(pub usize);
+
+impl Chunker for FixedSize {
+    fn next_chunk(&mut self, data: &[u8]) -> Option<usize> {
+        if data.len() >= self.0 {
+            Some(self.0)
+        } else {
+            None
+        }
+    }
+}
+
+/// Creates a new [`Chunker`] for each file, see [`chunker`](PackOptions::chunker)
+///
+/// ```
+/// use rs_car_ipfs::adder::{ChunkerHook, FixedSize, PackOptions};
+///
+/// let options = PackOptions {
+///     chunker: Some(ChunkerHook::new(|| FixedSize(1024))),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct ChunkerHook(Arc<NewChunkerFn>);
+
+type NewChunkerFn = dyn Fn() -> Box<dyn Chunker> + Send + Sync;
+
+impl ChunkerHook {
+    pub fn new<C, F>(f: F) -> Self
+    where
+        C: Chunker + 'static,
+        F: Fn() -> C + Send + Sync + 'static,
+    {
+        Self(Arc::new(move || Box::new(f())))
+    }
+
+    fn new_chunker(&self) -> Box<dyn Chunker> {
+        (self.0)()
+    }
+}
+
+impl fmt::Debug for ChunkerHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChunkerHook")
+    }
+}
+
+/// Chunk `file` with `profile` and check if the resulting root CID equals `expected_root`, i.e. if
+/// the DAG of `expected_root` is the canonical DAG for that profile.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::{adder::{verify_reconstructs_root, ChunkerProfile}, Cid};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut file = async_std::fs::File::open("tests/data/helloworld.txt").await?;
+///   let root_cid = Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf")?;
+///
+///   assert!(verify_reconstructs_root(&mut file, &root_cid, ChunkerProfile::KuboDefaultV0).await?);
+///   Ok(())
+/// }
+/// ```
+pub async fn verify_reconstructs_root<R: AsyncRead + Unpin>(
+    file: &mut R,
+    expected_root: &Cid,
+    profile: ChunkerProfile,
+) -> Result<bool, std::io::Error> {
+    let (root_cid, _) = compute_file_cid(file, profile).await?;
+    Ok(root_cid == *expected_root)
+}
+
+/// Chunk `input` with `profile` and return the root CID of its DAG and its length, the CID
+/// returned by `ipfs add` with the same settings and by
+/// [`write_file_car`](crate::import::write_file_car), without writing any block. Blocks are
+/// dropped once hashed, only the links of the nodes not complete yet are kept in memory.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::adder::{compute_file_cid, ChunkerProfile};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut file = async_std::fs::File::open("tests/data/helloworld.txt").await?;
+///
+///   let (root_cid, size) = compute_file_cid(&mut file, ChunkerProfile::KuboDefaultV0).await?;
+///   assert_eq!(root_cid.to_string(), "QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf");
+///   println!("{} bytes", size);
+///   Ok(())
+/// }
+/// ```
+pub async fn compute_file_cid<R: AsyncRead + Unpin>(
+    input: &mut R,
+    profile: ChunkerProfile,
+) -> Result<(Cid, u64), std::io::Error> {
+    compute_file_cid_with_options(input, &profile.into()).await
+}
+
+/// Same as [`compute_file_cid`] chunking `input` with `options`
+pub async fn compute_file_cid_with_options<R: AsyncRead + Unpin>(
+    input: &mut R,
+    options: &PackOptions,
+) -> Result<(Cid, u64), std::io::Error> {
+    let mut adder = options.file_adder();
+    let mut buf = vec![0u8; 65536];
+    let mut size = 0;
+
+    loop {
+        let n = input.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        adder.push(&buf[..n])?;
+        size += n as u64;
+    }
+
+    Ok((adder.finish(), size))
+}
+
+/// Complete the DAG of an adder fed with the output of a reader, and check it reproduces `root_cid`
+pub(crate) fn check_canonical(
+    adder: Option<FileAdder>,
+    root_cid: &Cid,
+) -> Result<(), ReadSingleFileError> {
+    match adder.map(FileAdder::finish) {
+        Some(computed) if computed != *root_cid => {
+            Err(ReadSingleFileError::NotCanonical { computed })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Incrementally builds a balanced UnixFS file DAG from pushed data, keeping in memory only the
+/// current partial chunk and the links of nodes not yet complete.
+pub(crate) struct FileAdder {
+    chunker: Box<dyn Chunker>,
+    max_links: usize,
+    raw_leaves: bool,
+    cid_version: u64,
+    /// Pushed data not chunked yet
+    chunk: Vec<u8>,
+    /// Links pending to be grouped at each depth, `levels[0]` are leaves
+    levels: Vec<Vec<Link>>,
+    /// Blocks and children of the nodes with links, if kept
+    branches: Option<Branches>,
+}
+
+/// Blocks of the nodes with links of a file DAG and their children with their file size, by CID
+pub(crate) type Branches = HashMap<Cid, (Vec<u8>, Vec<(Cid, u64)>)>;
+
+pub(crate) struct Link {
+    pub(crate) cid: Cid,
+    /// File bytes under this link
+    pub(crate) file_size: u64,
+    /// Cumulative size of the encoded blocks under this link
+    pub(crate) tsize: u64,
+}
+
+impl FileAdder {
+    pub(crate) fn new(
+        chunker: Box<dyn Chunker>,
+        max_links: usize,
+        raw_leaves: bool,
+        cid_version: u64,
+    ) -> Self {
+        Self {
+            chunker,
+            max_links,
+            raw_leaves,
+            cid_version,
+            chunk: vec![],
+            levels: vec![],
+            branches: None,
+        }
+    }
+
+    /// Keep the blocks of the nodes with links, see [`FileAdder::finish_link`]
+    pub(crate) fn keep_branches(mut self) -> Self {
+        self.branches = Some(HashMap::new());
+        self
+    }
+
+    /// Fails with `InvalidData` if the chunker returns a length outside of the data not chunked yet
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<(), std::io::Error> {
+        self.chunk.extend_from_slice(data);
+
+        // Drain the chunks found at once, the chunker may end many in a single push
+        let mut start = 0;
+        while start < self.chunk.len() {
+            let Some(len) = self.chunker.next_chunk(&self.chunk[start..]) else {
+                break;
+            };
+            if !(1..=self.chunk.len() - start).contains(&len) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "chunker returned a chunk of {} bytes out of the {} bytes not chunked yet",
+                        len,
+                        self.chunk.len() - start
+                    ),
+                ));
+            }
+            let chunk = self.chunk[start..start + len].to_vec();
+            start += len;
+            self.flush_chunk(chunk);
+        }
+        self.chunk.drain(..start);
+        Ok(())
+    }
+
+    /// Complete the DAG returning its root CID
+    pub(crate) fn finish(self) -> Cid {
+        self.finish_link().0.cid
+    }
+
+    /// Complete the DAG returning the link to its root, and the kept branches if any
+    pub(crate) fn finish_link(mut self) -> (Link, Branches) {
+        // An empty file is still a single empty leaf
+        if !self.chunk.is_empty() || self.levels.is_empty() {
+            let chunk = std::mem::take(&mut self.chunk);
+            self.flush_chunk(chunk);
+        }
+
+        let mut depth = 0;
+        loop {
+            let is_top = self.levels[depth + 1..]
+                .iter()
+                .all(|links| links.is_empty());
+            if is_top && self.levels[depth].len() == 1 {
+                let root = self.levels[depth].remove(0);
+                return (root, self.branches.unwrap_or_default());
+            }
+            if !self.levels[depth].is_empty() {
+                self.flush_level(depth);
+            }
+            depth += 1;
+        }
+    }
+
+    fn flush_chunk(&mut self, data: Vec<u8>) {
+        let file_size = data.len() as u64;
+        let (cid, block) = self.leaf(data);
+
+        self.push_link(
+            0,
+            Link {
+                cid,
+                file_size,
+                tsize: block.len() as u64,
+            },
+        );
+    }
+
+    fn push_link(&mut self, depth: usize, link: Link) {
+        if self.levels.len() <= depth {
+            self.levels.push(vec![]);
+        }
+        self.levels[depth].push(link);
+
+        if self.levels[depth].len() == self.max_links {
+            self.flush_level(depth);
+        }
+    }
+
+    /// Encode a chunk of the file as a leaf block
+    pub(crate) fn leaf(&self, data: Vec<u8>) -> (Cid, Vec<u8>) {
+        if self.raw_leaves {
+            (self.cid(RAW, &data), data)
+        } else {
+            let block = encode_node(
+                vec![],
+                UnixFs {
+                    Type: UnixFsType::File,
+                    Data: if data.is_empty() {
+                        None
+                    } else {
+                        Some(Cow::Borrowed(&data))
+                    },
+                    filesize: Some(data.len() as u64),
+                    ..Default::default()
+                },
+            );
+            (self.cid(DAG_PB, &block), block)
+        }
+    }
+
+    /// Group all pending links at `depth` into a new node one level up
+    fn flush_level(&mut self, depth: usize) {
+        let children = std::mem::take(&mut self.levels[depth]);
+
+        let file_size = children.iter().map(|link| link.file_size).sum();
+        let links_tsize: u64 = children.iter().map(|link| link.tsize).sum();
+        let block = encode_node(
+            children
+                .iter()
+                .map(|link| PBLink {
+                    Hash: Some(Cow::Owned(link.cid.to_bytes())),
+                    Name: Some(Cow::Borrowed("")),
+                    Tsize: Some(link.tsize),
+                })
+                .collect(),
+            UnixFs {
+                Type: UnixFsType::File,
+                filesize: Some(file_size),
+                blocksizes: children.iter().map(|link| link.file_size).collect(),
+                ..Default::default()
+            },
+        );
+
+        let link = Link {
+            cid: self.cid(DAG_PB, &block),
+            file_size,
+            tsize: block.len() as u64 + links_tsize,
+        };
+        if let Some(branches) = self.branches.as_mut() {
+            let children = children
+                .iter()
+                .map(|link| (link.cid, link.file_size))
+                .collect();
+            branches.insert(link.cid, (block, children));
+        }
+        self.push_link(depth + 1, link);
+    }
+
+    pub(crate) fn cid(&self, codec: u64, block: &[u8]) -> Cid {
+        let hash = Code::Sha2_256.digest(block);
+        if self.cid_version == 0 && codec == DAG_PB {
+            // unwrap: sha2-256 dag-pb is always a valid CIDv0
+            Cid::new_v0(hash).unwrap()
+        } else {
+            Cid::new_v1(codec, hash)
+        }
+    }
+}
+
+pub(crate) fn encode_node(links: Vec<PBLink<'_>>, data: UnixFs<'_>) -> Vec<u8> {
+    let node = FlatUnixFs { links, data };
+    let mut block = Vec::with_capacity(node.get_size());
+    // unwrap: writing to a Vec can not fail
+    node.write_message(&mut Writer::new(&mut block)).unwrap();
+    block
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileAdder, FixedSize};
+    use futures::executor;
+    use rs_car::CarReader;
+    use std::fs;
+
+    /// All `tests/data/*.normal.car` fixtures are created with `ipfs add --chunker=size-N`
+    #[test]
+    fn balanced_layout_matches_kubo_fixtures() {
+        for entry in fs::read_dir("tests/data").unwrap() {
+            let path = entry.unwrap().path();
+            let filename = path.file_name().unwrap().to_str().unwrap();
+            if !filename.ends_with(".normal.car") {
+                continue;
+            }
+
+            // seq_1000.txt.size-32.normal.car -> (seq_1000.txt, 32)
+            let (source, chunker) = filename.split_once(".size-").unwrap();
+            let chunk_size = chunker.split('.').next().unwrap().parse().unwrap();
+
+            let car = fs::read(&path).unwrap();
+            let expected_root = executor::block_on(async {
+                CarReader::new(&mut car.as_slice(), false)
+                    .await
+                    .unwrap()
+                    .header
+                    .roots[0]
+            });
+
+            let mut adder = FileAdder::new(Box::new(FixedSize(chunk_size)), 174, false, 0);
+            adder
+                .push(&fs::read(format!("tests/data/{}", source)).unwrap())
+                .unwrap();
+            assert_eq!(adder.finish(), expected_root, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn empty_file() {
+        // `ipfs add` of an empty file, with and without `--cid-version=1`
+        let adder = FileAdder::new(Box::new(FixedSize(262144)), 174, false, 0);
+        assert_eq!(
+            adder.finish().to_string(),
+            "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH"
+        );
+        let adder = FileAdder::new(Box::new(FixedSize(262144)), 174, true, 1);
+        assert_eq!(
+            adder.finish().to_string(),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+    }
+}
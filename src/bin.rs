@@ -1,13 +1,361 @@
-use async_std::io::{stdin, stdout};
-use rs_car_ipfs::single_file::read_single_file_buffer;
+use async_std::{
+    fs::File,
+    io::{stdin, stdout, Read},
+};
+use rs_car_ipfs::{
+    decompress::decompress_auto,
+    single_file::{
+        car_info, ls, read_single_file_buffer_with_options, Progress, ProgressHook,
+        ReadSingleFileError, ReadSingleFileOptions,
+    },
+};
+use std::{
+    io::{ErrorKind, Write},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "http")]
+#[path = "cli/fetch.rs"]
+mod fetch;
+
+const USAGE: &str = "Usage: car-ipfs [ls|info] [--decompress auto|none] [--progress|--quiet] \
+    [input.car] [< input.car] [> output]
+       car-ipfs fetch <cid> --output file [--gateway http://host:port] [--retries n] \
+    [--progress|--quiet]";
+
+/// Exit codes, distinct by cause of the failure for scripts
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+/// The root CID is missing from the CAR, is not a single root, or is not a file
+const EXIT_ROOT: i32 = 3;
+/// The CAR ends before the whole file DAG is read
+const EXIT_INCOMPLETE: i32 = 4;
+/// Reading the input or writing the output failed
+const EXIT_IO: i32 = 5;
+
+/// Min time between two renders of the progress line
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    /// Write the file to stdout
+    Extract,
+    /// List the DAG nodes instead of extracting the file
+    Ls,
+    /// Summarize the CAR header and blocks
+    Info,
+    /// Request the CAR of a file from a gateway and extract it
+    #[cfg(feature = "http")]
+    Fetch,
+}
+
+struct Args {
+    command: Option<Command>,
+    decompress: bool,
+    /// Render the extraction progress to stderr
+    progress: bool,
+    /// Only print errors, overrides `progress`
+    quiet: bool,
+    /// Read the CAR from this file instead of stdin, the CID to fetch with `fetch`
+    path: Option<String>,
+    gateway: Option<String>,
+    output: Option<String>,
+    retries: Option<String>,
+    #[cfg(feature = "http")]
+    fetch: Option<fetch::FetchArgs>,
+}
+
+impl Args {
+    /// Without a subcommand, a CAR read from stdin is extracted and a CAR file is summarized
+    fn command(&self) -> Command {
+        match (self.command, &self.path) {
+            (Some(command), _) => command,
+            (None, Some(_)) => Command::Info,
+            (None, None) => Command::Extract,
+        }
+    }
+}
 
 #[async_std::main]
 async fn main() {
-    let mut stdin = stdin();
-    let mut stdout = stdout();
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}\n{}", err, USAGE);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
 
-    if let Err(err) = read_single_file_buffer(&mut stdin, &mut stdout, None, None).await {
+    let res = match &args.path {
+        #[cfg(feature = "http")]
+        _ if args.command() == Command::Fetch => {
+            let fetch_args = args.fetch.as_ref().expect("validated by parse_args");
+            let show_progress = args.progress && !args.quiet;
+            let res = fetch::fetch(
+                fetch_args,
+                show_progress.then(ProgressLine::callback),
+                args.quiet,
+            )
+            .await;
+            if show_progress {
+                eprintln!();
+            }
+            res
+        }
+        Some(path) => match File::open(path).await {
+            Ok(file) => run_input(&args, file).await,
+            Err(err) => Err(err.into()),
+        },
+        None => run_input(&args, stdin()).await,
+    };
+
+    if let Err(err) = res {
         eprintln!("Error: {}", err);
-        std::process::exit(1);
+        std::process::exit(exit_code(&err));
+    }
+}
+
+fn exit_code(err: &ReadSingleFileError) -> i32 {
+    match err {
+        ReadSingleFileError::NotSingleRoot { .. }
+        | ReadSingleFileError::NoRootsInHeader
+        | ReadSingleFileError::RootCidNotFound { .. }
+        | ReadSingleFileError::RootCidIsNotFile => EXIT_ROOT,
+        ReadSingleFileError::PendingLinksAtEOF(_) | ReadSingleFileError::MissingNode(_) => {
+            EXIT_INCOMPLETE
+        }
+        // A CAR stream ending in the middle of a block
+        ReadSingleFileError::IoError(err) if err.kind() == ErrorKind::UnexpectedEof => {
+            EXIT_INCOMPLETE
+        }
+        ReadSingleFileError::IoError(_) => EXIT_IO,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Progress line rendered in place on stderr, never on stdout which may be the extracted file
+struct ProgressLine {
+    start: Instant,
+    last_render: Option<Instant>,
+}
+
+impl ProgressLine {
+    fn hook() -> ProgressHook {
+        ProgressHook::new(Self::callback())
+    }
+
+    fn callback() -> impl Fn(&Progress) + Send + Sync + 'static {
+        let line = Mutex::new(ProgressLine {
+            start: Instant::now(),
+            last_render: None,
+        });
+        move |progress| line.lock().unwrap().update(progress)
     }
+
+    fn update(&mut self, progress: &Progress) {
+        let now = Instant::now();
+        let done = progress.declared_size == Some(progress.bytes_written);
+        if !done
+            && self
+                .last_render
+                .is_some_and(|last| now - last < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_render = Some(now);
+
+        let elapsed = (now - self.start).as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            progress.bytes_written as f64 / elapsed
+        } else {
+            0.0
+        };
+        let mut line = format!(
+            "{} written, {} blocks, {}/s",
+            format_bytes(progress.bytes_written as f64),
+            progress.blocks_read,
+            format_bytes(rate)
+        );
+        if let Some(total) = progress.declared_size {
+            line.push_str(&format!(" of {}", format_bytes(total as f64)));
+            if rate > 0.0 {
+                let eta = total.saturating_sub(progress.bytes_written) as f64 / rate;
+                line.push_str(&format!(", ETA {:.0}s", eta));
+            }
+        }
+        // Clear the rest of the previous line
+        eprint!("\r{}\x1b[K", line);
+        let _ = std::io::stderr().flush();
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+async fn run_input<R: Read + Send + Unpin>(
+    args: &Args,
+    mut input: R,
+) -> Result<(), ReadSingleFileError> {
+    if args.decompress {
+        let mut input = decompress_auto(input).await?;
+        run(args, &mut input).await
+    } else {
+        run(args, &mut input).await
+    }
+}
+
+async fn run<R: Read + Send + Unpin>(
+    args: &Args,
+    input: &mut R,
+) -> Result<(), ReadSingleFileError> {
+    match args.command() {
+        Command::Ls => {
+            for (cid, node) in ls(input).await? {
+                print!("{} {:?} data={}", cid, node.unixfs_type, node.data_len);
+                if let Some(filesize) = node.filesize {
+                    print!(" filesize={}", filesize);
+                }
+                println!();
+                for link in node.links {
+                    print!("  {}", link.cid);
+                    if let Some(name) = link.name {
+                        print!(" name={:?}", name);
+                    }
+                    if let Some(tsize) = link.tsize {
+                        print!(" tsize={}", tsize);
+                    }
+                    println!();
+                }
+            }
+            Ok(())
+        }
+        Command::Info => {
+            let info = car_info(input, true).await?;
+            println!("version={}", info.version);
+            for root in &info.roots {
+                println!("root={}", root);
+            }
+            println!("blocks={} bytes={}", info.block_count, info.block_bytes);
+            println!(
+                "dag-pb={} raw={} other={}",
+                info.dag_pb_blocks, info.raw_blocks, info.other_blocks
+            );
+            if let Some(filesize) = info.root_filesize {
+                println!("filesize={}", filesize);
+            }
+            Ok(())
+        }
+        Command::Extract => {
+            let show_progress = args.progress && !args.quiet;
+            let options = ReadSingleFileOptions {
+                on_progress: show_progress.then(ProgressLine::hook),
+                ..Default::default()
+            };
+            let res = read_single_file_buffer_with_options(input, &mut stdout(), None, &options)
+                .await
+                .map(|_| ());
+            if show_progress {
+                eprintln!();
+            }
+            res
+        }
+        #[cfg(feature = "http")]
+        Command::Fetch => unreachable!("fetch does not read an input CAR"),
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args {
+        command: None,
+        decompress: false,
+        progress: false,
+        quiet: false,
+        path: None,
+        gateway: None,
+        output: None,
+        retries: None,
+        #[cfg(feature = "http")]
+        fetch: None,
+    };
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "ls" if parsed.command.is_none() => parsed.command = Some(Command::Ls),
+            "info" if parsed.command.is_none() => parsed.command = Some(Command::Info),
+            #[cfg(feature = "http")]
+            "fetch" if parsed.command.is_none() => parsed.command = Some(Command::Fetch),
+            #[cfg(not(feature = "http"))]
+            "fetch" if parsed.command.is_none() => {
+                return Err("fetch requires building with the http feature".to_string())
+            }
+            "--gateway" | "--output" | "-o" | "--retries" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing {} value", arg))?;
+                match arg.as_str() {
+                    "--gateway" => parsed.gateway = Some(value),
+                    "--retries" => parsed.retries = Some(value),
+                    _ => parsed.output = Some(value),
+                }
+            }
+            "--decompress" => match args.next().as_deref() {
+                Some("auto") => parsed.decompress = true,
+                Some("none") => parsed.decompress = false,
+                other => return Err(format!("invalid --decompress value {:?}", other)),
+            },
+            "--progress" => parsed.progress = true,
+            "--quiet" | "-q" => parsed.quiet = true,
+            _ if !arg.starts_with('-') && parsed.path.is_none() => parsed.path = Some(arg),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+
+    #[cfg(feature = "http")]
+    let fetching = parsed.command() == Command::Fetch;
+    #[cfg(not(feature = "http"))]
+    let fetching = false;
+    if parsed.progress && parsed.command() != Command::Extract && !fetching {
+        return Err("--progress is only available when extracting".to_string());
+    }
+    if !fetching
+        && (parsed.gateway.is_some() || parsed.output.is_some() || parsed.retries.is_some())
+    {
+        return Err("--gateway, --output and --retries are only available with fetch".to_string());
+    }
+    #[cfg(feature = "http")]
+    if fetching {
+        let cid = parsed.path.as_deref().ok_or("missing CID to fetch")?;
+        parsed.fetch = Some(fetch::FetchArgs {
+            cid: rs_car_ipfs::Cid::try_from(cid).map_err(|err| format!("invalid CID: {}", err))?,
+            gateway: fetch::Gateway::parse(
+                parsed.gateway.as_deref().unwrap_or(fetch::DEFAULT_GATEWAY),
+            )?,
+            output: parsed
+                .output
+                .clone()
+                .ok_or("fetch requires --output, a seekable file")?,
+            retries: match &parsed.retries {
+                Some(retries) => retries
+                    .parse()
+                    .map_err(|_| format!("invalid --retries value {:?}", retries))?,
+                None => fetch::DEFAULT_RETRIES,
+            },
+        });
+    }
+
+    Ok(parsed)
 }
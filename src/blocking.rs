@@ -0,0 +1,100 @@
+//! Blocking versions of the single file readers for synchronous code, reading from
+//! [`std::io::Read`] and writing to [`std::io::Write`].
+//!
+//! The readers block the calling thread until the whole file is written, so they must not run on
+//! a thread driving an async runtime. Instead of deadlocking or panicking, they detect it:
+//!
+//! - On a plain thread, e.g. `std::thread::spawn` or `async_std::task::spawn_blocking`, they run
+//!   to completion.
+//! - With the `tokio` feature, on a multi-threaded tokio runtime, including its
+//!   `tokio::task::spawn_blocking` threads, they run with `tokio::task::block_in_place` so the
+//!   other tasks of a worker are moved to another thread for the duration of the read.
+//! - With the `tokio` feature on any thread of a current-thread tokio runtime, with the
+//!   `async-std` feature in an async-std task, or inside [`futures::executor::block_on`], they fail
+//!   with [`BlockingInAsyncContext`](ReadSingleFileError::BlockingInAsyncContext). Await the async
+//!   readers of [`single_file`](crate::single_file) there instead, or call them from a
+//!   `std::thread`.
+//!
+//! # Examples
+//!
+//! ```
+//! use rs_car_ipfs::blocking::read_single_file_seek;
+//! use std::io::Cursor;
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!   let mut input = std::fs::File::open("tests/example.car")?;
+//!   let mut out = Cursor::new(Vec::new());
+//!
+//!   read_single_file_seek(&mut input, &mut out, None, &Default::default())?;
+//!   Ok(())
+//! }
+//! ```
+
+use futures::{executor, io::AllowStdIo, Future};
+use rs_car::Cid;
+use std::io::{Read, Seek, Write};
+
+use crate::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, ReadSingleFileError,
+    ReadSingleFileOptions, ReadSummary,
+};
+
+/// Blocking version of [`read_single_file_seek_with_options`]
+pub fn read_single_file_seek<R: Read + Send, W: Read + Write + Seek>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let mut car_input = AllowStdIo::new(car_input);
+    let mut out = AllowStdIo::new(out);
+    block_on(read_single_file_seek_with_options(
+        &mut car_input,
+        &mut out,
+        root_cid,
+        options,
+    ))?
+}
+
+/// Blocking version of [`read_single_file_buffer_with_options`]
+pub fn read_single_file_buffer<R: Read + Send, W: Write>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let mut car_input = AllowStdIo::new(car_input);
+    let mut out = AllowStdIo::new(out);
+    block_on(read_single_file_buffer_with_options(
+        &mut car_input,
+        &mut out,
+        root_cid,
+        options,
+    ))?
+}
+
+/// Run `future` on the current thread, unless it drives an async runtime, see the
+/// [module docs](self)
+fn block_on<F: Future>(future: F) -> Result<F::Output, ReadSingleFileError> {
+    #[cfg(feature = "tokio")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return match handle.runtime_flavor() {
+            tokio::runtime::RuntimeFlavor::MultiThread => {
+                Ok(tokio::task::block_in_place(|| executor::block_on(future)))
+            }
+            _ => Err(ReadSingleFileError::BlockingInAsyncContext),
+        };
+    }
+
+    #[cfg(feature = "async-std")]
+    if async_std::task::try_current().is_some() {
+        return Err(ReadSingleFileError::BlockingInAsyncContext);
+    }
+
+    // `executor::block_on` panics when nested
+    if executor::enter().is_err() {
+        return Err(ReadSingleFileError::BlockingInAsyncContext);
+    }
+
+    Ok(executor::block_on(future))
+}
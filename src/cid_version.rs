@@ -0,0 +1,83 @@
+//! Conversions of a CID between its version 0 and version 1 forms.
+//!
+//! A CIDv0 is the bare sha2-256 multihash of a dag-pb block, the same block has a CIDv1 of codec
+//! dag-pb with that multihash. Both address the same block, e.g. the root of a CAR can be
+//! displayed or matched in either form.
+
+use multihash::Code;
+use rs_car::Cid;
+
+use crate::adder::DAG_PB;
+
+/// Reason a CID has no version 0 form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CidVersionError {
+    /// Only dag-pb blocks have a CIDv0
+    NotDagPb { codec: u64 },
+    /// Only a sha2-256 multihash of 32 bytes fits a CIDv0
+    NotSha256 { code: u64, size: u8 },
+}
+
+impl std::fmt::Display for CidVersionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for CidVersionError {}
+
+/// CIDv1 of the block addressed by `cid`, returned as is if already a CIDv1
+///
+/// ```
+/// use rs_car_ipfs::{cid_version::to_cidv1, Cid};
+///
+/// let cid = Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf").unwrap();
+/// assert_eq!(
+///     to_cidv1(&cid).to_string(),
+///     "bafybeic3bgk45vusfhjgacofhqmfuyxkqbndhe4dkipnx3iqfdcjmykuja"
+/// );
+/// ```
+pub fn to_cidv1(cid: &Cid) -> Cid {
+    Cid::new_v1(cid.codec(), *cid.hash())
+}
+
+/// Whether `a` and `b` address the same block, in the same or in different versions
+///
+/// ```
+/// use rs_car_ipfs::{cid_version::{same_block, to_cidv1}, Cid};
+///
+/// let cid = Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf").unwrap();
+/// assert!(same_block(&cid, &to_cidv1(&cid)));
+/// ```
+pub fn same_block(a: &Cid, b: &Cid) -> bool {
+    a.codec() == b.codec() && a.hash() == b.hash()
+}
+
+/// Key of the block addressed by `cid`, the same in both CID versions, to match blocks streamed
+/// and linked in different versions in maps. Keys of the same block are [`same_block`]
+///
+/// ```
+/// use rs_car_ipfs::{cid_version::{block_key, to_cidv1}, Cid};
+///
+/// let cid = Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf").unwrap();
+/// assert_eq!(block_key(&cid), block_key(&to_cidv1(&cid)));
+/// ```
+pub fn block_key(cid: &Cid) -> Cid {
+    to_cidv1(cid)
+}
+
+/// CIDv0 of the block addressed by `cid`, returned as is if already a CIDv0. Fails if `cid` is
+/// not a dag-pb block addressed by its sha2-256 digest
+pub fn to_cidv0(cid: &Cid) -> Result<Cid, CidVersionError> {
+    if cid.codec() != DAG_PB {
+        return Err(CidVersionError::NotDagPb { codec: cid.codec() });
+    }
+    let hash = cid.hash();
+    if hash.code() != u64::from(Code::Sha2_256) || hash.size() != 32 {
+        return Err(CidVersionError::NotSha256 {
+            code: hash.code(),
+            size: hash.size(),
+        });
+    }
+    Ok(Cid::new_v0(*hash).expect("sha2-256 digest checked above"))
+}
@@ -0,0 +1,303 @@
+//! `car-ipfs fetch`, request the CAR of a file from a trustless gateway and extract it.
+//!
+//! A minimal HTTP/1.1 client over plain TCP, only `http://` gateways are supported, e.g. a local
+//! node or a TLS terminating proxy. The response body is cut at the last complete CAR frame when
+//! the connection fails, and the read resumes from a new request for the byte range not written
+//! yet, `entity-bytes=<written>:*`, as the next segment of [`read_single_file_multi`].
+
+use async_std::{
+    fs::OpenOptions,
+    io::{prelude::BufReadExt, BufReader, ReadExt, WriteExt},
+    net::TcpStream,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use rs_car_ipfs::{
+    single_file::{
+        read_single_file_multi, Progress, ProgressHook, ReadSingleFileError, ReadSingleFileOptions,
+    },
+    Cid,
+};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+pub const DEFAULT_GATEWAY: &str = "http://127.0.0.1:8080";
+
+/// Requests after the first one when a response ends before the file is complete
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Bytes read from the socket at once
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Gateway URL, `http://host[:port][/prefix]`
+#[derive(Clone)]
+pub struct Gateway {
+    host: String,
+    port: u16,
+    /// Path before `/ipfs/<cid>`, without a trailing slash
+    prefix: String,
+}
+
+impl Gateway {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None if url.starts_with("https://") => {
+                return Err("https gateways are not supported, no TLS in this build".to_string())
+            }
+            None => return Err(format!("invalid gateway URL {:?}", url)),
+        };
+        let (authority, prefix) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("invalid gateway port {:?}", port))?,
+            ),
+            _ => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("invalid gateway URL {:?}", url));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            prefix: prefix.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+pub struct FetchArgs {
+    pub cid: Cid,
+    pub gateway: Gateway,
+    pub output: String,
+    pub retries: u32,
+}
+
+/// Fetch the file `cid` into `output`, resuming up to `retries` times
+pub async fn fetch(
+    args: &FetchArgs,
+    on_progress: Option<impl Fn(&Progress) + Send + Sync + 'static>,
+    quiet: bool,
+) -> Result<(), ReadSingleFileError> {
+    let mut out = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&args.output)
+        .await?;
+
+    // Leaves are written in file order, the bytes written are the offset to resume from
+    let written = Arc::new(AtomicU64::new(0));
+    let recorder = written.clone();
+    let options = ReadSingleFileOptions {
+        on_progress: Some(ProgressHook::new(move |progress| {
+            recorder.store(progress.bytes_written, Ordering::Relaxed);
+            if let Some(on_progress) = &on_progress {
+                on_progress(progress);
+            }
+        })),
+        ..Default::default()
+    };
+
+    let segments = stream::unfold(0, |attempt| {
+        let written = written.clone();
+        async move {
+            let mut attempt = attempt;
+            while attempt <= args.retries {
+                let offset = written.load(Ordering::Relaxed);
+                let mut path = format!("/ipfs/{}?format=car&dag-scope=entity", args.cid);
+                if attempt > 0 {
+                    path.push_str(&format!("&entity-bytes={}:*", offset));
+                    if !quiet {
+                        eprintln!(
+                            "\nWarning: file incomplete, resuming at byte {} ({}/{})",
+                            offset, attempt, args.retries
+                        );
+                    }
+                }
+                attempt += 1;
+                match request(&args.gateway, &path).await {
+                    Ok(body) => return Some((car_frames(body), attempt)),
+                    Err(err) if !quiet => eprintln!("\nWarning: request failed: {}", err),
+                    Err(_) => {}
+                }
+            }
+            None
+        }
+    });
+
+    read_single_file_multi(Box::pin(segments), &mut out, Some(&args.cid), &options).await?;
+    Ok(())
+}
+
+/// The complete varint length prefixed frames of a CARv1 body, the header and then each block.
+/// A body ending in the middle of a frame, or failing, ends the segment after the last complete
+/// frame so the read can continue with the next one.
+fn car_frames(body: Body) -> impl futures::AsyncRead + Send + Unpin {
+    stream::unfold(Some((body, Vec::new())), |state| async move {
+        let (mut body, mut buf) = state?;
+        loop {
+            let complete = complete_frames_len(&buf);
+            if complete > 0 {
+                let rest = buf.split_off(complete);
+                return Some((Ok::<_, io::Error>(buf), Some((body, rest))));
+            }
+            match body.next_chunk().await {
+                Ok(Some(chunk)) => buf.extend_from_slice(&chunk),
+                Ok(None) | Err(_) => return None,
+            }
+        }
+    })
+    .boxed()
+    .into_async_read()
+}
+
+/// Length of the complete frames at the start of `buf`
+fn complete_frames_len(buf: &[u8]) -> usize {
+    let mut pos = 0;
+    loop {
+        let mut len: u64 = 0;
+        let mut i = pos;
+        loop {
+            let Some(byte) = buf.get(i) else {
+                return pos;
+            };
+            len |= u64::from(byte & 0x7f) << (7 * (i - pos));
+            i += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if i - pos >= 10 {
+                // Not a varint, let the CAR reader report it
+                return buf.len();
+            }
+        }
+        match i.checked_add(len as usize) {
+            Some(end) if end <= buf.len() => pos = end,
+            _ => return pos,
+        }
+    }
+}
+
+enum Framing {
+    Length(u64),
+    /// Bytes left in the current chunk, none before the next chunk header
+    Chunked(u64),
+    UntilClose,
+    Done,
+}
+
+struct Body {
+    reader: BufReader<TcpStream>,
+    framing: Framing,
+}
+
+impl Body {
+    /// Next bytes of the body, None at its end
+    async fn next_chunk(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let limit = match self.framing {
+            Framing::Done => return Ok(None),
+            Framing::Length(0) => {
+                self.framing = Framing::Done;
+                return Ok(None);
+            }
+            Framing::Length(remaining) => remaining,
+            Framing::Chunked(0) => {
+                let line = self.read_line().await?;
+                let size = line.split(';').next().unwrap_or_default().trim();
+                let size = u64::from_str_radix(size, 16).map_err(|_| invalid("chunk size"))?;
+                if size == 0 {
+                    self.framing = Framing::Done;
+                    return Ok(None);
+                }
+                self.framing = Framing::Chunked(size);
+                size
+            }
+            Framing::Chunked(remaining) => remaining,
+            Framing::UntilClose => READ_CHUNK as u64,
+        };
+
+        let mut chunk = vec![0; limit.min(READ_CHUNK as u64) as usize];
+        let n = self.reader.read(&mut chunk).await?;
+        chunk.truncate(n);
+        match &mut self.framing {
+            Framing::UntilClose if n == 0 => {
+                self.framing = Framing::Done;
+                return Ok(None);
+            }
+            _ if n == 0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Framing::Length(remaining) => *remaining -= n as u64,
+            Framing::Chunked(remaining) => {
+                *remaining -= n as u64;
+                if *remaining == 0 {
+                    // CRLF after the chunk data
+                    self.read_line().await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(Some(chunk))
+    }
+
+    async fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim_end().to_string())
+    }
+}
+
+/// Issue a CAR request for `path` and read the response head
+async fn request(gateway: &Gateway, path: &str) -> io::Result<Body> {
+    let mut stream = TcpStream::connect((gateway.host.as_str(), gateway.port)).await?;
+    let head = format!(
+        "GET {}{} HTTP/1.1\r\nHost: {}:{}\r\nAccept: application/vnd.ipld.car\r\n\
+         User-Agent: car-ipfs\r\nConnection: close\r\n\r\n",
+        gateway.prefix, path, gateway.host, gateway.port
+    );
+    stream.write_all(head.as_bytes()).await?;
+
+    let mut body = Body {
+        reader: BufReader::new(stream),
+        framing: Framing::UntilClose,
+    };
+    let status = body.read_line().await?;
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(io::Error::other(format!("gateway responded {:?}", status)));
+    }
+    loop {
+        let line = body.read_line().await?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(invalid("header"));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("transfer-encoding") && value.eq_ignore_ascii_case("chunked") {
+            body.framing = Framing::Chunked(0);
+        } else if name.eq_ignore_ascii_case("content-length")
+            && !matches!(body.framing, Framing::Chunked(_))
+        {
+            body.framing = Framing::Length(value.parse().map_err(|_| invalid("content length"))?);
+        }
+    }
+    Ok(body)
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid HTTP response {}", what),
+    )
+}
@@ -0,0 +1,140 @@
+//! Transparent decompression of CAR inputs compressed at rest.
+//!
+//! [`decompress_auto`] sniffs the magic bytes at the start of the input before the CAR header is
+//! parsed, and inserts the matching async decoder. Inputs without a known magic are passed through
+//! unchanged. Decoders are behind the `gzip` and `zstd` features.
+
+use futures::{
+    io::{BufReader, Chain, Cursor},
+    AsyncRead, AsyncReadExt,
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression format of a CAR input, or of the file written with
+/// [`compress_output`](crate::single_file::ReadSingleFileOptions::compress_output)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression format from the first bytes of an input. A CAR header never starts
+    /// with either magic, since its second byte is always the CBOR map prefix.
+    pub fn sniff(prefix: &[u8]) -> Self {
+        if prefix.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if prefix.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Input with the sniffed prefix bytes put back in front
+type Prefixed<R> = BufReader<Chain<Cursor<Vec<u8>>, R>>;
+
+/// `AsyncRead` over the decompressed contents of an input, see [`decompress_auto`]
+pub struct DecompressReader<R> {
+    compression: Compression,
+    inner: Inner<R>,
+}
+
+enum Inner<R> {
+    Plain(Prefixed<R>),
+    #[cfg(feature = "gzip")]
+    Gzip(async_compression::futures::bufread::GzipDecoder<Prefixed<R>>),
+    #[cfg(feature = "zstd")]
+    Zstd(async_compression::futures::bufread::ZstdDecoder<Prefixed<R>>),
+}
+
+impl<R> DecompressReader<R> {
+    /// Compression format detected on the input
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+}
+
+/// Sniff the compression format of `input` and wrap it with the matching decoder. Returns an
+/// `InvalidData` error if the input is compressed with a format whose feature is not enabled.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::{decompress::decompress_auto, single_file::read_single_file_seek};
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut input = decompress_auto(input).await?;
+///   let mut out = Cursor::new(Vec::new());
+///
+///   read_single_file_seek(&mut input, &mut out, None, None).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn decompress_auto<R: AsyncRead + Unpin>(
+    mut input: R,
+) -> Result<DecompressReader<R>, io::Error> {
+    let mut prefix = vec![0u8; ZSTD_MAGIC.len()];
+    let mut filled = 0;
+    while filled < prefix.len() {
+        let n = input.read(&mut prefix[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    prefix.truncate(filled);
+
+    let compression = Compression::sniff(&prefix);
+    let prefixed = BufReader::new(Cursor::new(prefix).chain(input));
+
+    let inner = match compression {
+        Compression::None => Inner::Plain(prefixed),
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Inner::Gzip(async_compression::futures::bufread::GzipDecoder::new(
+            prefixed,
+        )),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Inner::Zstd(async_compression::futures::bufread::ZstdDecoder::new(
+            prefixed,
+        )),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} compressed input, feature not enabled", compression),
+            ))
+        }
+    };
+
+    Ok(DecompressReader { compression, inner })
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DecompressReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match &mut self.get_mut().inner {
+            Inner::Plain(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(r) => Pin::new(r).poll_read(cx, buf),
+        }
+    }
+}
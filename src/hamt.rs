@@ -0,0 +1,211 @@
+//! Placement of the entries of UnixFS HAMT sharded directories, as written by kubo: the name of
+//! an entry is hashed with murmur3, and each level of shards consumes the next `log2(fanout)`
+//! bits of the hash to pick a bucket. Links of a shard are named with the bucket in upper case
+//! hex, followed by the entry name for entries, only the bucket for a nested shard.
+//!
+//! ```
+//! use rs_car_ipfs::{hamt::{find_shard_link, ShardLink}, single_file::ls};
+//!
+//! #[async_std::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!   let mut input = async_std::fs::File::open("tests/example.car").await?;
+//!
+//!   for (cid, node) in ls(&mut input).await? {
+//!     if let Ok(Some(ShardLink::Entry(link))) = find_shard_link(&node, "file.txt", 0) {
+//!       println!("file.txt of {} is {}", cid, link.cid);
+//!     }
+//!   }
+//!   Ok(())
+//! }
+//! ```
+
+use crate::{
+    single_file::{LinkInfo, ReadSingleFileError, UnixFsNodeInfo},
+    UnixFsType,
+};
+
+/// Multicodec of the murmur3-x64-64 hash, the only `hashType` of HAMT shards
+pub const HASH_MURMUR3: u64 = 0x22;
+/// `fanout` of the shards written by kubo
+pub const DEFAULT_FANOUT: u64 = 256;
+
+/// First 64 bits of murmur3 x64 128 with seed 0, big endian. Same as go's `murmur3.New64`
+pub fn murmur3_x64_64(data: &[u8]) -> [u8; 8] {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+
+    let (mut h1, mut h2) = (0u64, 0u64);
+    let blocks = data.chunks_exact(16);
+    let tail = blocks.remainder();
+    for block in blocks {
+        let (k1, k2) = read_u64_pair(block);
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+
+    let mut padded = [0u8; 16];
+    padded[..tail.len()].copy_from_slice(tail);
+    let (k1, k2) = read_u64_pair(&padded);
+    if tail.len() > 8 {
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    }
+    if !tail.is_empty() {
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    }
+
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+    h1.wrapping_add(h2).to_be_bytes()
+}
+
+fn read_u64_pair(block: &[u8]) -> (u64, u64) {
+    let mut k1 = [0u8; 8];
+    let mut k2 = [0u8; 8];
+    k1.copy_from_slice(&block[..8]);
+    k2.copy_from_slice(&block[8..16]);
+    (u64::from_le_bytes(k1), u64::from_le_bytes(k2))
+}
+
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^ (k >> 33)
+}
+
+/// Bucket of the entry `name` in a shard at `depth` below the root shard, 0 for the root. None
+/// if `fanout` is not a power of two of at least 2, or the 64 bits of the hash are consumed
+/// before `depth`
+pub fn bucket_index(name: &str, depth: usize, fanout: u64) -> Option<usize> {
+    if fanout < 2 || !fanout.is_power_of_two() {
+        return None;
+    }
+    let bits = fanout.trailing_zeros() as usize;
+    let start = depth.checked_mul(bits)?;
+    if start + bits > 64 {
+        return None;
+    }
+    let hash = u64::from_be_bytes(murmur3_x64_64(name.as_bytes()));
+    Some(((hash >> (64 - start - bits)) & (fanout - 1)) as usize)
+}
+
+/// Prefix of the names of the links of a shard of `fanout` to `bucket`, in upper case hex padded
+/// to the width of the largest bucket
+pub fn bucket_prefix(bucket: usize, fanout: u64) -> String {
+    let width = format!("{:X}", fanout.saturating_sub(1)).len();
+    format!("{:0width$X}", bucket, width = width)
+}
+
+/// Link of a shard to the bucket of an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardLink<'a> {
+    /// The entry itself
+    Entry(&'a LinkInfo),
+    /// A nested shard holding the bucket, to search at the next depth
+    Shard(&'a LinkInfo),
+}
+
+/// Bucket of the entry `name` in the HAMT shard `node` at `depth`, from the `hashType` and
+/// `fanout` of the node. Fails with [`InvalidUnixFs`](ReadSingleFileError::InvalidUnixFs) if the
+/// node is not a shard, is not hashed with murmur3 or has an invalid fanout
+pub fn shard_bucket(
+    node: &UnixFsNodeInfo,
+    name: &str,
+    depth: usize,
+) -> Result<usize, ReadSingleFileError> {
+    if node.unixfs_type != UnixFsType::HAMTShard {
+        return Err(ReadSingleFileError::InvalidUnixFs(format!(
+            "{:?} node is not a HAMT shard",
+            node.unixfs_type
+        )));
+    }
+    if node.hash_type != Some(HASH_MURMUR3) {
+        return Err(ReadSingleFileError::InvalidUnixFs(format!(
+            "unsupported HAMT hashType {:?}",
+            node.hash_type
+        )));
+    }
+    let fanout = node.fanout.unwrap_or(DEFAULT_FANOUT);
+    bucket_index(name, depth, fanout).ok_or_else(|| {
+        ReadSingleFileError::InvalidUnixFs(format!(
+            "invalid HAMT fanout {} at depth {}",
+            fanout, depth
+        ))
+    })
+}
+
+/// Link of the HAMT shard `node` at `depth` to the entry `name`, or to the nested shard to search
+/// for it. None if the bucket of the entry is empty or holds another entry, so the directory
+/// has no entry `name`
+pub fn find_shard_link<'a>(
+    node: &'a UnixFsNodeInfo,
+    name: &str,
+    depth: usize,
+) -> Result<Option<ShardLink<'a>>, ReadSingleFileError> {
+    let bucket = shard_bucket(node, name, depth)?;
+    let prefix = bucket_prefix(bucket, node.fanout.unwrap_or(DEFAULT_FANOUT));
+    for link in &node.links {
+        let Some(link_name) = link.name.as_deref().and_then(|n| n.strip_prefix(&prefix)) else {
+            continue;
+        };
+        if link_name.is_empty() {
+            return Ok(Some(ShardLink::Shard(link)));
+        }
+        if link_name == name {
+            return Ok(Some(ShardLink::Entry(link)));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket_index, bucket_prefix, murmur3_x64_64};
+
+    #[test]
+    fn murmur3_x64_64_vectors() {
+        assert_eq!(murmur3_x64_64(b""), [0; 8]);
+        assert_eq!(
+            murmur3_x64_64(b"hello"),
+            0xcbd8a7b341bd9b02u64.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn bucket_index_bits() {
+        // 0xcbd8a7b341bd9b02
+        assert_eq!(bucket_index("hello", 0, 256), Some(0xcb));
+        assert_eq!(bucket_index("hello", 1, 256), Some(0xd8));
+        assert_eq!(bucket_index("hello", 7, 256), Some(0x02));
+        assert_eq!(bucket_index("hello", 8, 256), None);
+        assert_eq!(bucket_index("hello", 0, 16), Some(0xc));
+        assert_eq!(bucket_index("hello", 1, 16), Some(0xb));
+        assert_eq!(bucket_index("hello", 0, 2), Some(1));
+        assert_eq!(bucket_index("hello", 2, 2), Some(0));
+        assert_eq!(bucket_index("hello", 0, 100), None);
+        assert_eq!(bucket_index("hello", 0, 1), None);
+    }
+
+    #[test]
+    fn bucket_prefix_width() {
+        assert_eq!(bucket_prefix(0xcb, 256), "CB");
+        assert_eq!(bucket_prefix(2, 256), "02");
+        assert_eq!(bucket_prefix(0xc, 16), "C");
+        assert_eq!(bucket_prefix(5, 4096), "005");
+    }
+}
@@ -0,0 +1,542 @@
+//! Import a local directory tree or file into a CAR, the write side counterpart of the readers.
+//!
+//! Files are chunked with a [`ChunkerProfile`] or [`PackOptions`] and directories are encoded as
+//! UnixFS `Directory` nodes, switching to a HAMT sharded directory when its links grow past
+//! 256 KiB, the same as `ipfs add -r --hidden` with the same settings. The CAR lists each node before its
+//! children so files can be read back with [`read_single_file_seek`](crate::single_file::read_single_file_seek).
+
+use rs_car::Cid;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    adder::{encode_node, Branches, ChunkerProfile, FileAdder, PackOptions, DAG_PB},
+    hamt::{murmur3_x64_64, DEFAULT_FANOUT, HASH_MURMUR3},
+    pb::{PBLink, UnixFs, UnixFsType},
+};
+
+/// Directories whose estimated size, the sum of the length of link names and CIDs, reaches this
+/// size are sharded. Same as kubo's `HAMTShardingSize`
+const HAMT_SHARDING_SIZE: usize = 256 * 1024;
+const HAMT_FANOUT: u64 = DEFAULT_FANOUT;
+
+/// Import the directory `dir_path` with all its files, sub-directories and symlinks, and write it
+/// to `out` as a CARv1 with a single root. Returns the root CID, the same as returned by
+/// `ipfs add -r --hidden` with the settings of `profile`.
+///
+/// Files are read twice, once to compute the DAG and once to write their blocks in order, so they
+/// must not change during the import.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::{adder::ChunkerProfile, import::write_directory_car};
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut out = Vec::new();
+///   let root_cid = write_directory_car("src", &mut out, ChunkerProfile::KuboDefaultV0)?;
+///   println!("{} {} bytes", root_cid, out.len());
+///   Ok(())
+/// }
+/// ```
+pub fn write_directory_car<W: Write>(
+    dir_path: impl AsRef<Path>,
+    out: &mut W,
+    profile: ChunkerProfile,
+) -> io::Result<Cid> {
+    write_directory_car_with_options(dir_path, out, &profile.into())
+}
+
+/// Same as [`write_directory_car`] chunking files with `options`
+pub fn write_directory_car_with_options<W: Write>(
+    dir_path: impl AsRef<Path>,
+    out: &mut W,
+    options: &PackOptions,
+) -> io::Result<Cid> {
+    let adder = options.file_adder();
+    let root = import_entry(dir_path.as_ref(), options, &adder)?;
+    if !matches!(root.kind, Kind::Directory { .. }) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a directory", dir_path.as_ref().display()),
+        ));
+    }
+
+    write_car_header(out, &root.cid)?;
+    write_entry(out, &root, &adder, &mut HashSet::new())?;
+    Ok(root.cid)
+}
+
+/// Import the file `file_path` chunked with `options`, and write it to `out` as a CARv1 with a
+/// single root. Returns the root CID, the same as returned by `ipfs add` with the same settings.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::{adder::PackOptions, import::write_file_car};
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   // ipfs add --chunker=size-1048576 --raw-leaves
+///   let options = PackOptions {
+///     chunk_size: 1048576,
+///     raw_leaves: true,
+///     ..Default::default()
+///   };
+///   let mut out = Vec::new();
+///   let root_cid = write_file_car("tests/data/rand_100K.bin", &mut out, &options)?;
+///   println!("{} {} bytes", root_cid, out.len());
+///   Ok(())
+/// }
+/// ```
+pub fn write_file_car<W: Write>(
+    file_path: impl AsRef<Path>,
+    out: &mut W,
+    options: &PackOptions,
+) -> io::Result<Cid> {
+    let file_path = file_path.as_ref();
+    if !fs::metadata(file_path)?.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file", file_path.display()),
+        ));
+    }
+    let adder = options.file_adder();
+    let root = import_entry(file_path, options, &adder)?;
+
+    write_car_header(out, &root.cid)?;
+    write_entry(out, &root, &adder, &mut HashSet::new())?;
+    Ok(root.cid)
+}
+
+/// Imported file, symlink or directory with its blocks, or the means to read them again
+struct Entry {
+    cid: Cid,
+    /// Cumulative size of the encoded blocks under this entry
+    tsize: u64,
+    kind: Kind,
+}
+
+enum Kind {
+    File {
+        path: PathBuf,
+        size: u64,
+        branches: Branches,
+    },
+    Symlink {
+        block: Vec<u8>,
+    },
+    Directory {
+        node: DirNode,
+        /// Sorted by name
+        entries: Vec<Entry>,
+    },
+}
+
+/// Block of a directory or of one of its HAMT shards
+struct DirNode {
+    cid: Cid,
+    block: Vec<u8>,
+    tsize: u64,
+    links: Vec<DirLink>,
+}
+
+enum DirLink {
+    Shard(DirNode),
+    /// Index of the linked entry in the directory entries
+    Entry(usize),
+}
+
+/// `adder` is only used for its settings, each file is chunked with a new adder of `options`
+fn import_entry(path: &Path, options: &PackOptions, adder: &FileAdder) -> io::Result<Entry> {
+    let file_type = fs::symlink_metadata(path)?.file_type();
+
+    if file_type.is_file() {
+        let mut file_adder = options.file_adder().keep_branches();
+        let mut file = fs::File::open(path)?;
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            file_adder.push(&buf[..n])?;
+        }
+        let (root, branches) = file_adder.finish_link();
+        Ok(Entry {
+            cid: root.cid,
+            tsize: root.tsize,
+            kind: Kind::File {
+                path: path.to_path_buf(),
+                size: root.file_size,
+                branches,
+            },
+        })
+    } else if file_type.is_symlink() {
+        let target = fs::read_link(path)?;
+        let block = encode_node(
+            vec![],
+            UnixFs {
+                Type: UnixFsType::Symlink,
+                Data: Some(Cow::Owned(utf8(target.into_os_string())?.into_bytes())),
+                ..Default::default()
+            },
+        );
+        Ok(Entry {
+            cid: adder.cid(DAG_PB, &block),
+            tsize: block.len() as u64,
+            kind: Kind::Symlink { block },
+        })
+    } else if file_type.is_dir() {
+        let mut named = vec![];
+        for dir_entry in fs::read_dir(path)? {
+            let dir_entry = dir_entry?;
+            let name = utf8(dir_entry.file_name())?;
+            named.push((name, import_entry(&dir_entry.path(), options, adder)?));
+        }
+        named.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let links = named
+            .iter()
+            .map(|(name, entry)| (name.as_str(), entry.cid, entry.tsize))
+            .collect::<Vec<_>>();
+        let node = build_directory(adder, &links)?;
+        Ok(Entry {
+            cid: node.cid,
+            tsize: node.tsize,
+            kind: Kind::Directory {
+                node,
+                entries: named.into_iter().map(|(_, entry)| entry).collect(),
+            },
+        })
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} is not a file, directory or symlink", path.display()),
+        ))
+    }
+}
+
+fn utf8(name: std::ffi::OsString) -> io::Result<String> {
+    name.into_string().map_err(|name| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("name {:?} is not valid UTF-8", name),
+        )
+    })
+}
+
+/// Encode a directory of `entries` sorted by name, as a single node or HAMT shards
+fn build_directory(adder: &FileAdder, entries: &[(&str, Cid, u64)]) -> io::Result<DirNode> {
+    let estimated_size: usize = entries
+        .iter()
+        .map(|(name, cid, _)| name.len() + cid.to_bytes().len())
+        .sum();
+
+    if estimated_size < HAMT_SHARDING_SIZE {
+        let links = entries
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _, _))| (name.to_string(), DirLink::Entry(index)))
+            .collect();
+        let data = UnixFs {
+            Type: UnixFsType::Directory,
+            ..Default::default()
+        };
+        Ok(encode_dir_node(adder, entries, links, data))
+    } else {
+        let mut shard = Shard::default();
+        for (index, (name, _, _)) in entries.iter().enumerate() {
+            shard.insert(0, &murmur3_x64_64(name.as_bytes()), index, entries)?;
+        }
+        Ok(shard.encode(adder, entries))
+    }
+}
+
+/// Encode a directory node or HAMT shard with its named `links`
+fn encode_dir_node(
+    adder: &FileAdder,
+    entries: &[(&str, Cid, u64)],
+    links: Vec<(String, DirLink)>,
+    data: UnixFs<'_>,
+) -> DirNode {
+    let (pb_links, links_tsize): (Vec<_>, Vec<_>) = links
+        .iter()
+        .map(|(name, link)| {
+            let (cid, tsize) = match link {
+                DirLink::Entry(index) => (entries[*index].1, entries[*index].2),
+                DirLink::Shard(node) => (node.cid, node.tsize),
+            };
+            let pb_link = PBLink {
+                Hash: Some(Cow::Owned(cid.to_bytes())),
+                Name: Some(Cow::Borrowed(name.as_str())),
+                Tsize: Some(tsize),
+            };
+            (pb_link, tsize)
+        })
+        .unzip();
+
+    let block = encode_node(pb_links, data);
+    DirNode {
+        cid: adder.cid(DAG_PB, &block),
+        tsize: block.len() as u64 + links_tsize.iter().sum::<u64>(),
+        block,
+        links: links.into_iter().map(|(_, link)| link).collect(),
+    }
+}
+
+/// HAMT shard under construction, with the same layout as kubo's: each level consumes one byte of
+/// the name hash, and a slot holds a sub-shard only if more than one name shares its prefix
+#[derive(Default)]
+struct Shard {
+    slots: BTreeMap<u8, Slot>,
+}
+
+enum Slot {
+    Entry(usize),
+    Shard(Shard),
+}
+
+impl Shard {
+    fn insert(
+        &mut self,
+        depth: usize,
+        hash: &[u8; 8],
+        index: usize,
+        entries: &[(&str, Cid, u64)],
+    ) -> io::Result<()> {
+        let slot_index = *hash.get(depth).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("HAMT hash collision on {:?}", entries[index].0),
+            )
+        })?;
+
+        let slot = match self.slots.remove(&slot_index) {
+            None => Slot::Entry(index),
+            Some(Slot::Shard(mut shard)) => {
+                shard.insert(depth + 1, hash, index, entries)?;
+                Slot::Shard(shard)
+            }
+            Some(Slot::Entry(other)) => {
+                let mut shard = Shard::default();
+                shard.insert(depth + 1, hash, index, entries)?;
+                let other_hash = murmur3_x64_64(entries[other].0.as_bytes());
+                shard.insert(depth + 1, &other_hash, other, entries)?;
+                Slot::Shard(shard)
+            }
+        };
+        self.slots.insert(slot_index, slot);
+        Ok(())
+    }
+
+    fn encode(self, adder: &FileAdder, entries: &[(&str, Cid, u64)]) -> DirNode {
+        // Bit i of the bitfield is set if slot i is used, as a big endian integer without
+        // leading zero bytes
+        let mut bitfield = [0u8; HAMT_FANOUT as usize / 8];
+        let mut links = vec![];
+        for (slot_index, slot) in self.slots {
+            bitfield[HAMT_FANOUT as usize / 8 - 1 - slot_index as usize / 8] |=
+                1 << (slot_index % 8);
+            // Links are named with the slot in hex, followed by the entry name for entries
+            links.push(match slot {
+                Slot::Entry(index) => (
+                    format!("{:02X}{}", slot_index, entries[index].0),
+                    DirLink::Entry(index),
+                ),
+                Slot::Shard(shard) => (
+                    format!("{:02X}", slot_index),
+                    DirLink::Shard(shard.encode(adder, entries)),
+                ),
+            });
+        }
+        let leading_zeros = bitfield.iter().take_while(|byte| **byte == 0).count();
+
+        let data = UnixFs {
+            Type: UnixFsType::HAMTShard,
+            Data: Some(Cow::Owned(bitfield[leading_zeros..].to_vec())),
+            hashType: Some(HASH_MURMUR3),
+            fanout: Some(HAMT_FANOUT),
+            ..Default::default()
+        };
+        encode_dir_node(adder, entries, links, data)
+    }
+}
+
+/// Write the blocks of `entry` not in `written` yet, each node before its children
+fn write_entry<W: Write>(
+    out: &mut W,
+    entry: &Entry,
+    adder: &FileAdder,
+    written: &mut HashSet<Cid>,
+) -> io::Result<()> {
+    match &entry.kind {
+        Kind::File {
+            path,
+            size,
+            branches,
+        } => write_file(out, (entry.cid, *size), path, branches, adder, written),
+        Kind::Symlink { block } => write_car_block_once(out, &entry.cid, block, written),
+        Kind::Directory { node, entries } => write_dir_node(out, node, entries, adder, written),
+    }
+}
+
+fn write_dir_node<W: Write>(
+    out: &mut W,
+    node: &DirNode,
+    entries: &[Entry],
+    adder: &FileAdder,
+    written: &mut HashSet<Cid>,
+) -> io::Result<()> {
+    write_car_block_once(out, &node.cid, &node.block, written)?;
+    for link in &node.links {
+        match link {
+            DirLink::Shard(shard) => write_dir_node(out, shard, entries, adder, written)?,
+            DirLink::Entry(index) => write_entry(out, &entries[*index], adder, written)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write the file DAG of `root` and its file size reading its leaves again from `path`, in file
+/// order
+fn write_file<W: Write>(
+    out: &mut W,
+    root: (Cid, u64),
+    path: &Path,
+    branches: &Branches,
+    adder: &FileAdder,
+    written: &mut HashSet<Cid>,
+) -> io::Result<()> {
+    let mut file = fs::File::open(path)?;
+    let mut pending = vec![root];
+
+    while let Some((cid, size)) = pending.pop() {
+        match branches.get(&cid) {
+            Some((block, children)) => {
+                write_car_block_once(out, &cid, block, written)?;
+                pending.extend(children.iter().rev());
+            }
+            None => {
+                let mut chunk = Vec::with_capacity(size as usize);
+                (&mut file).take(size).read_to_end(&mut chunk)?;
+                let (leaf_cid, block) = adder.leaf(chunk);
+                if leaf_cid != cid {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} changed during import", path.display()),
+                    ));
+                }
+                write_car_block_once(out, &cid, &block, written)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_car_header<W: Write>(out: &mut W, root: &Cid) -> io::Result<()> {
+    out.write_all(&car_header(root))
+}
+
+/// CARv1 header with a single root, dag-cbor `{"roots": [root], "version": 1}`, with its length
+/// prefix
+pub(crate) fn car_header(root: &Cid) -> Vec<u8> {
+    let mut header = vec![0xa2];
+    write_cbor_head(&mut header, 3, 5);
+    header.extend_from_slice(b"roots");
+    write_cbor_head(&mut header, 4, 1);
+    // Tag 42 with the CID bytes prefixed by the multibase identity prefix
+    header.extend_from_slice(&[0xd8, 0x2a]);
+    let cid_bytes = root.to_bytes();
+    write_cbor_head(&mut header, 2, cid_bytes.len() as u64 + 1);
+    header.push(0x00);
+    header.extend_from_slice(&cid_bytes);
+    write_cbor_head(&mut header, 3, 7);
+    header.extend_from_slice(b"version");
+    header.push(0x01);
+
+    let mut prefixed = vec![];
+    write_varint(&mut prefixed, header.len() as u64);
+    prefixed.extend_from_slice(&header);
+    prefixed
+}
+
+/// Length prefix and CID of the CAR section of a block of `block_len` bytes, written before it
+pub(crate) fn block_section_head(cid: &Cid, block_len: usize) -> Vec<u8> {
+    let cid_bytes = cid.to_bytes();
+    let mut head = vec![];
+    write_varint(&mut head, (cid_bytes.len() + block_len) as u64);
+    head.extend_from_slice(&cid_bytes);
+    head
+}
+
+fn write_car_block_once<W: Write>(
+    out: &mut W,
+    cid: &Cid,
+    block: &[u8],
+    written: &mut HashSet<Cid>,
+) -> io::Result<()> {
+    if !written.insert(*cid) {
+        return Ok(());
+    }
+    out.write_all(&block_section_head(cid, block.len()))?;
+    out.write_all(block)
+}
+
+fn write_cbor_head(buf: &mut Vec<u8>, major: u8, len: u64) {
+    let major = major << 5;
+    match len {
+        0..=23 => buf.push(major | len as u8),
+        24..=0xff => buf.extend_from_slice(&[major | 24, len as u8]),
+        _ => {
+            buf.push(major | 25);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::build_directory;
+    use crate::adder::ChunkerProfile;
+    use rs_car::Cid;
+
+    /// Directories created by go-ipfs linking the file "foobar\n" added with 5 blocks
+    #[test]
+    fn directory_matches_go_ipfs() {
+        let adder = ChunkerProfile::KuboDefaultV0.file_adder();
+        let foobar = Cid::try_from("QmRJHYTNvC3hmd9gJQARxLR1QMEincccBV53bBw524yyq6").unwrap();
+
+        let dir = build_directory(&adder, &[("a", foobar, 221)]).unwrap();
+        assert_eq!(
+            dir.cid.to_string(),
+            "QmQBseoi3b2FBrYhjM2E4mCF4Q7C8MgCUbzAbGNfyVwgNk"
+        );
+        let dir = build_directory(&adder, &[("a", foobar, 221), ("b", foobar, 221)]).unwrap();
+        assert_eq!(
+            dir.cid.to_string(),
+            "QmdbWuhpVCX9weVMMqvVTMeGwKMqCNJDbx7ZK1zG36sea7"
+        );
+        let dir = build_directory(&adder, &[]).unwrap();
+        assert_eq!(
+            dir.cid.to_string(),
+            "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+        );
+    }
+}
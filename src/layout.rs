@@ -0,0 +1,259 @@
+//! Linear layout of a UnixFS file DAG, to write its leaves in file order while its blocks are
+//! received in any order.
+//!
+//! The layout starts with the root CID as the only pending node. Nodes with links are expanded
+//! in place into their children once they are the next pending node, until only leaves remain.
+//! Each leaf is consumed when it is the next pending node, so the order of the consumed leaves is
+//! the order of the data in the file. The same CID can appear multiple times in the layout for
+//! files with duplicated content, but never under itself: blocks that do not match their CID can
+//! link an ancestor, check [`FileLayout::is_ancestor`] before expanding a node to reject such a
+//! cycle instead of expanding it forever.
+//!
+//! Nodes are matched by the block they address, a CIDv0 link is fed with the CIDv1 of its block
+//! and the other way around, see [`same_block`].
+//!
+//! # Examples
+//!
+//! ```
+//! use rs_car_ipfs::layout::{FileLayout, LeafDisposition};
+//! # use rs_car_ipfs::Cid;
+//! # let root = Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf").unwrap();
+//! # let (a, b) = (Cid::default(), Cid::default());
+//!
+//! let mut layout = FileLayout::new(root);
+//! assert_eq!(layout.next_ready(), Some(root));
+//! assert!(layout.feed_links(&root, 0, vec![a, b]));
+//! assert_eq!(layout.feed_leaf(&a, 4), LeafDisposition::Next);
+//! assert_eq!(layout.feed_leaf(&b, 4), LeafDisposition::Next);
+//! assert!(layout.is_complete());
+//! assert_eq!(layout.offset(), 8);
+//! ```
+
+use rs_car::Cid;
+
+use crate::cid_version::same_block;
+
+/// Pending nodes of a file DAG in file order, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct FileLayout {
+    /// Pending nodes in reverse file order, the next one is last
+    pending: Vec<Cid>,
+    offset: u64,
+    /// Expanded nodes from the root, with the length of `pending` without their children. Entries
+    /// of a fully consumed node are only removed on the next expansion
+    ancestors: Vec<(Cid, usize)>,
+}
+
+/// Result of feeding a leaf to a [`FileLayout`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeafDisposition {
+    /// The leaf was the next node of the layout and is consumed, its data goes at the offset
+    /// before the call
+    Next,
+    /// The leaf is pending but other nodes come before it
+    Deferred,
+    /// The leaf is not in the layout, either not linked by an expanded node yet or not part of
+    /// the file
+    Unknown,
+}
+
+impl FileLayout {
+    pub fn new(root: Cid) -> Self {
+        Self {
+            pending: vec![root],
+            offset: 0,
+            ancestors: vec![],
+        }
+    }
+
+    /// The next pending node, which is the only one that can be fed
+    pub fn next_ready(&self) -> Option<Cid> {
+        self.pending.last().copied()
+    }
+
+    /// Expand the links node `cid` into its `children` if it is the next pending node. Its inline
+    /// data of `data_len` bytes, if any, goes before its children. Returns false, without changes,
+    /// if `cid` is not the next pending node.
+    pub fn feed_links(&mut self, cid: &Cid, data_len: u64, children: Vec<Cid>) -> bool {
+        if !self
+            .pending
+            .last()
+            .is_some_and(|next| same_block(next, cid))
+        {
+            return false;
+        }
+        self.pending.pop();
+        let len = self.pending.len();
+        while self.ancestors.last().is_some_and(|(_, end)| *end > len) {
+            self.ancestors.pop();
+        }
+        self.ancestors.push((*cid, len));
+        self.pending.extend(children.into_iter().rev());
+        self.offset = self.offset.saturating_add(data_len);
+        true
+    }
+
+    /// Whether `cid` is an expanded node with the next pending node under it. Expanding the next
+    /// node if it is `cid` would be a cycle
+    pub fn is_ancestor(&self, cid: &Cid) -> bool {
+        // Lengths never decrease from the root, nodes not expanded anymore are at the end
+        self.ancestors
+            .iter()
+            .take_while(|(_, end)| *end < self.pending.len())
+            .any(|(ancestor, _)| same_block(ancestor, cid))
+    }
+
+    /// Consume the leaf `cid` of `len` bytes if it is the next pending node
+    pub fn feed_leaf(&mut self, cid: &Cid, len: u64) -> LeafDisposition {
+        let disposition = self.disposition(cid);
+        if disposition == LeafDisposition::Next {
+            self.pending.pop();
+            self.offset = self.offset.saturating_add(len);
+        }
+        disposition
+    }
+
+    /// What [`feed_leaf`](Self::feed_leaf) would do with `cid`, without consuming it
+    pub fn disposition(&self, cid: &Cid) -> LeafDisposition {
+        if self
+            .pending
+            .last()
+            .is_some_and(|next| same_block(next, cid))
+        {
+            LeafDisposition::Next
+        } else if self.pending.iter().any(|pending| same_block(pending, cid)) {
+            LeafDisposition::Deferred
+        } else {
+            LeafDisposition::Unknown
+        }
+    }
+
+    /// Pending nodes in file order, as [`remaining`](Self::remaining) without copying them
+    pub fn pending(&self) -> impl Iterator<Item = &Cid> {
+        self.pending.iter().rev()
+    }
+
+    /// Count of pending nodes, the length of [`pending`](Self::pending)
+    pub fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Length of the file laid out so far, the leaves and inline data consumed
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Whether all nodes of the file were consumed
+    pub fn is_complete(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Pending nodes in file order
+    pub fn remaining(&self) -> Vec<Cid> {
+        self.pending.iter().rev().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use multihash::{Code, MultihashDigest};
+
+    fn cid(name: &str) -> Cid {
+        Cid::new_v1(0x55, Code::Sha2_256.digest(name.as_bytes()))
+    }
+
+    #[test]
+    fn expansion() {
+        let [root, a, b, c, d] = ["root", "a", "b", "c", "d"].map(cid);
+        let mut layout = FileLayout::new(root);
+
+        // Only the next node can be expanded
+        assert!(!layout.feed_links(&a, 0, vec![c]));
+        assert!(layout.feed_links(&root, 2, vec![a, b]));
+        assert_eq!(layout.remaining(), vec![a, b]);
+        assert_eq!(layout.offset(), 2);
+
+        // Nested expansion keeps file order
+        assert!(layout.feed_links(&a, 0, vec![c, d]));
+        assert_eq!(layout.remaining(), vec![c, d, b]);
+        for leaf in [c, d, b] {
+            assert_eq!(layout.next_ready(), Some(leaf));
+            assert_eq!(layout.feed_leaf(&leaf, 3), LeafDisposition::Next);
+        }
+        assert!(layout.is_complete());
+        assert_eq!(layout.next_ready(), None);
+        assert_eq!(layout.offset(), 11);
+    }
+
+    #[test]
+    fn reordering() {
+        let [root, a, b, other] = ["root", "a", "b", "other"].map(cid);
+        let mut layout = FileLayout::new(root);
+
+        // Leaves before their parent are not known yet
+        assert_eq!(layout.feed_leaf(&b, 1), LeafDisposition::Unknown);
+        layout.feed_links(&root, 0, vec![a, b]);
+        assert_eq!(layout.feed_leaf(&b, 1), LeafDisposition::Deferred);
+        assert_eq!(layout.pending().collect::<Vec<_>>(), vec![&a, &b]);
+        assert_eq!(layout.feed_leaf(&other, 1), LeafDisposition::Unknown);
+        assert_eq!(layout.offset(), 0);
+
+        assert_eq!(layout.feed_leaf(&a, 1), LeafDisposition::Next);
+        assert_eq!(layout.feed_leaf(&b, 1), LeafDisposition::Next);
+        assert!(layout.is_complete());
+    }
+
+    #[test]
+    fn dedup() {
+        let [root, branch, a, b] = ["root", "branch", "a", "b"].map(cid);
+        let mut layout = FileLayout::new(root);
+
+        // The same branch and leaf twice
+        layout.feed_links(&root, 0, vec![branch, a, branch]);
+        layout.feed_links(&branch, 0, vec![a, b]);
+        assert_eq!(layout.remaining(), vec![a, b, a, branch]);
+
+        assert_eq!(layout.feed_leaf(&a, 1), LeafDisposition::Next);
+        assert_eq!(layout.feed_leaf(&a, 1), LeafDisposition::Deferred);
+        assert_eq!(layout.feed_leaf(&b, 1), LeafDisposition::Next);
+        assert_eq!(layout.feed_leaf(&a, 1), LeafDisposition::Next);
+        assert!(layout.feed_links(&branch, 0, vec![a, b]));
+        assert_eq!(layout.feed_leaf(&a, 1), LeafDisposition::Next);
+        assert_eq!(layout.feed_leaf(&b, 1), LeafDisposition::Next);
+        assert!(layout.is_complete());
+        assert_eq!(layout.offset(), 5);
+    }
+
+    #[test]
+    fn ancestors() {
+        let [root, branch, a, b] = ["root", "branch", "a", "b"].map(cid);
+        let mut layout = FileLayout::new(root);
+        assert!(!layout.is_ancestor(&root));
+
+        // A branch linking itself, through its child
+        layout.feed_links(&root, 0, vec![branch, branch, a]);
+        layout.feed_links(&branch, 0, vec![b, branch]);
+        assert!(layout.is_ancestor(&root) && layout.is_ancestor(&branch));
+        layout.feed_leaf(&b, 1);
+        assert_eq!(layout.next_ready(), Some(branch));
+        assert!(layout.is_ancestor(&branch));
+
+        // Its second link from the root is not under it
+        let mut layout = FileLayout::new(root);
+        layout.feed_links(&root, 0, vec![branch, branch, a]);
+        layout.feed_links(&branch, 0, vec![b]);
+        layout.feed_leaf(&b, 1);
+        assert_eq!(layout.next_ready(), Some(branch));
+        assert!(!layout.is_ancestor(&branch));
+        layout.feed_links(&branch, 0, vec![b]);
+        assert!(layout.is_ancestor(&branch));
+        layout.feed_leaf(&b, 1);
+        assert!(!layout.is_ancestor(&branch) && layout.is_ancestor(&root));
+
+        // The root is not an ancestor of nothing
+        layout.feed_leaf(&a, 1);
+        assert!(layout.is_complete());
+        assert!(!layout.is_ancestor(&root));
+    }
+}
@@ -4,8 +4,37 @@
 //!
 //! - To read a single file buffering the block dag [`single_file::read_single_file_buffer`]
 //! - To read a single file without buffering the block dag [`single_file::read_single_file_seek`]
+//! - To read a single file from a seekable CAR with a block index [`single_file::read_single_file_indexed`]
+//! - To read a single file from synchronous code (feature `tokio` to support tokio runtimes) [`blocking::read_single_file_seek`]
+//! - To read a gzip or zstd compressed CAR (features `gzip`, `zstd`) [`decompress::decompress_auto`]
+//! - To import a local directory tree into a CAR [`import::write_directory_car`]
+//! - To import a local file into a CAR with the chunker settings of `ipfs add` [`import::write_file_car`]
+//! - To display or match a root CID in its other version [`cid_version::to_cidv1`] and
+//!   [`cid_version::to_cidv0`]
+//! - To find the bucket of an entry in a HAMT sharded directory [`hamt::find_shard_link`]
+//! - To lay out a file DAG in file order for a custom reader [`layout::FileLayout`]
+//! - To report errors with a stable code and fields, e.g. as JSON (feature `serde`)
+//!   [`single_file::ReadSingleFileError::code`] and [`single_file::ErrorDetails`]
+//! - To log the header, blocks, write limit and summary of each read (feature `log`) through the
+//!   [log](https://crates.io/crates/log) facade, at debug and trace levels
 
+/// Event of the `log` facade with the `log` feature, removed at compile time without it
+macro_rules! log_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::$level!($($arg)+);
+    };
+}
+
+pub mod adder;
+pub mod blocking;
+pub mod cid_version;
+pub mod decompress;
+pub mod hamt;
+pub mod import;
+pub mod layout;
 mod pb;
 pub mod single_file;
 
+pub use pb::UnixFsType;
 pub use rs_car::Cid;
@@ -14,9 +14,42 @@ pub(crate) use merkledag::PBLink;
 pub(crate) use merkledag::PBNode;
 
 pub(crate) mod unixfs;
-pub(crate) use unixfs::mod_Data::DataType as UnixFsType;
+pub use unixfs::mod_Data::DataType as UnixFsType;
 pub(crate) use unixfs::Data as UnixFs;
 
+const WIRE_TYPE_START_GROUP: u32 = 3;
+const WIRE_TYPE_END_GROUP: u32 = 4;
+
+/// Skip the unknown field of `tag`, e.g. a future extension, as `BytesReader::read_unknown` does,
+/// also skipping deprecated groups, which it rejects. Fails only on malformed wire data.
+pub(crate) fn skip_unknown(
+    r: &mut quick_protobuf::BytesReader,
+    bytes: &[u8],
+    tag: u32,
+) -> ProtobufResult<()> {
+    // Field numbers of the groups being skipped, nested groups are not recursed into
+    let mut groups = vec![];
+    let mut tag = tag;
+    loop {
+        match tag & 0x7 {
+            WIRE_TYPE_START_GROUP => groups.push(tag >> 3),
+            WIRE_TYPE_END_GROUP => {
+                if groups.pop() != Some(tag >> 3) {
+                    return Err(quick_protobuf::Error::Message(format!(
+                        "unmatched end of group {}",
+                        tag >> 3
+                    )));
+                }
+            }
+            _ => r.read_unknown(bytes, tag)?,
+        }
+        if groups.is_empty() {
+            return Ok(());
+        }
+        tag = r.next_tag(bytes)?;
+    }
+}
+
 /// Failure cases for nested serialization, which allows recovery of the outer `PBNode` when desired.
 #[derive(Debug)]
 pub(crate) enum ParsingFailed<'a> {
@@ -36,12 +36,16 @@ impl<'a> MessageRead<'a> for Data<'a> {
                 Ok(18) => msg.Data = Some(r.read_bytes(bytes).map(Cow::Borrowed)?),
                 Ok(24) => msg.filesize = Some(r.read_uint64(bytes)?),
                 Ok(32) => msg.blocksizes.push(r.read_uint64(bytes)?),
+                // Packed encoding, which proto2 parsers must accept for repeated scalars
+                Ok(34) => msg
+                    .blocksizes
+                    .extend(r.read_packed(bytes, |r, bytes| r.read_uint64(bytes))?),
                 Ok(40) => msg.hashType = Some(r.read_uint64(bytes)?),
                 Ok(48) => msg.fanout = Some(r.read_uint64(bytes)?),
                 Ok(56) => msg.mode = Some(r.read_uint32(bytes)?),
                 Ok(66) => msg.mtime = Some(r.read_message::<UnixTime>(bytes)?),
                 Ok(t) => {
-                    r.read_unknown(bytes, t)?;
+                    skip_unknown(r, bytes, t)?;
                 }
                 Err(e) => return Err(e),
             }
@@ -173,7 +177,7 @@ impl<'a> MessageRead<'a> for UnixTime {
                 Ok(8) => msg.Seconds = r.read_int64(bytes)?,
                 Ok(21) => msg.FractionalNanoseconds = Some(r.read_fixed32(bytes)?),
                 Ok(t) => {
-                    r.read_unknown(bytes, t)?;
+                    skip_unknown(r, bytes, t)?;
                 }
                 Err(e) => return Err(e),
             }
@@ -206,7 +210,7 @@ impl<'a> MessageRead<'a> for Metadata<'a> {
             match r.next_tag(bytes) {
                 Ok(10) => msg.MimeType = Some(r.read_string(bytes).map(Cow::Borrowed)?),
                 Ok(t) => {
-                    r.read_unknown(bytes, t)?;
+                    skip_unknown(r, bytes, t)?;
                 }
                 Err(e) => return Err(e),
             }
@@ -0,0 +1,192 @@
+use futures::{AsyncRead, AsyncSeek, AsyncWrite, StreamExt};
+use rs_car::{CarReader, Cid};
+use std::{
+    collections::VecDeque,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::cid_version::same_block;
+
+use super::{
+    framing::FramingGuard,
+    read_single_file_buffer_with_options, read_single_file_seek_with_options,
+    util::{assert_header_single_file, declared_file_size, decode_block},
+    ReadSingleFileError, ReadSingleFileOptions, ReadSummary, RootSelector,
+};
+
+/// Read the file of `car_input` with [`read_single_file_buffer`](super::read_single_file_buffer)
+/// if it fits in `memory_budget` bytes, else with
+/// [`read_single_file_seek`](super::read_single_file_seek).
+///
+/// The buffer reader accepts the blocks of the CAR in any order but keeps the data of the whole
+/// file in memory, the seek reader keeps only the links of the DAG and reads duplicated leaves
+/// back from `out`, but needs parents before their children. In `benches/backends.rs` both
+/// readers have about the same speed, the seek reader being about 6% faster on large files and
+/// up to 20% on deep or wide DAGs. The memory of the buffer reader peaks at about the length of
+/// the file, the seek reader at the links of the DAG, less than 1 MiB for a file of 64 MiB in
+/// chunks of 256 KiB. The buffer reader is thus worth its memory for files that fit in the
+/// budget, for its tolerance of the order of the blocks.
+///
+/// The size of the file is the declared size of its root, read from the first `memory_budget`
+/// bytes of blocks of the CAR, which are kept and replayed to the chosen reader, each chunk of
+/// them being released once replayed. A root of an unknown size, or a root not found within them,
+/// selects the buffer reader only if the CAR ends within them. With [`RootSelector::LastBlock`],
+/// which only the buffer reader supports, the buffer reader is always used. `options` are passed
+/// to the chosen reader, the buffer reader with
+/// [`max_buffer`](ReadSingleFileOptions::max_buffer) capped to `memory_budget`, so that a file
+/// declaring a smaller size than its data fails with
+/// [`MaxBufferedData`](ReadSingleFileError::MaxBufferedData) rather than exceeding the budget.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::read_single_file_auto;
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut out = Cursor::new(Vec::new());
+///
+///   read_single_file_auto(&mut input, &mut out, None, 64 << 20, &Default::default()).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_auto<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    memory_budget: usize,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let mut recording = Recording {
+        inner: &mut *car_input,
+        recorded: VecDeque::new(),
+    };
+    let exceeds = exceeds_budget(&mut recording, root_cid, memory_budget, options).await;
+
+    let mut replayed = Replay {
+        recorded: recording.recorded,
+        pos: 0,
+        inner: car_input,
+    };
+    if exceeds {
+        read_single_file_seek_with_options(&mut replayed, out, root_cid, options).await
+    } else {
+        let options = ReadSingleFileOptions {
+            max_buffer: Some(
+                options
+                    .max_buffer
+                    .map_or(memory_budget, |max_buffer| max_buffer.min(memory_budget)),
+            ),
+            ..options.clone()
+        };
+        read_single_file_buffer_with_options(&mut replayed, out, root_cid, &options).await
+    }
+}
+
+/// Whether the file of `car_input` is larger than `budget` bytes, or its CAR if the size of the
+/// file is not known once `budget` bytes of blocks are read. Errors are left to the reader
+async fn exceeds_budget<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    root_cid: Option<&Cid>,
+    budget: usize,
+    options: &ReadSingleFileOptions,
+) -> bool {
+    if root_cid.is_none() && options.root_selector == RootSelector::LastBlock {
+        return false;
+    }
+    let mut car_input = FramingGuard::new(car_input);
+    let Ok(mut streamer) = CarReader::new(&mut car_input, false).await else {
+        return false;
+    };
+    let Ok(root_cid) = assert_header_single_file(&streamer.header, root_cid) else {
+        return false;
+    };
+
+    let mut blocks_len = 0;
+    while let Some(Ok((cid, block))) = streamer.next().await {
+        blocks_len += block.len();
+        if same_block(&cid, &root_cid) {
+            let declared = decode_block(&cid, &block, false)
+                .ok()
+                .and_then(|(inner, links)| declared_file_size(&inner, &links));
+            if let Some(declared) = declared {
+                return declared > budget as u64;
+            }
+        }
+        if blocks_len > budget {
+            return true;
+        }
+    }
+    false
+}
+
+/// Size of the chunks of [`Recording`], released one by one as they are replayed
+const RECORDING_CHUNK: usize = 64 << 10;
+
+/// Reader keeping a copy of the bytes read from `inner`, in chunks of up to [`RECORDING_CHUNK`]
+struct Recording<'a, R> {
+    inner: &'a mut R,
+    recorded: VecDeque<Vec<u8>>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Recording<'_, R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let res = Pin::new(&mut *this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = res {
+            let mut read = &buf[..n];
+            while !read.is_empty() {
+                match this.recorded.back_mut() {
+                    Some(chunk) if chunk.len() < RECORDING_CHUNK => {
+                        let take = read.len().min(RECORDING_CHUNK - chunk.len());
+                        chunk.extend_from_slice(&read[..take]);
+                        read = &read[take..];
+                    }
+                    _ => this.recorded.push_back(Vec::with_capacity(RECORDING_CHUNK)),
+                }
+            }
+        }
+        res
+    }
+}
+
+/// Reader of the `recorded` chunks then of `inner`, dropping each chunk once read so the
+/// recording does not outlive the part of the CAR the reader has yet to consume
+struct Replay<'a, R> {
+    recorded: VecDeque<Vec<u8>>,
+    /// Offset within the first chunk
+    pos: usize,
+    inner: &'a mut R,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for Replay<'_, R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if let Some(chunk) = this.recorded.front() {
+            let n = buf.len().min(chunk.len() - this.pos);
+            buf[..n].copy_from_slice(&chunk[this.pos..this.pos + n]);
+            this.pos += n;
+            if this.pos == chunk.len() {
+                this.recorded.pop_front();
+                this.pos = 0;
+            }
+            return Poll::Ready(Ok(n));
+        }
+        Pin::new(&mut *this.inner).poll_read(cx, buf)
+    }
+}
@@ -0,0 +1,63 @@
+use rs_car::Cid;
+use std::{fmt, sync::Arc};
+
+use super::ReadSingleFileError;
+
+/// Decision of a [`BlockErrorHook`] on a block that failed to validate or decode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Continue the read, handling the block's region of the file as set by [`SkipFill`]
+    Skip,
+    /// Stop the read returning the block error
+    Abort,
+}
+
+/// How the region of the file covered by a skipped block is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SkipFill {
+    /// Write zeros
+    #[default]
+    Zeros,
+    /// Seek past the region leaving whatever `out` already contains. A skipped region at the end
+    /// of the file ends with a written zero byte, so that `out` is as long as the file. Only used
+    /// by [`read_single_file_seek`](super::read_single_file_seek), other readers write zeros.
+    Unwritten,
+}
+
+/// Callback called with the CID and error of a block that fails hash validation or can not be
+/// decoded as UnixFS, see [`on_block_error`](super::ReadSingleFileOptions::on_block_error).
+///
+/// ```
+/// use rs_car_ipfs::single_file::{BlockErrorHook, ErrorAction, ReadSingleFileOptions};
+///
+/// let options = ReadSingleFileOptions {
+///     on_block_error: Some(BlockErrorHook::new(|cid, err| {
+///         eprintln!("skipping block {}: {}", cid, err);
+///         ErrorAction::Skip
+///     })),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct BlockErrorHook(Arc<BlockErrorFn>);
+
+type BlockErrorFn = dyn Fn(&Cid, &ReadSingleFileError) -> ErrorAction + Send + Sync;
+
+impl BlockErrorHook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Cid, &ReadSingleFileError) -> ErrorAction + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, cid: &Cid, err: &ReadSingleFileError) -> ErrorAction {
+        (self.0)(cid, err)
+    }
+}
+
+impl fmt::Debug for BlockErrorHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BlockErrorHook")
+    }
+}
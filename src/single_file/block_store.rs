@@ -0,0 +1,138 @@
+use futures::future::BoxFuture;
+use rs_car::Cid;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// Blocks available outside of the CAR, e.g. kept from the extraction of a previous CAR sharing
+/// blocks with this one, see [`block_store`](super::ReadSingleFileOptions::block_store).
+///
+/// Blocks returned by `get` are validated against their CID like the blocks of the CAR.
+pub trait BlockStore: Send + Sync {
+    /// The block `cid`, if present in the store
+    fn get<'a>(&'a self, cid: &'a Cid) -> BoxFuture<'a, Option<Vec<u8>>>;
+
+    /// Called with every block read from the CAR that decoded as UnixFS, to keep it for the next
+    /// reads. Does nothing by default
+    fn put<'a>(&'a self, cid: &'a Cid, block: &'a [u8]) -> BoxFuture<'a, ()> {
+        let _ = (cid, block);
+        Box::pin(async {})
+    }
+}
+
+/// A store kept by the caller, e.g. to inspect it after the reads
+impl<S: BlockStore + ?Sized> BlockStore for Arc<S> {
+    fn get<'a>(&'a self, cid: &'a Cid) -> BoxFuture<'a, Option<Vec<u8>>> {
+        (**self).get(cid)
+    }
+
+    fn put<'a>(&'a self, cid: &'a Cid, block: &'a [u8]) -> BoxFuture<'a, ()> {
+        (**self).put(cid, block)
+    }
+}
+
+/// [`BlockStore`] shared by the reads using it, see
+/// [`block_store`](super::ReadSingleFileOptions::block_store).
+///
+/// ```
+/// use rs_car_ipfs::single_file::{MemoryBlockStore, ReadSingleFileOptions, SharedBlockStore};
+///
+/// let options = ReadSingleFileOptions {
+///     block_store: Some(SharedBlockStore::new(MemoryBlockStore::default())),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct SharedBlockStore(Arc<dyn BlockStore>);
+
+impl SharedBlockStore {
+    pub fn new<S: BlockStore + 'static>(store: S) -> Self {
+        Self(Arc::new(store))
+    }
+}
+
+impl fmt::Debug for SharedBlockStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedBlockStore")
+    }
+}
+
+/// [`BlockStore`] in memory, keeping every block put into it
+#[derive(Debug, Default)]
+pub struct MemoryBlockStore(Mutex<HashMap<Cid, Vec<u8>>>);
+
+impl MemoryBlockStore {
+    pub fn insert(&self, cid: Cid, block: Vec<u8>) {
+        self.blocks().insert(cid, block);
+    }
+
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.blocks().contains_key(cid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks().is_empty()
+    }
+
+    fn blocks(&self) -> std::sync::MutexGuard<'_, HashMap<Cid, Vec<u8>>> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl BlockStore for MemoryBlockStore {
+    fn get<'a>(&'a self, cid: &'a Cid) -> BoxFuture<'a, Option<Vec<u8>>> {
+        let block = self.blocks().get(cid).cloned();
+        Box::pin(async move { block })
+    }
+
+    fn put<'a>(&'a self, cid: &'a Cid, block: &'a [u8]) -> BoxFuture<'a, ()> {
+        self.insert(*cid, block.to_vec());
+        Box::pin(async {})
+    }
+}
+
+/// Lookups of a read in its optional block store, each CID at most once
+pub(crate) struct StoreLookups<'a> {
+    store: Option<&'a SharedBlockStore>,
+    looked_up: HashSet<Cid>,
+}
+
+impl<'a> StoreLookups<'a> {
+    pub(crate) fn new(store: Option<&'a SharedBlockStore>) -> Self {
+        Self {
+            store,
+            looked_up: HashSet::new(),
+        }
+    }
+
+    /// The first of `cids` found in the store, with its block, skipping CIDs already looked up
+    pub(crate) async fn find<'c>(
+        &mut self,
+        cids: impl Iterator<Item = &'c Cid>,
+    ) -> Option<(Cid, Vec<u8>)> {
+        let store = self.store?;
+        for cid in cids {
+            if !self.looked_up.insert(*cid) {
+                continue;
+            }
+            if let Some(block) = store.0.get(cid).await {
+                return Some((*cid, block));
+            }
+        }
+        None
+    }
+
+    pub(crate) async fn put(&self, cid: &Cid, block: &[u8]) {
+        if let Some(store) = self.store {
+            store.0.put(cid, block).await
+        }
+    }
+}
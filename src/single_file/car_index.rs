@@ -0,0 +1,232 @@
+use futures::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+use rs_car::{CarReader, Cid};
+use std::{collections::HashMap, io::SeekFrom};
+
+use crate::cid_version::{to_cidv0, to_cidv1};
+
+use super::{framing::FramingGuard, ReadSingleFileError};
+
+/// CARv2 pragma + fixed header length, see <https://ipld.io/specs/transport/car/carv2/>
+const CARV2_PRAGMA_SIZE: u64 = 11;
+const CARV2_HEADER_SIZE: usize = 40;
+/// Arbitrary high value to prevent big allocations, same as rs-car
+const MAX_BLOCK_LEN: u64 = 1073741824;
+
+/// Map of block CID to the byte offset of its section within a CAR file. The offset points to
+/// the leading varint of the section `[varint|CID|block]`, counted from the start of the CAR
+/// (including the CARv2 pragma and header if present).
+///
+/// Can be filled by the caller if block offsets are known before hand, or built by scanning a
+/// seekable CAR with [`CarIndex::build`].
+#[derive(Debug, Default, Clone)]
+pub struct CarIndex {
+    offsets: HashMap<Cid, u64>,
+}
+
+impl CarIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, cid: Cid, offset: u64) {
+        self.offsets.insert(cid, offset);
+    }
+
+    /// Offset of the block `cid`, indexed in the same or in the other CID version
+    pub fn get(&self, cid: &Cid) -> Option<u64> {
+        if let Some(offset) = self.offsets.get(cid) {
+            return Some(*offset);
+        }
+        let other = match to_cidv1(cid) {
+            v1 if v1 != *cid => v1,
+            _ => to_cidv0(cid).ok()?,
+        };
+        self.offsets.get(&other).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// CID of the block at the largest offset, the last block of the CAR
+    pub fn last_block(&self) -> Option<Cid> {
+        self.offsets
+            .iter()
+            .max_by_key(|(_, offset)| **offset)
+            .map(|(cid, _)| *cid)
+    }
+
+    /// Scan the block sections of a CARv1 or CARv2 `car_input` recording the offset of each one.
+    /// Only the section headers are read, block data is skipped with seeks.
+    ///
+    /// Note: an existing CARv2 index section is not parsed, the data section is scanned instead.
+    pub async fn build<R: AsyncRead + AsyncSeek + Send + Unpin>(
+        car_input: &mut R,
+    ) -> Result<CarIndex, ReadSingleFileError> {
+        car_input.seek(SeekFrom::Start(0)).await?;
+
+        // Let rs-car parse and validate the header. The block stream is lazy, so the bytes it
+        // consumed end at the first block section
+        let mut guarded = FramingGuard::new(&mut *car_input);
+        let characteristics_v2 = CarReader::new(&mut guarded, false)
+            .await?
+            .header
+            .characteristics_v2;
+        let mut offset = guarded.position();
+        car_input.seek(SeekFrom::Start(offset)).await?;
+
+        let data_end = match characteristics_v2 {
+            Some(_) => {
+                car_input.seek(SeekFrom::Start(CARV2_PRAGMA_SIZE)).await?;
+                let mut header = [0u8; CARV2_HEADER_SIZE];
+                car_input.read_exact(&mut header).await?;
+                let data_offset = u64_from_le(&header[16..24]);
+                let data_size = u64_from_le(&header[24..32]);
+                car_input.seek(SeekFrom::Start(offset)).await?;
+                Some(data_offset.saturating_add(data_size))
+            }
+            None => None,
+        };
+
+        let mut index = CarIndex::new();
+
+        loop {
+            if let Some(data_end) = data_end {
+                if offset >= data_end {
+                    break;
+                }
+            }
+
+            let section = match read_section_header(car_input, offset).await? {
+                Some(section) => section,
+                // EOF at the start of a section, CARv1 end of stream
+                None => break,
+            };
+
+            index.insert(section.cid, offset);
+
+            offset += section.varint_len as u64 + section.len;
+            car_input.seek(SeekFrom::Start(offset)).await?;
+        }
+
+        Ok(index)
+    }
+}
+
+/// Read the block section at `offset` of `car_input`, returning its CID and data. A block longer
+/// than `max_block_size` fails with [`BlockTooLarge`](ReadSingleFileError::BlockTooLarge) before
+/// it is read
+pub(crate) async fn read_block_at<R: AsyncRead + AsyncSeek + Unpin>(
+    car_input: &mut R,
+    offset: u64,
+    max_block_size: usize,
+) -> Result<(Cid, Vec<u8>), ReadSingleFileError> {
+    car_input.seek(SeekFrom::Start(offset)).await?;
+
+    let section = read_section_header(car_input, offset).await?.ok_or(
+        ReadSingleFileError::InvalidCarIndex(format!("no block section at offset {}", offset)),
+    )?;
+
+    let size = usize::try_from(section.len).unwrap_or(usize::MAX) - section.cid_len;
+    if size > max_block_size {
+        return Err(ReadSingleFileError::BlockTooLarge {
+            cid: section.cid,
+            size,
+            limit: max_block_size,
+        });
+    }
+
+    let data_start = offset + (section.varint_len + section.cid_len) as u64;
+    car_input.seek(SeekFrom::Start(data_start)).await?;
+    let mut data = vec![0u8; size];
+    car_input.read_exact(&mut data).await?;
+
+    Ok((section.cid, data))
+}
+
+/// Block section `[varint|CID|block]` prefix
+struct SectionHeader {
+    /// Value of the leading varint = CID + block length
+    len: u64,
+    varint_len: usize,
+    cid_len: usize,
+    cid: Cid,
+}
+
+/// Reads the `[varint|CID]` prefix of the block section at the current position of `r`, which
+/// must be `offset`. Returns None on EOF at the first byte, to signal the end of a CARv1 stream.
+///
+/// Reads ahead past the CID, the caller must seek to the next position it wants.
+async fn read_section_header<R: AsyncRead + Unpin>(
+    r: &mut R,
+    offset: u64,
+) -> Result<Option<SectionHeader>, ReadSingleFileError> {
+    // Max size of u64 varint + generous bound for supported CID lengths
+    let mut buf = [0u8; 10 + 128];
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = r.read(&mut buf[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    if filled == 0 {
+        return Ok(None);
+    }
+    let buf = &buf[..filled];
+
+    let (len, varint_len) = decode_varint_u64(buf).ok_or(ReadSingleFileError::InvalidCarIndex(
+        format!("invalid block section varint at offset {}", offset),
+    ))?;
+    if len == 0 || len > MAX_BLOCK_LEN {
+        return Err(ReadSingleFileError::InvalidCarIndex(format!(
+            "invalid block section length {} at offset {}",
+            len, offset
+        )));
+    }
+
+    let mut cid_bytes = &buf[varint_len..];
+    let cid = Cid::read_bytes(&mut cid_bytes)
+        .map_err(|err| ReadSingleFileError::InvalidCarIndex(err.to_string()))?;
+    let cid_len = buf.len() - varint_len - cid_bytes.len();
+    if cid_len as u64 > len {
+        return Err(ReadSingleFileError::InvalidCarIndex(format!(
+            "block section length {} shorter than its CID at offset {}",
+            len, offset
+        )));
+    }
+
+    Ok(Some(SectionHeader {
+        len,
+        varint_len,
+        cid_len,
+        cid,
+    }))
+}
+
+fn decode_varint_u64(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut result: u64 = 0;
+
+    // Max size of u64 varint
+    for (i, byte) in buf.iter().take(10).enumerate() {
+        result |= u64::from(byte & 0b0111_1111) << (i * 7);
+
+        // If is last byte = leftmost bit is zero
+        if byte & 0b1000_0000 == 0 {
+            return Some((result, i + 1));
+        }
+    }
+
+    None
+}
+
+fn u64_from_le(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(bytes);
+    u64::from_le_bytes(buf)
+}
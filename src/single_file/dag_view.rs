@@ -0,0 +1,187 @@
+use futures::{stream, AsyncRead, AsyncWrite, StreamExt};
+use rs_car::{CarReader, Cid};
+use std::collections::{HashMap, HashSet};
+
+use crate::cid_version::same_block;
+
+use super::{
+    framing::FramingGuard,
+    inspect::node_info,
+    single_file_buffer::read_blocks,
+    util::{decode_block, single_root},
+    validate_pool::ValidationPool,
+    ReadSingleFileError, ReadSingleFileOptions, ReadSummary, UnixFsNodeInfo,
+};
+
+/// Read every block of a CAR into memory, to inspect its DAG and extract several files or
+/// subtrees of it without streaming the CAR again, see [`DagView`].
+///
+/// Blocks are validated against their CID as they are read, the read fails on the first invalid
+/// block. Blocks that are not UnixFS are kept without links, and only fail a
+/// [`write_file`](DagView::write_file) that reaches them. `max_blocks` limits the count of blocks
+/// read and `max_buffer` the total length of the blocks kept, failing with
+/// [`MaxBufferedData`](ReadSingleFileError::MaxBufferedData).
+///
+/// ```
+/// use futures::io::Cursor;
+/// use rs_car_ipfs::single_file::load_dag;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let dag = load_dag(&mut input, &Default::default()).await?;
+///
+///   let mut out = Cursor::new(Vec::new());
+///   dag.write_file(None, &mut out, &Default::default()).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn load_dag<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    options: &ReadSingleFileOptions,
+) -> Result<DagView, ReadSingleFileError> {
+    // With `validation_concurrency`, blocks are hashed on the workers of `pool` instead
+    let pool = options.validation_pool()?;
+    let mut car_input =
+        FramingGuard::new(car_input).with_max_block_size(options.block_size_limit());
+    let streamer = CarReader::new(&mut car_input, pool.is_none()).await?;
+    let roots = streamer.header.roots.clone();
+    let mut streamer = ValidationPool::validate(pool.as_ref(), streamer);
+
+    let mut blocks = HashMap::new();
+    let mut links = HashMap::new();
+    let mut order = vec![];
+    let mut buffered_len: usize = 0;
+    while let Some(item) = streamer.next().await {
+        let (cid, block) = item?;
+        order.push(cid);
+        options.check_blocks_read(order.len())?;
+        options.check_block_size(&cid, block.len())?;
+        if let Some(max_buffer) = options.max_buffer {
+            buffered_len += block.len();
+            if buffered_len > max_buffer {
+                return Err(ReadSingleFileError::MaxBufferedData(max_buffer));
+            }
+        }
+        let block_links = decode_block(&cid, &block, false)
+            .map(|(_, links)| links)
+            .unwrap_or_default();
+        links.insert(cid, block_links);
+        blocks.insert(cid, block);
+    }
+    drop(streamer);
+
+    Ok(DagView {
+        roots,
+        blocks,
+        links,
+        order,
+        car_bytes_read: car_input.position(),
+    })
+}
+
+/// Blocks of a CAR held in memory by [`load_dag`], with the links of each block.
+///
+/// Files are written from the view with the checks of
+/// [`read_single_file_buffer`](super::read_single_file_buffer), as many times as needed and from
+/// any node of the view. The blocks of the file are copied for each write.
+#[derive(Debug, Clone)]
+pub struct DagView {
+    roots: Vec<Cid>,
+    blocks: HashMap<Cid, Vec<u8>>,
+    links: HashMap<Cid, Vec<Cid>>,
+    /// CIDs in CAR order, with duplicated blocks once each time they were read
+    order: Vec<Cid>,
+    car_bytes_read: u64,
+}
+
+impl DagView {
+    /// Roots of the CAR header
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Count of distinct blocks
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// CIDs of the blocks in CAR order, duplicated blocks of the CAR included
+    pub fn cids(&self) -> &[Cid] {
+        &self.order
+    }
+
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.blocks.contains_key(cid)
+    }
+
+    pub fn block(&self, cid: &Cid) -> Option<&[u8]> {
+        self.blocks.get(cid).map(|block| block.as_slice())
+    }
+
+    /// Links of `cid` in order, empty for leaves and blocks that are not UnixFS. None if the
+    /// block is not in the CAR
+    pub fn links(&self, cid: &Cid) -> Option<&[Cid]> {
+        self.links.get(cid).map(|links| links.as_slice())
+    }
+
+    /// The UnixFS node `cid`, with the metadata of its links. None if the block is not in the CAR
+    pub fn node(&self, cid: &Cid) -> Option<Result<UnixFsNodeInfo, ReadSingleFileError>> {
+        let block = self.blocks.get(cid)?;
+        Some(decode_block(cid, block, false).map(|(inner, links)| node_info(&inner, links)))
+    }
+
+    /// Length of the CAR read by [`load_dag`]
+    pub fn car_bytes_read(&self) -> u64 {
+        self.car_bytes_read
+    }
+
+    /// Write the file `root_cid`, or the single root of the CAR header if None, to `out`. The
+    /// root can be any node of the view, e.g. a subtree of a larger file. Blocks are already
+    /// validated, and [`ReadSummary::car_bytes_read`] is 0
+    pub async fn write_file<W: AsyncWrite + Unpin>(
+        &self,
+        root_cid: Option<&Cid>,
+        out: &mut W,
+        options: &ReadSingleFileOptions,
+    ) -> Result<ReadSummary, ReadSingleFileError> {
+        let root_cid = single_root(&self.roots, root_cid)?;
+        // The root may be in the view in another CID version than requested
+        let root_block = match self.blocks.contains_key(&root_cid) {
+            true => Some(root_cid),
+            false => self
+                .blocks
+                .keys()
+                .find(|cid| same_block(cid, &root_cid))
+                .copied(),
+        };
+        let reachable = root_block.map_or(vec![], |root| self.reachable(&root));
+        let blocks = stream::iter(
+            reachable
+                .into_iter()
+                .map(|cid| Ok::<_, ReadSingleFileError>((cid, self.blocks[&cid].clone()))),
+        );
+        read_blocks(blocks, false, out, root_cid, &self.roots, options, &mut 0).await
+    }
+
+    /// Blocks of the view under `root`, itself included, in depth-first order
+    fn reachable(&self, root: &Cid) -> Vec<Cid> {
+        let mut seen = HashSet::new();
+        let mut reachable = vec![];
+        let mut stack = vec![*root];
+        while let Some(cid) = stack.pop() {
+            let Some(links) = self.links.get(&cid) else {
+                continue;
+            };
+            if seen.insert(cid) {
+                reachable.push(cid);
+                stack.extend(links.iter().rev());
+            }
+        }
+        reachable
+    }
+}
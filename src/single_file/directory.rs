@@ -0,0 +1,444 @@
+use futures::{AsyncRead, StreamExt};
+use rs_car::{CarReader, Cid};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::pb::{FlatUnixFs, UnixFsType};
+
+use super::{
+    framing::FramingGuard,
+    util::{assert_header_single_file, decode_block, inline_block, links_to_cids, root_not_found},
+    ReadSingleFileError, ReadSummary,
+};
+#[cfg(feature = "async-std")]
+use super::{read_single_file_from_blocks, util::single_root, DagView, ReadSingleFileOptions};
+
+/// Named child of a UnixFS directory node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub cid: Cid,
+    /// Cumulative size of the child DAG declared by the link, if any
+    pub tsize: Option<u64>,
+}
+
+/// Decode the block of a UnixFS directory node `cid` into its entries, in link order.
+///
+/// Every link of a directory must have a non-empty `Name`, otherwise returns
+/// [`UnnamedDirectoryEntry`](ReadSingleFileError::UnnamedDirectoryEntry) with the CID of the
+/// unnamed child. Sharded directories (HAMTShard) are not supported.
+pub fn decode_directory(
+    cid: &Cid,
+    block: &[u8],
+) -> Result<Vec<DirectoryEntry>, ReadSingleFileError> {
+    let inner = FlatUnixFs::try_from(block)
+        .map_err(|err| ReadSingleFileError::InvalidUnixFs(err.to_string()))?;
+
+    if inner.data.Type != UnixFsType::Directory {
+        return Err(ReadSingleFileError::UnexpectedNodeType {
+            cid: *cid,
+            found: inner.data.Type,
+        });
+    }
+
+    let cids = links_to_cids(&inner.links)?;
+
+    inner
+        .links
+        .iter()
+        .zip(cids)
+        .map(|(link, cid)| match link.Name.as_deref() {
+            Some(name) if !name.is_empty() => Ok(DirectoryEntry {
+                name: name.to_string(),
+                cid,
+                tsize: link.Tsize,
+            }),
+            _ => Err(ReadSingleFileError::UnnamedDirectoryEntry { cid }),
+        })
+        .collect()
+}
+
+/// What to do with a symlink of the DAG pointing outside the extraction root, see
+/// [`check_symlink`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Fail with [`PathEscape`](ReadSingleFileError::PathEscape)
+    #[default]
+    Reject,
+    /// Do not create the symlink
+    Skip,
+}
+
+/// Path to write the entry `name` of a directory extracted to `dir`, checked to stay within
+/// `root`, the target directory of the whole extraction. Both must exist.
+///
+/// `name` must be a single normal path component: no separator, `.`, `..`, absolute path or NUL.
+/// `dir` and an existing entry are resolved following the symlinks already on disk, which must
+/// not lead outside `root`. Otherwise returns [`PathEscape`](ReadSingleFileError::PathEscape).
+pub fn safe_entry_path(
+    root: &Path,
+    dir: &Path,
+    name: &str,
+) -> Result<PathBuf, ReadSingleFileError> {
+    let escape = || ReadSingleFileError::PathEscape {
+        name: name.to_string(),
+    };
+    check_entry_name(name)?;
+
+    let root = root.canonicalize()?;
+    let path = dir.canonicalize()?.join(name);
+    let resolved = match path.canonicalize() {
+        Ok(resolved) => resolved,
+        // A dangling symlink would create its target wherever it points
+        Err(err) if err.kind() == io::ErrorKind::NotFound && path.symlink_metadata().is_ok() => {
+            return Err(escape())
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => path.clone(),
+        Err(err) => return Err(err.into()),
+    };
+    if !resolved.starts_with(&root) {
+        return Err(escape());
+    }
+    Ok(path)
+}
+
+/// Check that a symlink `name` of a directory extracted to `dir`, pointing to `target` as stored
+/// in a UnixFS `Symlink` node, stays within `root`. Relative targets are resolved from `dir`
+/// without following symlinks, absolute targets must be within `root` too. Returns `Ok(false)`
+/// if the symlink must not be created with [`SymlinkPolicy::Skip`].
+pub fn check_symlink(
+    root: &Path,
+    dir: &Path,
+    name: &str,
+    target: &str,
+    policy: SymlinkPolicy,
+) -> Result<bool, ReadSingleFileError> {
+    let path = safe_entry_path(root, dir, name)?;
+    let root = root.canonicalize()?;
+
+    let mut resolved = PathBuf::new();
+    let base = path.parent().unwrap_or(&root);
+    for component in base.join(target).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+
+    match (resolved.starts_with(&root), policy) {
+        (true, _) => Ok(true),
+        (false, SymlinkPolicy::Skip) => Ok(false),
+        (false, SymlinkPolicy::Reject) => Err(ReadSingleFileError::PathEscape {
+            name: name.to_string(),
+        }),
+    }
+}
+
+/// Reject names of directory entries that are not a single normal path component
+fn check_entry_name(name: &str) -> Result<(), ReadSingleFileError> {
+    let mut components = Path::new(name).components();
+    let single_component = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !single_component || name.contains(['/', '\\', '\0']) {
+        return Err(ReadSingleFileError::PathEscape {
+            name: name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Kind of a [`PlannedEntry`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// Entry that an extraction would create, see [`plan_extraction`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedEntry {
+    /// Path relative to the target directory, empty for the root
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    /// Length of a file, or of the target of a symlink. 0 for directories
+    pub size: u64,
+    /// Something already exists at the path, other than a directory for a planned directory
+    pub conflict: bool,
+}
+
+/// Manifest of an extraction, see [`plan_extraction`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExtractionPlan {
+    /// Entries in DAG order, each directory before its content
+    pub entries: Vec<PlannedEntry>,
+    /// Total length of the files
+    pub total_bytes: u64,
+    /// Number of entries with a conflict
+    pub conflicts: usize,
+}
+
+/// Dry run of extracting the DAG at `root_cid`, or the single root of the CAR, to `target`:
+/// list the files, directories and symlinks that would be written with their sizes, flagging
+/// paths that already exist. Nothing is written, the filesystem is only read to find conflicts.
+///
+/// The whole CAR is read and buffered in memory. A `File` or raw root is a single entry with an
+/// empty path, written to `target` itself. Files are not decoded, their size is the `filesize`
+/// declared by their root node. Entries linked with an identity CID carry their node inline and
+/// have no block in the CAR. Entry names are checked as with [`safe_entry_path`].
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::plan_extraction;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///
+///   let plan = plan_extraction(&mut input, None, "out".as_ref()).await?;
+///   for entry in &plan.entries {
+///     println!("{} {} bytes", entry.path.display(), entry.size);
+///   }
+///   Ok(())
+/// }
+/// ```
+pub async fn plan_extraction<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    root_cid: Option<&Cid>,
+    target: &Path,
+) -> Result<ExtractionPlan, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let mut streamer = CarReader::new(&mut car_input, true).await?;
+    let root_cid = assert_header_single_file(&streamer.header, root_cid)?;
+
+    let mut blocks = HashMap::new();
+    while let Some(item) = streamer.next().await {
+        let (cid, block) = item?;
+        blocks.insert(cid, block);
+    }
+    if !blocks.contains_key(&root_cid) {
+        return Err(root_not_found(
+            root_cid,
+            blocks.len(),
+            &streamer.header.roots,
+        ));
+    }
+
+    let mut plan = ExtractionPlan::default();
+    let mut pending = vec![(PathBuf::new(), root_cid)];
+    while let Some((path, cid)) = pending.pop() {
+        let block = blocks
+            .get(&cid)
+            .map(Vec::as_slice)
+            .or_else(|| inline_block(&cid))
+            .ok_or(ReadSingleFileError::MissingNode(cid))?;
+        let (inner, _) = decode_block(&cid, block, false)?;
+
+        let (kind, size) = match inner.data.Type {
+            UnixFsType::Directory => {
+                let entries = decode_directory(&cid, block)?;
+                // Reversed to pop them in link order
+                for entry in entries.into_iter().rev() {
+                    check_entry_name(&entry.name)?;
+                    pending.push((path.join(&entry.name), entry.cid));
+                }
+                (EntryKind::Directory, 0)
+            }
+            UnixFsType::File | UnixFsType::Raw => {
+                let data_len = inner.data.Data.as_ref().map_or(0, |data| data.len() as u64);
+                let size = inner.data.filesize.unwrap_or(data_len);
+                plan.total_bytes = plan.total_bytes.saturating_add(size);
+                (EntryKind::File, size)
+            }
+            UnixFsType::Symlink => {
+                let data_len = inner.data.Data.as_ref().map_or(0, |data| data.len() as u64);
+                (EntryKind::Symlink, data_len)
+            }
+            found => return Err(ReadSingleFileError::UnexpectedNodeType { cid, found }),
+        };
+
+        let conflict = match target.join(&path).symlink_metadata() {
+            Ok(existing) => !(kind == EntryKind::Directory && existing.is_dir()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => false,
+            Err(err) => return Err(err.into()),
+        };
+        plan.conflicts += conflict as usize;
+        plan.entries.push(PlannedEntry {
+            path,
+            kind,
+            size,
+            conflict,
+        });
+    }
+
+    Ok(plan)
+}
+
+/// File written by `extract_directory`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedFile {
+    /// Path relative to the target directory
+    pub path: PathBuf,
+    pub cid: Cid,
+    pub summary: ReadSummary,
+}
+
+/// Extract the DAG at `root_cid`, or the single root of the CAR, to the existing directory
+/// `target`, naming each file and directory by the `Name` of the link to it. Entries of a link
+/// without a name, and a `File` or raw root, are named by their CID. Returns the files written
+/// in DAG order.
+///
+/// Entries linked with an identity CID, e.g. tiny files inlined in their directory, carry their
+/// node inline and are extracted without a block of their own in the CAR.
+///
+/// The whole CAR is read into memory with [`load_dag`](super::load_dag) and `options`, which
+/// also apply to each file. Entry names are checked as with [`safe_entry_path`]. Nothing is
+/// written over: an entry that already exists, or a second entry of a directory with the same
+/// name, fails with [`EntryExists`](ReadSingleFileError::EntryExists). Symlinks and sharded
+/// directories are not supported and fail with
+/// [`UnexpectedNodeType`](ReadSingleFileError::UnexpectedNodeType). Only available with the
+/// `async-std` feature
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::extract_directory;
+///
+/// # #[cfg(not(feature = "async-std"))]
+/// # fn main() {}
+/// # #[cfg(feature = "async-std")]
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let target = std::env::temp_dir().join("example-extract");
+///   std::fs::create_dir_all(&target)?;
+///
+///   for file in extract_directory(&mut input, None, &target, &Default::default()).await? {
+///     println!("{} {} bytes", file.path.display(), file.summary.bytes_written);
+///   }
+///   # std::fs::remove_dir_all(&target)?;
+///   Ok(())
+/// }
+/// ```
+#[cfg(feature = "async-std")]
+pub async fn extract_directory<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    root_cid: Option<&Cid>,
+    target: &Path,
+    options: &ReadSingleFileOptions,
+) -> Result<Vec<ExtractedFile>, ReadSingleFileError> {
+    let dag = super::load_dag(car_input, options).await?;
+    let root_cid = single_root(dag.roots(), root_cid)?;
+    let root_block = dag
+        .block(&root_cid)
+        .ok_or_else(|| root_not_found(root_cid, dag.len(), dag.roots()))?;
+
+    // Path relative to `target` of each entry, named by its link or by its CID
+    let mut pending = vec![];
+    match decode_block(&root_cid, root_block, false)?.0.data.Type {
+        UnixFsType::Directory => push_entries(&mut pending, Path::new(""), &root_cid, root_block)?,
+        _ => pending.push((PathBuf::from(root_cid.to_string()), root_cid)),
+    }
+
+    let mut files = vec![];
+    while let Some((path, cid)) = pending.pop() {
+        let inline = !dag.contains(&cid);
+        let block = dag
+            .block(&cid)
+            .or_else(|| inline_block(&cid))
+            .ok_or(ReadSingleFileError::MissingNode(cid))?;
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let dir = target.join(path.parent().unwrap_or(Path::new("")));
+        let full_path = safe_entry_path(target, &dir, name)?;
+        let exists = |err: io::Error| match err.kind() {
+            io::ErrorKind::AlreadyExists => ReadSingleFileError::EntryExists {
+                name: path.display().to_string(),
+            },
+            _ => err.into(),
+        };
+
+        match decode_block(&cid, block, false)?.0.data.Type {
+            UnixFsType::Directory => {
+                async_std::fs::create_dir(&full_path)
+                    .await
+                    .map_err(exists)?;
+                push_entries(&mut pending, &path, &cid, block)?;
+            }
+            UnixFsType::File | UnixFsType::Raw => {
+                let mut out = async_std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&full_path)
+                    .await
+                    .map_err(exists)?;
+                let summary = match inline {
+                    // Carried by its identity CID, with the blocks of its links in the view
+                    true => {
+                        let mut blocks = vec![(cid, block.to_vec())];
+                        blocks.extend(reachable_blocks(&dag, &cid, block)?);
+                        read_single_file_from_blocks(&blocks, &mut out, &cid, options).await?
+                    }
+                    false => dag.write_file(Some(&cid), &mut out, options).await?,
+                };
+                files.push(ExtractedFile { path, cid, summary });
+            }
+            found => return Err(ReadSingleFileError::UnexpectedNodeType { cid, found }),
+        }
+    }
+
+    Ok(files)
+}
+
+/// Push the entries of the directory node `cid` at `path` to be popped in link order, each named
+/// by its link or by its CID
+#[cfg(feature = "async-std")]
+fn push_entries(
+    pending: &mut Vec<(PathBuf, Cid)>,
+    path: &Path,
+    cid: &Cid,
+    block: &[u8],
+) -> Result<(), ReadSingleFileError> {
+    let (inner, links) = decode_block(cid, block, false)?;
+    for (link, child) in inner.links.iter().zip(links).rev() {
+        let name = match link.Name.as_deref() {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => child.to_string(),
+        };
+        pending.push((path.join(name), child));
+    }
+    Ok(())
+}
+
+/// Blocks of `dag` under the links of the node `cid` carried inline by its identity CID
+#[cfg(feature = "async-std")]
+fn reachable_blocks(
+    dag: &DagView,
+    cid: &Cid,
+    block: &[u8],
+) -> Result<Vec<(Cid, Vec<u8>)>, ReadSingleFileError> {
+    let mut seen = std::collections::HashSet::new();
+    let mut blocks = vec![];
+    let mut stack = decode_block(cid, block, false)?.1;
+    while let Some(cid) = stack.pop() {
+        let Some(block) = dag.block(&cid) else {
+            continue;
+        };
+        if seen.insert(cid) {
+            stack.extend(dag.links(&cid).unwrap_or_default());
+            blocks.push((cid, block.to_vec()));
+        }
+    }
+    Ok(blocks)
+}
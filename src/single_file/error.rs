@@ -1,10 +1,45 @@
 use rs_car::{CarDecodeError, Cid};
 
+use crate::pb::UnixFsType;
+
+use super::{
+    framing::{MalformedHeader, SectionTooLarge},
+    MapLeafError,
+};
+
+/// Errors of the single file readers.
+///
+/// A malformed CAR stream is a [`CarDecodeError`](Self::CarDecodeError) with the kind of
+/// structure that failed to decode, e.g. the header, a block section or a CID. A stream ending in
+/// the middle of a structure is an [`IoError`](Self::IoError) of kind `UnexpectedEof`.
+///
+/// # State of `out` on error
+///
+/// - [`IoError`](Self::IoError): no guarantee, `out` may hold any partial write.
+/// - [`NotCanonical`](Self::NotCanonical), [`FileSizeMismatch`](Self::FileSizeMismatch) and
+///   [`SizeMismatch`](Self::SizeMismatch): the whole file was written, but is not finalized.
+/// - [`WriteLimitExceeded`](Self::WriteLimitExceeded): nothing was written past the limit.
+/// - [`MapLeaf`](Self::MapLeaf): the file was written up to the data the hook failed on.
+/// - [`DeclaredSizeExceedsLimit`](Self::DeclaredSizeExceedsLimit) and
+///   [`ExpectedSizeMismatch`](Self::ExpectedSizeMismatch): nothing was written, the size
+///   declared by the root is checked before writing.
+/// - Any other error: [`read_single_file_buffer`](super::read_single_file_buffer) wrote nothing,
+///   since it only writes once the whole CAR is read. The seek and indexed readers wrote a
+///   prefix of the file, except for regions of blocks skipped with
+///   [`SkipFill::Unwritten`](super::SkipFill::Unwritten). Bytes past the prefix are left as they
+///   were, or zeros up to the declared size with
+///   [`preallocate`](super::ReadSingleFileOptions::preallocate).
+///
+/// With [`validate_before_write`](super::ReadSingleFileOptions::validate_before_write),
+/// [`read_single_file_buffer`](super::read_single_file_buffer) writes nothing on any error other
+/// than an `IoError` of `out`.
 #[derive(Debug)]
 pub enum ReadSingleFileError {
     IoError(std::io::Error),
     CarDecodeError(CarDecodeError),
-    NotSingleRoot { roots: Vec<Cid> },
+    NotSingleRoot {
+        roots: Vec<Cid>,
+    },
     InvalidUnixFs(String),
     InvalidUnixFsHash(String),
     MissingNode(Cid),
@@ -14,13 +49,446 @@ pub enum ReadSingleFileError {
     PendingLinksAtEOF(Vec<Cid>),
     PBLinkHasNoHash,
     InternalError(String),
-    WriteLimitExceeded(usize),
+    /// Writing the file would take `attempted` bytes past the `write_limit` of the read, with
+    /// `attempted` the total length the file would have once the next node is written. Checked
+    /// before writing the node, `out` holds at most `limit` bytes of the file. `cid` is the node
+    /// that was about to be written: a leaf read from the CAR, or a leaf or subtree already
+    /// written and about to be copied again from `out`
+    WriteLimitExceeded {
+        limit: usize,
+        attempted: usize,
+        cid: Option<Cid>,
+    },
+    InvalidCarIndex(String),
+    MaxBlocksExceeded {
+        limit: usize,
+        read: usize,
+    },
+    UnexpectedNodeType {
+        cid: Cid,
+        found: UnixFsType,
+    },
+    NotCanonical {
+        computed: Cid,
+    },
+    UnnamedDirectoryEntry {
+        cid: Cid,
+    },
+    SkippedBlockUnknownSize(Cid),
+    UnsupportedCodec(u64),
+    FileSizeMismatch {
+        declared: u64,
+        written: u64,
+    },
+    /// The block of `root` is not in the CAR stream, which claims to contain `header_roots`
+    RootCidNotFound {
+        root: Cid,
+        blocks_seen: usize,
+        header_roots: Box<[Cid]>,
+    },
+    FileSizeUnknown(Cid),
+    SegmentRootMismatch {
+        segment: usize,
+        roots: Vec<Cid>,
+    },
+    BlockingInAsyncContext,
+    SizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    ExpectedSizeMismatch {
+        expected: u64,
+        declared: u64,
+    },
+    /// The link `cid` has a digest of a different length than the `expected` length of its hash
+    /// function, e.g. a truncated hash
+    InvalidLinkDigestLength {
+        cid: Cid,
+        expected: usize,
+    },
+    /// The directory entry or symlink `name` resolves outside the extraction root
+    PathEscape {
+        name: String,
+    },
+    /// The links `node` declares no blocksizes and the block of its link at `link_index` is not in
+    /// the CAR, so the extents of the file after it are unknown
+    BlocksizesUnknown {
+        node: Cid,
+        link_index: usize,
+    },
+    /// The root declares a `filesize` larger than the `write_limit` of the read
+    DeclaredSizeExceedsLimit {
+        declared: u64,
+        limit: usize,
+    },
+    /// The [`map_leaf`](super::ReadSingleFileOptions::map_leaf) hook failed on the data of `cid`
+    MapLeaf {
+        cid: Cid,
+        error: MapLeafError,
+    },
+    /// The reader does not support [`map_leaf`](super::ReadSingleFileOptions::map_leaf)
+    MapLeafUnsupported,
+    /// The node `cid` links to itself through its children, only possible with blocks not matching
+    /// their CID
+    CycleDetected(Cid),
+    /// The leaf `cid` has `actual` bytes of data but its parent declares `declared` bytes in its
+    /// `blocksizes`. Checked by the seek and buffer readers
+    BlocksizeMismatch {
+        cid: Cid,
+        declared: u64,
+        actual: u64,
+    },
+    /// The CAR header lists no roots and no root CID was given to the read. CARs without roots
+    /// are read with an explicit root CID, or with
+    /// [`RootSelector::LastBlock`](super::RootSelector::LastBlock) unless they have no blocks
+    NoRootsInHeader,
+    /// The CAR header, or the CARv1 header of a CARv2, is not a dag-cbor map of only `version`
+    /// and `roots`, e.g. it has an unexpected or duplicated key. Rejected even though rs-car
+    /// ignores such keys, since other implementations may read the header differently
+    MalformedHeader(String),
+    /// A leaf arriving before the leaves preceding it in the file needs `needed` bytes of leaves
+    /// buffered by the seek reader, more than its
+    /// [`reorder_window`](super::ReadSingleFileOptions::reorder_window) of `limit` bytes
+    ReorderWindowExceeded {
+        needed: usize,
+        limit: usize,
+    },
+    /// The visitor of [`visit_file_leaves`](super::visit_file_leaves) failed on the bytes at
+    /// `offset` in the file
+    VisitorError {
+        offset: u64,
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// A link to `cid` declares a `Tsize` of `declared` bytes, fewer than the `actual` bytes of
+    /// file data under it or more than the `actual` bytes of its blocks. Checked with
+    /// [`check_link_tsize`](super::ReadSingleFileOptions::check_link_tsize)
+    TsizeMismatch {
+        cid: Cid,
+        declared: u64,
+        actual: u64,
+    },
+    /// The reader can not compress the file with
+    /// [`compress_output`](super::ReadSingleFileOptions::compress_output), or the feature of the
+    /// format is not enabled
+    CompressedOutputUnsupported,
+    /// The block `cid` of `size` bytes is larger than the
+    /// [`max_block_size`](super::ReadSingleFileOptions::max_block_size) of `limit` bytes
+    BlockTooLarge {
+        cid: Cid,
+        size: usize,
+        limit: usize,
+    },
+    /// The entry `name`, its path relative to the extraction root, already exists. Two entries of
+    /// a directory with the same name are not written over each other either
+    EntryExists {
+        name: String,
+    },
+}
+
+impl ReadSingleFileError {
+    /// Stable identifier of the variant, the snake case of its name, e.g.
+    /// `pending_links_at_eof`. Codes of existing variants never change, to be matched by
+    /// consumers in other languages instead of the `Debug` output
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::IoError(_) => "io_error",
+            Self::CarDecodeError(_) => "car_decode_error",
+            Self::NotSingleRoot { .. } => "not_single_root",
+            Self::InvalidUnixFs(_) => "invalid_unixfs",
+            Self::InvalidUnixFsHash(_) => "invalid_unixfs_hash",
+            Self::MissingNode(_) => "missing_node",
+            Self::MaxBufferedData(_) => "max_buffered_data",
+            Self::RootCidIsNotFile => "root_cid_is_not_file",
+            Self::DataNodesNotSorted => "data_nodes_not_sorted",
+            Self::PendingLinksAtEOF(_) => "pending_links_at_eof",
+            Self::PBLinkHasNoHash => "pblink_has_no_hash",
+            Self::InternalError(_) => "internal_error",
+            Self::WriteLimitExceeded { .. } => "write_limit_exceeded",
+            Self::InvalidCarIndex(_) => "invalid_car_index",
+            Self::MaxBlocksExceeded { .. } => "max_blocks_exceeded",
+            Self::UnexpectedNodeType { .. } => "unexpected_node_type",
+            Self::NotCanonical { .. } => "not_canonical",
+            Self::UnnamedDirectoryEntry { .. } => "unnamed_directory_entry",
+            Self::SkippedBlockUnknownSize(_) => "skipped_block_unknown_size",
+            Self::UnsupportedCodec(_) => "unsupported_codec",
+            Self::FileSizeMismatch { .. } => "file_size_mismatch",
+            Self::RootCidNotFound { .. } => "root_cid_not_found",
+            Self::FileSizeUnknown(_) => "file_size_unknown",
+            Self::SegmentRootMismatch { .. } => "segment_root_mismatch",
+            Self::BlockingInAsyncContext => "blocking_in_async_context",
+            Self::SizeMismatch { .. } => "size_mismatch",
+            Self::ExpectedSizeMismatch { .. } => "expected_size_mismatch",
+            Self::InvalidLinkDigestLength { .. } => "invalid_link_digest_length",
+            Self::PathEscape { .. } => "path_escape",
+            Self::BlocksizesUnknown { .. } => "blocksizes_unknown",
+            Self::DeclaredSizeExceedsLimit { .. } => "declared_size_exceeds_limit",
+            Self::MapLeaf { .. } => "map_leaf",
+            Self::MapLeafUnsupported => "map_leaf_unsupported",
+            Self::CycleDetected(_) => "cycle_detected",
+            Self::BlocksizeMismatch { .. } => "blocksize_mismatch",
+            Self::NoRootsInHeader => "no_roots_in_header",
+            Self::MalformedHeader(_) => "malformed_header",
+            Self::ReorderWindowExceeded { .. } => "reorder_window_exceeded",
+            Self::VisitorError { .. } => "visitor_error",
+            Self::TsizeMismatch { .. } => "tsize_mismatch",
+            Self::CompressedOutputUnsupported => "compressed_output_unsupported",
+            Self::BlockTooLarge { .. } => "block_too_large",
+            Self::EntryExists { .. } => "entry_exists",
+        }
+    }
+
+    /// The [`code`](Self::code) and fields of the variant in plain types, see [`ErrorDetails`]
+    pub fn details(&self) -> ErrorDetails {
+        let details = ErrorDetails {
+            code: self.code(),
+            ..Default::default()
+        };
+        let cid = |cid: &Cid| Some(cid.to_string());
+        let cids = |cids: &[Cid]| cids.iter().map(Cid::to_string).collect();
+        match self {
+            Self::IoError(err) => ErrorDetails {
+                message: Some(err.to_string()),
+                ..details
+            },
+            Self::CarDecodeError(err) => ErrorDetails {
+                message: Some(err.to_string()),
+                ..details
+            },
+            Self::InvalidUnixFs(message)
+            | Self::InvalidUnixFsHash(message)
+            | Self::InternalError(message)
+            | Self::InvalidCarIndex(message)
+            | Self::MalformedHeader(message) => ErrorDetails {
+                message: Some(message.clone()),
+                ..details
+            },
+            Self::NotSingleRoot { roots } => ErrorDetails {
+                roots: cids(roots),
+                ..details
+            },
+            Self::SegmentRootMismatch { segment, roots } => ErrorDetails {
+                roots: cids(roots),
+                index: Some(*segment as u64),
+                ..details
+            },
+            Self::MissingNode(node)
+            | Self::UnnamedDirectoryEntry { cid: node }
+            | Self::SkippedBlockUnknownSize(node)
+            | Self::FileSizeUnknown(node)
+            | Self::CycleDetected(node)
+            | Self::NotCanonical { computed: node } => ErrorDetails {
+                cid: cid(node),
+                ..details
+            },
+            Self::MaxBufferedData(limit) => ErrorDetails {
+                limit: Some(*limit as u64),
+                ..details
+            },
+            Self::PendingLinksAtEOF(pending) => ErrorDetails {
+                cids: cids(pending),
+                ..details
+            },
+            Self::WriteLimitExceeded {
+                limit,
+                attempted,
+                cid: node,
+            } => ErrorDetails {
+                cid: node.as_ref().map(|node| node.to_string()),
+                limit: Some(*limit as u64),
+                actual: Some(*attempted as u64),
+                ..details
+            },
+            Self::MaxBlocksExceeded { limit, read } => ErrorDetails {
+                limit: Some(*limit as u64),
+                actual: Some(*read as u64),
+                ..details
+            },
+            Self::UnexpectedNodeType { cid: node, found } => ErrorDetails {
+                cid: cid(node),
+                node_type: Some(format!("{:?}", found)),
+                ..details
+            },
+            Self::UnsupportedCodec(codec) => ErrorDetails {
+                codec: Some(*codec),
+                ..details
+            },
+            Self::FileSizeMismatch { declared, written } => ErrorDetails {
+                declared: Some(*declared),
+                actual: Some(*written),
+                ..details
+            },
+            Self::RootCidNotFound {
+                root,
+                blocks_seen,
+                header_roots,
+            } => ErrorDetails {
+                cid: cid(root),
+                roots: cids(header_roots),
+                actual: Some(*blocks_seen as u64),
+                ..details
+            },
+            Self::SizeMismatch { expected, actual } => ErrorDetails {
+                expected: Some(*expected),
+                actual: Some(*actual),
+                ..details
+            },
+            Self::ExpectedSizeMismatch { expected, declared } => ErrorDetails {
+                expected: Some(*expected),
+                declared: Some(*declared),
+                ..details
+            },
+            Self::InvalidLinkDigestLength {
+                cid: link,
+                expected,
+            } => ErrorDetails {
+                cid: cid(link),
+                expected: Some(*expected as u64),
+                ..details
+            },
+            Self::PathEscape { name } | Self::EntryExists { name } => ErrorDetails {
+                name: Some(name.clone()),
+                ..details
+            },
+            Self::BlocksizesUnknown { node, link_index } => ErrorDetails {
+                cid: cid(node),
+                index: Some(*link_index as u64),
+                ..details
+            },
+            Self::DeclaredSizeExceedsLimit { declared, limit } => ErrorDetails {
+                declared: Some(*declared),
+                limit: Some(*limit as u64),
+                ..details
+            },
+            Self::BlocksizeMismatch {
+                cid: leaf,
+                declared,
+                actual,
+            }
+            | Self::TsizeMismatch {
+                cid: leaf,
+                declared,
+                actual,
+            } => ErrorDetails {
+                cid: cid(leaf),
+                declared: Some(*declared),
+                actual: Some(*actual),
+                ..details
+            },
+            Self::BlockTooLarge {
+                cid: block,
+                size,
+                limit,
+            } => ErrorDetails {
+                cid: cid(block),
+                actual: Some(*size as u64),
+                limit: Some(*limit as u64),
+                ..details
+            },
+            Self::ReorderWindowExceeded { needed, limit } => ErrorDetails {
+                actual: Some(*needed as u64),
+                limit: Some(*limit as u64),
+                ..details
+            },
+            Self::VisitorError { offset, error } => ErrorDetails {
+                index: Some(*offset),
+                message: Some(error.to_string()),
+                ..details
+            },
+            Self::MapLeaf { cid: leaf, error } => ErrorDetails {
+                cid: cid(leaf),
+                message: Some(error.to_string()),
+                ..details
+            },
+            Self::RootCidIsNotFile
+            | Self::DataNodesNotSorted
+            | Self::PBLinkHasNoHash
+            | Self::BlockingInAsyncContext
+            | Self::MapLeafUnsupported
+            | Self::CompressedOutputUnsupported
+            | Self::NoRootsInHeader => details,
+        }
+    }
+}
+
+/// Fields of a [`ReadSingleFileError`] in plain types, e.g. for a JSON response. Only the fields
+/// of the variant are set, under the same names for all variants:
+///
+/// - `cid`: the node the error is about, the computed CID for `not_canonical` and the missing
+///   root for `root_cid_not_found`
+/// - `cids`: the pending links for `pending_links_at_eof`
+/// - `roots`: the roots of the CAR header or segment
+/// - `expected`, `declared` and `actual`: the sizes compared, or for `root_cid_not_found` the
+///   blocks seen, for `max_blocks_exceeded` the blocks read, for `write_limit_exceeded` the
+///   length that would have been written and for `reorder_window_exceeded` the bytes needed
+/// - `limit`: the limit set by the options
+/// - `index`: the link of `blocksizes_unknown`, the segment of `segment_root_mismatch` or the
+///   offset of `visitor_error`
+/// - `message`: the description of the error, for variants with a message or a source error
+///
+/// With the `serde` feature, both the details and the error serialize as a struct of `code` and
+/// the fields set.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorDetails {
+    /// See [`ReadSingleFileError::code`]
+    pub code: &'static str,
+    pub cid: Option<String>,
+    pub cids: Vec<String>,
+    pub roots: Vec<String>,
+    pub expected: Option<u64>,
+    pub declared: Option<u64>,
+    pub actual: Option<u64>,
+    pub limit: Option<u64>,
+    pub index: Option<u64>,
+    pub codec: Option<u64>,
+    /// UnixFS type of `unexpected_node_type`, e.g. `Directory`
+    pub node_type: Option<String>,
+    /// Entry of `path_escape`
+    pub name: Option<String>,
+    pub message: Option<String>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ErrorDetails {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("ErrorDetails", 13)?;
+        state.serialize_field("code", self.code)?;
+        macro_rules! optional {
+            ($($field:ident),*) => {$(
+                match &self.$field {
+                    Some(value) => state.serialize_field(stringify!($field), value)?,
+                    None => state.skip_field(stringify!($field))?,
+                }
+            )*};
+        }
+        macro_rules! list {
+            ($($field:ident),*) => {$(
+                match self.$field.is_empty() {
+                    false => state.serialize_field(stringify!($field), &self.$field)?,
+                    true => state.skip_field(stringify!($field))?,
+                }
+            )*};
+        }
+        optional!(cid);
+        list!(cids, roots);
+        optional!(expected, declared, actual, limit, index, codec, node_type, name, message);
+        state.end()
+    }
+}
+
+/// Serialized as its [`ErrorDetails`]
+#[cfg(feature = "serde")]
+impl serde::Serialize for ReadSingleFileError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.details().serialize(serializer)
+    }
 }
 
 impl From<CarDecodeError> for ReadSingleFileError {
     fn from(error: CarDecodeError) -> Self {
         match error {
-            CarDecodeError::IoError(err) => ReadSingleFileError::IoError(err),
+            CarDecodeError::IoError(err) => err.into(),
             err => ReadSingleFileError::CarDecodeError(err),
         }
     }
@@ -28,6 +496,33 @@ impl From<CarDecodeError> for ReadSingleFileError {
 
 impl From<std::io::Error> for ReadSingleFileError {
     fn from(error: std::io::Error) -> Self {
+        // Malformed framing rejected before rs-car decodes it, passed through as an I/O error
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<CarDecodeError>())
+        {
+            let inner = error.into_inner().and_then(|inner| inner.downcast().ok());
+            return ReadSingleFileError::CarDecodeError(*inner.expect("checked above"));
+        }
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<MalformedHeader>())
+        {
+            let inner = error
+                .into_inner()
+                .and_then(|inner| inner.downcast::<MalformedHeader>().ok());
+            return ReadSingleFileError::MalformedHeader(inner.expect("checked above").0);
+        }
+        if error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<SectionTooLarge>())
+        {
+            let inner = error
+                .into_inner()
+                .and_then(|inner| inner.downcast::<SectionTooLarge>().ok());
+            let SectionTooLarge { cid, size, limit } = *inner.expect("checked above");
+            return ReadSingleFileError::BlockTooLarge { cid, size, limit };
+        }
         ReadSingleFileError::IoError(error)
     }
 }
@@ -43,7 +538,41 @@ impl std::error::Error for ReadSingleFileError {
         match self {
             ReadSingleFileError::IoError(err) => Some(err),
             ReadSingleFileError::CarDecodeError(err) => Some(err),
+            ReadSingleFileError::MapLeaf { error, .. } => Some(error.as_ref()),
+            ReadSingleFileError::VisitorError { error, .. } => Some(error.as_ref()),
             _ => None,
         }
     }
 }
+
+/// Error of the `_verified` readers, with the length of the prefix of the file that was written
+/// to `out` and verified against the hashes of its blocks before the error. Bytes of `out` past
+/// the prefix are not verified, and the rest of the file can be requested from this offset. For
+/// an [`IoError`](ReadSingleFileError::IoError) the prefix may not have been flushed.
+#[derive(Debug)]
+pub struct VerifiedPrefixError {
+    pub error: ReadSingleFileError,
+    pub verified_prefix_bytes: u64,
+}
+
+impl From<VerifiedPrefixError> for ReadSingleFileError {
+    fn from(error: VerifiedPrefixError) -> Self {
+        error.error
+    }
+}
+
+impl std::fmt::Display for VerifiedPrefixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} after {} verified bytes",
+            self.error, self.verified_prefix_bytes
+        )
+    }
+}
+
+impl std::error::Error for VerifiedPrefixError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
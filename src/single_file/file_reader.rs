@@ -0,0 +1,458 @@
+use futures::{
+    channel::mpsc,
+    future,
+    io::copy,
+    stream::{self, BoxStream, IntoAsyncRead},
+    AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, Future, FutureExt, SinkExt, StreamExt,
+    TryStreamExt,
+};
+use rs_car::{CarReader, Cid};
+use std::{
+    collections::HashMap,
+    io,
+    ops::Range,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    task::{Context, Poll},
+};
+
+use crate::{
+    cid_version::{block_key, same_block},
+    layout::FileLayout,
+    pb::UnixFsType,
+};
+
+use super::{
+    framing::FramingGuard,
+    output::{finalize, NoSync},
+    tail::TailInput,
+    util::{
+        assert_header_single_file, check_cycle, data_range, decode_block, leaf_data, root_not_found,
+    },
+    Finalize, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
+
+/// Write only the first `n` bytes of the file of `car_input` to `out`, e.g. to sniff or preview
+/// it. The CAR is read only until the blocks of the first `n` bytes are known, so nodes past the
+/// prefix are never decoded. The whole file is written if it is shorter than `n`.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::read_single_file_prefix;
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut out = Cursor::new(Vec::new());
+///
+///   let summary = read_single_file_prefix(&mut input, &mut out, None, 4096).await?;
+///   assert!(summary.bytes_written <= 4096);
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_prefix<R: AsyncRead + Send + Unpin, W: AsyncWrite + Unpin>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    n: u64,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    // Not buffered, `car_input` is not read past the blocks of the prefix
+    let options = ReadSingleFileOptions {
+        read_buffer_capacity: Some(0),
+        ..Default::default()
+    };
+    let mut reader = CarFileReader::new(car_input, root_cid, &options);
+    let bytes_written = copy((&mut reader).take(n), out)
+        .await
+        .map_err(from_io_error)?;
+    let finalized = finalize::<_, NoSync>(out, Finalize::default()).await?;
+
+    Ok(ReadSummary {
+        root_cid: reader.root_cid(),
+        bytes_written,
+        finalized,
+        ..Default::default()
+    }
+    .completed())
+}
+
+/// Call `visitor` with the bytes of the file of `car_input` in file order, with their offset in
+/// the file, without writing them anywhere, e.g. to scan the content. Each call is passed the
+/// data of one leaf, or the inline data of a node with links, and the read waits for the returned
+/// future before decoding more blocks. The future can not borrow the bytes, copy them if needed.
+///
+/// Blocks are ordered the same way as [`CarFileReader`], with their data kept in memory within
+/// [`max_buffer`](ReadSingleFileOptions::max_buffer). An error of the visitor stops the read with
+/// [`VisitorError`](ReadSingleFileError::VisitorError). The summary counts the bytes visited in
+/// [`bytes_written`](ReadSummary::bytes_written).
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::visit_file_leaves;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut lines = 0;
+///
+///   visit_file_leaves(&mut input, None, &Default::default(), |_offset, data| {
+///     lines += data.iter().filter(|byte| **byte == b'\n').count();
+///     async { Ok::<_, std::io::Error>(()) }
+///   })
+///   .await?;
+///   println!("{} lines", lines);
+///   Ok(())
+/// }
+/// ```
+pub async fn visit_file_leaves<R, F, Fut, E>(
+    car_input: &mut R,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+    mut visitor: F,
+) -> Result<ReadSummary, ReadSingleFileError>
+where
+    R: AsyncRead + Send + Unpin,
+    F: FnMut(u64, &[u8]) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let mut car_input =
+        FramingGuard::new(options.buffer_input(TailInput::new(car_input, options.tail.clone())))
+            .with_max_block_size(options.block_size_limit());
+    let mut state = State::new(
+        &mut car_input,
+        root_cid.copied(),
+        Default::default(),
+        options.clone(),
+    );
+    while let Some(chunk) = state.next_chunk().await? {
+        let offset = state.bytes_read - chunk.range.len() as u64;
+        visitor(offset, chunk.as_ref()).await.map_err(|error| {
+            ReadSingleFileError::VisitorError {
+                offset,
+                error: error.into(),
+            }
+        })?;
+    }
+    let summary = ReadSummary {
+        root_cid: state.root_cid,
+        bytes_written: state.bytes_read,
+        blocks_read: state.blocks_read,
+        ..Default::default()
+    };
+    drop(state);
+
+    Ok(ReadSummary {
+        car_bytes_read: car_input.position(),
+        ..summary
+    }
+    .completed())
+}
+
+/// [`AsyncRead`] of the single file of a CAR stream, to hand the file to code expecting a reader.
+/// The CAR is decoded as the file is read: each read only consumes `car_input` until the next
+/// bytes of the file are known, at most one chunk ahead, and EOF is returned once the whole file
+/// was read.
+///
+/// Blocks can be in any order. Data nodes are kept in memory until the end of the read, since a
+/// later node can link them again, so their total length is bounded by
+/// [`max_buffer`](ReadSingleFileOptions::max_buffer). Options about `out` and
+/// [`on_block_error`](ReadSingleFileOptions::on_block_error) are not used.
+///
+/// Read errors of `car_input` are returned as is. Other errors are an [`io::Error`] of kind
+/// `Other` wrapping the [`ReadSingleFileError`], available with [`io::Error::get_ref`] or
+/// [`io::Error::into_inner`].
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::CarFileReader;
+/// use futures::io::{copy, Cursor};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = Cursor::new(std::fs::read("tests/example.car")?);
+///   let mut out = Vec::new();
+///
+///   let reader = CarFileReader::new(&mut input, None, &Default::default());
+///   copy(reader, &mut out).await?;
+///   Ok(())
+/// }
+/// ```
+pub struct CarFileReader<'a> {
+    inner: IntoAsyncRead<BoxStream<'a, io::Result<FileChunk>>>,
+    root_cid: Arc<OnceLock<Cid>>,
+}
+
+impl<'a> CarFileReader<'a> {
+    pub fn new<R: AsyncRead + Send + Unpin>(
+        car_input: &'a mut R,
+        root_cid: Option<&Cid>,
+        options: &ReadSingleFileOptions,
+    ) -> Self {
+        let root_cid = root_cid.copied();
+        let resolved_root = Arc::new(OnceLock::new());
+        let state_root = resolved_root.clone();
+        let options = options.clone();
+        // The reader borrows the guard, so chunks are produced by a future owning both and
+        // passed through a channel
+        let (mut chunks_tx, chunks_rx) = mpsc::channel(0);
+        let produce = async move {
+            let tail = options.tail.clone();
+            let mut car_input =
+                FramingGuard::new(options.buffer_input(TailInput::new(car_input, tail)))
+                    .with_max_block_size(options.block_size_limit());
+            let mut state = State::new(&mut car_input, root_cid, state_root, options);
+            loop {
+                let (chunk, done) = match state.next_chunk().await {
+                    Ok(Some(chunk)) => (Ok(chunk), false),
+                    Ok(None) => break,
+                    Err(err) => (Err(err), true),
+                };
+                // A send error means the reader was dropped
+                if chunks_tx.send(chunk).await.is_err() || done {
+                    break;
+                }
+            }
+        };
+        let chunks = stream::select(
+            produce.into_stream().filter_map(|()| future::ready(None)),
+            chunks_rx,
+        )
+        .map_err(|err| match err {
+            ReadSingleFileError::IoError(err) => err,
+            err => io::Error::other(err),
+        })
+        .boxed();
+
+        Self {
+            inner: chunks.into_async_read(),
+            root_cid: resolved_root,
+        }
+    }
+
+    /// Root CID of the file, the single root of the CAR header if none was given. None until
+    /// the header is read by the first read
+    pub fn root_cid(&self) -> Option<Cid> {
+        self.root_cid.get().copied()
+    }
+}
+
+impl AsyncRead for CarFileReader<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncBufRead for CarFileReader<'_> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut self.get_mut().inner).poll_fill_buf(cx)
+    }
+
+    fn consume(mut self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut self.inner).consume(amt)
+    }
+}
+
+/// Unwrap the [`ReadSingleFileError`] returned by [`CarFileReader`] as an [`io::Error`]
+fn from_io_error(err: io::Error) -> ReadSingleFileError {
+    if !err
+        .get_ref()
+        .is_some_and(|inner| inner.is::<ReadSingleFileError>())
+    {
+        return ReadSingleFileError::IoError(err);
+    }
+    *err.into_inner()
+        .and_then(|inner| inner.downcast().ok())
+        .expect("checked above")
+}
+
+/// Bytes of the file, as a range of the block they were decoded from to not copy them
+struct FileChunk {
+    block: Arc<Vec<u8>>,
+    range: Range<usize>,
+}
+
+impl AsRef<[u8]> for FileChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.block[self.range.clone()]
+    }
+}
+
+enum UnixFsNode {
+    Links {
+        links: Vec<Cid>,
+        data: Option<(Arc<Vec<u8>>, Range<usize>)>,
+    },
+    Data(Arc<Vec<u8>>, Range<usize>),
+    Unexpected(UnixFsType),
+}
+
+struct State<'a, R> {
+    /// Taken to decode the CAR header on the first read
+    car_input: Option<&'a mut R>,
+    streamer: Option<CarReader<'a, R>>,
+    root_cid: Option<Cid>,
+    /// Shared with the reader once the root CID is known
+    resolved_root: Arc<OnceLock<Cid>>,
+    options: ReadSingleFileOptions,
+    /// Set once the root CID is known from the header
+    layout: Option<FileLayout>,
+    /// Read nodes keyed by [`block_key`]
+    nodes: HashMap<Cid, UnixFsNode>,
+    buffered_data_len: usize,
+    blocks_read: usize,
+    bytes_read: u64,
+}
+
+impl<'a, R: AsyncRead + Send + Unpin> State<'a, R> {
+    fn new(
+        car_input: &'a mut R,
+        root_cid: Option<Cid>,
+        resolved_root: Arc<OnceLock<Cid>>,
+        options: ReadSingleFileOptions,
+    ) -> Self {
+        Self {
+            car_input: Some(car_input),
+            streamer: None,
+            root_cid,
+            resolved_root,
+            options,
+            layout: None,
+            nodes: HashMap::new(),
+            buffered_data_len: 0,
+            blocks_read: 0,
+            bytes_read: 0,
+        }
+    }
+
+    /// Next bytes of the file, reading blocks until they are known. None once the whole file
+    /// was returned
+    async fn next_chunk(&mut self) -> Result<Option<FileChunk>, ReadSingleFileError> {
+        if let Some(car_input) = self.car_input.take() {
+            self.options.reject_map_leaf()?;
+            self.options.reject_compress_output()?;
+            let streamer = CarReader::new(car_input, true).await?;
+            let root_cid = assert_header_single_file(&streamer.header, self.root_cid.as_ref())?;
+            self.root_cid = Some(root_cid);
+            // Only set here, once
+            let _ = self.resolved_root.set(root_cid);
+            self.layout = Some(FileLayout::new(root_cid));
+            self.streamer = Some(streamer);
+        }
+        let (Some(streamer), Some(layout), Some(root_cid)) =
+            (self.streamer.as_mut(), self.layout.as_mut(), self.root_cid)
+        else {
+            return Ok(None);
+        };
+
+        loop {
+            while let Some(first) = layout.next_ready() {
+                let chunk = match self.nodes.get(&block_key(&first)) {
+                    Some(UnixFsNode::Data(block, range)) => {
+                        layout.feed_leaf(&first, range.len() as u64);
+                        Some((block, range))
+                    }
+                    Some(UnixFsNode::Links { links, data }) => {
+                        check_cycle(layout, &first)?;
+                        let data_len = data.as_ref().map_or(0, |(_, range)| range.len());
+                        layout.feed_links(&first, data_len as u64, links.clone());
+                        data.as_ref().map(|(block, range)| (block, range))
+                    }
+                    Some(UnixFsNode::Unexpected(found)) => {
+                        return Err(ReadSingleFileError::UnexpectedNodeType {
+                            cid: first,
+                            found: *found,
+                        })
+                    }
+                    // Next node is not yet known, read more blocks
+                    None => break,
+                };
+
+                if let Some((block, range)) = chunk.filter(|(_, range)| !range.is_empty()) {
+                    self.options
+                        .check_write_limit(self.bytes_read, range.len() as u64, &first)?;
+                    self.bytes_read += range.len() as u64;
+                    return Ok(Some(FileChunk {
+                        block: block.clone(),
+                        range: range.clone(),
+                    }));
+                }
+            }
+
+            if layout.is_complete() {
+                self.options.check_expected_size(self.bytes_read)?;
+                // Release the blocks and the CAR input
+                self.streamer = None;
+                self.nodes.clear();
+                return Ok(None);
+            }
+
+            let Some(item) = streamer.next().await else {
+                return Err(if self.nodes.contains_key(&block_key(&root_cid)) {
+                    ReadSingleFileError::PendingLinksAtEOF(layout.remaining())
+                } else {
+                    root_not_found(root_cid, self.blocks_read, &streamer.header.roots)
+                });
+            };
+            let (cid, block) = item?;
+
+            self.blocks_read += 1;
+            self.options.check_blocks_read(self.blocks_read)?;
+            self.options.check_block_size(&cid, block.len())?;
+
+            let (mut inner, links) = decode_block(&cid, &block, false)?;
+            // The root may be streamed in another CID version than requested
+            if same_block(&cid, &root_cid) {
+                self.options.descend_single_entry_root(&mut inner)?;
+                self.options.unwrap_metadata_root(&mut inner, &links)?;
+                self.options.check_file_root(&cid, &mut inner)?;
+                self.options.check_declared_size(inner.data.filesize)?;
+                self.options.check_declared_limit(inner.data.filesize)?;
+            }
+
+            let data = if links.is_empty() {
+                Some(leaf_data(&block, inner.data.Data, inner.data.filesize)?)
+            } else {
+                inner.data.Data.filter(|data| !data.is_empty())
+            };
+            let range = match &data {
+                Some(data) => Some(data_range(&block, data)?),
+                None => None,
+            };
+            let node_type = inner.data.Type;
+
+            if let Some(max_buffer) = self.options.max_buffer {
+                self.buffered_data_len += range.as_ref().map_or(0, |range| range.len());
+                if self.buffered_data_len > max_buffer {
+                    return Err(ReadSingleFileError::MaxBufferedData(max_buffer));
+                }
+            }
+
+            let block = Arc::new(block);
+            let node = if self
+                .options
+                .check_node_type(&cid, node_type, !links.is_empty())
+                .is_err()
+            {
+                // Only an error if the node is part of the file DAG, checked when reached
+                UnixFsNode::Unexpected(node_type)
+            } else if links.is_empty() {
+                UnixFsNode::Data(block, range.unwrap_or_default())
+            } else {
+                UnixFsNode::Links {
+                    links,
+                    data: range.map(|range| (block, range)),
+                }
+            };
+            self.nodes.insert(block_key(&cid), node);
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use futures::{io::Cursor, AsyncRead, FutureExt};
+use rs_car::{CarDecodeError, CarReader, Cid};
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+/// Limits of rs-car, past which it returns an error on its own
+const MAX_HEADER_LEN: u64 = 1048576;
+const MAX_PADDING_LEN: u64 = 1073741824;
+const MAX_BLOCK_LEN: u64 = 1073741824;
+const CARV2_PRAGMA_SIZE: u64 = 11;
+const CARV2_HEADER_SIZE: usize = 40;
+/// Max digest length of the CIDs decoded by rs-car
+const MAX_DIGEST_LEN: u64 = 64;
+/// Block section varint + CID of at most 4 varints and the digest
+const SECTION_PREFIX_LEN: usize = 10 + 4 * 10 + MAX_DIGEST_LEN as usize;
+
+/// Pass-through reader of a CAR stream checking its framing before `CarReader` decodes it.
+///
+/// rs-car trusts some lengths of the stream and panics on an arithmetic overflow or an
+/// `unwrap` when they are malformed: a CARv2 header with a data offset or size shorter than the
+/// headers it covers, a block section shorter than its CID, a CID version other than 0 and 1 or
+/// a digest longer than 64 bytes. Those are returned here as an [`io::ErrorKind::InvalidData`]
+/// error wrapping the [`CarDecodeError`] rs-car would have returned for them, unwrapped again
+/// when converted to a `ReadSingleFileError`. Headers with keys rs-car ignores are rejected
+/// the same way with a [`MalformedHeader`]. With [`with_max_block_size`](Self::with_max_block_size)
+/// a block section declaring a larger block is rejected with a [`SectionTooLarge`], before rs-car
+/// allocates the block. Each structure is read ahead before any of its bytes is returned,
+/// everything else is left for rs-car to validate.
+pub(crate) struct FramingGuard<R> {
+    inner: R,
+    /// Bytes read from `inner` and not yet returned, starting at `position`
+    ahead: Vec<u8>,
+    /// Offset in the stream of the next byte returned
+    position: u64,
+    /// Next structure to check and its offset, None once nothing is left to check
+    next: Option<(u64, Structure)>,
+    /// End of the block sections of a CARv2
+    data_end: Option<u64>,
+    /// Max length of the block of a section, None for the limit of rs-car only
+    max_block_size: Option<usize>,
+    eof: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Structure {
+    /// CARv1 header, or the pragma of a CARv2
+    Header,
+    V2Header,
+    /// CARv1 header within a CARv2
+    InnerHeader {
+        data_size: u64,
+    },
+    Section,
+}
+
+/// Outcome of checking a structure with the bytes read ahead
+enum Check {
+    /// More bytes are needed to check it
+    NeedMore,
+    /// Next structure to check and its offset
+    Next(u64, Structure),
+    /// Left to rs-car from here, which returns an error itself
+    Stop,
+}
+
+impl<R: AsyncRead + Unpin> FramingGuard<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            ahead: vec![],
+            position: 0,
+            next: Some((0, Structure::Header)),
+            data_end: None,
+            max_block_size: None,
+            eof: false,
+        }
+    }
+
+    /// Reject block sections declaring a block longer than `max_block_size` bytes
+    pub(crate) fn with_max_block_size(mut self, max_block_size: usize) -> Self {
+        self.max_block_size = Some(max_block_size);
+        self
+    }
+
+    /// Count of bytes returned so far, the offset in the stream of the next byte read
+    pub(crate) fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn check(&mut self, at: u64, structure: Structure) -> io::Result<Check> {
+        let ahead = &self.ahead[..];
+        Ok(match structure {
+            Structure::Header => {
+                let Some((len, varint_len)) = decode_varint(ahead) else {
+                    return Ok(Check::NeedMore);
+                };
+                if len > MAX_HEADER_LEN {
+                    return Ok(Check::Stop);
+                }
+                let header_len = varint_len as u64 + len;
+                let Some(header) = ahead.get(..header_len as usize) else {
+                    return Ok(Check::NeedMore);
+                };
+                // Let rs-car tell a CARv1 from a CARv2, which would read its header next
+                match CarReader::new(&mut Cursor::new(header), false).now_or_never() {
+                    Some(Ok(_)) => {
+                        check_header_keys(&header[varint_len..]).map_err(malformed_header)?;
+                        Check::Next(at + header_len, Structure::Section)
+                    }
+                    Some(Err(CarDecodeError::IoError(err)))
+                        if err.kind() == io::ErrorKind::UnexpectedEof =>
+                    {
+                        check_header_keys(&header[varint_len..]).map_err(malformed_header)?;
+                        Check::Next(at + header_len, Structure::V2Header)
+                    }
+                    _ => Check::Stop,
+                }
+            }
+            Structure::V2Header => {
+                let Some(header) = ahead.get(..CARV2_HEADER_SIZE) else {
+                    return Ok(Check::NeedMore);
+                };
+                let data_offset = u64::from_le_bytes(header[16..24].try_into().unwrap());
+                let data_size = u64::from_le_bytes(header[24..32].try_into().unwrap());
+                let Some(padding) =
+                    data_offset.checked_sub(CARV2_PRAGMA_SIZE + CARV2_HEADER_SIZE as u64)
+                else {
+                    return Err(invalid(CarDecodeError::InvalidCarV2Header(format!(
+                        "CARv2 data offset {} within its header",
+                        data_offset
+                    ))));
+                };
+                if padding > MAX_PADDING_LEN {
+                    return Ok(Check::Stop);
+                }
+                Check::Next(
+                    at + CARV2_HEADER_SIZE as u64 + padding,
+                    Structure::InnerHeader { data_size },
+                )
+            }
+            Structure::InnerHeader { data_size } => {
+                let Some((len, varint_len)) = decode_varint(ahead) else {
+                    return Ok(Check::NeedMore);
+                };
+                if len > MAX_HEADER_LEN {
+                    return Ok(Check::Stop);
+                }
+                let header_len = varint_len as u64 + len;
+                let Some(blocks_len) = data_size.checked_sub(header_len) else {
+                    return Err(invalid(CarDecodeError::InvalidCarV2Header(format!(
+                        "CARv2 data size {} shorter than its header of {} bytes",
+                        data_size, header_len
+                    ))));
+                };
+                let Some(header) = ahead.get(..header_len as usize) else {
+                    return Ok(Check::NeedMore);
+                };
+                if CarReader::new(&mut Cursor::new(header), false)
+                    .now_or_never()
+                    .is_some_and(|res| res.is_ok())
+                {
+                    check_header_keys(&header[varint_len..]).map_err(malformed_header)?;
+                }
+                self.data_end = Some(at + header_len + blocks_len);
+                Check::Next(at + header_len, Structure::Section)
+            }
+            Structure::Section => {
+                if self.data_end.is_some_and(|data_end| at >= data_end) {
+                    return Ok(Check::Stop);
+                }
+                match check_section(ahead, self.max_block_size)? {
+                    Some(Some(section_len)) => Check::Next(at + section_len, Structure::Section),
+                    Some(None) => Check::Stop,
+                    None => Check::NeedMore,
+                }
+            }
+        })
+    }
+}
+
+/// Check the `[varint|CID]` prefix of a block section as rs-car decodes it, and the length of its
+/// block against `max_block_size`. Returns the length of the whole section, None if more bytes are
+/// needed, or Some(None) if rs-car returns an error
+fn check_section(ahead: &[u8], max_block_size: Option<usize>) -> io::Result<Option<Option<u64>>> {
+    // Offset in `ahead` past the varints decoded so far
+    let mut read = 0;
+    let mut next_varint = || {
+        let (value, len) = decode_varint(&ahead[read..])?;
+        read += len;
+        Some((value, read as u64))
+    };
+
+    let Some((len, varint_len)) = next_varint() else {
+        return Ok(None);
+    };
+    if len == 0 || len > MAX_BLOCK_LEN {
+        return Ok(Some(None));
+    }
+
+    let (Some((version, _)), Some((codec, read_cid))) = (next_varint(), next_varint()) else {
+        return Ok(None);
+    };
+    let cid_len = if [version, codec] == [0x12, 0x20] {
+        // CIDv0, a bare sha2-256 multihash
+        read_cid - varint_len + 32
+    } else {
+        match version {
+            0 => return Ok(Some(None)),
+            1 => {}
+            _ => {
+                return Err(invalid(CarDecodeError::InvalidCid(format!(
+                    "block CID version {}",
+                    version
+                ))))
+            }
+        }
+        let (Some(_), Some((size, read_cid))) = (next_varint(), next_varint()) else {
+            return Ok(None);
+        };
+        if size > MAX_DIGEST_LEN {
+            return Err(invalid(CarDecodeError::InvalidMultihash(format!(
+                "block CID digest of {} bytes",
+                size
+            ))));
+        }
+        read_cid - varint_len + size
+    };
+
+    if cid_len > len {
+        return Err(invalid(CarDecodeError::InvalidBlockHeader(format!(
+            "block section of {} bytes shorter than its CID of {} bytes",
+            len, cid_len
+        ))));
+    }
+    if let Some(limit) = max_block_size {
+        let size = usize::try_from(len - cid_len).unwrap_or(usize::MAX);
+        if size > limit {
+            let Some(cid) = ahead.get(varint_len as usize..(varint_len + cid_len) as usize) else {
+                return Ok(None);
+            };
+            // An invalid CID is left for rs-car to report
+            if let Ok(cid) = Cid::try_from(cid) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    SectionTooLarge { cid, size, limit },
+                ));
+            }
+        }
+    }
+    Ok(Some(Some(varint_len + len)))
+}
+
+/// Decode a varint at the start of `buf`, None if `buf` ends before it does. An invalid varint is
+/// returned as is, rs-car fails on it
+fn decode_varint(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut result: u64 = 0;
+    for (i, byte) in buf.iter().take(10).enumerate() {
+        result |= u64::from(byte & 0b0111_1111) << (i * 7);
+        if byte & 0b1000_0000 == 0 || i == 9 {
+            return Some((result, i + 1));
+        }
+    }
+    None
+}
+
+fn invalid(error: CarDecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Header accepted by rs-car but not a dag-cbor map of only `version` and `roots`, passed
+/// through as an I/O error like [`CarDecodeError`]
+#[derive(Debug)]
+pub(crate) struct MalformedHeader(pub(crate) String);
+
+impl std::fmt::Display for MalformedHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MalformedHeader {}
+
+/// Block section declaring a block of `size` bytes, more than the `limit` of the reader, passed
+/// through as an I/O error like [`CarDecodeError`]
+#[derive(Debug)]
+pub(crate) struct SectionTooLarge {
+    pub(crate) cid: Cid,
+    pub(crate) size: usize,
+    pub(crate) limit: usize,
+}
+
+impl std::fmt::Display for SectionTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "block {} of {} bytes larger than {} bytes",
+            self.cid, self.size, self.limit
+        )
+    }
+}
+
+impl std::error::Error for SectionTooLarge {}
+
+fn malformed_header(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, MalformedHeader(reason))
+}
+
+/// Check that the dag-cbor `header` is a map of a `version` and optionally `roots`, each at most
+/// once, with nothing after the map. Values are left
+/// for rs-car to check. rs-car ignores other keys and keeps the last value of a duplicated key,
+/// so headers read differently by other implementations are rejected instead
+fn check_header_keys(header: &[u8]) -> Result<(), String> {
+    let mut at = 0;
+    let (major, len) = cbor_head(header, &mut at)?;
+    if major != CBOR_MAP {
+        return Err("header is not a map".to_owned());
+    }
+    let mut keys: Vec<&[u8]> = vec![];
+    for _ in 0..len {
+        let (major, key_len) = cbor_head(header, &mut at)?;
+        let key = usize::try_from(key_len)
+            .ok()
+            .and_then(|key_len| header.get(at..at.checked_add(key_len)?))
+            .ok_or("header truncated in a key")?;
+        at += key.len();
+        if major != CBOR_TEXT || !matches!(key, b"version" | b"roots") {
+            return Err(format!(
+                "unexpected header key {:?}",
+                String::from_utf8_lossy(key)
+            ));
+        }
+        if keys.contains(&key) {
+            return Err(format!(
+                "duplicated header key {:?}",
+                String::from_utf8_lossy(key)
+            ));
+        }
+        keys.push(key);
+        skip_cbor_item(header, &mut at)?;
+    }
+    if !keys.contains(&&b"version"[..]) {
+        return Err("header has no version".to_owned());
+    }
+    if at != header.len() {
+        return Err(format!("{} bytes after the header map", header.len() - at));
+    }
+    Ok(())
+}
+
+const CBOR_BYTES: u8 = 2;
+const CBOR_TEXT: u8 = 3;
+const CBOR_ARRAY: u8 = 4;
+const CBOR_MAP: u8 = 5;
+const CBOR_TAG: u8 = 6;
+
+/// Decode the major type and argument of the CBOR item at `at`, advancing past them. Indefinite
+/// lengths are not valid dag-cbor
+fn cbor_head(buf: &[u8], at: &mut usize) -> Result<(u8, u64), String> {
+    let first = *buf.get(*at).ok_or("header truncated")?;
+    *at += 1;
+    let len = match first & 0x1f {
+        info @ 0..=23 => return Ok((first >> 5, info.into())),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        info => return Err(format!("header item with additional info {}", info)),
+    };
+    let bytes = buf.get(*at..*at + len).ok_or("header truncated")?;
+    *at += len;
+    let value = bytes
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | u64::from(*byte));
+    Ok((first >> 5, value))
+}
+
+/// Advance `at` past the CBOR item there, with its nested items
+fn skip_cbor_item(buf: &[u8], at: &mut usize) -> Result<(), String> {
+    // Items left to skip, each takes at least one byte
+    let mut remaining: u64 = 1;
+    while remaining > 0 {
+        remaining -= 1;
+        let (major, value) = cbor_head(buf, at)?;
+        let nested = match major {
+            CBOR_BYTES | CBOR_TEXT => {
+                *at = usize::try_from(value)
+                    .ok()
+                    .and_then(|len| at.checked_add(len))
+                    .filter(|end| *end <= buf.len())
+                    .ok_or("header truncated")?;
+                0
+            }
+            CBOR_ARRAY => value,
+            CBOR_MAP => value.saturating_mul(2),
+            CBOR_TAG => 1,
+            // Integers, simple values and floats are only their head
+            _ => 0,
+        };
+        remaining = remaining.saturating_add(nested);
+        if remaining > (buf.len() - *at) as u64 {
+            return Err("header truncated".to_owned());
+        }
+    }
+    Ok(())
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for FramingGuard<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        // Check the next structure once reached, reading ahead as much as needed
+        while let Some((at, structure)) = me.next.filter(|(at, _)| *at == me.position) {
+            match me.check(at, structure)? {
+                Check::NeedMore if !me.eof => {
+                    let filled = me.ahead.len();
+                    me.ahead.resize(filled + SECTION_PREFIX_LEN.max(256), 0);
+                    let res = Pin::new(&mut me.inner).poll_read(cx, &mut me.ahead[filled..]);
+                    let n = match res {
+                        Poll::Ready(Ok(n)) => n,
+                        other => {
+                            me.ahead.truncate(filled);
+                            return other;
+                        }
+                    };
+                    me.ahead.truncate(filled + n);
+                    me.eof = n == 0;
+                }
+                Check::NeedMore | Check::Stop => me.next = None,
+                Check::Next(at, structure) => me.next = Some((at, structure)),
+            }
+        }
+
+        let limit = match me.next {
+            Some((at, _)) => ((at - me.position) as usize).min(buf.len()),
+            None => buf.len(),
+        };
+        let n = if me.ahead.is_empty() {
+            ready!(Pin::new(&mut me.inner).poll_read(cx, &mut buf[..limit]))?
+        } else {
+            let n = limit.min(me.ahead.len());
+            buf[..n].copy_from_slice(&me.ahead[..n]);
+            me.ahead.drain(..n);
+            n
+        };
+        me.position += n as u64;
+        Poll::Ready(Ok(n))
+    }
+}
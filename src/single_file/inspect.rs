@@ -0,0 +1,440 @@
+use futures::{AsyncRead, StreamExt};
+use rs_car::{CarReader, Cid};
+use std::collections::HashMap;
+
+use crate::{
+    layout::FileLayout,
+    pb::{FlatUnixFs, UnixFsType},
+};
+
+use super::{
+    framing::FramingGuard,
+    util::{
+        assert_header_single_file, check_cycle, declared_file_size, decode_block, is_file_root,
+        links_to_cids, root_not_found, CODEC_DAG_PB, CODEC_RAW,
+    },
+    ReadSingleFileError, ReadSingleFileOptions,
+};
+
+/// Decoded UnixFS node metadata, see [`decode_unixfs_node`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnixFsNodeInfo {
+    pub unixfs_type: UnixFsType,
+    /// Length of the inline `Data` of the node
+    pub data_len: usize,
+    /// Declared length of the file under this node
+    pub filesize: Option<u64>,
+    pub blocksizes: Vec<u64>,
+    /// Multicodec of the hash of the entry names of a `HAMTShard`, see [`crate::hamt`]
+    pub hash_type: Option<u64>,
+    /// Number of buckets of a `HAMTShard`
+    pub fanout: Option<u64>,
+    pub links: Vec<LinkInfo>,
+}
+
+/// dag-pb link with its metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkInfo {
+    pub cid: Cid,
+    pub name: Option<String>,
+    pub tsize: Option<u64>,
+}
+
+/// Decode a dag-pb `block` as a UnixFS node, keeping the metadata of its links
+pub fn decode_unixfs_node(block: &[u8]) -> Result<UnixFsNodeInfo, ReadSingleFileError> {
+    let inner = FlatUnixFs::try_from(block)
+        .map_err(|err| ReadSingleFileError::InvalidUnixFs(err.to_string()))?;
+    Ok(node_info(&inner, links_to_cids(&inner.links)?))
+}
+
+pub(crate) fn node_info(inner: &FlatUnixFs<'_>, cids: Vec<Cid>) -> UnixFsNodeInfo {
+    UnixFsNodeInfo {
+        unixfs_type: inner.data.Type,
+        data_len: inner.data.Data.as_ref().map_or(0, |data| data.len()),
+        filesize: inner.data.filesize,
+        blocksizes: inner.data.blocksizes.clone(),
+        hash_type: inner.data.hashType,
+        fanout: inner.data.fanout,
+        links: inner
+            .links
+            .iter()
+            .zip(cids)
+            .map(|(link, cid)| LinkInfo {
+                cid,
+                name: link.Name.as_ref().map(|name| name.to_string()),
+                tsize: link.Tsize,
+            })
+            .collect(),
+    }
+}
+
+/// List all blocks of the CAR stream `car_input` in stream order with their decoded UnixFS node.
+/// Raw codec blocks are listed as `Raw` nodes.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::ls;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///
+///   for (cid, node) in ls(&mut input).await? {
+///     println!("{} {:?} {} links", cid, node.unixfs_type, node.links.len());
+///   }
+///   Ok(())
+/// }
+/// ```
+pub async fn ls<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+) -> Result<Vec<(Cid, UnixFsNodeInfo)>, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let mut streamer = CarReader::new(&mut car_input, true).await?;
+    let mut nodes = vec![];
+
+    while let Some(item) = streamer.next().await {
+        let (cid, block) = item?;
+        let (inner, cids) = decode_block(&cid, &block, false)?;
+        nodes.push((cid, node_info(&inner, cids)));
+    }
+
+    Ok(nodes)
+}
+
+/// Root of a CAR stream and what kind of block it is, see [`inspect_root`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootInfo {
+    pub cid: Cid,
+    /// Multicodec of the root CID, e.g. `0x70` dag-pb, `0x55` raw or `0x71` dag-cbor
+    pub codec: u64,
+    /// Version of the root CID, 0 or 1
+    pub cid_version: u64,
+    /// CAR format version, 1 or 2
+    pub car_version: u64,
+}
+
+/// Read only the header of the CAR stream `car_input` and describe its root, `root_cid` if
+/// provided or else the single root of the header. The root block itself is not read.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::inspect_root;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///
+///   let root = inspect_root(&mut input, None).await?;
+///   assert_eq!((root.codec, root.cid_version), (0x70, 0));
+///   Ok(())
+/// }
+/// ```
+pub async fn inspect_root<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    root_cid: Option<&Cid>,
+) -> Result<RootInfo, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let streamer = CarReader::new(&mut car_input, true).await?;
+    let cid = assert_header_single_file(&streamer.header, root_cid)?;
+    Ok(RootInfo {
+        cid,
+        codec: cid.codec(),
+        cid_version: cid.version().into(),
+        car_version: streamer.header.version as u64,
+    })
+}
+
+/// Summary of a CAR header and its blocks, see [`car_info`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CarInfo {
+    /// CAR format version, 1 or 2
+    pub version: u64,
+    pub roots: Vec<Cid>,
+    pub block_count: usize,
+    /// Total length of the block payloads, excluding CIDs and framing
+    pub block_bytes: u64,
+    pub dag_pb_blocks: usize,
+    pub raw_blocks: usize,
+    /// Blocks with any other codec
+    pub other_blocks: usize,
+    /// Declared filesize of the first root, if it is a UnixFS file included in the CAR
+    pub root_filesize: Option<u64>,
+}
+
+/// Stream the whole CAR `car_input` and summarize its header and blocks, without decoding the
+/// file. Blocks that are not valid UnixFS are only counted. With `validate_hash` the read fails on
+/// the first block not matching its CID.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::car_info;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///
+///   let info = car_info(&mut input, true).await?;
+///   println!("{} blocks, filesize {:?}", info.block_count, info.root_filesize);
+///   Ok(())
+/// }
+/// ```
+pub async fn car_info<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    validate_hash: bool,
+) -> Result<CarInfo, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let mut streamer = CarReader::new(&mut car_input, validate_hash).await?;
+    let mut info = CarInfo {
+        version: streamer.header.version as u64,
+        roots: streamer.header.roots.clone(),
+        block_count: 0,
+        block_bytes: 0,
+        dag_pb_blocks: 0,
+        raw_blocks: 0,
+        other_blocks: 0,
+        root_filesize: None,
+    };
+
+    while let Some(item) = streamer.next().await {
+        let (cid, block) = item?;
+        info.block_count += 1;
+        info.block_bytes += block.len() as u64;
+        match cid.codec() {
+            CODEC_DAG_PB => info.dag_pb_blocks += 1,
+            CODEC_RAW => info.raw_blocks += 1,
+            _ => info.other_blocks += 1,
+        }
+
+        if info.roots.first() == Some(&cid) {
+            if let Ok((inner, _)) = decode_block(&cid, &block, false) {
+                if matches!(inner.data.Type, UnixFsType::File | UnixFsType::Raw) {
+                    info.root_filesize = inner.data.filesize;
+                }
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+/// Length of the file under `root_cid` of the CAR stream `car_input`, reading only until its root
+/// block instead of extracting the file. The length is the declared `filesize` of the root, or
+/// else its inline data and the `blocksizes` of its children. Fails with
+/// [`FileSizeUnknown`](ReadSingleFileError::FileSizeUnknown) if the root declares neither.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::file_size;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///
+///   println!("{} bytes", file_size(&mut input, None).await?);
+///   Ok(())
+/// }
+/// ```
+pub async fn file_size<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    root_cid: Option<&Cid>,
+) -> Result<u64, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let mut streamer = CarReader::new(&mut car_input, true).await?;
+    let root_cid = assert_header_single_file(&streamer.header, root_cid)?;
+
+    let mut blocks_seen = 0;
+    while let Some(item) = streamer.next().await {
+        let (cid, block) = item?;
+        blocks_seen += 1;
+        if cid != root_cid {
+            continue;
+        }
+
+        let (inner, links) = decode_block(&cid, &block, false)?;
+        if !is_file_root(&cid, inner.data.Type) {
+            return Err(ReadSingleFileError::RootCidIsNotFile);
+        }
+        return declared_file_size(&inner, &links).ok_or(ReadSingleFileError::FileSizeUnknown(cid));
+    }
+
+    Err(root_not_found(
+        root_cid,
+        blocks_seen,
+        &streamer.header.roots,
+    ))
+}
+
+/// Range of the file under a block, see [`file_layout`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeafExtent {
+    /// The leaf, or a links node for the data inline before its children
+    pub cid: Cid,
+    /// Offset of the range within the file
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Extents of the data of the file under `root_cid` of the CAR stream `car_input` in file order,
+/// without extracting it, e.g. to plan range requests. Links are expanded as by the readers, and
+/// a leaf appears once per occurrence in the file. The length of a leaf is its data if its block
+/// is in the CAR, or else the blocksize declared by its parent, so leaf blocks are not required
+/// when the links nodes declare blocksizes. Fails with
+/// [`BlocksizesUnknown`](ReadSingleFileError::BlocksizesUnknown) for a leaf of neither.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::file_layout;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///
+///   for extent in file_layout(&mut input, None).await? {
+///     println!("{} {}+{}", extent.cid, extent.offset, extent.len);
+///   }
+///   Ok(())
+/// }
+/// ```
+pub async fn file_layout<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    root_cid: Option<&Cid>,
+) -> Result<Vec<LeafExtent>, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let mut streamer = CarReader::new(&mut car_input, true).await?;
+    let root_cid = assert_header_single_file(&streamer.header, root_cid)?;
+    let options = ReadSingleFileOptions::default();
+
+    // Only the lengths of the leaves are kept
+    let mut nodes = HashMap::new();
+    let mut blocks_seen = 0;
+    while let Some(item) = streamer.next().await {
+        let (cid, block) = item?;
+        blocks_seen += 1;
+        let (inner, links) = decode_block(&cid, &block, false)?;
+        let data_len = inner.data.Data.as_ref().map_or(0, |data| data.len() as u64);
+        let node = if links.is_empty() {
+            Extent::Leaf {
+                unixfs_type: inner.data.Type,
+                len: data_len,
+            }
+        } else {
+            // blocksizes are only usable if there is one per link
+            let sizes =
+                (inner.data.blocksizes.len() == links.len()).then(|| inner.data.blocksizes.clone());
+            Extent::Links {
+                unixfs_type: inner.data.Type,
+                data_len,
+                links,
+                sizes,
+            }
+        };
+        nodes.insert(cid, node);
+    }
+
+    let Some(root) = nodes.get(&root_cid) else {
+        return Err(root_not_found(
+            root_cid,
+            blocks_seen,
+            &streamer.header.roots,
+        ));
+    };
+    let root_type = match root {
+        Extent::Leaf { unixfs_type, .. } | Extent::Links { unixfs_type, .. } => *unixfs_type,
+    };
+    if !is_file_root(&root_cid, root_type) {
+        return Err(ReadSingleFileError::RootCidIsNotFile);
+    }
+
+    let mut layout = FileLayout::new(root_cid);
+    // Declared length of each link, or the node and index of the link without one
+    let mut declared: HashMap<Cid, Result<u64, (Cid, usize)>> = HashMap::new();
+    let mut extents = vec![];
+    while let Some(cid) = layout.next_ready() {
+        let offset = layout.offset();
+        match nodes.get(&cid) {
+            Some(Extent::Links {
+                unixfs_type,
+                data_len,
+                links,
+                sizes,
+            }) => {
+                options.check_node_type(&cid, *unixfs_type, true)?;
+                check_cycle(&layout, &cid)?;
+                for (i, link) in links.iter().enumerate() {
+                    let size = match sizes {
+                        Some(sizes) => Ok(sizes[i]),
+                        None => Err((cid, i)),
+                    };
+                    // A leaf linked more than once may only be declared by some of its parents
+                    if !matches!(declared.get(link), Some(Ok(_))) {
+                        declared.insert(*link, size);
+                    }
+                }
+                if *data_len > 0 {
+                    extents.push(LeafExtent {
+                        cid,
+                        offset,
+                        len: *data_len,
+                    });
+                }
+                layout.feed_links(&cid, *data_len, links.clone());
+            }
+            Some(Extent::Leaf { unixfs_type, len }) => {
+                options.check_node_type(&cid, *unixfs_type, false)?;
+                if let Some(Ok(size)) = declared.get(&cid) {
+                    if size != len {
+                        return Err(ReadSingleFileError::InvalidUnixFs(format!(
+                            "leaf {} of {} bytes declared with a blocksize of {}",
+                            cid, len, size
+                        )));
+                    }
+                }
+                extents.push(LeafExtent {
+                    cid,
+                    offset,
+                    len: *len,
+                });
+                layout.feed_leaf(&cid, *len);
+            }
+            // Not in the CAR, taken as a single extent of its declared length
+            None => match declared.get(&cid) {
+                Some(Ok(len)) => {
+                    extents.push(LeafExtent {
+                        cid,
+                        offset,
+                        len: *len,
+                    });
+                    layout.feed_leaf(&cid, *len);
+                }
+                Some(Err((node, link_index))) => {
+                    return Err(ReadSingleFileError::BlocksizesUnknown {
+                        node: *node,
+                        link_index: *link_index,
+                    })
+                }
+                None => return Err(ReadSingleFileError::MissingNode(cid)),
+            },
+        }
+    }
+
+    Ok(extents)
+}
+
+/// What [`file_layout`] keeps of a block
+enum Extent {
+    Leaf {
+        unixfs_type: UnixFsType,
+        len: u64,
+    },
+    Links {
+        unixfs_type: UnixFsType,
+        data_len: u64,
+        links: Vec<Cid>,
+        sizes: Option<Vec<u64>>,
+    },
+}
@@ -0,0 +1,43 @@
+use std::{borrow::Cow, fmt, sync::Arc};
+
+/// Error returned by a [`MapLeafHook`], failing the read with
+/// [`MapLeaf`](super::ReadSingleFileError::MapLeaf)
+pub type MapLeafError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Callback called with the data of each leaf before it is written, returning the data to write
+/// instead, see [`map_leaf`](super::ReadSingleFileOptions::map_leaf).
+///
+/// ```
+/// use rs_car_ipfs::single_file::{MapLeafHook, ReadSingleFileOptions};
+/// use std::borrow::Cow;
+///
+/// let options = ReadSingleFileOptions {
+///     map_leaf: Some(MapLeafHook::new(|data| {
+///         Ok(Cow::Owned(data.iter().map(|byte| byte ^ 0x5a).collect()))
+///     })),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct MapLeafHook(Arc<MapLeafFn>);
+
+type MapLeafFn = dyn Fn(&[u8]) -> Result<Cow<'_, [u8]>, MapLeafError> + Send + Sync;
+
+impl MapLeafHook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&[u8]) -> Result<Cow<'_, [u8]>, MapLeafError> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>, MapLeafError> {
+        (self.0)(data)
+    }
+}
+
+impl fmt::Debug for MapLeafHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MapLeafHook")
+    }
+}
@@ -0,0 +1,193 @@
+use futures::{future::BoxFuture, AsyncRead, AsyncSeek, AsyncWrite};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::{SetLen, SyncAll};
+
+/// Writable memory of a fixed length backing a [`MappedOutput`], e.g. a memory map of a file
+/// sized up front. Implemented for `memmap2::MmapMut` with the `memmap2` feature, implement it
+/// for the map type of another mapping crate with `flush` writing back its pages.
+pub trait MappedRegion: AsRef<[u8]> + AsMut<[u8]> {
+    /// Write the modified pages back to the mapped file, called by [`SyncAll`]. Memory that is
+    /// not backed by a file has nothing to do
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl MappedRegion for Vec<u8> {}
+
+impl MappedRegion for Box<[u8]> {}
+
+impl MappedRegion for &mut [u8] {}
+
+#[cfg(feature = "memmap2")]
+impl MappedRegion for memmap2::MmapMut {
+    fn flush(&mut self) -> io::Result<()> {
+        memmap2::MmapMut::flush(self)
+    }
+}
+
+/// Output writing into a memory region of a fixed length, e.g. a memory map of a file sized to
+/// the `filesize` of the root with [`file_size`](super::file_size). Writes are copies into the
+/// region and never block, seeks over runs of zeros leave the region untouched, which is
+/// already zero for a file extended with `set_len`.
+///
+/// The region can not grow: a write past its end fails with `WriteZero`, and
+/// [`SetLen`] to a larger length fails, so with `preallocate` the region must be at least the
+/// declared size of the file. [`SetLen`] to a shorter length zeroes the region past it, so
+/// `truncate` and `cleanup_on_error` leave no previous contents behind. Flushing does nothing,
+/// [`SyncAll`] calls [`MappedRegion::flush`].
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::{file_size, read_single_file_seek_file, MappedOutput};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let len = file_size(&mut async_std::fs::File::open("tests/example.car").await?, None).await?;
+///
+///   // A memory map of a file extended to `len` bytes instead, e.g. `memmap2::MmapMut`
+///   let mut out = MappedOutput::new(vec![0; len as usize]);
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   read_single_file_seek_file(&mut input, &mut out, None, &Default::default()).await?;
+///   assert_eq!(out.position(), len);
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MappedOutput<M> {
+    region: M,
+    position: u64,
+}
+
+impl<M: MappedRegion> MappedOutput<M> {
+    /// Write `region` starting at position 0
+    pub fn new(region: M) -> Self {
+        Self {
+            region,
+            position: 0,
+        }
+    }
+
+    /// Position of the next read or write within the region
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn get_ref(&self) -> &M {
+        &self.region
+    }
+
+    pub fn into_inner(self) -> M {
+        self.region
+    }
+
+    fn len(&self) -> u64 {
+        self.region.as_ref().len() as u64
+    }
+}
+
+impl<M: MappedRegion + Unpin> AsyncWrite for MappedOutput<M> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let len = self.len();
+        if self.position >= len && !buf.is_empty() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                format!("write past the end of the mapped region of {} bytes", len),
+            )));
+        }
+        let start = self.position.min(len) as usize;
+        let written = buf.len().min(len as usize - start);
+        self.region.as_mut()[start..start + written].copy_from_slice(&buf[..written]);
+        self.position += written as u64;
+        Poll::Ready(Ok(written))
+    }
+
+    /// Writes are copies into the region, not buffered
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<M: MappedRegion + Unpin> AsyncRead for MappedOutput<M> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let region = self.region.as_ref();
+        let start = (self.position as usize).min(region.len());
+        let read = buf.len().min(region.len() - start);
+        buf[..read].copy_from_slice(&region[start..start + read]);
+        self.position += read as u64;
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl<M: MappedRegion + Unpin> AsyncSeek for MappedOutput<M> {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.position = offset;
+                return Poll::Ready(Ok(offset));
+            }
+            SeekFrom::Current(offset) => (self.position, offset),
+            SeekFrom::End(offset) => (self.len(), offset),
+        };
+        match base.checked_add_signed(offset) {
+            Some(position) => {
+                self.position = position;
+                Poll::Ready(Ok(position))
+            }
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ))),
+        }
+    }
+}
+
+impl<M: MappedRegion> SetLen for MappedOutput<M> {
+    /// The region has a fixed length: zeroes it past `len` as a truncated file would read, fails
+    /// beyond its length
+    fn set_len(&mut self, len: u64) -> BoxFuture<'_, io::Result<()>> {
+        let res = match len <= self.len() {
+            true => {
+                self.region.as_mut()[len as usize..].fill(0);
+                Ok(())
+            }
+            false => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "mapped region of {} bytes can not be extended to {} bytes",
+                    self.len(),
+                    len
+                ),
+            )),
+        };
+        Box::pin(futures::future::ready(res))
+    }
+}
+
+impl<M: MappedRegion> SyncAll for MappedOutput<M> {
+    fn sync_all(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(futures::future::ready(self.region.flush()))
+    }
+}
@@ -35,16 +35,154 @@
 //! 7 [-][-][-][-][-][-][-][-][-][-][-][-]
 //! ```
 //!
+//! The link stack is available to custom readers as [`FileLayout`](crate::layout::FileLayout).
+//!
 //! # Usage
 //!
 //! - To read a single file buffering the block dag [`read_single_file_buffer`]
 //! - To read a single file without buffering the block dag [`read_single_file_seek`]
+//! - To pick between the two from the declared size of the file and a memory budget
+//!   [`read_single_file_auto`]
+//! - To read with one set of options from the type of `out`, seek for outputs that can be read
+//!   back and buffer otherwise [`ReadSingleFileOptions::read_seek`] and
+//!   [`ReadSingleFileOptions::read_buffered`]
+//! - To read a single file from a seekable CAR with a block index [`read_single_file_indexed`]
+//! - To read a single file from blocks already in memory, without a CAR
+//!   [`read_single_file_from_blocks`]
+//! - To load a CAR into memory once, to inspect its DAG and extract several files or subtrees
+//!   of it [`load_dag`]
+//! - To know how much of the file was verified when a read fails, e.g. to resume it with a range
+//!   request [`read_single_file_seek_verified`]
+//! - To write the file with a write-only handle and read it back with another one
+//!   [`read_single_file_seek_split`]
+//! - To poll the progress of a read from another task, e.g. the next block it is waiting for
+//!   [`read_single_file_seek_with_handle`]
+//! - To read a single file split across several CAR responses [`read_single_file_multi`]
+//! - To extract several files from one CAR in a single pass, decoding each block once
+//!   [`extract_many`]
+//! - To extract every root of a CAR to a directory in a single pass, with the `async-std` feature
+//!   `read_all_files`
+//! - To write the file with positional I/O, leaving the offset of its descriptor untouched, with
+//!   the `pwrite` feature on Unix `read_single_file_pwrite`
+//! - To write the file into a memory map of a file sized up front, e.g. a `memmap2::MmapMut`
+//!   with the `memmap2` feature [`MappedOutput`]
+//! - To read the file as an `AsyncRead`, e.g. to pass it to a hasher or an HTTP response [`CarFileReader`]
+//! - To read only the first bytes of a file, e.g. to preview it [`read_single_file_prefix`]
+//! - To pass the bytes of a file with their offset to a callback instead of writing them, e.g. to
+//!   scan the content [`visit_file_leaves`]
+//! - To serve blocks shared by several CARs from a store instead of the CAR [`BlockStore`]
+//! - To fetch blocks missing from the CAR from elsewhere, e.g. a gateway [`ResolveMissing`]
+//! - To decrypt or otherwise transform the data of each leaf as it is written [`MapLeafHook`]
+//! - To extract a file from a CAR that is still being written [`TailOptions`]
+//! - To hash the blocks of large CARs on several threads while decoding them
+//!   [`validation_concurrency`](ReadSingleFileOptions::validation_concurrency)
+//! - To limit the bandwidth of the writes to `out`, or measure it [`RateLimit`] and
+//!   [`ThroughputHook`]
+//! - To read from a `Stream` of byte chunks, e.g. an HTTP response body [`read_single_file_seek_stream`]
+//! - To list the entries of a directory node [`decode_directory`]
+//! - To extract directory entries to disk without escaping the target directory
+//!   [`safe_entry_path`] and [`check_symlink`]
+//! - To extract a directory to disk, naming each entry by its link or else its CID, with the
+//!   `async-std` feature `extract_directory`
+//! - To preview what extracting a DAG would write, without writing [`plan_extraction`]
+//! - To copy the blocks of one file or directory of a CAR to a smaller CAR, e.g. to re-serve it
+//!   [`extract_subgraph_car`]
+//! - To extract a file while writing the blocks of its DAG to a smaller CAR, e.g. to prune a CAR
+//!   of several roots [`read_single_file_and_reexport`]
+//! - To inspect the DAG structure of a CAR, with link names and sizes [`ls`]
+//! - To summarize the header and blocks of a CAR [`car_info`]
+//! - To get the length of a file before extracting it [`file_size`]
+//! - To get the offsets of the leaves of a file without extracting it [`file_layout`]
+//! - To serve random reads of a file from a seekable CAR, e.g. HTTP Range requests
+//!   [`FileOffsetIndex`]
+//! - To know the codec and CID version of the root of a CAR [`inspect_root`]
+//!
+//! The futures of the readers are `Send` when their inputs are, so extractions can be spawned on
+//! a multi-threaded executor.
 
+mod auto;
+mod block_error;
+mod block_store;
+mod car_index;
+mod dag_view;
+mod directory;
 mod error;
+mod file_reader;
+mod framing;
+mod inspect;
+mod map_leaf;
+mod mapped;
+mod multi_file;
+mod offset_index;
+mod options;
+mod output;
+mod output_hash;
+#[cfg(all(unix, feature = "pwrite"))]
+mod positional;
+mod progress;
+mod resolve_missing;
+mod router;
 mod single_file_buffer;
+mod single_file_indexed;
 mod single_file_seek;
+mod sniff;
+mod stream_input;
+mod subgraph;
+mod summary;
+mod tail;
+mod throttle;
 mod util;
+mod validate_pool;
 
-pub use error::ReadSingleFileError;
-pub use single_file_buffer::read_single_file_buffer;
-pub use single_file_seek::read_single_file_seek;
+pub use auto::read_single_file_auto;
+pub use block_error::{BlockErrorHook, ErrorAction, SkipFill};
+pub use block_store::{BlockStore, MemoryBlockStore, SharedBlockStore};
+pub use car_index::CarIndex;
+pub use dag_view::{load_dag, DagView};
+#[cfg(feature = "async-std")]
+pub use directory::extract_directory;
+pub use directory::{
+    check_symlink, decode_directory, plan_extraction, safe_entry_path, DirectoryEntry, EntryKind,
+    ExtractedFile, ExtractionPlan, PlannedEntry, SymlinkPolicy,
+};
+pub use error::{ErrorDetails, ReadSingleFileError, VerifiedPrefixError};
+pub use file_reader::{read_single_file_prefix, visit_file_leaves, CarFileReader};
+pub use inspect::{
+    car_info, decode_unixfs_node, file_layout, file_size, inspect_root, ls, CarInfo, LeafExtent,
+    LinkInfo, RootInfo, UnixFsNodeInfo,
+};
+pub use map_leaf::{MapLeafError, MapLeafHook};
+pub use mapped::{MappedOutput, MappedRegion};
+pub use multi_file::extract_many;
+#[cfg(feature = "async-std")]
+pub use multi_file::read_all_files;
+pub use offset_index::{FileOffsetIndex, LeafRange};
+pub use options::{
+    ReadSingleFileOptions, RootSelector, DEFAULT_MAX_BLOCK_SIZE, DEFAULT_READ_BUFFER_CAPACITY,
+    DEFAULT_REORDER_WINDOW,
+};
+pub use output::{Finalize, SetLen, SyncAll};
+pub use output_hash::OutputHash;
+#[cfg(all(unix, feature = "pwrite"))]
+pub use positional::{read_single_file_pwrite, PositionalFile};
+pub use progress::{Progress, ProgressHandle, ProgressHook};
+pub use resolve_missing::{ResolveHook, ResolveMissing};
+pub use single_file_buffer::{
+    read_single_file_buffer, read_single_file_buffer_verified,
+    read_single_file_buffer_with_options, read_single_file_from_blocks,
+};
+pub use single_file_indexed::{read_single_file_indexed, read_single_file_indexed_with_options};
+pub use single_file_seek::{
+    read_single_file_multi, read_single_file_seek, read_single_file_seek_file,
+    read_single_file_seek_split, read_single_file_seek_verified, read_single_file_seek_with_handle,
+    read_single_file_seek_with_options,
+};
+#[cfg(feature = "sniff")]
+pub use sniff::sniff_content_type;
+pub use stream_input::{
+    read_single_file_buffer_stream, read_single_file_seek_stream, stream_car_input,
+};
+pub use subgraph::{extract_subgraph_car, read_single_file_and_reexport, SubgraphStats};
+pub use summary::ReadSummary;
+pub use tail::{SleepHook, TailOptions};
+pub use throttle::{RateLimit, Throughput, ThroughputHook};
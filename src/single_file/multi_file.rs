@@ -0,0 +1,317 @@
+use futures::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use rs_car::Cid;
+use std::{collections::HashMap, io::SeekFrom, sync::Arc};
+
+use crate::{
+    cid_version::same_block,
+    layout::{FileLayout, LeafDisposition},
+};
+
+use super::{
+    framing::FramingGuard,
+    output::FromOut,
+    router::{BlockRouter, RoutedNode},
+    single_file_seek::{copy_from_to_itself, write_maybe_sparse, Holes},
+    util::{check_cycle, is_file_root, root_not_found},
+    ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
+
+/// Extract several files from one CAR stream in a single pass, e.g. the files of a directory or
+/// several requested paths. Each request is the root CID of a file and the `out` it is written
+/// to from its start, as with [`read_single_file_seek`](super::read_single_file_seek), but each
+/// block is validated and decoded once for all of them.
+///
+/// Returns the result of each request in order. A request failing, e.g. on a node of its DAG that
+/// is not UnixFS or on an error of its `out`, does not stop the others. Only a failure of the CAR
+/// stream itself, such as a malformed block section or a block not matching its CID, fails all
+/// of them as the outer error. The read stops once every request completed or failed. The
+/// summaries have `bytes_written`, the holes left in `out` and `blocks_read`, the count of blocks
+/// read when the file was complete.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::{Cid, single_file::extract_many};
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let root_cid = Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf")?;
+///   let (mut a, mut b) = (Cursor::new(Vec::new()), Cursor::new(Vec::new()));
+///
+///   let results = extract_many(&mut input, vec![(root_cid, &mut a), (root_cid, &mut b)]).await?;
+///   for result in results {
+///     println!("{} bytes", result?.bytes_written);
+///   }
+///   Ok(())
+/// }
+/// ```
+pub async fn extract_many<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    requests: Vec<(Cid, W)>,
+) -> Result<Vec<Result<ReadSummary, ReadSingleFileError>>, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let router = BlockRouter::new(&mut car_input).await?;
+    let files = requests
+        .into_iter()
+        .map(|(root_cid, out)| FileExtraction::new(root_cid, out))
+        .collect();
+    route_files(router, files).await
+}
+
+/// Extract every root of the CAR header as a file in a single pass, each written to a file of
+/// `out_dir` named by its root CID, like [`extract_many`] with the roots of the header. Roots
+/// listed more than once are extracted once. Returns the result of each root in the order of
+/// the header, a root failing, e.g. on a file that can not be created or a root that is not a
+/// file, does not stop the others. Only available with the `async-std` feature
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::read_all_files;
+///
+/// # #[cfg(not(feature = "async-std"))]
+/// # fn main() {}
+/// # #[cfg(feature = "async-std")]
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///
+///   for (root_cid, result) in read_all_files(&mut input, &std::env::temp_dir()).await? {
+///     println!("{}: {} bytes", root_cid, result?.bytes_written);
+///   }
+///   Ok(())
+/// }
+/// ```
+#[cfg(feature = "async-std")]
+pub async fn read_all_files<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    out_dir: &std::path::Path,
+) -> Result<Vec<(Cid, Result<ReadSummary, ReadSingleFileError>)>, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let router = BlockRouter::new(&mut car_input).await?;
+
+    let mut roots: Vec<Cid> = vec![];
+    for root_cid in router.header_roots() {
+        if !roots.contains(root_cid) {
+            roots.push(*root_cid);
+        }
+    }
+    let mut files = vec![];
+    let mut open_errors = vec![];
+    for root_cid in &roots {
+        let out = async_std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(out_dir.join(root_cid.to_string()))
+            .await;
+        match out {
+            Ok(out) => files.push(FileExtraction::new(*root_cid, out)),
+            Err(err) => open_errors.push((*root_cid, err)),
+        }
+    }
+
+    let mut results = route_files(router, files).await?.into_iter();
+    Ok(roots
+        .into_iter()
+        .map(|root_cid| {
+            let result = match open_errors.iter().position(|(cid, _)| *cid == root_cid) {
+                Some(i) => Err(open_errors.swap_remove(i).1.into()),
+                None => results.next().expect("one result per opened file"),
+            };
+            (root_cid, result)
+        })
+        .collect())
+}
+
+/// Feed the blocks of `router` to `files` until every file completed or failed, or the CAR ends
+async fn route_files<R: AsyncRead + Send + Unpin, W: AsyncSeek + AsyncRead + AsyncWrite + Unpin>(
+    mut router: BlockRouter<'_, R>,
+    mut files: Vec<FileExtraction<W>>,
+) -> Result<Vec<Result<ReadSummary, ReadSingleFileError>>, ReadSingleFileError> {
+    let mut results = vec![];
+    for file in &mut files {
+        let start = file.out.seek(SeekFrom::Start(0)).await;
+        results.push(start.err().map(|err| Err(err.into())));
+    }
+
+    while results.iter().any(Option::is_none) {
+        let Some(item) = router.next_block().await else {
+            break;
+        };
+        let (cid, node) = item?;
+        for (file, result) in files.iter_mut().zip(results.iter_mut()) {
+            if result.is_some() {
+                continue;
+            }
+            match file.feed(&cid, &node).await {
+                Ok(true) => *result = Some(file.finish(router.blocks_read()).await),
+                Ok(false) => {}
+                Err(err) => *result = Some(Err(err)),
+            }
+        }
+    }
+
+    // Requests not complete at the end of the CAR
+    Ok(files
+        .iter()
+        .zip(results)
+        .map(|(file, result)| {
+            result.unwrap_or_else(|| {
+                Err(match file.nodes.contains_key(&file.root_cid) {
+                    true => ReadSingleFileError::PendingLinksAtEOF(file.layout.remaining()),
+                    false => {
+                        root_not_found(file.root_cid, router.blocks_read(), router.header_roots())
+                    }
+                })
+            })
+        })
+        .collect())
+}
+
+/// State of one file of an [`extract_many`], writing it as its blocks arrive like
+/// [`read_single_file_seek`](super::read_single_file_seek)
+struct FileExtraction<W> {
+    root_cid: Cid,
+    out: W,
+    layout: FileLayout,
+    nodes: HashMap<Cid, Node>,
+    out_ptr: u64,
+    holes: Holes,
+}
+
+enum Node {
+    /// A node with links, or a block that failed to decode
+    Routed(Arc<RoutedNode>),
+    /// Leaf already written at `start`
+    DataPtr { start: u64, size: usize },
+}
+
+impl<W: AsyncSeek + AsyncRead + AsyncWrite + Unpin> FileExtraction<W> {
+    fn new(root_cid: Cid, out: W) -> Self {
+        Self {
+            root_cid,
+            out,
+            layout: FileLayout::new(root_cid),
+            nodes: HashMap::new(),
+            out_ptr: 0,
+            holes: Holes::default(),
+        }
+    }
+
+    /// Write what the block `cid` makes ready. Returns true once the whole file is written
+    async fn feed(
+        &mut self,
+        cid: &Cid,
+        node: &Arc<RoutedNode>,
+    ) -> Result<bool, ReadSingleFileError> {
+        let options = ReadSingleFileOptions::default();
+        // The root may be streamed in another CID version than requested
+        if same_block(cid, &self.root_cid) {
+            let unixfs_type = match &**node {
+                RoutedNode::Leaf { unixfs_type, .. } | RoutedNode::Links { unixfs_type, .. } => {
+                    *unixfs_type
+                }
+                RoutedNode::Invalid(_) => return Err(node.error(cid)),
+            };
+            if !is_file_root(cid, unixfs_type) {
+                return Err(ReadSingleFileError::RootCidIsNotFile);
+            }
+        }
+
+        match &**node {
+            RoutedNode::Leaf {
+                unixfs_type,
+                block,
+                data,
+            } => match self.layout.disposition(cid) {
+                LeafDisposition::Next => {
+                    options.check_node_type(cid, *unixfs_type, false)?;
+                    let data = &block[data.clone().ok_or_else(|| node.error(cid))?];
+                    write_maybe_sparse(&mut self.out, data, &mut self.holes).await?;
+                    self.nodes.insert(
+                        *cid,
+                        Node::DataPtr {
+                            start: self.out_ptr,
+                            size: data.len(),
+                        },
+                    );
+                    self.out_ptr += data.len() as u64;
+                    self.layout.feed_leaf(cid, data.len() as u64);
+                }
+                LeafDisposition::Deferred => return Err(ReadSingleFileError::DataNodesNotSorted),
+                // Not part of this file, or linked by a node not read yet
+                LeafDisposition::Unknown => return Ok(false),
+            },
+            RoutedNode::Links { .. } | RoutedNode::Invalid(_) => {
+                self.nodes.insert(*cid, Node::Routed(node.clone()));
+            }
+        }
+
+        while let Some(first) = self.layout.next_ready() {
+            match self.nodes.get(&first) {
+                // Duplicated data, copied from where it was written
+                Some(Node::DataPtr { start, size }) => {
+                    copy_from_to_itself(
+                        &mut self.out,
+                        &mut FromOut,
+                        &mut self.holes,
+                        *start,
+                        self.out_ptr,
+                        *size,
+                        |_| Ok(()),
+                    )
+                    .await?;
+                    self.out_ptr += *size as u64;
+                    self.layout.feed_leaf(&first, *size as u64);
+                }
+                Some(Node::Routed(routed)) => match &**routed {
+                    RoutedNode::Links {
+                        unixfs_type,
+                        links,
+                        block,
+                        data,
+                    } => {
+                        options.check_node_type(&first, *unixfs_type, true)?;
+                        check_cycle(&self.layout, &first)?;
+                        let data = &block[data.clone()];
+                        write_maybe_sparse(&mut self.out, data, &mut self.holes).await?;
+                        self.out_ptr += data.len() as u64;
+                        self.layout
+                            .feed_links(&first, data.len() as u64, links.clone());
+                    }
+                    _ => return Err(routed.error(&first)),
+                },
+                None => break,
+            }
+        }
+
+        Ok(self.layout.is_complete())
+    }
+
+    async fn finish(&mut self, blocks_read: usize) -> Result<ReadSummary, ReadSingleFileError> {
+        // Holes and copies must leave `out` exactly at the end of the file
+        let position = self.out.stream_position().await?;
+        if position != self.out_ptr {
+            return Err(ReadSingleFileError::InternalError(format!(
+                "file length {} does not match output position {}",
+                self.out_ptr, position
+            )));
+        }
+        self.out.flush().await?;
+        Ok(ReadSummary {
+            root_cid: Some(self.root_cid),
+            bytes_written: self.out_ptr,
+            sparse_bytes: self.holes.bytes,
+            holes_count: self.holes.count,
+            blocks_read,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,148 @@
+use futures::{AsyncRead, AsyncSeek, AsyncWrite, AsyncWriteExt};
+use rs_car::Cid;
+use std::ops::Range;
+
+use crate::cid_version::same_block;
+
+use super::{
+    car_index::read_block_at,
+    file_layout,
+    util::{decode_block, leaf_data},
+    CarIndex, LeafExtent, ReadSingleFileError, DEFAULT_MAX_BLOCK_SIZE,
+};
+
+/// Map of the byte ranges of a file to the blocks holding them, to serve random reads of the file,
+/// e.g. HTTP Range requests, without walking its DAG again for each read. Built once from the
+/// extents of [`file_layout`], the blocks of a range are then read from a seekable CAR with a
+/// [`CarIndex`].
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::{CarIndex, FileOffsetIndex};
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let offsets = FileOffsetIndex::build(&mut input, None).await?;
+///   let index = CarIndex::build(&mut input).await?;
+///
+///   let mut out = Cursor::new(Vec::new());
+///   offsets.read_range(&mut input, &index, 2..6, &mut out).await?;
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FileOffsetIndex {
+    /// Extents in file order, each starting where the previous one ends
+    extents: Vec<LeafExtent>,
+}
+
+/// Part of a block within a range of the file, see [`FileOffsetIndex::query`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeafRange {
+    /// The leaf, or a links node for the data inline before its children
+    pub cid: Cid,
+    /// Offset of the part within the file
+    pub file_offset: u64,
+    /// Offset of the part within the data of the block
+    pub leaf_offset: u64,
+    pub len: u64,
+}
+
+impl FileOffsetIndex {
+    /// Index the file under `root_cid` of the CAR stream `car_input`, see [`file_layout`]
+    pub async fn build<R: AsyncRead + Send + Unpin>(
+        car_input: &mut R,
+        root_cid: Option<&Cid>,
+    ) -> Result<Self, ReadSingleFileError> {
+        Ok(Self::from_extents(file_layout(car_input, root_cid).await?))
+    }
+
+    /// Index of `extents` as returned by [`file_layout`], in file order
+    pub fn from_extents(extents: Vec<LeafExtent>) -> Self {
+        Self { extents }
+    }
+
+    pub fn extents(&self) -> &[LeafExtent] {
+        &self.extents
+    }
+
+    /// Length of the file
+    pub fn len(&self) -> u64 {
+        self.extents
+            .last()
+            .map_or(0, |extent| extent.offset + extent.len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Parts of the blocks holding `range` of the file in file order. The range is clamped to the
+    /// length of the file
+    pub fn query(&self, range: Range<u64>) -> Vec<LeafRange> {
+        let end = range.end.min(self.len());
+        let first = self
+            .extents
+            .partition_point(|extent| extent.offset + extent.len <= range.start);
+        self.extents[first..]
+            .iter()
+            .take_while(|extent| extent.offset < end)
+            .filter(|extent| extent.len > 0)
+            .map(|extent| {
+                let start = range.start.max(extent.offset);
+                LeafRange {
+                    cid: extent.cid,
+                    file_offset: start,
+                    leaf_offset: start - extent.offset,
+                    len: end.min(extent.offset + extent.len) - start,
+                }
+            })
+            .collect()
+    }
+
+    /// Write `range` of the file to `out`, reading only its blocks from the seekable CAR
+    /// `car_input` at their offsets in `index`. Blocks are validated against their CID, and fail
+    /// with [`BlockTooLarge`](ReadSingleFileError::BlockTooLarge) past
+    /// [`DEFAULT_MAX_BLOCK_SIZE`]. Returns the count of bytes written, less than the range if it
+    /// ends past the file
+    pub async fn read_range<R: AsyncRead + AsyncSeek + Unpin, W: AsyncWrite + Unpin>(
+        &self,
+        car_input: &mut R,
+        index: &CarIndex,
+        range: Range<u64>,
+        out: &mut W,
+    ) -> Result<u64, ReadSingleFileError> {
+        let mut bytes_written = 0;
+        for part in self.query(range) {
+            let offset = index
+                .get(&part.cid)
+                .ok_or(ReadSingleFileError::MissingNode(part.cid))?;
+            let (block_cid, block) =
+                read_block_at(car_input, offset, DEFAULT_MAX_BLOCK_SIZE).await?;
+            if !same_block(&block_cid, &part.cid) {
+                return Err(ReadSingleFileError::InvalidCarIndex(format!(
+                    "index offset {} of {} points to block {}",
+                    offset, part.cid, block_cid
+                )));
+            }
+            let (inner, _) = decode_block(&part.cid, &block, true)?;
+            let data = leaf_data(&block, inner.data.Data, inner.data.filesize)?;
+            let start = part.leaf_offset as usize;
+            let data = data.get(start..start + part.len as usize).ok_or_else(|| {
+                ReadSingleFileError::InvalidUnixFs(format!(
+                    "block {} of {} bytes indexed with {} bytes at {}",
+                    part.cid,
+                    data.len(),
+                    part.len,
+                    part.leaf_offset
+                ))
+            })?;
+            out.write_all(data).await?;
+            bytes_written += part.len;
+        }
+        Ok(bytes_written)
+    }
+}
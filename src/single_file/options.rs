@@ -0,0 +1,638 @@
+use futures::{io::BufReader, AsyncRead, AsyncSeek, AsyncWrite};
+use quick_protobuf::{BytesReader, MessageRead};
+use rs_car::Cid;
+use std::num::NonZeroUsize;
+
+use crate::{
+    adder::ChunkerProfile,
+    decompress::Compression,
+    pb::{unixfs::Metadata, FlatUnixFs, UnixFs, UnixFsType},
+};
+
+use super::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, util::is_file_root,
+    validate_pool::ValidationPool, BlockErrorHook, ErrorAction, Finalize, MapLeafHook, OutputHash,
+    Progress, ProgressHook, RateLimit, ReadSingleFileError, ReadSummary, ResolveMissing,
+    SharedBlockStore, SkipFill, TailOptions, ThroughputHook,
+};
+
+/// Options for the single file readers. `Default` is the unrestricted behavior, apart from
+/// `require_file_root`.
+///
+/// ```
+/// use rs_car_ipfs::single_file::ReadSingleFileOptions;
+///
+/// let options = ReadSingleFileOptions {
+///     max_blocks: Some(10_000),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReadSingleFileOptions {
+    /// Max total length of data nodes buffered in memory. Only used by
+    /// [`read_single_file_buffer`](super::read_single_file_buffer),
+    /// [`CarFileReader`](super::CarFileReader) and
+    /// [`visit_file_leaves`](super::visit_file_leaves)
+    pub max_buffer: Option<usize>,
+    /// Max total bytes written to `out`. A root declaring a larger `filesize` fails with
+    /// [`DeclaredSizeExceedsLimit`](ReadSingleFileError::DeclaredSizeExceedsLimit) as soon as it
+    /// is read, before writing anything, otherwise the read fails with
+    /// [`WriteLimitExceeded`](ReadSingleFileError::WriteLimitExceeded) once the limit is crossed.
+    /// Used by [`read_single_file_seek`](super::read_single_file_seek),
+    /// [`read_single_file_buffer`](super::read_single_file_buffer) and
+    /// [`CarFileReader`](super::CarFileReader)
+    pub write_limit: Option<usize>,
+    /// Max count of blocks read from the CAR stream, including blocks not part of the file
+    pub max_blocks: Option<usize>,
+    /// Accept nodes of any UnixFS type within the file DAG. By default leaves must be of type
+    /// `File` or `Raw` and nodes with links of type `File`, otherwise the read fails with
+    /// [`UnexpectedNodeType`](ReadSingleFileError::UnexpectedNodeType)
+    pub lenient_node_types: bool,
+    /// Re-chunk the written output with this profile and check it reproduces the root CID,
+    /// failing with [`NotCanonical`](ReadSingleFileError::NotCanonical) otherwise. See
+    /// [`verify_reconstructs_root`](crate::adder::verify_reconstructs_root)
+    pub verify_canonical: Option<ChunkerProfile>,
+    /// Called on blocks that fail hash validation or can not be decoded as UnixFS, to skip them
+    /// instead of failing the read. Skipped regions of the file are written as set by
+    /// `skip_fill`, which requires the parent node to declare `blocksizes`. By default the read
+    /// fails on the first block error. Not used by
+    /// [`read_single_file_indexed`](super::read_single_file_indexed) with an index
+    pub on_block_error: Option<BlockErrorHook>,
+    pub skip_fill: SkipFill,
+    /// Called before each block is read from `car_input` and once the file is written, with the
+    /// bytes written so far and the size declared by the root as soon as it is known, e.g. to
+    /// render a progress bar. [`read_single_file_buffer`](super::read_single_file_buffer) writes
+    /// the file once the whole DAG is read, it is also called after each write. Only used by
+    /// [`read_single_file_seek`](super::read_single_file_seek) and
+    /// [`read_single_file_buffer`](super::read_single_file_buffer)
+    pub on_progress: Option<ProgressHook>,
+    /// Called with the data of each leaf, and the inline data of nodes with links, in file order,
+    /// to write the returned data instead, e.g. to decrypt the file. Lengths, `write_limit`,
+    /// `expected_size` and the summary are those of the returned data, while `verify_canonical`
+    /// re-chunks the data of the DAG. An error fails the read with
+    /// [`MapLeaf`](ReadSingleFileError::MapLeaf). Only used by
+    /// [`read_single_file_buffer`](super::read_single_file_buffer), which knows the whole DAG
+    /// before writing, other readers fail with
+    /// [`MapLeafUnsupported`](ReadSingleFileError::MapLeafUnsupported) instead of writing the
+    /// untransformed data
+    pub map_leaf: Option<MapLeafHook>,
+    /// Map every leaf and check the whole output against `write_limit`, `expected_size` and
+    /// `verify_canonical` before writing its first byte, so that `out` is left untouched by any
+    /// error other than an [`IoError`](ReadSingleFileError::IoError) of `out` itself, e.g. for an
+    /// append-only sink. Data returned by `map_leaf` is held in memory until written. Only used
+    /// by [`read_single_file_buffer`](super::read_single_file_buffer)
+    pub validate_before_write: bool,
+    /// Flush `out` after at least this many bytes were written since the last flush
+    pub flush_every: Option<usize>,
+    /// What is done to `out` before returning Ok, flushing it by default
+    pub finalize: Finalize,
+    /// Write the file starting at this position of `out`, leaving bytes before it untouched. Runs
+    /// of zeros are written as holes, so the range must read as zeros or be past the end of `out`.
+    /// Only used by [`read_single_file_seek`](super::read_single_file_seek)
+    pub base_offset: u64,
+    /// Experimental: write a leaf arriving before the nodes preceding it in the file at its offset,
+    /// computed from the `blocksizes` declared by their parents, instead of failing with
+    /// [`DataNodesNotSorted`](ReadSingleFileError::DataNodesNotSorted) or discarding it if its
+    /// parent is not expanded yet. Nearly sorted CARs, e.g. exported level by level, are then
+    /// read without buffering the leaves. The read still fails with `DataNodesNotSorted` if the
+    /// size of a preceding node is unknown, and with
+    /// [`InvalidUnixFs`](ReadSingleFileError::InvalidUnixFs) if the leaf turns out not to be at
+    /// that offset. Only used by [`read_single_file_seek`](super::read_single_file_seek)
+    pub write_ahead: bool,
+    /// Max total length of the leaves arriving before the leaves preceding them in the file that
+    /// are held in memory until their turn, e.g. for CARs exported level by level. `None` is
+    /// [`DEFAULT_REORDER_WINDOW`], a leaf needing more fails the read with
+    /// [`ReorderWindowExceeded`](ReadSingleFileError::ReorderWindowExceeded), and `Some(0)` fails
+    /// on the first such leaf with [`DataNodesNotSorted`](ReadSingleFileError::DataNodesNotSorted).
+    /// Leaves arriving before their parent are still discarded, and `write_ahead` writes leaves
+    /// with a known offset instead of holding them. Only used by
+    /// [`read_single_file_seek`](super::read_single_file_seek)
+    pub reorder_window: Option<usize>,
+    /// Max length of a block read from the CAR, past which the read fails with
+    /// [`BlockTooLarge`](ReadSingleFileError::BlockTooLarge), since IPFS implementations do not
+    /// exchange blocks larger than 2 MiB. Checked against the length declared by the block
+    /// section, before the block is allocated. `None` is [`DEFAULT_MAX_BLOCK_SIZE`],
+    /// `Some(usize::MAX)` accepts blocks of any length. Used by the single file readers and
+    /// [`load_dag`](super::load_dag)
+    pub max_block_size: Option<usize>,
+    /// Capacity of the buffer `car_input` is read through, [`DEFAULT_READ_BUFFER_CAPACITY`] if
+    /// None. Larger buffers make fewer reads of `car_input`, which helps on fast disks, at the
+    /// cost of memory and of reading `car_input` ahead of the blocks decoded. `Some(0)` reads
+    /// `car_input` directly. Used by the readers streaming `car_input`: the seek and buffer readers
+    /// and [`CarFileReader`](super::CarFileReader)
+    pub read_buffer_capacity: Option<usize>,
+    /// Once the root node is read, extend `out` to `base_offset` plus the declared `filesize` of
+    /// the root, and fail with [`FileSizeMismatch`](ReadSingleFileError::FileSizeMismatch) if a
+    /// different length is written. `out` is never shortened. Only used by
+    /// [`read_single_file_seek`](super::read_single_file_seek), see
+    /// [`read_single_file_seek_file`](super::read_single_file_seek_file)
+    pub preallocate: bool,
+    /// Truncate `out` to `base_offset` before writing the file, so an output reused from a longer
+    /// file keeps none of its previous contents, neither past the end of the file nor in the
+    /// holes left over runs of zeros. Only used by
+    /// [`read_single_file_seek_file`](super::read_single_file_seek_file), which sets the length
+    /// of `out` with [`SetLen`](super::SetLen)
+    pub truncate: bool,
+    /// On any error, truncate `out` back to `base_offset` before returning it, so a failed read
+    /// leaves no partial file behind. The original error is returned even if truncating fails.
+    /// Only used by [`read_single_file_seek_file`](super::read_single_file_seek_file)
+    pub cleanup_on_error: bool,
+    /// Total length of the file expected by the caller, e.g. from a manifest. As soon as the root
+    /// node is read, the read fails with
+    /// [`ExpectedSizeMismatch`](ReadSingleFileError::ExpectedSizeMismatch) if it declares a
+    /// different `filesize`, or is a raw block of a different length. Once the whole file is
+    /// written, it fails with [`SizeMismatch`](ReadSingleFileError::SizeMismatch) if a different
+    /// length was written, whatever the size declared by the DAG
+    pub expected_size: Option<u64>,
+    /// Fail with [`RootCidIsNotFile`](ReadSingleFileError::RootCidIsNotFile) if the root is a
+    /// UnixFS `Metadata` node. By default, a `Metadata` root with a single link, as wrapped by
+    /// older tools, is read as the file it links to and its MIME type is returned in
+    /// [`ReadSummary::mime_type`](super::ReadSummary::mime_type). `Metadata` nodes are never
+    /// accepted elsewhere in the file DAG
+    pub reject_metadata_root: bool,
+    /// Read a root that is a UnixFS `Directory` or `HAMTShard` with a single entry as the file of
+    /// that entry, e.g. a gateway response to `/ipfs/<dir>/file.txt?format=car` rooted at the
+    /// directory. The name of the entry is returned in
+    /// [`ReadSummary::descended_entry`](super::ReadSummary::descended_entry). A directory root
+    /// with several entries, or a shard linking a nested shard, still fails with
+    /// [`RootCidIsNotFile`](ReadSingleFileError::RootCidIsNotFile), and an entry that is not a
+    /// file with [`UnexpectedNodeType`](ReadSingleFileError::UnexpectedNodeType)
+    pub auto_descend_single_entry: bool,
+    /// Fail with [`RootCidIsNotFile`](ReadSingleFileError::RootCidIsNotFile) for a root that is
+    /// not a `File` or `Raw` node, or a raw block, the default. If false the root is read whatever
+    /// its UnixFS type, as a `File` node: its data and the content of its links are the file, and
+    /// the nodes under it are checked as usual. A `Metadata` root still fails with
+    /// `reject_metadata_root`. Only used by
+    /// [`read_single_file_seek`](super::read_single_file_seek),
+    /// [`read_single_file_buffer`](super::read_single_file_buffer),
+    /// [`read_single_file_indexed`](super::read_single_file_indexed) and
+    /// [`CarFileReader`](super::CarFileReader)
+    pub require_file_root: bool,
+    /// Sniff the content type of the file from its first bytes while it is written, returned in
+    /// [`ReadSummary::content_type`](super::ReadSummary::content_type)
+    #[cfg(feature = "sniff")]
+    pub sniff_content_type: bool,
+    /// Wait for more data when `car_input` reaches EOF before the whole file DAG is read, to
+    /// extract a CAR while it is still being appended to. By default a read of 0 bytes from
+    /// `car_input` is the end of the CAR, and the read fails with
+    /// [`PendingLinksAtEOF`](ReadSingleFileError::PendingLinksAtEOF) if links are pending. Only
+    /// used by [`read_single_file_seek`](super::read_single_file_seek) and
+    /// [`CarFileReader`](super::CarFileReader), see [`TailOptions`]
+    pub tail: Option<TailOptions>,
+    /// Accept the segments after the first whatever the roots of their header, e.g. for the CARs
+    /// of a DAG split by deal or retrieval tools, each with its own roots. By default their roots
+    /// must include the root of the file, or the read fails with
+    /// [`SegmentRootMismatch`](ReadSingleFileError::SegmentRootMismatch). Only used by
+    /// [`read_single_file_multi`](super::read_single_file_multi)
+    pub ignore_segment_roots: bool,
+    /// Blocks of the file DAG found in this store are not required in the CAR, and blocks read
+    /// from the CAR are put into it, e.g. to extract incremental snapshots sharing most of their
+    /// blocks. The next node of the file is looked up before reading each block from the CAR, so
+    /// the store is preferred. Blocks read from the store are not counted in
+    /// [`ReadSummary::blocks_read`](super::ReadSummary::blocks_read). Only used by
+    /// [`read_single_file_seek`](super::read_single_file_seek) and
+    /// [`read_single_file_buffer`](super::read_single_file_buffer)
+    pub block_store: Option<SharedBlockStore>,
+    /// Fetch the blocks of the file still pending at the end of the CAR from elsewhere instead of
+    /// failing with [`PendingLinksAtEOF`](ReadSingleFileError::PendingLinksAtEOF) right away.
+    /// Resolved blocks are not counted in
+    /// [`ReadSummary::blocks_read`](super::ReadSummary::blocks_read), but are held to
+    /// `max_blocks` and `max_block_size` like the blocks of the CAR. Only used by
+    /// [`read_single_file_seek`](super::read_single_file_seek), see [`ResolveMissing`]
+    pub resolve_missing: Option<ResolveMissing>,
+    /// Max rate of the bytes written to `out`, sleeping between blocks as needed, see
+    /// [`RateLimit`]. Holes of runs of zeros are not counted. Only used by
+    /// [`read_single_file_seek`](super::read_single_file_seek) and
+    /// [`read_single_file_buffer`](super::read_single_file_buffer)
+    pub rate_limit: Option<RateLimit>,
+    /// Called with the throughput of the writes to `out` at most every 100 ms while writing, and
+    /// once the file is written. Used by the same readers as `rate_limit`
+    pub on_throughput: Option<ThroughputHook>,
+    /// Hash the blocks of the CAR on this many worker threads while the next blocks are decoded,
+    /// instead of hashing each block before decoding the next one, e.g. for large CARs on
+    /// multicore machines. Up to twice as many blocks are read ahead of the block being written,
+    /// as long as `car_input` has them ready, and blocks are still written in the order of the
+    /// CAR once validated. Not used with `on_block_error`, which validates each block inline to
+    /// skip it. Only used by [`read_single_file_seek`](super::read_single_file_seek),
+    /// [`read_single_file_buffer`](super::read_single_file_buffer) and
+    /// [`read_single_file_from_blocks`](super::read_single_file_from_blocks)
+    pub validation_concurrency: Option<NonZeroUsize>,
+    /// Root of the file when no root CID is passed to the reader. Only used by
+    /// [`read_single_file_buffer`](super::read_single_file_buffer) and
+    /// [`read_single_file_indexed`](super::read_single_file_indexed), the other readers need the
+    /// root before its children and always read the single root of the CAR header
+    pub root_selector: RootSelector,
+    /// Check the `Tsize` of each link of the file DAG against the blocks under it, once the whole
+    /// DAG is read and before writing: it must be at least the bytes of file data under the link,
+    /// and at most the bytes of its blocks, each counted once per link to it, or the read fails
+    /// with [`TsizeMismatch`](ReadSingleFileError::TsizeMismatch). Links without a `Tsize`, or
+    /// with blocks skipped under them, are not checked. Only used by
+    /// [`read_single_file_buffer`](super::read_single_file_buffer)
+    pub check_link_tsize: bool,
+    /// Compress the file with an encoder on the way to `out`, with the `gzip` or `zstd` feature,
+    /// e.g. to store it compressed once its blocks are validated. Lengths, `write_limit`,
+    /// `expected_size` and the summary are those of the file before compression, and `out` is
+    /// closed once the compressed stream ends. A format whose feature is not enabled fails with
+    /// [`CompressedOutputUnsupported`](ReadSingleFileError::CompressedOutputUnsupported). Only
+    /// used by [`read_single_file_buffer`](super::read_single_file_buffer) and
+    /// [`read_single_file_from_blocks`](super::read_single_file_from_blocks), which write the
+    /// file in order: the seek and indexed readers and [`CarFileReader`](super::CarFileReader)
+    /// fail with `CompressedOutputUnsupported`, seeks would break the compressed stream
+    pub compress_output: Compression,
+    /// Hash the file with this function as it is written, returning the digest in
+    /// [`ReadSummary::output_digest`](super::ReadSummary::output_digest) without reading `out`
+    /// again. The digest is of the bytes written, as returned by `map_leaf`, before
+    /// `compress_output`, and with zeros for skipped blocks. Only used by
+    /// [`read_single_file_buffer`](super::read_single_file_buffer), which writes every byte in
+    /// file order: the seek reader replays and writes ahead out of order
+    pub hash_output: Option<OutputHash>,
+}
+
+/// How the root of the file is found when no root CID is passed to the reader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RootSelector {
+    /// The single root of the CAR header
+    #[default]
+    Header,
+    /// The CID of the last block of the CAR whatever the roots of the header, for streaming CARs
+    /// written children first without declaring their root. The buffer reader keeps every block
+    /// of the CAR until its end, up to `max_buffer`, since the file DAG is only known once the root
+    /// is read. Fails with [`NoRootsInHeader`](ReadSingleFileError::NoRootsInHeader) for a CAR
+    /// without blocks
+    LastBlock,
+}
+
+/// Default of [`ReadSingleFileOptions::reorder_window`], 8 MiB
+pub const DEFAULT_REORDER_WINDOW: usize = 8 * 1024 * 1024;
+
+/// Default of [`ReadSingleFileOptions::max_block_size`], the 2 MiB block limit of Bitswap with
+/// 1 KiB of margin for the protobuf framing of a 2 MiB chunk
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 2 * 1024 * 1024 + 1024;
+
+/// Default of [`ReadSingleFileOptions::read_buffer_capacity`]
+pub const DEFAULT_READ_BUFFER_CAPACITY: usize = 64 * 1024;
+
+impl Default for ReadSingleFileOptions {
+    fn default() -> Self {
+        Self {
+            max_buffer: Default::default(),
+            write_limit: Default::default(),
+            max_blocks: Default::default(),
+            lenient_node_types: Default::default(),
+            verify_canonical: Default::default(),
+            on_block_error: Default::default(),
+            skip_fill: Default::default(),
+            on_progress: Default::default(),
+            map_leaf: Default::default(),
+            validate_before_write: Default::default(),
+            flush_every: Default::default(),
+            finalize: Default::default(),
+            base_offset: Default::default(),
+            write_ahead: Default::default(),
+            reorder_window: Default::default(),
+            max_block_size: Default::default(),
+            read_buffer_capacity: Default::default(),
+            preallocate: Default::default(),
+            truncate: Default::default(),
+            cleanup_on_error: Default::default(),
+            expected_size: Default::default(),
+            reject_metadata_root: Default::default(),
+            auto_descend_single_entry: Default::default(),
+            require_file_root: true,
+            #[cfg(feature = "sniff")]
+            sniff_content_type: Default::default(),
+            tail: Default::default(),
+            ignore_segment_roots: Default::default(),
+            block_store: Default::default(),
+            resolve_missing: Default::default(),
+            rate_limit: Default::default(),
+            on_throughput: Default::default(),
+            validation_concurrency: Default::default(),
+            root_selector: Default::default(),
+            check_link_tsize: Default::default(),
+            compress_output: Default::default(),
+            hash_output: Default::default(),
+        }
+    }
+}
+
+impl ReadSingleFileOptions {
+    /// Read the file of `car_input` to `out` with these options and
+    /// [`read_single_file_seek_with_options`]. The
+    /// recommended reader for outputs that can seek and be read back, such as files: it keeps only
+    /// the links of the DAG in memory. Fails before reading `car_input` with the error of
+    /// [`check_seek`](Self::check_seek), e.g. to fall back to [`read_buffered`](Self::read_buffered)
+    ///
+    /// ```
+    /// use rs_car_ipfs::single_file::ReadSingleFileOptions;
+    /// use futures::io::Cursor;
+    ///
+    /// #[async_std::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let options = ReadSingleFileOptions {
+    ///       max_blocks: Some(10_000),
+    ///       ..Default::default()
+    ///   };
+    ///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+    ///   let mut out = Cursor::new(Vec::new());
+    ///
+    ///   options.read_seek(&mut input, &mut out, None).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn read_seek<
+        R: AsyncRead + Send + Unpin,
+        W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+    >(
+        &self,
+        car_input: &mut R,
+        out: &mut W,
+        root_cid: Option<&Cid>,
+    ) -> Result<ReadSummary, ReadSingleFileError> {
+        read_single_file_seek_with_options(car_input, out, root_cid, self).await
+    }
+
+    /// Read the file of `car_input` to `out` with these options and
+    /// [`read_single_file_buffer_with_options`], for
+    /// write-only outputs such as sockets or pipes. Keeps the data of the file in memory until it
+    /// can be written in order, up to `max_buffer`
+    pub async fn read_buffered<R: AsyncRead + Send + Unpin, W: AsyncWrite + Unpin>(
+        &self,
+        car_input: &mut R,
+        out: &mut W,
+        root_cid: Option<&Cid>,
+    ) -> Result<ReadSummary, ReadSingleFileError> {
+        read_single_file_buffer_with_options(car_input, out, root_cid, self).await
+    }
+
+    /// Check that the seek and indexed readers support these options, the check they make
+    /// before reading anything: `map_leaf` fails with
+    /// [`MapLeafUnsupported`](ReadSingleFileError::MapLeafUnsupported) and `compress_output` with
+    /// [`CompressedOutputUnsupported`](ReadSingleFileError::CompressedOutputUnsupported). The
+    /// buffer reader supports all the options
+    pub fn check_seek(&self) -> Result<(), ReadSingleFileError> {
+        self.reject_map_leaf()?;
+        self.reject_compress_output()
+    }
+
+    pub(crate) fn reorder_window_limit(&self) -> usize {
+        self.reorder_window.unwrap_or(DEFAULT_REORDER_WINDOW)
+    }
+
+    /// Workers hashing the blocks of a read with `validation_concurrency`, the blocks are then
+    /// not validated by `CarReader`
+    pub(crate) fn validation_pool(&self) -> Result<Option<ValidationPool>, ReadSingleFileError> {
+        match self.validation_concurrency {
+            Some(threads) if self.on_block_error.is_none() => {
+                Ok(Some(ValidationPool::new(threads)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Check the UnixFS type of a node that is part of the file DAG
+    pub(crate) fn check_node_type(
+        &self,
+        cid: &Cid,
+        found: UnixFsType,
+        has_links: bool,
+    ) -> Result<(), ReadSingleFileError> {
+        let expected = match found {
+            UnixFsType::File => true,
+            UnixFsType::Raw => !has_links,
+            // Its data is not file content, even with `lenient_node_types`
+            UnixFsType::Metadata => {
+                return Err(ReadSingleFileError::UnexpectedNodeType { cid: *cid, found })
+            }
+            _ => false,
+        };
+        if expected || self.lenient_node_types {
+            Ok(())
+        } else {
+            Err(ReadSingleFileError::UnexpectedNodeType { cid: *cid, found })
+        }
+    }
+
+    /// Turn a `Metadata` root node into a `File` node without data linking to the wrapped file,
+    /// unless `reject_metadata_root`. Returns the MIME type of the metadata.
+    pub(crate) fn unwrap_metadata_root(
+        &self,
+        inner: &mut FlatUnixFs<'_>,
+        links: &[Cid],
+    ) -> Result<Option<String>, ReadSingleFileError> {
+        if inner.data.Type != UnixFsType::Metadata || self.reject_metadata_root {
+            return Ok(None);
+        }
+        if links.len() != 1 {
+            return Err(ReadSingleFileError::InvalidUnixFs(format!(
+                "Metadata root with {} links",
+                links.len()
+            )));
+        }
+
+        let data = inner.data.Data.take().unwrap_or_default();
+        let metadata = Metadata::from_reader(&mut BytesReader::from_bytes(&data), &data)
+            .map_err(|err| ReadSingleFileError::InvalidUnixFs(err.to_string()))?;
+        inner.data = UnixFs {
+            Type: UnixFsType::File,
+            ..Default::default()
+        };
+        Ok(metadata.MimeType.map(|mime_type| mime_type.into_owned()))
+    }
+
+    /// Turn a directory root node with a single entry into a `File` node without data linking to
+    /// the entry, if `auto_descend_single_entry`. Returns the name of the entry.
+    pub(crate) fn descend_single_entry_root(
+        &self,
+        inner: &mut FlatUnixFs<'_>,
+    ) -> Result<Option<String>, ReadSingleFileError> {
+        let unixfs_type = inner.data.Type;
+        if !self.auto_descend_single_entry
+            || !matches!(unixfs_type, UnixFsType::Directory | UnixFsType::HAMTShard)
+        {
+            return Ok(None);
+        }
+        let [link] = &inner.links[..] else {
+            return Err(ReadSingleFileError::RootCidIsNotFile);
+        };
+
+        let mut name = link.Name.as_deref().unwrap_or_default();
+        if unixfs_type == UnixFsType::HAMTShard {
+            // Links of a shard are named with the hex prefix of the hash of the entry name, a link
+            // named only with the prefix is a nested shard
+            let fanout = inner.data.fanout.unwrap_or(256);
+            let prefix_len = format!("{:x}", fanout.saturating_sub(1)).len();
+            name = match name.get(prefix_len..) {
+                Some(entry) if !entry.is_empty() => entry,
+                _ => return Err(ReadSingleFileError::RootCidIsNotFile),
+            };
+        }
+        let name = name.to_string();
+
+        inner.data = UnixFs {
+            Type: UnixFsType::File,
+            ..Default::default()
+        };
+        Ok(Some(name))
+    }
+
+    /// Check that the root node `cid` is a file, or turn it into a `File` node unless
+    /// `require_file_root`
+    pub(crate) fn check_file_root(
+        &self,
+        cid: &Cid,
+        inner: &mut FlatUnixFs<'_>,
+    ) -> Result<(), ReadSingleFileError> {
+        if is_file_root(cid, inner.data.Type) {
+            return Ok(());
+        }
+        if self.require_file_root
+            || (inner.data.Type == UnixFsType::Metadata && self.reject_metadata_root)
+        {
+            return Err(ReadSingleFileError::RootCidIsNotFile);
+        }
+        inner.data.Type = UnixFsType::File;
+        Ok(())
+    }
+
+    /// Returns Ok if `err` on block `cid` must be skipped, else `err`
+    pub(crate) fn handle_block_error(
+        &self,
+        cid: &Cid,
+        err: ReadSingleFileError,
+    ) -> Result<(), ReadSingleFileError> {
+        match &self.on_block_error {
+            Some(hook) if hook.call(cid, &err) == ErrorAction::Skip => Ok(()),
+            _ => Err(err),
+        }
+    }
+
+    /// Fail the readers that do not support `compress_output`
+    pub(crate) fn reject_compress_output(&self) -> Result<(), ReadSingleFileError> {
+        match self.compress_output {
+            Compression::None => Ok(()),
+            _ => Err(ReadSingleFileError::CompressedOutputUnsupported),
+        }
+    }
+
+    /// Fail the readers that do not support `map_leaf`
+    pub(crate) fn reject_map_leaf(&self) -> Result<(), ReadSingleFileError> {
+        match self.map_leaf {
+            Some(_) => Err(ReadSingleFileError::MapLeafUnsupported),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn report_progress(&self, progress: Progress) {
+        if let Some(hook) = &self.on_progress {
+            hook.call(&progress);
+        }
+    }
+
+    /// Check the size declared by the root node against `expected_size`, before writing it
+    pub(crate) fn check_declared_size(
+        &self,
+        declared: Option<u64>,
+    ) -> Result<(), ReadSingleFileError> {
+        match (self.expected_size, declared) {
+            (Some(expected), Some(declared)) if declared != expected => {
+                Err(ReadSingleFileError::ExpectedSizeMismatch { expected, declared })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check the size declared by the root node against `write_limit`, before writing it
+    pub(crate) fn check_declared_limit(
+        &self,
+        declared: Option<u64>,
+    ) -> Result<(), ReadSingleFileError> {
+        match (self.write_limit, declared) {
+            (Some(limit), Some(declared)) if declared > limit as u64 => {
+                log_event!(
+                    debug,
+                    "declared size of {} bytes exceeds the write limit of {} bytes",
+                    declared,
+                    limit
+                );
+                Err(ReadSingleFileError::DeclaredSizeExceedsLimit { declared, limit })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that writing `len` more bytes of the node `cid` after the `written` bytes of the
+    /// file stays within `write_limit`, before writing them. The only check of the limit, for all
+    /// the writes of the readers
+    pub(crate) fn check_write_limit(
+        &self,
+        written: u64,
+        len: u64,
+        cid: &Cid,
+    ) -> Result<(), ReadSingleFileError> {
+        let limit = self.write_limit.unwrap_or(usize::MAX);
+        let attempted = written.saturating_add(len);
+        if attempted > limit as u64 {
+            log_event!(
+                debug,
+                "write of {} bytes of {} exceeds the write limit of {} bytes",
+                attempted,
+                cid,
+                limit
+            );
+            return Err(ReadSingleFileError::WriteLimitExceeded {
+                limit,
+                attempted: attempted.try_into().unwrap_or(usize::MAX),
+                cid: Some(*cid),
+            });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_expected_size(&self, actual: u64) -> Result<(), ReadSingleFileError> {
+        match self.expected_size {
+            Some(expected) if actual != expected => {
+                Err(ReadSingleFileError::SizeMismatch { expected, actual })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// [`max_block_size`](Self::max_block_size) or its default
+    pub(crate) fn block_size_limit(&self) -> usize {
+        self.max_block_size.unwrap_or(DEFAULT_MAX_BLOCK_SIZE)
+    }
+
+    pub(crate) fn check_block_size(
+        &self,
+        cid: &Cid,
+        size: usize,
+    ) -> Result<(), ReadSingleFileError> {
+        let limit = self.block_size_limit();
+        if size > limit {
+            return Err(ReadSingleFileError::BlockTooLarge {
+                cid: *cid,
+                size,
+                limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// `car_input` read through a buffer of [`read_buffer_capacity`](Self::read_buffer_capacity)
+    pub(crate) fn buffer_input<R: AsyncRead>(&self, car_input: R) -> BufReader<R> {
+        let capacity = self
+            .read_buffer_capacity
+            .unwrap_or(DEFAULT_READ_BUFFER_CAPACITY);
+        BufReader::with_capacity(capacity, car_input)
+    }
+
+    pub(crate) fn check_blocks_read(&self, read: usize) -> Result<(), ReadSingleFileError> {
+        match self.max_blocks {
+            Some(limit) if read > limit => {
+                Err(ReadSingleFileError::MaxBlocksExceeded { limit, read })
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use futures::{
+    future::BoxFuture, io::Cursor, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite,
+    AsyncWriteExt,
+};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::decompress::Compression;
+
+use super::ReadSingleFileError;
+
+/// Outputs that can set their logical length up front, e.g. with `File::set_len`. Used by
+/// [`read_single_file_seek_file`](super::read_single_file_seek_file) to preallocate the file from
+/// the declared size of the root node.
+///
+/// Implement it on a wrapper to use other file types, e.g. `tokio::fs::File` with a compat layer.
+pub trait SetLen {
+    /// Set the length of the output to `len` bytes, extending it with zeros or truncating it
+    fn set_len(&mut self, len: u64) -> BoxFuture<'_, io::Result<()>>;
+}
+
+#[cfg(feature = "async-std")]
+impl SetLen for async_std::fs::File {
+    fn set_len(&mut self, len: u64) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async_std::fs::File::set_len(self, len))
+    }
+}
+
+impl SetLen for Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> BoxFuture<'_, io::Result<()>> {
+        self.get_mut().resize(len as usize, 0);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Outputs that can sync their contents to durable storage, e.g. with `File::sync_all`. Used by
+/// [`read_single_file_seek_file`](super::read_single_file_seek_file) for
+/// [`Finalize::FlushAndSync`].
+pub trait SyncAll {
+    fn sync_all(&mut self) -> BoxFuture<'_, io::Result<()>>;
+}
+
+#[cfg(feature = "async-std")]
+impl SyncAll for async_std::fs::File {
+    fn sync_all(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async_std::fs::File::sync_all(self))
+    }
+}
+
+impl SyncAll for Cursor<Vec<u8>> {
+    fn sync_all(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// What is done to `out` once the whole file is written, before returning Ok. Levels are ordered,
+/// a level includes the previous ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Finalize {
+    /// Return as soon as the last write completes
+    None,
+    /// Flush `out`
+    #[default]
+    Flush,
+    /// Flush and sync `out` with [`SyncAll`]. Readers that do not require [`SyncAll`] only flush,
+    /// see [`ReadSummary::finalized`](super::ReadSummary::finalized)
+    FlushAndSync,
+}
+
+/// How the readers sync `out`
+pub(crate) trait SyncOutput<W> {
+    /// Sync `out`, returns false if not supported
+    async fn sync(out: &mut W) -> io::Result<bool>;
+}
+
+pub(crate) struct NoSync;
+
+impl<W> SyncOutput<W> for NoSync {
+    async fn sync(_: &mut W) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+pub(crate) struct UseSyncAll;
+
+impl<W: SyncAll> SyncOutput<W> for UseSyncAll {
+    async fn sync(out: &mut W) -> io::Result<bool> {
+        out.sync_all().await?;
+        Ok(true)
+    }
+}
+
+/// Finalize `out` up to `level` with `S`, returns the level completed
+pub(crate) async fn finalize<W: AsyncWrite + Unpin, S: SyncOutput<W>>(
+    out: &mut W,
+    level: Finalize,
+) -> io::Result<Finalize> {
+    if level == Finalize::None {
+        return Ok(Finalize::None);
+    }
+    out.flush().await?;
+    if level == Finalize::FlushAndSync && S::sync(out).await? {
+        Ok(Finalize::FlushAndSync)
+    } else {
+        Ok(Finalize::Flush)
+    }
+}
+
+/// How the seek reader extends `out` to the declared file size, and truncates it
+pub(crate) trait Preallocate<W> {
+    /// Extend `out` to `len` bytes. Only called if `out` is shorter, the position of `out` is
+    /// restored by the caller
+    async fn extend(out: &mut W, len: u64) -> io::Result<()>;
+
+    /// Truncate `out` to `len` bytes, nothing if not supported
+    async fn truncate(out: &mut W, len: u64) -> io::Result<()>;
+}
+
+/// Fallback for any seekable output, writes a zero byte at `len - 1`
+pub(crate) struct WriteLastByte;
+
+impl<W: AsyncSeek + AsyncWrite + Unpin> Preallocate<W> for WriteLastByte {
+    async fn extend(out: &mut W, len: u64) -> io::Result<()> {
+        out.seek(SeekFrom::Start(len - 1)).await?;
+        out.write_all(&[0]).await
+    }
+
+    async fn truncate(_: &mut W, _: u64) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) struct UseSetLen;
+
+impl<W: SetLen> Preallocate<W> for UseSetLen {
+    async fn extend(out: &mut W, len: u64) -> io::Result<()> {
+        out.set_len(len).await
+    }
+
+    async fn truncate(out: &mut W, len: u64) -> io::Result<()> {
+        out.set_len(len).await
+    }
+}
+
+/// Extend `out` to `len` bytes with `P` if it is shorter, keeping its current position
+pub(crate) async fn preallocate<W: AsyncSeek + Unpin, P: Preallocate<W>>(
+    out: &mut W,
+    len: u64,
+) -> io::Result<()> {
+    let position = out.stream_position().await?;
+    let current_len = out.seek(SeekFrom::End(0)).await?;
+    if current_len < len {
+        P::extend(out, len).await?;
+    }
+    out.seek(SeekFrom::Start(position)).await?;
+    Ok(())
+}
+
+/// Where the seek reader reads back the bytes of `out` it already wrote, to copy de-duplicated
+/// blocks
+pub(crate) trait ReadBack<W> {
+    /// Fill `buf` with the bytes of the output at `offset`. `out` must be left at `resume`, the
+    /// position of the next write
+    async fn read_at(
+        &mut self,
+        out: &mut W,
+        offset: u64,
+        buf: &mut [u8],
+        resume: u64,
+    ) -> io::Result<()>;
+}
+
+/// Read back from `out` itself, moving its position back and forth
+pub(crate) struct FromOut;
+
+impl<W: AsyncSeek + AsyncRead + Unpin> ReadBack<W> for FromOut {
+    async fn read_at(
+        &mut self,
+        out: &mut W,
+        offset: u64,
+        buf: &mut [u8],
+        resume: u64,
+    ) -> io::Result<()> {
+        out.seek(SeekFrom::Start(offset)).await?;
+        out.read_exact(buf).await?;
+        out.seek(SeekFrom::Start(resume)).await?;
+        Ok(())
+    }
+}
+
+/// Read back from a separate handle onto the storage of `out`, which keeps its position. `out` is
+/// flushed first so the handle sees all bytes written.
+pub(crate) struct FromHandle<'a, R>(pub &'a mut R);
+
+impl<W: AsyncWrite + Unpin, R: AsyncSeek + AsyncRead + Unpin> ReadBack<W> for FromHandle<'_, R> {
+    async fn read_at(
+        &mut self,
+        out: &mut W,
+        offset: u64,
+        buf: &mut [u8],
+        _resume: u64,
+    ) -> io::Result<()> {
+        out.flush().await?;
+        self.0.seek(SeekFrom::Start(offset)).await?;
+        self.0.read_exact(buf).await
+    }
+}
+
+/// `out` behind the encoder of [`compress_output`](super::ReadSingleFileOptions::compress_output)
+pub(crate) enum CompressOutput<'a, W> {
+    Plain(&'a mut W),
+    #[cfg(feature = "gzip")]
+    Gzip(async_compression::futures::write::GzipEncoder<&'a mut W>),
+    #[cfg(feature = "zstd")]
+    Zstd(async_compression::futures::write::ZstdEncoder<&'a mut W>),
+}
+
+impl<'a, W: AsyncWrite + Unpin> CompressOutput<'a, W> {
+    /// Fails with [`CompressedOutputUnsupported`](ReadSingleFileError::CompressedOutputUnsupported)
+    /// if the feature of `compression` is not enabled
+    pub(crate) fn new(
+        out: &'a mut W,
+        compression: Compression,
+    ) -> Result<Self, ReadSingleFileError> {
+        Ok(match compression {
+            Compression::None => Self::Plain(out),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                Self::Gzip(async_compression::futures::write::GzipEncoder::new(out))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                Self::Zstd(async_compression::futures::write::ZstdEncoder::new(out))
+            }
+            #[allow(unreachable_patterns)]
+            _ => return Err(ReadSingleFileError::CompressedOutputUnsupported),
+        })
+    }
+
+    /// End the compressed stream, which closes `out`, and return `out`
+    pub(crate) async fn finish(self) -> io::Result<&'a mut W> {
+        match self {
+            Self::Plain(out) => Ok(out),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(mut encoder) => {
+                encoder.close().await?;
+                Ok(encoder.into_inner())
+            }
+            #[cfg(feature = "zstd")]
+            Self::Zstd(mut encoder) => {
+                encoder.close().await?;
+                Ok(encoder.into_inner())
+            }
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for CompressOutput<'_, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(w) => Pin::new(w).poll_write(cx, buf),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(w) => Pin::new(w).poll_write(cx, buf),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => Pin::new(w).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(w) => Pin::new(w).poll_flush(cx),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(w) => Pin::new(w).poll_flush(cx),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => Pin::new(w).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(w) => Pin::new(w).poll_close(cx),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(w) => Pin::new(w).poll_close(cx),
+            #[cfg(feature = "zstd")]
+            Self::Zstd(w) => Pin::new(w).poll_close(cx),
+        }
+    }
+}
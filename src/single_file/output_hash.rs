@@ -0,0 +1,63 @@
+use multihash::{
+    Blake2b256, Blake2b512, Code, Hasher, Multihash, MultihashDigest, Sha2_256, Sha2_512,
+};
+
+/// Hash function of [`hash_output`](super::ReadSingleFileOptions::hash_output)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputHash {
+    Sha2_256,
+    Sha2_512,
+    Blake2b256,
+    Blake2b512,
+}
+
+impl OutputHash {
+    /// Multihash code of the function, e.g. to compare the digest with the hash of a CID
+    pub fn code(self) -> Code {
+        match self {
+            Self::Sha2_256 => Code::Sha2_256,
+            Self::Sha2_512 => Code::Sha2_512,
+            Self::Blake2b256 => Code::Blake2b256,
+            Self::Blake2b512 => Code::Blake2b512,
+        }
+    }
+}
+
+/// Hashes the bytes of the file in order as they are written to `out`
+pub(crate) enum OutputHasher {
+    Sha2_256(Sha2_256),
+    Sha2_512(Sha2_512),
+    Blake2b256(Blake2b256),
+    Blake2b512(Blake2b512),
+}
+
+impl OutputHasher {
+    pub fn new(hash: OutputHash) -> Self {
+        match hash {
+            OutputHash::Sha2_256 => Self::Sha2_256(Default::default()),
+            OutputHash::Sha2_512 => Self::Sha2_512(Default::default()),
+            OutputHash::Blake2b256 => Self::Blake2b256(Default::default()),
+            OutputHash::Blake2b512 => Self::Blake2b512(Default::default()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha2_256(hasher) => hasher.update(data),
+            Self::Sha2_512(hasher) => hasher.update(data),
+            Self::Blake2b256(hasher) => hasher.update(data),
+            Self::Blake2b512(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finish(mut self) -> Multihash {
+        let (code, digest) = match &mut self {
+            Self::Sha2_256(hasher) => (Code::Sha2_256, hasher.finalize()),
+            Self::Sha2_512(hasher) => (Code::Sha2_512, hasher.finalize()),
+            Self::Blake2b256(hasher) => (Code::Blake2b256, hasher.finalize()),
+            Self::Blake2b512(hasher) => (Code::Blake2b512, hasher.finalize()),
+        };
+        code.wrap(digest)
+            .expect("digests of at most 64 bytes fit a Multihash")
+    }
+}
@@ -0,0 +1,139 @@
+use futures::{future::BoxFuture, AsyncRead, AsyncSeek, AsyncWrite};
+use rs_car::Cid;
+use std::{
+    fs::File,
+    io::{self, SeekFrom},
+    os::unix::fs::FileExt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use super::{
+    read_single_file_seek_file, ReadSingleFileError, ReadSingleFileOptions, ReadSummary, SetLen,
+    SyncAll,
+};
+
+/// Output writing to a file with positional I/O, `pwrite` and `pread`, at a position of its own.
+/// Seeks only move that position, the offset of the file descriptor is never used nor moved, so
+/// other users of the same descriptor are not disturbed.
+///
+/// Each read and write is a blocking system call on the polling thread, as fast as the page cache
+/// for local files. Use a plain async file for storage that can stall, e.g. a network mount.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::{read_single_file_seek_file, PositionalFile};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let file = std::fs::File::create(std::env::temp_dir().join("example-pwrite.out"))?;
+///
+///   let mut out = PositionalFile::new(&file);
+///   read_single_file_seek_file(&mut input, &mut out, None, &Default::default()).await?;
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct PositionalFile<'a> {
+    file: &'a File,
+    position: u64,
+}
+
+impl<'a> PositionalFile<'a> {
+    /// Write `file` starting at position 0. A raw descriptor can be borrowed as a `File` with
+    /// `std::mem::ManuallyDrop::new(File::from_raw_fd(fd))`
+    pub fn new(file: &'a File) -> Self {
+        Self { file, position: 0 }
+    }
+
+    /// Position of the next read or write within the file
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+}
+
+impl AsyncWrite for PositionalFile<'_> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let written = self.file.write_at(buf, self.position)?;
+        self.position += written as u64;
+        Poll::Ready(Ok(written))
+    }
+
+    /// Positional writes are not buffered
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for PositionalFile<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let read = self.file.read_at(buf, self.position)?;
+        self.position += read as u64;
+        Poll::Ready(Ok(read))
+    }
+}
+
+impl AsyncSeek for PositionalFile<'_> {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.position = offset;
+                return Poll::Ready(Ok(offset));
+            }
+            SeekFrom::Current(offset) => (self.position, offset),
+            SeekFrom::End(offset) => (self.file.metadata()?.len(), offset),
+        };
+        match base.checked_add_signed(offset) {
+            Some(position) => {
+                self.position = position;
+                Poll::Ready(Ok(position))
+            }
+            None => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ))),
+        }
+    }
+}
+
+impl SetLen for PositionalFile<'_> {
+    fn set_len(&mut self, len: u64) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(futures::future::ready(self.file.set_len(len)))
+    }
+}
+
+impl SyncAll for PositionalFile<'_> {
+    fn sync_all(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        Box::pin(futures::future::ready(self.file.sync_all()))
+    }
+}
+
+/// Same as [`read_single_file_seek_file`] writing `file` with positional I/O through a
+/// [`PositionalFile`], so the offset of its descriptor is left untouched
+pub async fn read_single_file_pwrite<R: AsyncRead + Send + Unpin>(
+    car_input: &mut R,
+    file: &File,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let mut out = PositionalFile::new(file);
+    read_single_file_seek_file(car_input, &mut out, root_cid, options).await
+}
@@ -0,0 +1,116 @@
+use rs_car::Cid;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::layout::FileLayout;
+
+/// Progress of a read, see [`on_progress`](super::ReadSingleFileOptions::on_progress)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Progress {
+    /// Length of the file written to `out` so far
+    pub bytes_written: u64,
+    /// Count of blocks read from `car_input`, including blocks not part of the file
+    pub blocks_read: usize,
+    /// Length of the file declared by the root node, once it was read
+    pub declared_size: Option<u64>,
+}
+
+/// Callback called with the [`Progress`] of a read, see
+/// [`on_progress`](super::ReadSingleFileOptions::on_progress).
+///
+/// ```
+/// use rs_car_ipfs::single_file::{ProgressHook, ReadSingleFileOptions};
+///
+/// let options = ReadSingleFileOptions {
+///     on_progress: Some(ProgressHook::new(|progress| {
+///         eprintln!("{} bytes written", progress.bytes_written);
+///     })),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct ProgressHook(Arc<ProgressFn>);
+
+type ProgressFn = dyn Fn(&Progress) + Send + Sync;
+
+impl ProgressHook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, progress: &Progress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressHook")
+    }
+}
+
+/// Shared snapshot of a running read of
+/// [`read_single_file_seek_with_handle`](super::read_single_file_seek_with_handle), updated after
+/// each block. Unlike [`ProgressHook`] it is polled, e.g. from another task serving a health
+/// endpoint, and also tells which nodes the read is waiting for.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressHandle(Arc<Snapshot>);
+
+#[derive(Debug, Default)]
+struct Snapshot {
+    bytes_written: AtomicU64,
+    blocks_read: AtomicUsize,
+    pending_links_len: AtomicUsize,
+    pending_head: Mutex<Option<Cid>>,
+}
+
+impl ProgressHandle {
+    /// Length of the file written to `out` so far
+    pub fn bytes_written(&self) -> u64 {
+        self.0.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Count of blocks read from `car_input`, including blocks not part of the file
+    pub fn blocks_read(&self) -> usize {
+        self.0.blocks_read.load(Ordering::Relaxed)
+    }
+
+    /// Count of nodes of the file not written yet, leaves and nodes with links not expanded yet,
+    /// 0 once the file is written
+    pub fn pending_links_len(&self) -> usize {
+        self.0.pending_links_len.load(Ordering::Relaxed)
+    }
+
+    /// Next node of the file the read is waiting for, None before the CAR header is read and
+    /// once the file is written
+    pub fn pending_head(&self) -> Option<Cid> {
+        *self
+            .0
+            .pending_head
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn update(&self, bytes_written: u64, blocks_read: usize, layout: &FileLayout) {
+        let snapshot = &self.0;
+        *snapshot
+            .pending_head
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = layout.next_ready();
+        snapshot
+            .pending_links_len
+            .store(layout.pending_len(), Ordering::Relaxed);
+        snapshot.blocks_read.store(blocks_read, Ordering::Relaxed);
+        snapshot
+            .bytes_written
+            .store(bytes_written, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,86 @@
+use futures::future::{select, BoxFuture, Either};
+use rs_car::Cid;
+use std::{fmt, sync::Arc, time::Duration};
+
+use super::SleepHook;
+
+/// Fetch the blocks of the file still pending at the end of the CAR from elsewhere, e.g. a peer or
+/// a gateway, see [`resolve_missing`](super::ReadSingleFileOptions::resolve_missing).
+///
+/// `resolve` is called with the next pending block of the file once the CAR ended, up to
+/// `attempts` times while it returns None or does not complete within `timeout`. The read
+/// continues with the returned block, which is validated against its CID like the blocks of the
+/// CAR, and fails with [`PendingLinksAtEOF`](super::ReadSingleFileError::PendingLinksAtEOF) once
+/// a block can not be resolved.
+///
+/// ```
+/// use rs_car_ipfs::single_file::{ReadSingleFileOptions, ResolveHook, ResolveMissing, SleepHook};
+/// use std::time::Duration;
+///
+/// let options = ReadSingleFileOptions {
+///     resolve_missing: Some(ResolveMissing::new(
+///         ResolveHook::new(|cid| Box::pin(async move { fetch_from_gateway(cid).await })),
+///         Duration::from_secs(30),
+///         SleepHook::new(|duration| Box::pin(async_std::task::sleep(duration))),
+///     )),
+///     ..Default::default()
+/// };
+/// # async fn fetch_from_gateway(_: rs_car_ipfs::Cid) -> Option<Vec<u8>> { None }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResolveMissing {
+    pub resolve: ResolveHook,
+    /// Calls of `resolve` for a missing block before the read fails, at least one call is made
+    pub attempts: usize,
+    /// Time waited for each call of `resolve`
+    pub timeout: Duration,
+    pub sleep: SleepHook,
+}
+
+impl ResolveMissing {
+    /// Resolve with `resolve`, waiting up to `timeout` for each of 3 attempts per block
+    pub fn new(resolve: ResolveHook, timeout: Duration, sleep: SleepHook) -> Self {
+        Self {
+            resolve,
+            attempts: 3,
+            timeout,
+            sleep,
+        }
+    }
+
+    /// The block `cid`, None if no attempt returned it in time
+    pub(crate) async fn resolve(&self, cid: Cid) -> Option<(Cid, Vec<u8>)> {
+        for _ in 0..self.attempts.max(1) {
+            match select(self.resolve.call(cid), self.sleep.call(self.timeout)).await {
+                Either::Left((Some(block), _)) => return Some((cid, block)),
+                Either::Left((None, _)) | Either::Right(_) => {}
+            }
+        }
+        None
+    }
+}
+
+/// Callback fetching a block by its CID, see [`ResolveMissing`]
+#[derive(Clone)]
+pub struct ResolveHook(Arc<ResolveFn>);
+
+type ResolveFn = dyn Fn(Cid) -> BoxFuture<'static, Option<Vec<u8>>> + Send + Sync;
+
+impl ResolveHook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Cid) -> BoxFuture<'static, Option<Vec<u8>>> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, cid: Cid) -> BoxFuture<'static, Option<Vec<u8>>> {
+        (self.0)(cid)
+    }
+}
+
+impl fmt::Debug for ResolveHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ResolveHook")
+    }
+}
@@ -0,0 +1,114 @@
+use futures::{AsyncRead, StreamExt};
+use rs_car::{CarReader, Cid};
+use std::{ops::Range, sync::Arc};
+
+use crate::pb::UnixFsType;
+
+use super::{
+    util::{data_range, decode_block, leaf_data},
+    ReadSingleFileError,
+};
+
+/// Demultiplexer of a CAR stream read for several files at once: each block is validated and
+/// decoded once, then handed as a shared [`RoutedNode`] to the state of every file
+pub(crate) struct BlockRouter<'a, R> {
+    streamer: CarReader<'a, R>,
+    blocks_read: usize,
+}
+
+/// Block of a [`BlockRouter`], decoded once for all the files it may be part of
+pub(crate) enum RoutedNode {
+    /// Leaf, with the range of its data unless it has none, an error only once the leaf is
+    /// written after checking its type
+    Leaf {
+        unixfs_type: UnixFsType,
+        block: Vec<u8>,
+        data: Option<Range<usize>>,
+    },
+    /// Node with links, and the range of its inline data
+    Links {
+        unixfs_type: UnixFsType,
+        links: Vec<Cid>,
+        block: Vec<u8>,
+        data: Range<usize>,
+    },
+    /// Block that failed to decode, only an error for the files it is part of
+    Invalid(Vec<u8>),
+}
+
+impl<'a, R: AsyncRead + Send + Unpin> BlockRouter<'a, R> {
+    pub(crate) async fn new(car_input: &'a mut R) -> Result<Self, ReadSingleFileError> {
+        Ok(Self {
+            streamer: CarReader::new(car_input, true).await?,
+            blocks_read: 0,
+        })
+    }
+
+    pub(crate) fn header_roots(&self) -> &[Cid] {
+        &self.streamer.header.roots
+    }
+
+    /// Count of blocks read so far
+    pub(crate) fn blocks_read(&self) -> usize {
+        self.blocks_read
+    }
+
+    /// Next block of the stream. An error is a failure of the stream itself, for all the files
+    pub(crate) async fn next_block(
+        &mut self,
+    ) -> Option<Result<(Cid, Arc<RoutedNode>), ReadSingleFileError>> {
+        let (cid, block) = match self.streamer.next().await? {
+            Ok(item) => item,
+            Err(err) => return Some(Err(err.into())),
+        };
+        self.blocks_read += 1;
+        let node = match RoutedNode::decode(&cid, &block) {
+            Ok(node) => node,
+            Err(_) => RoutedNode::Invalid(block),
+        };
+        Some(Ok((cid, Arc::new(node))))
+    }
+}
+
+impl RoutedNode {
+    fn decode(cid: &Cid, block: &[u8]) -> Result<Self, ReadSingleFileError> {
+        let (inner, links) = decode_block(cid, block, false)?;
+        let unixfs_type = inner.data.Type;
+        if links.is_empty() {
+            let data = match leaf_data(block, inner.data.Data, inner.data.filesize) {
+                Ok(data) => Some(data_range(block, &data)?),
+                Err(_) => None,
+            };
+            Ok(Self::Leaf {
+                unixfs_type,
+                block: block.to_vec(),
+                data,
+            })
+        } else {
+            let data = match inner.data.Data.filter(|data| !data.is_empty()) {
+                Some(data) => data_range(block, &data)?,
+                None => 0..0,
+            };
+            Ok(Self::Links {
+                unixfs_type,
+                links,
+                block: block.to_vec(),
+                data,
+            })
+        }
+    }
+
+    /// The error of an [`Invalid`](Self::Invalid) node `cid`, or of a leaf without data
+    pub(crate) fn error(&self, cid: &Cid) -> ReadSingleFileError {
+        match self {
+            Self::Invalid(block) => Self::decode(cid, block).err(),
+            Self::Leaf {
+                block, data: None, ..
+            } => decode_block(cid, block, false)
+                .and_then(|(inner, _)| leaf_data(block, inner.data.Data, inner.data.filesize))
+                .err(),
+            _ => None,
+        }
+        .unwrap_or_else(|| ReadSingleFileError::InternalError(format!("{} is not invalid", cid)))
+    }
+}
@@ -1,12 +1,32 @@
-use futures::{AsyncRead, AsyncWrite, AsyncWriteExt, StreamExt};
+use futures::{stream, AsyncRead, AsyncWrite, AsyncWriteExt, Stream, StreamExt};
 use rs_car::{CarReader, Cid};
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
 
-use crate::pb::{FlatUnixFs, UnixFsType};
+use crate::{
+    adder::{check_canonical, FileAdder},
+    cid_version::{block_key, same_block},
+    layout::FileLayout,
+    pb::UnixFsType,
+};
 
 use super::{
-    util::{assert_header_single_file, links_to_cids},
-    ReadSingleFileError,
+    block_store::StoreLookups,
+    framing::FramingGuard,
+    output::{finalize, CompressOutput, NoSync},
+    output_hash::OutputHasher,
+    sniff::ContentSniffer,
+    throttle::Pacer,
+    util::{
+        assert_header_single_file, check_blocksize, check_cycle, data_range, decode_block,
+        leaf_data, root_not_found, PeriodicFlush, ZEROS,
+    },
+    validate_pool::ValidationPool,
+    Progress, ReadSingleFileError, ReadSingleFileOptions, ReadSummary, RootSelector,
+    VerifiedPrefixError,
 };
 
 /// Read CAR stream from `car_input` as a single file buffering the block dag in memory
@@ -33,81 +53,703 @@ pub async fn read_single_file_buffer<R: AsyncRead + Send + Unpin, W: AsyncWrite
     out: &mut W,
     root_cid: Option<&Cid>,
     max_buffer: Option<usize>,
-) -> Result<(), ReadSingleFileError> {
-    let mut streamer = CarReader::new(car_input, true).await?;
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let options = ReadSingleFileOptions {
+        max_buffer,
+        ..Default::default()
+    };
+    read_single_file_buffer_with_options(car_input, out, root_cid, &options).await
+}
+
+/// Same as [`read_single_file_buffer`] with all [`ReadSingleFileOptions`] available
+pub async fn read_single_file_buffer_with_options<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    read_buffer(car_input, out, root_cid, options, &mut 0).await
+}
+
+/// Same as [`read_single_file_buffer_with_options`] reporting on error how many bytes of the file
+/// were written to `out` and verified against their blocks, see [`VerifiedPrefixError`]. Since
+/// the file is only written once the whole CAR is read, this is non-zero only for errors while
+/// writing `out` or after.
+pub async fn read_single_file_buffer_verified<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, VerifiedPrefixError> {
+    let mut verified_prefix_bytes = 0;
+    read_buffer(
+        car_input,
+        out,
+        root_cid,
+        options,
+        &mut verified_prefix_bytes,
+    )
+    .await
+    .map_err(|error| VerifiedPrefixError {
+        error,
+        verified_prefix_bytes,
+    })
+}
+
+/// Same as [`read_single_file_buffer_with_options`] for blocks already in memory instead of a CAR
+/// stream, e.g. taken from a block store or another container. Blocks are validated against
+/// their CID and may be in any order, blocks not part of the file are ignored. The root is
+/// mandatory since there is no header, and [`ReadSummary::car_bytes_read`] is 0.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::{Cid, single_file::read_single_file_from_blocks};
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let block = b"hello world".to_vec();
+///   let root_cid = Cid::try_from("bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e")?;
+///   let mut out = Cursor::new(Vec::new());
+///
+///   read_single_file_from_blocks(&[(root_cid, block)], &mut out, &root_cid, &Default::default())
+///     .await?;
+///   assert_eq!(out.into_inner(), b"hello world");
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_from_blocks<W: AsyncWrite + Unpin>(
+    blocks: &[(Cid, Vec<u8>)],
+    out: &mut W,
+    root_cid: &Cid,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let pool = options.validation_pool()?;
+    let blocks = stream::iter(
+        blocks
+            .iter()
+            .map(|(cid, block)| Ok::<_, ReadSingleFileError>((*cid, block.clone()))),
+    );
+    let blocks = ValidationPool::validate(pool.as_ref(), blocks);
+    read_blocks(blocks, pool.is_none(), out, *root_cid, &[], options, &mut 0).await
+}
+
+/// `verified` is kept to the length of the file written to `out` up to the first skipped block
+async fn read_buffer<R: AsyncRead + Send + Unpin, W: AsyncWrite + Unpin>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+    verified: &mut u64,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    // With a block error hook, blocks are validated here to know the CID of the failing block
+    let skip_block_errors = options.on_block_error.is_some();
+    // With `validation_concurrency`, blocks are hashed on the workers of `pool` instead
+    let pool = options.validation_pool()?;
+    let mut car_input = FramingGuard::new(options.buffer_input(car_input))
+        .with_max_block_size(options.block_size_limit());
+    let streamer = CarReader::new(&mut car_input, !skip_block_errors && pool.is_none()).await?;
 
     // Optional verification of the root_cid
-    let root_cid = assert_header_single_file(&streamer.header, root_cid)?;
+    let root_cid = match (root_cid, options.root_selector) {
+        (None, RootSelector::LastBlock) => None,
+        (root_cid, _) => Some(assert_header_single_file(&streamer.header, root_cid)?),
+    };
+    let header_roots = streamer.header.roots.clone();
 
-    // In-memory buffer of data nodes
+    let mut blocks = ValidationPool::validate(pool.as_ref(), streamer);
+    let summary = match root_cid {
+        Some(root_cid) => {
+            read_blocks(
+                blocks,
+                skip_block_errors,
+                out,
+                root_cid,
+                &header_roots,
+                options,
+                verified,
+            )
+            .await?
+        }
+        None => {
+            // The root is only known once the CAR ends, every block is held within `max_buffer`
+            let mut buffered = vec![];
+            let mut buffered_len: usize = 0;
+            while let Some(item) = blocks.next().await {
+                let (cid, block) = item?;
+                options.check_block_size(&cid, block.len())?;
+                if let Some(max_buffer) = options.max_buffer {
+                    buffered_len += block.len();
+                    if buffered_len > max_buffer {
+                        return Err(ReadSingleFileError::MaxBufferedData(max_buffer));
+                    }
+                }
+                buffered.push((cid, block));
+                options.check_blocks_read(buffered.len())?;
+            }
+            drop(blocks);
+            let root_cid = match buffered.last() {
+                Some((cid, _)) => *cid,
+                None => return Err(ReadSingleFileError::NoRootsInHeader),
+            };
+            read_blocks(
+                stream::iter(buffered.into_iter().map(Ok)),
+                skip_block_errors,
+                out,
+                root_cid,
+                &header_roots,
+                options,
+                verified,
+            )
+            .await?
+        }
+    };
+    Ok(ReadSummary {
+        car_bytes_read: car_input.position(),
+        ..summary
+    }
+    .completed())
+}
+
+/// Read the file `root_cid` from `blocks`, validating them against their CID if `validate`.
+/// Blocks are only read until every node of the file DAG is known, `header_roots` are the roots
+/// claimed by their container
+pub(crate) async fn read_blocks<S, W>(
+    mut blocks: S,
+    validate: bool,
+    out: &mut W,
+    root_cid: Cid,
+    header_roots: &[Cid],
+    options: &ReadSingleFileOptions,
+    verified: &mut u64,
+) -> Result<ReadSummary, ReadSingleFileError>
+where
+    S: Stream<Item = Result<(Cid, Vec<u8>), ReadSingleFileError>> + Send + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut out = CompressOutput::new(out, options.compress_output)?;
+    // In-memory buffer of data nodes, keyed by `block_key`
     let mut nodes = HashMap::new();
     let mut buffered_data_len: usize = 0;
+    let mut blocks_read: usize = 0;
+    let mut skipped_blocks = vec![];
+    let mut mime_type = None;
+    let mut descended_entry = None;
+    let mut missing = MissingNodes::new(root_cid);
+    let mut declared_size = None;
+    let mut store = StoreLookups::new(options.block_store.as_ref());
 
     // Can the same data block be referenced multiple times? Say in a file with lots of duplicate content
 
-    while let Some(item) = streamer.next().await {
-        let (cid, block) = item?;
+    // Stop reading once every node of the file DAG is known, blocks past it are not decoded
+    while !missing.is_empty() {
+        options.report_progress(Progress {
+            blocks_read,
+            declared_size,
+            ..Default::default()
+        });
+        // Missing nodes are taken from the block store if there, else from the CAR
+        let stored = store.find(missing.iter()).await;
+        let from_store = stored.is_some();
+        let (cid, block) = match stored {
+            Some(stored) => stored,
+            None => {
+                let Some(item) = blocks.next().await else {
+                    break;
+                };
+                item?
+            }
+        };
+        // The root may be streamed in another CID version than requested
+        let is_root = same_block(&cid, &root_cid);
+
+        if !from_store {
+            blocks_read += 1;
+            options.check_blocks_read(blocks_read)?;
+            options.check_block_size(&cid, block.len())?;
+        }
 
-        let inner = FlatUnixFs::try_from(block.as_slice())
-            .map_err(|err| ReadSingleFileError::InvalidUnixFs(err.to_string()))?;
+        let (mut inner, links) = match decode_block(&cid, &block, validate || from_store) {
+            Ok(decoded) if from_store => decoded,
+            Ok(decoded) => {
+                store.put(&cid, &block).await;
+                decoded
+            }
+            Err(err) => {
+                options.handle_block_error(&cid, err)?;
+                skipped_blocks.push(cid);
+                nodes.insert(block_key(&cid), UnixFsNode::Skipped);
+                missing.read(&nodes, &cid);
+                continue;
+            }
+        };
+
+        if is_root {
+            descended_entry = options
+                .descend_single_entry_root(&mut inner)?
+                .or(descended_entry);
+            mime_type = options
+                .unwrap_metadata_root(&mut inner, &links)?
+                .or(mime_type);
+        }
 
         // Check that the root CID is a file for sanity
-        if cid == root_cid && inner.data.Type != UnixFsType::File {
-            return Err(ReadSingleFileError::RootCidIsNotFile);
+        if is_root {
+            options.check_file_root(&cid, &mut inner)?;
+            options.check_declared_size(inner.data.filesize)?;
+            options.check_declared_limit(inner.data.filesize)?;
+            declared_size = inner.data.filesize;
         }
 
-        if inner.links.is_empty() {
+        let has_links = !links.is_empty();
+        if options
+            .check_node_type(&cid, inner.data.Type, has_links)
+            .is_err()
+        {
+            // Only an error if the node is part of the file DAG, checked when flattening
+            nodes.insert(block_key(&cid), UnixFsNode::Unexpected(inner.data.Type));
+        } else if !has_links {
             // Leaf data node
-            let data = inner.data.Data.ok_or(ReadSingleFileError::InvalidUnixFs(
-                "unixfs data node has not Data field".to_string(),
-            ))?;
+            let data = leaf_data(&block, inner.data.Data, inner.data.filesize)?;
 
             // Allow to limit max buffered data to prevent OOM
-            if let Some(max_buffer) = max_buffer {
+            if let Some(max_buffer) = options.max_buffer {
                 buffered_data_len += data.len();
                 if buffered_data_len > max_buffer {
                     return Err(ReadSingleFileError::MaxBufferedData(max_buffer));
                 }
             }
 
-            // TODO: Is it possible to prevent having to clone here?
-            nodes.insert(cid, UnixFsNode::Data(data.to_vec()));
+            // Keep the block instead of copying its data out
+            let range = data_range(&block, &data)?;
+            nodes.insert(
+                block_key(&cid),
+                UnixFsNode::Data(BlockSlice { block, range }),
+            );
         } else {
-            // Intermediary node (links)
-            nodes.insert(cid, UnixFsNode::Links(links_to_cids(&inner.links)?));
+            // Intermediary node (links). A File node may also carry the first bytes of its range
+            // inline, written before its children
+            let range = match inner.data.Data.filter(|data| !data.is_empty()) {
+                Some(data) => Some(data_range(&block, &data)?),
+                None => None,
+            };
+            if let Some(max_buffer) = options.max_buffer {
+                buffered_data_len += range.as_ref().map_or(0, |range| range.len());
+                if buffered_data_len > max_buffer {
+                    return Err(ReadSingleFileError::MaxBufferedData(max_buffer));
+                }
+            }
+
+            // The root links may have been replaced by those of the node under it
+            let tsizes = match inner.links.len() == links.len() {
+                true => inner.links.iter().map(|link| link.Tsize).collect(),
+                false => vec![None; links.len()],
+            };
+            nodes.insert(
+                block_key(&cid),
+                UnixFsNode::Links {
+                    links,
+                    blocksizes: inner.data.blocksizes,
+                    tsizes,
+                    block_len: block.len() as u64,
+                    data: range.map(|range| BlockSlice { block, range }),
+                },
+            );
         };
+        missing.read(&nodes, &cid);
     }
 
-    for data in flatten_tree(&nodes, &root_cid)? {
-        out.write_all(data).await?
+    if !nodes.contains_key(&block_key(&root_cid)) {
+        return Err(root_not_found(root_cid, blocks_read, header_roots));
     }
+    drop(blocks);
 
+    let mut bytes_written = 0;
+    let mut flush = PeriodicFlush::new(options.flush_every);
+    let mut canonical = options.verify_canonical.map(|profile| profile.file_adder());
+    let mut skipped = false;
+    let mut sniffer = ContentSniffer::new(options);
+    let mut hasher = options.hash_output.map(OutputHasher::new);
+    let progress = |bytes_written| Progress {
+        bytes_written,
+        blocks_read,
+        declared_size,
+    };
+    let chunks = flatten_tree(&nodes, &root_cid)?;
+    if options.check_link_tsize {
+        check_link_tsizes(&nodes, &root_cid)?;
+    }
+    let pieces: Box<dyn Iterator<Item = _> + Send> = if options.validate_before_write {
+        let pieces = chunks
+            .into_iter()
+            .map(|chunk| map_chunk(options, chunk))
+            .collect::<Result<Vec<_>, _>>()?;
+        validate_output(&pieces, &root_cid, options)?;
+        // Already verified
+        canonical = None;
+        Box::new(pieces.into_iter().map(Ok))
+    } else {
+        Box::new(chunks.into_iter().map(|chunk| map_chunk(options, chunk)))
+    };
+    let mut pacer = Pacer::new(options);
+    for piece in pieces {
+        options.report_progress(progress(bytes_written));
+        pacer.pace(bytes_written).await;
+        match piece? {
+            Piece::Data {
+                cid,
+                data,
+                dag_data,
+            } => {
+                options.check_write_limit(bytes_written, data.len() as u64, &cid)?;
+                out.write_all(&data).await?;
+                bytes_written += data.len() as u64;
+                if !skipped {
+                    *verified = bytes_written;
+                }
+                flush.wrote(&mut out, data.len()).await?;
+                if let Some(adder) = canonical.as_mut() {
+                    adder.push(dag_data)?;
+                }
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.update(&data);
+                }
+                sniffer.wrote(bytes_written - data.len() as u64, &data);
+            }
+            // `out` can not seek, skipped blocks are always zero filled
+            Piece::Skipped(cid, size) => {
+                // `size` is declared by the parent, possibly far larger than the file
+                options.check_write_limit(bytes_written, size, &cid)?;
+                skipped = true;
+                let mut remaining = size;
+                while remaining > 0 {
+                    let zeros = &ZEROS[..remaining.min(ZEROS.len() as u64) as usize];
+                    out.write_all(zeros).await?;
+                    flush.wrote(&mut out, zeros.len()).await?;
+                    if let Some(hasher) = hasher.as_mut() {
+                        hasher.update(zeros);
+                    }
+                    remaining -= zeros.len() as u64;
+                }
+                if let Some(adder) = canonical.as_mut() {
+                    push_zeros(adder, size)?;
+                }
+                bytes_written += size;
+            }
+        }
+    }
+
+    options.report_progress(progress(bytes_written));
+
+    options.check_expected_size(bytes_written)?;
+    check_canonical(canonical, &root_cid)?;
+    let finalized = finalize::<_, NoSync>(out.finish().await?, options.finalize).await?;
+    pacer.finish(bytes_written);
+
+    Ok(ReadSummary {
+        root_cid: Some(root_cid),
+        bytes_written,
+        blocks_read,
+        skipped_blocks,
+        finalized,
+        mime_type,
+        descended_entry,
+        #[cfg(feature = "sniff")]
+        content_type: sniffer.content_type(),
+        output_digest: hasher.map(OutputHasher::finish),
+        ..Default::default()
+    })
+}
+
+/// Data of a chunk as written to `out`
+enum Piece<'a> {
+    Data {
+        cid: Cid,
+        /// Returned by the `map_leaf` hook
+        data: Cow<'a, [u8]>,
+        /// Data of the DAG
+        dag_data: &'a [u8],
+    },
+    Skipped(Cid, u64),
+}
+
+fn map_chunk<'a>(
+    options: &ReadSingleFileOptions,
+    chunk: Chunk<'a>,
+) -> Result<Piece<'a>, ReadSingleFileError> {
+    Ok(match chunk {
+        Chunk::Data(cid, dag_data) => Piece::Data {
+            cid,
+            data: match &options.map_leaf {
+                Some(hook) => hook
+                    .call(dag_data)
+                    .map_err(|error| ReadSingleFileError::MapLeaf { cid, error })?,
+                None => Cow::Borrowed(dag_data),
+            },
+            dag_data,
+        },
+        Chunk::Skipped(cid, size) => Piece::Skipped(cid, size),
+    })
+}
+
+/// Runs the checks done while and after writing `pieces`, for
+/// [`validate_before_write`](ReadSingleFileOptions::validate_before_write)
+fn validate_output(
+    pieces: &[Piece],
+    root_cid: &Cid,
+    options: &ReadSingleFileOptions,
+) -> Result<(), ReadSingleFileError> {
+    let mut canonical = options.verify_canonical.map(|profile| profile.file_adder());
+    let mut len: u64 = 0;
+    for piece in pieces {
+        match piece {
+            Piece::Data {
+                cid,
+                data,
+                dag_data,
+            } => {
+                options.check_write_limit(len, data.len() as u64, cid)?;
+                len += data.len() as u64;
+                if let Some(adder) = canonical.as_mut() {
+                    adder.push(dag_data)?;
+                }
+            }
+            Piece::Skipped(cid, size) => {
+                options.check_write_limit(len, *size, cid)?;
+                len += *size;
+                if let Some(adder) = canonical.as_mut() {
+                    push_zeros(adder, *size)?;
+                }
+            }
+        }
+    }
+    options.check_expected_size(len)?;
+    check_canonical(canonical, root_cid)
+}
+
+fn push_zeros(adder: &mut FileAdder, mut size: u64) -> Result<(), std::io::Error> {
+    while size > 0 {
+        let zeros = &ZEROS[..size.min(ZEROS.len() as u64) as usize];
+        adder.push(zeros)?;
+        size -= zeros.len() as u64;
+    }
     Ok(())
 }
 
+/// Nodes reachable from the root that were not read yet, to know when the whole file DAG is known.
+/// Nodes are keyed by [`block_key`], missing ones are kept as linked
+struct MissingNodes {
+    /// Nodes reachable from the root already read
+    reached: HashSet<Cid>,
+    missing: HashMap<Cid, Cid>,
+}
+
+impl MissingNodes {
+    fn new(root_cid: Cid) -> Self {
+        Self {
+            reached: HashSet::new(),
+            missing: HashMap::from([(block_key(&root_cid), root_cid)]),
+        }
+    }
+
+    /// Update with the node `cid` just added to `nodes`, following its links to nodes already
+    /// read before it
+    fn read(&mut self, nodes: &HashMap<Cid, UnixFsNode>, cid: &Cid) {
+        let key = block_key(cid);
+        if self.missing.remove(&key).is_none() {
+            return;
+        }
+        let mut stack = vec![key];
+        while let Some(key) = stack.pop() {
+            if !self.reached.insert(key) {
+                continue;
+            }
+            let Some(UnixFsNode::Links { links, .. }) = nodes.get(&key) else {
+                continue;
+            };
+            for link in links {
+                let key = block_key(link);
+                if self.reached.contains(&key) {
+                    continue;
+                }
+                if nodes.contains_key(&key) {
+                    stack.push(key);
+                } else {
+                    self.missing.insert(key, *link);
+                }
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Cid> {
+        self.missing.values()
+    }
+}
+
+/// Returns the file contents under `root_cid` in order
 fn flatten_tree<'a>(
     nodes: &'a HashMap<Cid, UnixFsNode>,
     root_cid: &Cid,
-) -> Result<Vec<&'a Vec<u8>>, ReadSingleFileError> {
-    let node = nodes
-        .get(root_cid)
-        .ok_or(ReadSingleFileError::MissingNode(*root_cid))?;
-
-    Ok(match node {
-        UnixFsNode::Data(data) => vec![data],
-        UnixFsNode::Links(links) => {
-            let mut out = vec![];
-            for link in links {
-                for data in flatten_tree(nodes, link)? {
-                    out.push(data);
+) -> Result<Vec<Chunk<'a>>, ReadSingleFileError> {
+    let mut layout = FileLayout::new(*root_cid);
+    // Length of nodes declared by their parents blocksizes, to fill skipped blocks and check leaves
+    let mut sizes = HashMap::new();
+    let mut chunks = vec![];
+
+    while let Some(cid) = layout.next_ready() {
+        let node = nodes
+            .get(&block_key(&cid))
+            .ok_or(ReadSingleFileError::MissingNode(cid))?;
+
+        match node {
+            UnixFsNode::Data(data) => {
+                check_blocksize(&sizes, &cid, data.as_slice().len() as u64)?;
+                chunks.push(Chunk::Data(cid, data.as_slice()));
+                layout.feed_leaf(&cid, data.as_slice().len() as u64);
+            }
+            UnixFsNode::Skipped => {
+                let size = *sizes
+                    .get(&block_key(&cid))
+                    .ok_or(ReadSingleFileError::SkippedBlockUnknownSize(cid))?;
+                chunks.push(Chunk::Skipped(cid, size));
+                layout.feed_leaf(&cid, size);
+            }
+            UnixFsNode::Unexpected(found) => {
+                return Err(ReadSingleFileError::UnexpectedNodeType { cid, found: *found })
+            }
+            UnixFsNode::Links {
+                links,
+                blocksizes,
+                data,
+                ..
+            } => {
+                check_cycle(&layout, &cid)?;
+                // blocksizes are only usable if there is one per link
+                if blocksizes.len() == links.len() {
+                    sizes.extend(links.iter().map(block_key).zip(blocksizes.iter().copied()));
+                }
+                let data = data.as_ref().map_or(&[][..], |data| data.as_slice());
+                if !data.is_empty() {
+                    chunks.push(Chunk::Data(cid, data));
                 }
+                layout.feed_links(&cid, data.len() as u64, links.clone());
             }
-            out
         }
-    })
+    }
+
+    Ok(chunks)
+}
+
+/// Check the `Tsize` of each link of the acyclic DAG under `root_cid` against the file data and
+/// the blocks under the link, counted once per link. Subtrees with skipped blocks are not checked
+fn check_link_tsizes(
+    nodes: &HashMap<Cid, UnixFsNode>,
+    root_cid: &Cid,
+) -> Result<(), ReadSingleFileError> {
+    // Bytes of file data and of blocks under each node, None if it has skipped blocks
+    let mut sizes: HashMap<Cid, Option<(u64, u64)>> = HashMap::new();
+    let mut stack = vec![(*root_cid, false)];
+
+    while let Some((cid, children_done)) = stack.pop() {
+        if sizes.contains_key(&cid) {
+            continue;
+        }
+        let Some(UnixFsNode::Links {
+            links,
+            tsizes,
+            block_len,
+            data,
+            ..
+        }) = nodes.get(&block_key(&cid))
+        else {
+            let leaf = match nodes.get(&block_key(&cid)) {
+                Some(UnixFsNode::Data(data)) => {
+                    Some((data.as_slice().len() as u64, data.block.len() as u64))
+                }
+                _ => None,
+            };
+            sizes.insert(cid, leaf);
+            continue;
+        };
+        if !children_done {
+            stack.push((cid, true));
+            stack.extend(links.iter().map(|link| (*link, false)));
+            continue;
+        }
+
+        let mut file_bytes = data.as_ref().map_or(0, |data| data.as_slice().len() as u64);
+        let mut dag_bytes = *block_len;
+        let mut known = true;
+        for (link, tsize) in links.iter().zip(tsizes) {
+            let Some((link_file_bytes, link_dag_bytes)) = sizes.get(link).copied().flatten() else {
+                known = false;
+                continue;
+            };
+            match *tsize {
+                Some(declared) if declared < link_file_bytes => {
+                    return Err(ReadSingleFileError::TsizeMismatch {
+                        cid: *link,
+                        declared,
+                        actual: link_file_bytes,
+                    })
+                }
+                Some(declared) if declared > link_dag_bytes => {
+                    return Err(ReadSingleFileError::TsizeMismatch {
+                        cid: *link,
+                        declared,
+                        actual: link_dag_bytes,
+                    })
+                }
+                _ => {}
+            }
+            file_bytes = file_bytes.saturating_add(link_file_bytes);
+            dag_bytes = dag_bytes.saturating_add(link_dag_bytes);
+        }
+        sizes.insert(cid, known.then_some((file_bytes, dag_bytes)));
+    }
+    Ok(())
+}
+
+/// Data of a node as a range of its block, kept alive to not copy the data
+struct BlockSlice {
+    block: Vec<u8>,
+    range: Range<usize>,
+}
+
+impl BlockSlice {
+    fn as_slice(&self) -> &[u8] {
+        &self.block[self.range.clone()]
+    }
 }
 
 enum UnixFsNode {
-    Links(Vec<Cid>),
-    Data(Vec<u8>),
+    Links {
+        links: Vec<Cid>,
+        blocksizes: Vec<u64>,
+        /// `Tsize` of each link
+        tsizes: Vec<Option<u64>>,
+        block_len: u64,
+        data: Option<BlockSlice>,
+    },
+    Data(BlockSlice),
+    Unexpected(UnixFsType),
+    Skipped,
+}
+
+enum Chunk<'a> {
+    /// Data of the node `cid`
+    Data(Cid, &'a [u8]),
+    /// Zero filled in place of the node `cid`
+    Skipped(Cid, u64),
 }
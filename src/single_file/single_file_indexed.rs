@@ -0,0 +1,187 @@
+use futures::{AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use rs_car::{CarReader, Cid};
+use std::io::SeekFrom;
+
+use crate::{adder::check_canonical, cid_version::same_block, layout::FileLayout};
+
+use super::{
+    car_index::read_block_at,
+    framing::FramingGuard,
+    output::{finalize, NoSync},
+    read_single_file_buffer_with_options,
+    sniff::ContentSniffer,
+    util::{
+        assert_header_single_file, check_cycle, decode_block, leaf_data, root_not_found,
+        PeriodicFlush,
+    },
+    CarIndex, ReadSingleFileError, ReadSingleFileOptions, ReadSummary, RootSelector,
+};
+
+/// Read a seekable CAR `car_input` as a single file, using `index` to jump directly to the blocks
+/// of the file DAG instead of streaming the whole CAR. Blocks not reachable from the root are
+/// never read, and de-duplicated blocks are read again from `car_input` so only `AsyncWrite` is
+/// required from `out`.
+///
+/// Without an `index` falls back to [`read_single_file_buffer`](super::read_single_file_buffer)
+/// streaming the CAR from its start.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::{read_single_file_indexed, CarIndex};
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/data/helloworld.txt.size-1.normal.car").await?;
+///   let mut out = Cursor::new(Vec::new());
+///
+///   let index = CarIndex::build(&mut input).await?;
+///   read_single_file_indexed(&mut input, &mut out, None, Some(&index)).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_indexed<
+    R: AsyncRead + AsyncSeek + Send + Unpin,
+    W: AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    index: Option<&CarIndex>,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    read_single_file_indexed_with_options(car_input, out, root_cid, index, &Default::default())
+        .await
+}
+
+/// Same as [`read_single_file_indexed`] with all [`ReadSingleFileOptions`] available. When
+/// reading with an `index` the block error options and `max_buffer` do not apply, since only
+/// blocks of the file DAG are read and none is buffered. De-duplicated blocks count against
+/// `max_blocks` each time they are read.
+pub async fn read_single_file_indexed_with_options<
+    R: AsyncRead + AsyncSeek + Send + Unpin,
+    W: AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    index: Option<&CarIndex>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    car_input.seek(SeekFrom::Start(0)).await?;
+
+    let index = match index {
+        Some(index) => index,
+        None => {
+            return read_single_file_buffer_with_options(car_input, out, root_cid, options).await
+        }
+    };
+    options.check_seek()?;
+
+    let root_cid = match root_cid {
+        Some(root_cid) => *root_cid,
+        None if options.root_selector == RootSelector::LastBlock => index
+            .last_block()
+            .ok_or(ReadSingleFileError::NoRootsInHeader)?,
+        None => {
+            let mut guarded = FramingGuard::new(&mut *car_input);
+            let streamer = CarReader::new(&mut guarded, false).await?;
+            assert_header_single_file(&streamer.header, None)?
+        }
+    };
+
+    // Depth-first walk of the file DAG
+    let mut layout = FileLayout::new(root_cid);
+    let mut bytes_written = 0;
+    let mut blocks_read = 0;
+    let mut flush = PeriodicFlush::new(options.flush_every);
+    let mut canonical = options.verify_canonical.map(|profile| profile.file_adder());
+    let mut sniffer = ContentSniffer::new(options);
+    let mut mime_type = None;
+    let mut descended_entry = None;
+
+    while let Some(cid) = layout.next_ready() {
+        let offset = match index.get(&cid) {
+            Some(offset) => offset,
+            None if cid == root_cid => {
+                car_input.seek(SeekFrom::Start(0)).await?;
+                let mut guarded = FramingGuard::new(&mut *car_input);
+                let streamer = CarReader::new(&mut guarded, false).await?;
+                return Err(root_not_found(cid, index.len(), &streamer.header.roots));
+            }
+            None => return Err(ReadSingleFileError::MissingNode(cid)),
+        };
+        let (block_cid, block) =
+            read_block_at(car_input, offset, options.block_size_limit()).await?;
+        blocks_read += 1;
+        options.check_blocks_read(blocks_read)?;
+        if !same_block(&block_cid, &cid) {
+            return Err(ReadSingleFileError::InvalidCarIndex(format!(
+                "index offset {} of {} points to block {}",
+                offset, cid, block_cid
+            )));
+        }
+        options.check_block_size(&cid, block.len())?;
+        let (mut inner, links) = decode_block(&cid, &block, true)?;
+        if cid == root_cid {
+            descended_entry = options.descend_single_entry_root(&mut inner)?;
+            mime_type = options.unwrap_metadata_root(&mut inner, &links)?;
+        }
+
+        // Check that the root CID is a file for sanity
+        if cid == root_cid {
+            options.check_file_root(&cid, &mut inner)?;
+            options.check_declared_size(inner.data.filesize)?;
+            options.check_declared_limit(inner.data.filesize)?;
+        }
+
+        options.check_node_type(&cid, inner.data.Type, !links.is_empty())?;
+
+        if links.is_empty() {
+            // Leaf data node
+            let data = leaf_data(&block, inner.data.Data, inner.data.filesize)?;
+            options.check_write_limit(bytes_written, data.len() as u64, &cid)?;
+            out.write_all(&data).await?;
+            flush.wrote(out, data.len()).await?;
+            bytes_written += data.len() as u64;
+            if let Some(adder) = canonical.as_mut() {
+                adder.push(&data)?;
+            }
+            sniffer.wrote(bytes_written - data.len() as u64, &data);
+            layout.feed_leaf(&cid, data.len() as u64);
+        } else {
+            // Intermediary node (links), with optional inline data before its children
+            check_cycle(&layout, &cid)?;
+            let mut data_len = 0;
+            if let Some(data) = inner.data.Data.filter(|data| !data.is_empty()) {
+                options.check_write_limit(bytes_written, data.len() as u64, &cid)?;
+                out.write_all(&data).await?;
+                flush.wrote(out, data.len()).await?;
+                bytes_written += data.len() as u64;
+                if let Some(adder) = canonical.as_mut() {
+                    adder.push(&data)?;
+                }
+                sniffer.wrote(bytes_written - data.len() as u64, &data);
+                data_len = data.len() as u64;
+            }
+            layout.feed_links(&cid, data_len, links);
+        }
+    }
+
+    options.check_expected_size(bytes_written)?;
+    check_canonical(canonical, &root_cid)?;
+    let finalized = finalize::<_, NoSync>(out, options.finalize).await?;
+
+    Ok(ReadSummary {
+        root_cid: Some(root_cid),
+        bytes_written,
+        blocks_read,
+        finalized,
+        mime_type,
+        descended_entry,
+        #[cfg(feature = "sniff")]
+        content_type: sniffer.content_type(),
+        ..Default::default()
+    }
+    .completed())
+}
@@ -1,19 +1,46 @@
 use futures::{
-    AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, StreamExt,
+    stream, AsyncRead, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, Future, Stream,
+    StreamExt,
 };
 use rs_car::{CarReader, Cid};
-use std::{collections::HashMap, io::SeekFrom};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, SeekFrom},
+};
 
-use crate::pb::{FlatUnixFs, UnixFsType};
+use crate::{
+    adder::{check_canonical, FileAdder},
+    cid_version::{block_key, same_block},
+    layout::{FileLayout, LeafDisposition},
+    pb::UnixFsType,
+};
 
 use super::{
-    util::{assert_header_single_file, links_to_cids},
-    ReadSingleFileError,
+    block_store::StoreLookups,
+    framing::FramingGuard,
+    output::{
+        finalize, preallocate, FromHandle, FromOut, NoSync, Preallocate, ReadBack, SyncOutput,
+        UseSetLen, UseSyncAll, WriteLastByte,
+    },
+    sniff::ContentSniffer,
+    tail::TailInput,
+    throttle::Pacer,
+    util::{
+        assert_header_single_file, check_blocksize, check_cycle, decode_block, leaf_data,
+        root_not_found, PeriodicFlush, ZEROS,
+    },
+    validate_pool::ValidationPool,
+    Progress, ProgressHandle, ReadSingleFileError, ReadSingleFileOptions, ReadSummary, SetLen,
+    SkipFill, SyncAll, VerifiedPrefixError,
 };
 
 /// Read CAR stream from `car_input` as a single file without buffering the block dag in memory,
 /// reading de-duplicated blocks from `out`.
 ///
+/// The CAR ends at the first read of 0 bytes from `car_input`. To extract a CAR that is still
+/// being written, set [`tail`](ReadSingleFileOptions::tail) with
+/// [`read_single_file_seek_with_options`].
+///
 /// # Examples
 ///
 /// ```
@@ -38,114 +65,802 @@ pub async fn read_single_file_seek<
     out: &mut W,
     root_cid: Option<&Cid>,
     write_limit: Option<usize>,
-) -> Result<(), ReadSingleFileError> {
-    let write_limit = write_limit.unwrap_or(usize::MAX);
-    let mut streamer = CarReader::new(car_input, true).await?;
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let options = ReadSingleFileOptions {
+        write_limit,
+        ..Default::default()
+    };
+    read_single_file_seek_with_options(car_input, out, root_cid, &options).await
+}
+
+/// Same as [`read_single_file_seek`] with all [`ReadSingleFileOptions`] available. With
+/// [`preallocate`](ReadSingleFileOptions::preallocate) `out` is extended by writing a zero byte
+/// at the end of the file, and [`Finalize::FlushAndSync`](super::Finalize::FlushAndSync) only
+/// flushes `out`
+pub async fn read_single_file_seek_with_options<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let segments = stream::iter([car_input]);
+    read_seek::<_, _, _, _, WriteLastByte, NoSync>(
+        segments,
+        out,
+        &mut FromOut,
+        root_cid,
+        options,
+        &mut 0,
+        None,
+    )
+    .await
+}
+
+/// Same as [`read_single_file_seek_with_options`], with a [`ProgressHandle`] to poll the progress
+/// of the returned future while it runs, e.g. from another task
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::read_single_file_seek_with_handle;
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut out = Cursor::new(Vec::new());
+///   let options = Default::default();
+///
+///   let (read, handle) = read_single_file_seek_with_handle(&mut input, &mut out, None, &options);
+///   let reporter = handle.clone();
+///   async_std::task::spawn(async move {
+///     eprintln!("waiting for {:?}", reporter.pending_head());
+///   });
+///   read.await?;
+///   assert_eq!(handle.pending_links_len(), 0);
+///   Ok(())
+/// }
+/// ```
+pub fn read_single_file_seek_with_handle<
+    'a,
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+>(
+    car_input: &'a mut R,
+    out: &'a mut W,
+    root_cid: Option<&'a Cid>,
+    options: &'a ReadSingleFileOptions,
+) -> (
+    impl Future<Output = Result<ReadSummary, ReadSingleFileError>> + 'a,
+    ProgressHandle,
+) {
+    let handle = ProgressHandle::default();
+    let reader_handle = handle.clone();
+    let read = async move {
+        let segments = stream::iter([car_input]);
+        read_seek::<_, _, _, _, WriteLastByte, NoSync>(
+            segments,
+            out,
+            &mut FromOut,
+            root_cid,
+            options,
+            &mut 0,
+            Some(&reader_handle),
+        )
+        .await
+    };
+    (read, handle)
+}
+
+/// Same as [`read_single_file_seek_with_options`] for `out` files, with
+/// [`preallocate`](ReadSingleFileOptions::preallocate) and
+/// [`truncate`](ReadSingleFileOptions::truncate) setting the length of `out` with [`SetLen`] and
+/// [`Finalize::FlushAndSync`](super::Finalize::FlushAndSync) syncing it with [`SyncAll`]. With
+/// [`cleanup_on_error`](ReadSingleFileOptions::cleanup_on_error) `out` is truncated back to
+/// `base_offset` if the read fails
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::{read_single_file_seek_file, Finalize, ReadSingleFileOptions};
+///
+/// # #[cfg(not(feature = "async-std"))]
+/// # fn main() {}
+/// # #[cfg(feature = "async-std")]
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut out = async_std::fs::OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open(std::env::temp_dir().join("example.out"))
+///     .await?;
+///   let options = ReadSingleFileOptions {
+///     preallocate: true,
+///     finalize: Finalize::FlushAndSync,
+///     ..Default::default()
+///   };
+///
+///   read_single_file_seek_file(&mut input, &mut out, None, &options).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_seek_file<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + SetLen + SyncAll + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let segments = stream::iter([car_input]);
+    let result = read_seek::<_, _, _, _, UseSetLen, UseSyncAll>(
+        segments,
+        out,
+        &mut FromOut,
+        root_cid,
+        options,
+        &mut 0,
+        None,
+    )
+    .await;
+
+    // Async drop is not available, the partial file is cleaned up here before returning
+    if result.is_err() && options.cleanup_on_error {
+        let _ = out.set_len(options.base_offset).await;
+    }
+    result
+}
+
+/// Same as [`read_single_file_seek_with_options`] reporting on error how many bytes of the file
+/// were written to `out` and verified against their blocks, see [`VerifiedPrefixError`]. Blocks
+/// are written as soon as they are next in the file layout, so the prefix grows while the CAR is
+/// read.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::read_single_file_seek_verified;
+/// use futures::io::Cursor;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut out = Cursor::new(Vec::new());
+///
+///   match read_single_file_seek_verified(&mut input, &mut out, None, &Default::default()).await {
+///     Ok(_) => {}
+///     Err(err) => println!("retry from byte {}: {}", err.verified_prefix_bytes, err.error),
+///   }
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_seek_verified<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+>(
+    car_input: &mut R,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, VerifiedPrefixError> {
+    let segments = stream::iter([car_input]);
+    let mut verified_prefix_bytes = 0;
+    read_seek::<_, _, _, _, WriteLastByte, NoSync>(
+        segments,
+        out,
+        &mut FromOut,
+        root_cid,
+        options,
+        &mut verified_prefix_bytes,
+        None,
+    )
+    .await
+    .map_err(|error| VerifiedPrefixError {
+        error,
+        verified_prefix_bytes,
+    })
+}
+
+/// Same as [`read_single_file_seek_with_options`] reading the blocks of the file from a sequence of
+/// CAR `segments`, as returned by trustless gateways splitting a DAG across several responses.
+/// The root is established by the header of the first segment, unless `root_cid` is provided, and
+/// the headers of the next segments must include it in their roots, unless
+/// [`ignore_segment_roots`](ReadSingleFileOptions::ignore_segment_roots) is set to read CARs split
+/// by other tools. The file DAG is only required to be complete after the last segment.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::read_single_file_multi;
+/// use futures::{io::Cursor, stream};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let segments = vec![std::fs::read("tests/example.car")?];
+///   let mut out = Cursor::new(Vec::new());
+///
+///   let segments = stream::iter(segments.into_iter().map(Cursor::new));
+///   read_single_file_multi(segments, &mut out, None, &Default::default()).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_multi<
+    I: Stream<Item = R> + Unpin,
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+>(
+    segments: I,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    read_seek::<_, _, _, _, WriteLastByte, NoSync>(
+        segments,
+        out,
+        &mut FromOut,
+        root_cid,
+        options,
+        &mut 0,
+        None,
+    )
+    .await
+}
+
+/// Same as [`read_single_file_seek_with_options`] writing the file with `out_write` and reading
+/// de-duplicated blocks back with `out_read`, two independently seekable handles onto the same
+/// storage. The position of `out_write` only moves forward with the file, e.g. for a write-only
+/// upload with a local shadow copy to read from. `out_write` is flushed before each read from
+/// `out_read`, so `out_read` sees the bytes written.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::read_single_file_seek_split;
+///
+/// # #[cfg(not(feature = "async-std"))]
+/// # fn main() {}
+/// # #[cfg(feature = "async-std")]
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let path = std::env::temp_dir().join("example-split.out");
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut out_write = async_std::fs::File::create(&path).await?;
+///   let mut out_read = async_std::fs::File::open(&path).await?;
+///
+///   let options = Default::default();
+///   read_single_file_seek_split(&mut input, &mut out_write, &mut out_read, None, &options).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_seek_split<
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncWrite + Unpin,
+    R1: AsyncSeek + AsyncRead + Unpin,
+>(
+    car_input: &mut R,
+    out_write: &mut W,
+    out_read: &mut R1,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    let segments = stream::iter([car_input]);
+    read_seek::<_, _, _, _, WriteLastByte, NoSync>(
+        segments,
+        out_write,
+        &mut FromHandle(out_read),
+        root_cid,
+        options,
+        &mut 0,
+        None,
+    )
+    .await
+}
+
+/// `verified` is kept to the length of the file written to `out` up to the first skipped block.
+/// Bytes already written are read back from `read_back`
+async fn read_seek<
+    I: Stream<Item = R> + Unpin,
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncWrite + Unpin,
+    B: ReadBack<W>,
+    P: Preallocate<W>,
+    S: SyncOutput<W>,
+>(
+    mut segments: I,
+    out: &mut W,
+    read_back: &mut B,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+    verified: &mut u64,
+    handle: Option<&ProgressHandle>,
+) -> Result<ReadSummary, ReadSingleFileError> {
+    options.check_seek()?;
+    // With a block error hook, blocks are validated here to know the CID of the failing block
+    let skip_block_errors = options.on_block_error.is_some();
+    // With `validation_concurrency`, blocks are hashed on the workers of `pool` instead
+    let pool = options.validation_pool()?;
+    let car_validates = !skip_block_errors && pool.is_none();
+    let mut segment = segments
+        .next()
+        .await
+        .map(|segment| {
+            FramingGuard::new(options.buffer_input(TailInput::new(segment, options.tail.clone())))
+                .with_max_block_size(options.block_size_limit())
+        })
+        .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "no CAR segment to read"))?;
+    let mut streamer = ValidationPool::validate(
+        pool.as_ref(),
+        CarReader::new(&mut segment, car_validates).await?,
+    );
 
     // Optional verification of the root_cid
-    let root_cid = assert_header_single_file(&streamer.header, root_cid)?;
+    let root_cid = assert_header_single_file(&streamer.get_ref().header, root_cid)?;
+    let header_roots = streamer.get_ref().header.roots.clone();
+    let mut segment_index = 0;
 
-    // In-memory buffer of nodes, except the data contents of data nodes
+    // All writes are relative to `base_offset`, `out_ptr` is the position within the file
+    let base_offset = options.base_offset;
+    if options.truncate {
+        P::truncate(out, base_offset).await?;
+    }
+    out.seek(SeekFrom::Start(base_offset)).await?;
+
+    // In-memory buffer of nodes, except the data contents of data nodes. Maps are keyed by
+    // `block_key` so links match blocks streamed in the other CID version
     let mut nodes = HashMap::new();
-    let mut sorted_links = SortedLinks::new(root_cid);
+    let mut layout = FileLayout::new(root_cid);
+    let mut mime_type = None;
+    let mut descended_entry = None;
     let mut out_ptr = 0;
     let mut total_bytes_written = 0usize;
+    let mut holes = Holes::default();
+    let mut canonical = options.verify_canonical.map(|profile| profile.file_adder());
+    let mut blocks_read = 0usize;
+    let mut skipped_blocks = vec![];
+    let mut flush = PeriodicFlush::new(options.flush_every);
+    // Length of nodes declared by their parents blocksizes, to fill skipped blocks and check leaves
+    let mut sizes = HashMap::new();
+    // Declared filesize of the root, enforced at the end if `out` was preallocated
+    let mut preallocated_size = None;
+    // Zero filled or unwritten regions of skipped blocks are not verified
+    let mut skipped = false;
+    let mut sniffer = ContentSniffer::new(options);
+    // Length of the segments before the current one
+    let mut car_bytes_read = 0;
+    let mut declared_size = None;
+    // Leaves written at their offset with `write_ahead`, not reached yet
+    let mut written_ahead = HashSet::new();
+    // Nodes with links linking each CID, to find the offset of leaves with `write_ahead` and the
+    // leaves of the file to hold within `reorder_window`
+    let mut parents = HashMap::new();
+    // Length of the leaves held in `nodes` until their turn, within `reorder_window`
+    let mut reordered = 0usize;
+    let reorder_window = options.reorder_window_limit();
+    let mut store = StoreLookups::new(options.block_store.as_ref());
+    let mut pacer = Pacer::new(options);
+    // Set once the last segment ended, only resolved blocks may follow
+    let mut car_ended = false;
+    // Blocks returned by `resolve_missing`, counted against `max_blocks` with `blocks_read`
+    let mut resolved_blocks = 0;
 
-    while let Some(item) = streamer.next().await {
+    // Stop reading once the file is written, blocks past it are not decoded, and a tailed CAR
+    // may never reach EOF
+    loop {
+        options.report_progress(Progress {
+            bytes_written: total_bytes_written as u64,
+            blocks_read,
+            declared_size,
+        });
+        if let Some(handle) = handle {
+            handle.update(total_bytes_written as u64, blocks_read, &layout);
+        }
+        if layout.is_complete() {
+            drop(streamer);
+            break;
+        }
+        pacer.pace(total_bytes_written as u64 - holes.bytes).await;
+        // The next node of the file is taken from the block store if there, else from the CAR, and
+        // once the CAR ended from `resolve_missing`
+        let (stored, resolved) = match store.find(layout.next_ready().iter()).await {
+            None if car_ended => {
+                let resolved = match (&options.resolve_missing, layout.next_ready()) {
+                    (Some(resolver), Some(next)) => resolver.resolve(next).await,
+                    _ => None,
+                };
+                if resolved.is_none() {
+                    drop(streamer);
+                    break;
+                }
+                (resolved, true)
+            }
+            stored => (stored, false),
+        };
+        let from_store = stored.is_some();
+        let item = match stored {
+            Some(stored) => Ok(stored),
+            None => match streamer.next().await {
+                Some(item) => item,
+                None => {
+                    // Continue with the next segment, its header must reference the same root
+                    let Some(next) = segments.next().await else {
+                        car_ended = true;
+                        continue;
+                    };
+                    drop(streamer);
+                    car_bytes_read += segment.position();
+                    segment = FramingGuard::new(
+                        options.buffer_input(TailInput::new(next, options.tail.clone())),
+                    )
+                    .with_max_block_size(options.block_size_limit());
+                    segment_index += 1;
+                    streamer = ValidationPool::validate(
+                        pool.as_ref(),
+                        CarReader::new(&mut segment, car_validates).await?,
+                    );
+                    if !options.ignore_segment_roots
+                        && !streamer.get_ref().header.roots.contains(&root_cid)
+                    {
+                        return Err(ReadSingleFileError::SegmentRootMismatch {
+                            segment: segment_index,
+                            roots: streamer.get_ref().header.roots.clone(),
+                        });
+                    }
+                    continue;
+                }
+            },
+        };
         let (cid, block) = item?;
+        // The root may be streamed in another CID version than requested
+        let is_root = same_block(&cid, &root_cid);
 
-        let inner = FlatUnixFs::try_from(block.as_slice())
-            .map_err(|err| ReadSingleFileError::InvalidUnixFs(err.to_string()))?;
-
-        // Check that the root CID is a file for sanity
-        if cid == root_cid && inner.data.Type != UnixFsType::File {
-            return Err(ReadSingleFileError::RootCidIsNotFile);
+        // Resolved blocks come from a peer or gateway, held to the limits of the CAR
+        if resolved {
+            resolved_blocks += 1;
+            options.check_blocks_read(blocks_read + resolved_blocks)?;
+            options.check_block_size(&cid, block.len())?;
+        } else if !from_store {
+            blocks_read += 1;
+            options.check_blocks_read(blocks_read)?;
+            options.check_block_size(&cid, block.len())?;
         }
 
-        let node = if inner.links.is_empty() {
-            // Leaf data node
-            // - Only write nodes that are the next possible write
-            // - If the CID of the data node is not known, discard
-            // - If the CID of the node is known but is not the first, error
-            match sorted_links.find(cid) {
-                FindResult::IsNext => {} // Ok
-                // This check is unnecessary for correctness but would allow to detect
-                // a corrupt CAR stream. Otherwise this function would error with PendingLinksAtEOF
-                FindResult::NotNext => return Err(ReadSingleFileError::DataNodesNotSorted),
-                FindResult::Unknown => continue,
+        let node = match decode_block(&cid, &block, skip_block_errors || from_store) {
+            Err(err) => {
+                options.handle_block_error(&cid, err)?;
+                skipped_blocks.push(cid);
+                UnixFsNode::Skipped
             }
+            Ok((mut inner, links)) => {
+                if !from_store {
+                    store.put(&cid, &block).await;
+                }
+                if is_root {
+                    descended_entry = options
+                        .descend_single_entry_root(&mut inner)?
+                        .or(descended_entry);
+                    mime_type = options
+                        .unwrap_metadata_root(&mut inner, &links)?
+                        .or(mime_type);
+                }
 
-            let data = inner.data.Data.ok_or(ReadSingleFileError::InvalidUnixFs(
-                "unixfs data node has not Data field".to_string(),
-            ))?;
+                // Check that the root CID is a file for sanity
+                if is_root {
+                    options.check_file_root(&cid, &mut inner)?;
+                    options.check_declared_size(inner.data.filesize)?;
+                    options.check_declared_limit(inner.data.filesize)?;
+                    declared_size = inner.data.filesize;
+                }
 
-            // check if the write limit will be exceeded before writing
-            if total_bytes_written + data.len() > write_limit {
-                return Err(ReadSingleFileError::WriteLimitExceeded(
-                    total_bytes_written + data.len(),
-                ));
-            }
+                if is_root && options.preallocate && preallocated_size.is_none() {
+                    if let Some(filesize) = inner.data.filesize.filter(|size| *size > 0) {
+                        preallocate::<_, P>(out, base_offset + filesize).await?;
+                        preallocated_size = Some(filesize);
+                    }
+                }
 
-            // Write data now, and keep a record for potential future writes
-            if data.len() >= 32 && data.iter().all(|&x| x == 0) {
-                out.seek(SeekFrom::Current((data.len() - 1) as i64))
-                    .await
-                    .map_err(ReadSingleFileError::IoError)?;
-                out.write(&[0])
-                    .await
-                    .map_err(ReadSingleFileError::IoError)?;
-            } else {
-                out.write_all(&data)
-                    .await
-                    .map_err(ReadSingleFileError::IoError)?;
-            }
+                if links.is_empty() {
+                    // Leaf data node
+                    // - Only write nodes that are the next possible write
+                    // - If the CID of the data node is not known, discard
+                    // - If the CID of the node is known but is not the first, keep it in memory
+                    //   within `reorder_window`, or write it at its offset with `write_ahead`
+                    let placement = match layout.disposition(&cid) {
+                        LeafDisposition::Next => LeafPlacement::Next,
+                        // Already written, copied once reached
+                        _ if options.write_ahead && nodes.contains_key(&block_key(&cid)) => {
+                            continue
+                        }
+                        LeafDisposition::Deferred if options.write_ahead => LeafPlacement::Ahead(
+                            ahead_offset(&layout, &cid, out_ptr, &nodes, &sizes, &parents)
+                                .ok_or(ReadSingleFileError::DataNodesNotSorted)?,
+                        ),
+                        // Linked by a node not expanded yet
+                        LeafDisposition::Unknown if options.write_ahead => {
+                            match ahead_offset(&layout, &cid, out_ptr, &nodes, &sizes, &parents) {
+                                Some(start) => LeafPlacement::Ahead(start),
+                                None => continue,
+                            }
+                        }
+                        // This check is unnecessary for correctness but would allow to detect
+                        // a corrupt CAR stream. Otherwise this function would error with PendingLinksAtEOF
+                        LeafDisposition::Deferred if reorder_window == 0 => {
+                            return Err(ReadSingleFileError::DataNodesNotSorted)
+                        }
+                        LeafDisposition::Unknown if reorder_window == 0 => continue,
+                        // Already held or written, written or copied once reached
+                        _ if nodes.contains_key(&block_key(&cid)) => continue,
+                        LeafDisposition::Deferred => LeafPlacement::Reordered,
+                        // Linked by a node of the file not expanded yet
+                        LeafDisposition::Unknown
+                            if ancestors(&cid, &parents).iter().any(|ancestor| {
+                                layout.disposition(ancestor) != LeafDisposition::Unknown
+                            }) =>
+                        {
+                            LeafPlacement::Reordered
+                        }
+                        LeafDisposition::Unknown => continue,
+                    };
+
+                    options.check_node_type(&cid, inner.data.Type, false)?;
+
+                    let data = leaf_data(&block, inner.data.Data, inner.data.filesize)?;
+                    check_blocksize(&sizes, &cid, data.len() as u64)?;
 
-            total_bytes_written += data.len();
+                    if let LeafPlacement::Reordered = placement {
+                        let needed = reordered + data.len();
+                        if needed > reorder_window {
+                            return Err(ReadSingleFileError::ReorderWindowExceeded {
+                                needed,
+                                limit: reorder_window,
+                            });
+                        }
+                        reordered = needed;
+                        nodes.insert(block_key(&cid), UnixFsNode::Reordered(data.into_owned()));
+                        continue;
+                    }
+
+                    // check if the write limit will be exceeded before writing
+                    options.check_write_limit(
+                        total_bytes_written as u64,
+                        data.len() as u64,
+                        &cid,
+                    )?;
 
-            // Wrote `cid` advance write ptr and sorted links pointer
-            let size = data.len();
-            let start = out_ptr;
-            out_ptr += size;
-            sorted_links.advance()?;
+                    if let LeafPlacement::Ahead(start) = placement {
+                        // Written in place now, only checked and passed over once reached
+                        out.seek(SeekFrom::Start(base_offset + start as u64))
+                            .await?;
+                        write_maybe_sparse(out, &data, &mut holes).await?;
+                        out.seek(SeekFrom::Start(base_offset + out_ptr as u64))
+                            .await?;
+                        flush.wrote(out, data.len()).await?;
+                        total_bytes_written += data.len();
+                        written_ahead.insert(block_key(&cid));
+                        nodes.insert(
+                            block_key(&cid),
+                            UnixFsNode::DataPtr {
+                                start,
+                                size: data.len(),
+                            },
+                        );
+                        continue;
+                    }
 
-            UnixFsNode::DataPtr { start, size }
-        } else {
-            // Intermediary node (links)
-            UnixFsNode::Links(links_to_cids(&inner.links)?)
+                    // Write data now, and keep a record for potential future writes
+                    write_maybe_sparse(out, &data, &mut holes).await?;
+                    flush.wrote(out, data.len()).await?;
+                    if let Some(adder) = canonical.as_mut() {
+                        adder.push(&data)?;
+                    }
+                    sniffer.wrote(out_ptr as u64, &data);
+
+                    total_bytes_written += data.len();
+
+                    // Wrote `cid` advance write ptr and sorted links pointer
+                    let size = data.len();
+                    let start = out_ptr;
+                    out_ptr += size;
+                    if !skipped {
+                        *verified = out_ptr as u64;
+                    }
+                    layout.feed_leaf(&cid, size as u64);
+
+                    UnixFsNode::DataPtr { start, size }
+                } else if options
+                    .check_node_type(&cid, inner.data.Type, true)
+                    .is_err()
+                {
+                    // Only an error if the node is part of the file DAG, checked when reached in the layout
+                    UnixFsNode::Unexpected(inner.data.Type)
+                } else {
+                    // Intermediary node (links)
+                    // blocksizes are only usable if there is one per link
+                    if inner.data.blocksizes.len() == links.len() {
+                        sizes.extend(
+                            links
+                                .iter()
+                                .map(block_key)
+                                .zip(inner.data.blocksizes.iter().copied()),
+                        );
+                    }
+                    if options.write_ahead || reorder_window > 0 {
+                        for link in &links {
+                            parents
+                                .entry(block_key(link))
+                                .or_insert_with(Vec::new)
+                                .push(block_key(&cid));
+                        }
+                    }
+                    // A File node may also carry the first bytes of its range inline
+                    let data = inner
+                        .data
+                        .Data
+                        .map(|data| data.to_vec())
+                        .unwrap_or_default();
+                    UnixFsNode::Links { links, data }
+                }
+            }
         };
 
-        nodes.insert(cid, node);
+        nodes.insert(block_key(&cid), node);
 
         // Attempt to progress on potential pending nodes
         // See module docs for a more detailed explanation
-        while let Some(first) = sorted_links.first() {
-            match nodes.get(first) {
+        while let Some(first) = layout.next_ready() {
+            match nodes.get(&block_key(&first)) {
+                // Next node in the file layout was written ahead at its offset, pass over it
+                Some(UnixFsNode::DataPtr { start, size })
+                    if written_ahead.remove(&block_key(&first)) =>
+                {
+                    if *start != out_ptr {
+                        return Err(ReadSingleFileError::InvalidUnixFs(format!(
+                            "leaf {} written at offset {} declared by blocksizes is at {}",
+                            first, start, out_ptr
+                        )));
+                    }
+                    let mut offset = out_ptr as u64;
+                    let read_data = canonical.is_some() || sniffer.wants(offset);
+                    pass_in_place(
+                        out,
+                        read_back,
+                        base_offset + offset,
+                        *size,
+                        read_data,
+                        |chunk| {
+                            if let Some(adder) = canonical.as_mut() {
+                                adder.push(chunk)?;
+                            }
+                            sniffer.wrote(offset, chunk);
+                            offset += chunk.len() as u64;
+                            Ok(())
+                        },
+                    )
+                    .await?;
+
+                    out_ptr += size;
+                    if !skipped {
+                        *verified = out_ptr as u64;
+                    }
+                    layout.feed_leaf(&first, *size as u64);
+                }
                 // Next node in the file layout is an existing node of already written data.
                 // Use AsyncSeek to read from disk and write into new location
                 Some(UnixFsNode::DataPtr { start, size }) => {
                     // check if the write limit will be exceeded before copying
-                    if total_bytes_written + size > write_limit {
-                        return Err(ReadSingleFileError::WriteLimitExceeded(
-                            total_bytes_written + size,
-                        ));
-                    }
+                    options.check_write_limit(total_bytes_written as u64, *size as u64, &first)?;
+                    let mut offset = out_ptr as u64;
                     copy_from_to_itself(
                         out,
-                        *start,
-                        out_ptr,
+                        read_back,
+                        &mut holes,
+                        base_offset + *start as u64,
+                        base_offset + out_ptr as u64,
                         *size,
-                        &mut total_bytes_written,
-                        write_limit
+                        |chunk| {
+                            if let Some(adder) = canonical.as_mut() {
+                                adder.push(chunk)?;
+                            }
+                            sniffer.wrote(offset, chunk);
+                            offset += chunk.len() as u64;
+                            Ok(())
+                        },
                     )
                     .await?;
+                    flush.wrote(out, *size).await?;
+                    total_bytes_written += size;
 
                     // Wrote `cid` advance write ptr and sorted links pointer
                     out_ptr += size;
-                    sorted_links.advance()?;
+                    if !skipped {
+                        *verified = out_ptr as u64;
+                    }
+                    layout.feed_leaf(&first, *size as u64);
+                }
+                // Next node in the file layout is a leaf held in memory until its turn, written
+                // now and kept as written data for the next occurrences
+                Some(UnixFsNode::Reordered(data)) => {
+                    options.check_write_limit(
+                        total_bytes_written as u64,
+                        data.len() as u64,
+                        &first,
+                    )?;
+                    write_maybe_sparse(out, data, &mut holes).await?;
+                    flush.wrote(out, data.len()).await?;
+                    if let Some(adder) = canonical.as_mut() {
+                        adder.push(data)?;
+                    }
+                    sniffer.wrote(out_ptr as u64, data);
+                    total_bytes_written += data.len();
+
+                    let size = data.len();
+                    let start = out_ptr;
+                    out_ptr += size;
+                    if !skipped {
+                        *verified = out_ptr as u64;
+                    }
+                    layout.feed_leaf(&first, size as u64);
+                    reordered -= size;
+                    nodes.insert(block_key(&first), UnixFsNode::DataPtr { start, size });
                 }
                 // Next node in the file layout is an existing links node, apply insert_replace
-                Some(UnixFsNode::Links(links)) => {
-                    sorted_links.insert_replace(&first.clone(), links.clone())
+                // Inline data of the links node is written first, before its children
+                Some(UnixFsNode::Links { links, data }) => {
+                    check_cycle(&layout, &first)?;
+                    if !data.is_empty() {
+                        options.check_write_limit(
+                            total_bytes_written as u64,
+                            data.len() as u64,
+                            &first,
+                        )?;
+                        write_maybe_sparse(out, data, &mut holes).await?;
+                        flush.wrote(out, data.len()).await?;
+                        if let Some(adder) = canonical.as_mut() {
+                            adder.push(data)?;
+                        }
+                        sniffer.wrote(out_ptr as u64, data);
+                        total_bytes_written += data.len();
+                        out_ptr += data.len();
+                        if !skipped {
+                            *verified = out_ptr as u64;
+                        }
+                    }
+                    layout.feed_links(&first, data.len() as u64, links.clone());
+                }
+                // Next node in the file layout failed to decode and was skipped
+                Some(UnixFsNode::Skipped) => {
+                    skipped = true;
+                    let size = *sizes
+                        .get(&block_key(&first))
+                        .ok_or(ReadSingleFileError::SkippedBlockUnknownSize(first))?;
+                    // `size` is declared by the parent, possibly far larger than the file
+                    options.check_write_limit(total_bytes_written as u64, size, &first)?;
+                    let size = size as usize;
+                    layout.feed_leaf(&first, size as u64);
+                    write_skipped(
+                        out,
+                        size as u64,
+                        options.skip_fill,
+                        layout.is_complete(),
+                        &mut canonical,
+                        &mut holes,
+                    )
+                    .await?;
+                    flush.wrote(out, size).await?;
+                    total_bytes_written += size;
+
+                    out_ptr += size;
+                }
+                Some(UnixFsNode::Unexpected(found)) => {
+                    return Err(ReadSingleFileError::UnexpectedNodeType {
+                        cid: first,
+                        found: *found,
+                    })
                 }
                 // Next node is not yet known, continue
                 None => break,
@@ -153,129 +868,325 @@ pub async fn read_single_file_seek<
         }
     }
 
-    match sorted_links.remaining() {
-        Some(links) => Err(ReadSingleFileError::PendingLinksAtEOF(links.to_vec())),
-        None => Ok(()),
+    // The root block is never seen with a wrong root CID, not just an incomplete DAG
+    if !nodes.contains_key(&block_key(&root_cid)) {
+        return Err(root_not_found(root_cid, blocks_read, &header_roots));
     }
-}
 
-/// Tracks the unixfs links progressively building the linear layout of the target file
-/// New links are inserted in place recursively expanding the tree to its leafs.
-struct SortedLinks<T: PartialEq + Clone> {
-    pub sorted_items: Vec<T>,
-    items_ptr: usize,
-}
+    if !layout.is_complete() {
+        return Err(ReadSingleFileError::PendingLinksAtEOF(layout.remaining()));
+    }
+    car_bytes_read += segment.position();
 
-impl<T: PartialEq + Clone> SortedLinks<T> {
-    fn new(root: T) -> Self {
-        Self {
-            sorted_items: vec![root],
-            items_ptr: 0,
-        }
+    // Holes and copies must leave `out` exactly at the end of the logical file
+    let position = out.stream_position().await?;
+    if out_ptr != total_bytes_written || position != base_offset + out_ptr as u64 {
+        return Err(ReadSingleFileError::InternalError(format!(
+            "file length {} does not match bytes written {} or output position {}",
+            out_ptr,
+            total_bytes_written,
+            position.saturating_sub(base_offset)
+        )));
     }
 
-    fn find(&self, item: T) -> FindResult {
-        // TODO: Optimize with a Set if necessary
-        match self
-            .sorted_items
-            .iter()
-            .skip(self.items_ptr)
-            // Note: position index is relative to the skipped elements
-            .position(|x| *x == item)
-        {
-            Some(0) => FindResult::IsNext,
-            Some(_) => FindResult::NotNext,
-            None => FindResult::Unknown,
+    if let Some(declared) = preallocated_size {
+        if declared != out_ptr as u64 {
+            return Err(ReadSingleFileError::FileSizeMismatch {
+                declared,
+                written: out_ptr as u64,
+            });
         }
     }
 
-    fn first(&self) -> Option<&T> {
-        self.sorted_items.get(self.items_ptr)
+    options.check_expected_size(total_bytes_written as u64)?;
+    check_canonical(canonical, &root_cid)?;
+    let finalized = finalize::<_, S>(out, options.finalize).await?;
+    pacer.finish(total_bytes_written as u64 - holes.bytes);
+
+    Ok(ReadSummary {
+        root_cid: Some(root_cid),
+        bytes_written: total_bytes_written as u64,
+        sparse_bytes: holes.bytes,
+        holes_count: holes.count,
+        blocks_read,
+        car_bytes_read,
+        skipped_blocks,
+        finalized,
+        mime_type,
+        descended_entry,
+        #[cfg(feature = "sniff")]
+        content_type: sniffer.content_type(),
+        ..Default::default()
     }
+    .completed())
+}
 
-    fn advance(&mut self) -> Result<(), ReadSingleFileError> {
-        // items_ptr max value is the Vec len() to signal that all items are consumed
-        if self.items_ptr >= self.sorted_items.len() {
-            return Err(ReadSingleFileError::InternalError(
-                "attempting to increase items_ptr beyond items length".to_string(),
-            ));
-        }
+enum UnixFsNode {
+    Links {
+        links: Vec<Cid>,
+        data: Vec<u8>,
+    },
+    DataPtr {
+        start: usize,
+        size: usize,
+    },
+    /// Data of a leaf arriving before its turn, not written yet
+    Reordered(Vec<u8>),
+    Unexpected(UnixFsType),
+    Skipped,
+}
+
+/// Where a leaf arriving from the CAR is written
+enum LeafPlacement {
+    /// At the end of the written data, as the next node of the file
+    Next,
+    /// At its offset ahead of the written data, with `write_ahead`
+    Ahead(usize),
+    /// Once the leaves before it are written, held in memory until then
+    Reordered,
+}
 
-        self.items_ptr += 1;
+/// Holes left in `out` by sparse writes
+#[derive(Default)]
+pub(crate) struct Holes {
+    /// Bytes skipped with a seek instead of written
+    pub(crate) bytes: u64,
+    pub(crate) count: u64,
+}
 
-        Ok(())
+impl Holes {
+    fn add(&mut self, bytes: u64) {
+        if bytes > 0 {
+            self.bytes += bytes;
+            self.count += 1;
+        }
     }
+}
+
+/// Max length of the chunks copied by [`copy_from_to_itself`]
+const COPY_CHUNK_LEN: usize = 65536;
+
+/// Copy `size` bytes of the output from `src_offset` to `dest_offset`, in chunks of at most
+/// [`COPY_CHUNK_LEN`] so the memory used does not depend on `size`. Each chunk is passed to
+/// `on_chunk` once written. Chunks of zeros are left as `holes`
+pub(crate) async fn copy_from_to_itself<W: AsyncSeek + AsyncWrite + Unpin, B: ReadBack<W>>(
+    out: &mut W,
+    read_back: &mut B,
+    holes: &mut Holes,
+    src_offset: u64,
+    dest_offset: u64,
+    size: usize,
+    mut on_chunk: impl FnMut(&[u8]) -> io::Result<()>,
+) -> Result<(), ReadSingleFileError> {
+    let mut buffer = vec![0; size.min(COPY_CHUNK_LEN)];
+    let mut copied = 0;
+    // At least one iteration, to leave `out` at `dest_offset` even if `size` is 0
+    loop {
+        let chunk = &mut buffer[..(size - copied).min(COPY_CHUNK_LEN)];
 
-    fn remaining(&self) -> Option<&[T]> {
-        if self.items_ptr >= self.sorted_items.len() {
-            None
-        } else {
-            Some(self.sorted_items.split_at(self.items_ptr).1)
+        read_back
+            .read_at(
+                out,
+                src_offset + copied as u64,
+                chunk,
+                dest_offset + copied as u64,
+            )
+            .await
+            .map_err(ReadSingleFileError::IoError)?;
+        write_maybe_sparse(out, chunk, holes).await?;
+
+        on_chunk(chunk)?;
+        copied += chunk.len();
+        if copied >= size {
+            break;
         }
     }
 
-    /// Replace the item of `root` with `children`
-    fn insert_replace(&mut self, root: &T, children: Vec<T>) {
-        if let Some(index) = self.sorted_items.iter().position(|x| x == root) {
-            self.sorted_items.splice(index..index + 1, children);
+    Ok(())
+}
+
+/// Offset in the file of the first occurrence of the leaf `cid` not written yet, within the
+/// pending nodes of `layout` or the nodes with links it expands to. The sizes of the nodes before
+/// it are added to `out_ptr`, only the nodes linking `cid` are expanded. None if `cid` is not
+/// linked by a known node or the size of a node before it is unknown
+fn ahead_offset(
+    layout: &FileLayout,
+    cid: &Cid,
+    out_ptr: usize,
+    nodes: &HashMap<Cid, UnixFsNode>,
+    sizes: &HashMap<Cid, u64>,
+    parents: &HashMap<Cid, Vec<Cid>>,
+) -> Option<usize> {
+    let mut walk = AheadWalk {
+        cid,
+        nodes,
+        sizes,
+        ancestors: ancestors(cid, parents),
+        path: HashSet::new(),
+    };
+    match walk.walk(layout.pending(), out_ptr)? {
+        Walked::Found(offset) => Some(offset),
+        Walked::Passed(_) => None,
+    }
+}
+
+/// Keys of the nodes with links linking `cid`, directly or through other nodes
+fn ancestors(cid: &Cid, parents: &HashMap<Cid, Vec<Cid>>) -> HashSet<Cid> {
+    let mut ancestors = HashSet::new();
+    let mut stack = parents.get(&block_key(cid)).cloned().unwrap_or_default();
+    while let Some(parent) = stack.pop() {
+        if ancestors.insert(parent) {
+            stack.extend(parents.get(&parent).into_iter().flatten());
         }
     }
+    ancestors
 }
 
-enum FindResult {
-    IsNext,
-    NotNext,
-    Unknown,
+struct AheadWalk<'a> {
+    cid: &'a Cid,
+    nodes: &'a HashMap<Cid, UnixFsNode>,
+    sizes: &'a HashMap<Cid, u64>,
+    ancestors: HashSet<Cid>,
+    /// Nodes being expanded, to not loop on a cyclic DAG
+    path: HashSet<Cid>,
 }
 
-enum UnixFsNode {
-    Links(Vec<Cid>),
-    DataPtr { start: usize, size: usize },
+enum Walked {
+    /// Offset of the leaf
+    Found(usize),
+    /// Offset past the nodes, not including the leaf
+    Passed(usize),
 }
 
-async fn copy_from_to_itself<W: AsyncSeek + AsyncRead + AsyncWrite + Unpin>(
-    r: &mut W,
-    src_offset: usize,
-    dest_offset: usize,
+impl AheadWalk<'_> {
+    fn walk<'a>(
+        &mut self,
+        entries: impl Iterator<Item = &'a Cid>,
+        mut offset: usize,
+    ) -> Option<Walked> {
+        for entry in entries {
+            if same_block(entry, self.cid) {
+                return Some(Walked::Found(offset));
+            }
+            let key = block_key(entry);
+            match self.nodes.get(&key) {
+                Some(UnixFsNode::Links { links, data }) if self.ancestors.contains(&key) => {
+                    if !self.path.insert(key) {
+                        return None;
+                    }
+                    let walked = self.walk(links.iter(), offset.checked_add(data.len())?)?;
+                    self.path.remove(&key);
+                    match walked {
+                        Walked::Found(offset) => return Some(Walked::Found(offset)),
+                        Walked::Passed(end) => offset = end,
+                    }
+                }
+                Some(UnixFsNode::DataPtr { size, .. }) => offset = offset.checked_add(*size)?,
+                _ => {
+                    let size = usize::try_from(*self.sizes.get(&key)?).ok()?;
+                    offset = offset.checked_add(size)?;
+                }
+            }
+        }
+        Some(Walked::Passed(offset))
+    }
+}
+
+/// Move `out` past `size` bytes already written at `offset`, passing them to `on_chunk` if
+/// `read_data` is set
+async fn pass_in_place<W: AsyncSeek + AsyncWrite + Unpin, B: ReadBack<W>>(
+    out: &mut W,
+    read_back: &mut B,
+    offset: u64,
     size: usize,
-    total_bytes_written: &mut usize,
-    write_limit: usize,
+    read_data: bool,
+    mut on_chunk: impl FnMut(&[u8]) -> io::Result<()>,
 ) -> Result<(), ReadSingleFileError> {
-    // check if the write limit will be exceeded before writing
-    if *total_bytes_written + size > write_limit {
-        return Err(ReadSingleFileError::WriteLimitExceeded(
-            *total_bytes_written + size,
-        ));
+    let end = offset + size as u64;
+    if read_data {
+        let mut buffer = vec![0; size.min(COPY_CHUNK_LEN)];
+        let mut read = 0;
+        while read < size {
+            let chunk = &mut buffer[..(size - read).min(COPY_CHUNK_LEN)];
+            read_back
+                .read_at(out, offset + read as u64, chunk, end)
+                .await
+                .map_err(ReadSingleFileError::IoError)?;
+            on_chunk(chunk)?;
+            read += chunk.len();
+        }
     }
-
-    r.seek(SeekFrom::Start(src_offset as u64))
-        .await
-        .map_err(ReadSingleFileError::IoError)?;
-
-    let mut buffer = vec![0; size];
-    r.read_exact(&mut buffer)
+    out.seek(SeekFrom::Start(end))
         .await
         .map_err(ReadSingleFileError::IoError)?;
+    Ok(())
+}
 
-    r.seek(SeekFrom::Start(dest_offset as u64))
-        .await
-        .map_err(ReadSingleFileError::IoError)?;
+/// Fill `size` bytes at the current position of `out` for a skipped block, left as `holes` if
+/// not written. A region left unwritten at the end of the file, `at_end`, ends with a written zero
+/// so that `out` is as long as the file, like a trailing run of zeros of [`write_maybe_sparse`]
+async fn write_skipped<W: AsyncSeek + AsyncWrite + Unpin>(
+    out: &mut W,
+    size: u64,
+    fill: SkipFill,
+    at_end: bool,
+    canonical: &mut Option<FileAdder>,
+    holes: &mut Holes,
+) -> Result<(), ReadSingleFileError> {
+    match fill {
+        SkipFill::Unwritten => {
+            let unwritten = match at_end && size > 0 {
+                true => size - 1,
+                false => size,
+            };
+            let offset = i64::try_from(unwritten).map_err(|_| {
+                ReadSingleFileError::InvalidUnixFs(format!("skipped block size {} too large", size))
+            })?;
+            out.seek(SeekFrom::Current(offset))
+                .await
+                .map_err(ReadSingleFileError::IoError)?;
+            if unwritten < size {
+                out.write_all(&[0])
+                    .await
+                    .map_err(ReadSingleFileError::IoError)?;
+            }
+            holes.add(unwritten);
+            Ok(())
+        }
+        SkipFill::Zeros => {
+            let mut remaining = size;
+            while remaining > 0 {
+                let zeros = &ZEROS[..remaining.min(ZEROS.len() as u64) as usize];
+                write_maybe_sparse(out, zeros, holes).await?;
+                if let Some(adder) = canonical.as_mut() {
+                    adder.push(zeros)?;
+                }
+                remaining -= zeros.len() as u64;
+            }
+            Ok(())
+        }
+    }
+}
 
-    if buffer.len() >= 32 && buffer.iter().all(|&x| x == 0) {
-        r.seek(SeekFrom::Current((buffer.len() - 1) as i64))
+/// Write `data` at the current position of `out`. Runs of at least 32 zeros are written as a hole,
+/// seeking past all bytes except the last one, and added to `holes`.
+pub(crate) async fn write_maybe_sparse<W: AsyncSeek + AsyncWrite + Unpin>(
+    out: &mut W,
+    data: &[u8],
+    holes: &mut Holes,
+) -> Result<(), ReadSingleFileError> {
+    if data.len() >= 32 && data.iter().all(|&x| x == 0) {
+        out.seek(SeekFrom::Current((data.len() - 1) as i64))
             .await
             .map_err(ReadSingleFileError::IoError)?;
-        r.write(&[0])
+        out.write_all(&[0])
             .await
             .map_err(ReadSingleFileError::IoError)?;
+        holes.add(data.len() as u64 - 1);
     } else {
-        r.write_all(&buffer)
+        out.write_all(data)
             .await
             .map_err(ReadSingleFileError::IoError)?;
     }
-
-    *total_bytes_written += size;
-
-
     Ok(())
 }
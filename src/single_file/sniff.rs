@@ -0,0 +1,65 @@
+use super::ReadSingleFileOptions;
+
+/// MIME type of a file from the magic bytes at its start, e.g. `"image/png"`. `head` only needs
+/// the first bytes of the file, the first leaf of a file DAG is enough.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::sniff_content_type;
+///
+/// assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n"), Some("image/png"));
+/// assert_eq!(sniff_content_type(b"hello world"), None);
+/// ```
+#[cfg(feature = "sniff")]
+pub fn sniff_content_type(head: &[u8]) -> Option<&'static str> {
+    infer::get(head).map(|kind| kind.mime_type())
+}
+
+/// Length of the start of the file kept to sniff its content type, enough for all magic bytes
+#[cfg(feature = "sniff")]
+const HEAD_LEN: usize = 8192;
+
+/// Sniffs the content type from the start of the file, if
+/// [`sniff_content_type`](ReadSingleFileOptions::sniff_content_type) is set. Only data written
+/// contiguously from the start of the file is kept.
+pub(crate) struct ContentSniffer {
+    #[cfg(feature = "sniff")]
+    head: Option<Vec<u8>>,
+}
+
+impl ContentSniffer {
+    #[allow(unused_variables)]
+    pub fn new(options: &ReadSingleFileOptions) -> Self {
+        Self {
+            #[cfg(feature = "sniff")]
+            head: options.sniff_content_type.then(Vec::new),
+        }
+    }
+
+    /// Whether data written at `offset` of the file would be kept
+    #[allow(unused_variables)]
+    pub fn wants(&self, offset: u64) -> bool {
+        #[cfg(feature = "sniff")]
+        if let Some(head) = self.head.as_ref() {
+            return offset == head.len() as u64 && head.len() < HEAD_LEN;
+        }
+        false
+    }
+
+    /// Record `data` written at `offset` of the file
+    #[allow(unused_variables)]
+    pub fn wrote(&mut self, offset: u64, data: &[u8]) {
+        #[cfg(feature = "sniff")]
+        if let Some(head) = self.head.as_mut() {
+            if offset == head.len() as u64 && head.len() < HEAD_LEN {
+                head.extend_from_slice(&data[..data.len().min(HEAD_LEN - head.len())]);
+            }
+        }
+    }
+
+    #[cfg(feature = "sniff")]
+    pub fn content_type(&self) -> Option<&'static str> {
+        self.head.as_deref().and_then(sniff_content_type)
+    }
+}
@@ -0,0 +1,67 @@
+use futures::{stream::IntoAsyncRead, AsyncRead, AsyncSeek, AsyncWrite, TryStream, TryStreamExt};
+use rs_car::Cid;
+
+use super::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, ReadSingleFileError,
+    ReadSingleFileOptions, ReadSummary,
+};
+
+/// Adapt a stream of byte chunks, as returned by most HTTP clients, to the `AsyncRead` expected
+/// by the readers. Chunk boundaries are arbitrary and do not need to align with CAR sections.
+pub fn stream_car_input<S>(stream: S) -> IntoAsyncRead<S>
+where
+    S: TryStream<Error = std::io::Error> + Unpin,
+    S::Ok: AsRef<[u8]>,
+{
+    stream.into_async_read()
+}
+
+/// Same as [`read_single_file_buffer_with_options`] reading the CAR from a stream of byte chunks.
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::single_file::{read_single_file_buffer_stream, ReadSingleFileOptions};
+/// use futures::{io::Cursor, stream};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let car = std::fs::read("tests/example.car")?;
+///   let chunks = car.chunks(100).map(|chunk| Ok(chunk.to_vec())).collect::<Vec<_>>();
+///   let mut out = Cursor::new(Vec::new());
+///
+///   let options = ReadSingleFileOptions::default();
+///   read_single_file_buffer_stream(stream::iter(chunks), &mut out, None, &options).await?;
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_buffer_stream<S, W>(
+    car_input: S,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError>
+where
+    S: TryStream<Error = std::io::Error> + Send + Unpin,
+    S::Ok: AsRef<[u8]> + Send,
+    W: AsyncWrite + Unpin,
+{
+    let mut car_input = stream_car_input(car_input);
+    read_single_file_buffer_with_options(&mut car_input, out, root_cid, options).await
+}
+
+/// Same as [`read_single_file_seek_with_options`] reading the CAR from a stream of byte chunks
+pub async fn read_single_file_seek_stream<S, W>(
+    car_input: S,
+    out: &mut W,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<ReadSummary, ReadSingleFileError>
+where
+    S: TryStream<Error = std::io::Error> + Send + Unpin,
+    S::Ok: AsRef<[u8]> + Send,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+{
+    let mut car_input = stream_car_input(car_input);
+    read_single_file_seek_with_options(&mut car_input, out, root_cid, options).await
+}
@@ -0,0 +1,273 @@
+use futures::{
+    channel::mpsc, future, ready, AsyncRead, AsyncSeek, AsyncWrite, AsyncWriteExt, Sink, StreamExt,
+    TryStreamExt,
+};
+use rs_car::{CarReader, Cid};
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+
+use crate::import::{block_section_head, car_header};
+
+use super::{
+    framing::FramingGuard,
+    read_single_file_seek_with_options,
+    util::{assert_header_single_file, decode_block, root_not_found},
+    ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
+
+/// Counts of an [`extract_subgraph_car`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SubgraphStats {
+    /// Blocks of the DAG written to `car_output`
+    pub blocks_written: usize,
+    /// Length of the CAR written to `car_output`, including its header
+    pub bytes_written: u64,
+    /// Blocks read from `car_input`
+    pub blocks_read: usize,
+    /// Blocks read that are not part of the DAG, or duplicates of a block already written
+    pub blocks_skipped: usize,
+}
+
+/// Copy the blocks of the DAG under `root_cid` from the CAR stream `car_input` to a new CARv1
+/// `car_output` with `root_cid` as its single root, e.g. to re-serve one file of a large CAR. The
+/// blocks are copied verbatim, without decoding the file, and everything else is skipped. Links
+/// are followed as the readers do, so the DAG must be UnixFS. The read stops once every block of
+/// the DAG is written.
+///
+/// Blocks arrive in the order of `car_input`, except for blocks read before any link to them:
+/// those are buffered in memory, up to `max_buffer` bytes, and written after the block linking
+/// them. Exceeding `max_buffer` fails with
+/// [`MaxBufferedData`](ReadSingleFileError::MaxBufferedData), and blocks of the DAG not in the
+/// CAR with [`PendingLinksAtEOF`](ReadSingleFileError::PendingLinksAtEOF).
+///
+/// # Examples
+///
+/// ```
+/// use rs_car_ipfs::{Cid, single_file::extract_subgraph_car};
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut output = async_std::fs::File::create(std::env::temp_dir().join("sub.car")).await?;
+///   let root_cid = Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf")?;
+///
+///   let stats = extract_subgraph_car(&mut input, &mut output, Some(&root_cid), None).await?;
+///   println!("{} blocks", stats.blocks_written);
+///   Ok(())
+/// }
+/// ```
+pub async fn extract_subgraph_car<R: AsyncRead + Send + Unpin, W: AsyncWrite + Unpin>(
+    car_input: &mut R,
+    car_output: &mut W,
+    root_cid: Option<&Cid>,
+    max_buffer: Option<usize>,
+) -> Result<SubgraphStats, ReadSingleFileError> {
+    let mut car_input = FramingGuard::new(car_input);
+    let mut streamer = CarReader::new(&mut car_input, true).await?;
+    let root_cid = assert_header_single_file(&streamer.header, root_cid)?;
+
+    let header = car_header(&root_cid);
+    car_output.write_all(&header).await?;
+    let mut stats = SubgraphStats {
+        bytes_written: header.len() as u64,
+        ..Default::default()
+    };
+
+    // Blocks written or about to be
+    let mut reached = HashSet::from([root_cid]);
+    // Linked by a block written, not read yet
+    let mut pending = HashSet::from([root_cid]);
+    // Read before any link to them
+    let mut buffered = HashMap::new();
+    let mut buffered_len: usize = 0;
+
+    while !pending.is_empty() {
+        let Some(item) = streamer.next().await else {
+            break;
+        };
+        let (cid, block) = item?;
+        stats.blocks_read += 1;
+
+        if !pending.remove(&cid) {
+            if reached.contains(&cid) || buffered.contains_key(&cid) {
+                stats.blocks_skipped += 1;
+                continue;
+            }
+            buffered_len += block.len();
+            if let Some(max_buffer) = max_buffer.filter(|max| buffered_len > *max) {
+                return Err(ReadSingleFileError::MaxBufferedData(max_buffer));
+            }
+            buffered.insert(cid, block);
+            continue;
+        }
+
+        // Write the block, then the buffered blocks under it depth first in link order
+        let mut stack = vec![(cid, block)];
+        while let Some((cid, block)) = stack.pop() {
+            let (_, links) = decode_block(&cid, &block, false)?;
+            let head = block_section_head(&cid, block.len());
+            car_output.write_all(&head).await?;
+            car_output.write_all(&block).await?;
+            stats.blocks_written += 1;
+            stats.bytes_written += (head.len() + block.len()) as u64;
+
+            let mut children = vec![];
+            for link in links {
+                if !reached.insert(link) {
+                    continue;
+                }
+                match buffered.remove(&link) {
+                    Some(block) => {
+                        buffered_len -= block.len();
+                        children.push((link, block));
+                    }
+                    None => {
+                        pending.insert(link);
+                    }
+                }
+            }
+            stack.extend(children.into_iter().rev());
+        }
+    }
+    stats.blocks_skipped += buffered.len();
+
+    if pending.contains(&root_cid) {
+        return Err(root_not_found(
+            root_cid,
+            stats.blocks_read,
+            &streamer.header.roots,
+        ));
+    }
+    if !pending.is_empty() {
+        return Err(ReadSingleFileError::PendingLinksAtEOF(
+            pending.into_iter().collect(),
+        ));
+    }
+    car_output.flush().await?;
+
+    Ok(stats)
+}
+
+/// Extract the file under `root_cid` from the CAR stream `car_input` to `out`, as
+/// [`read_single_file_seek_with_options`] does, while writing the blocks of its DAG to a new
+/// minimized CARv1 `car_output` as [`extract_subgraph_car`] does, e.g. to prune a CAR of several
+/// roots down to one file while serving it. `car_input` is read once.
+///
+/// The file is read from the minimized CAR as it is written, so its blocks arrive after the
+/// block linking them whatever the order of `car_input`. `max_buffer` bounds the blocks of
+/// `car_input` read before any link to them, and the counts of blocks of the [`ReadSummary`] are
+/// of the minimized CAR, see [`SubgraphStats`] for `car_input`. Invalid blocks always fail the
+/// read, `on_block_error` is not used.
+///
+/// # Examples
+///
+/// ```
+/// use futures::io::Cursor;
+/// use rs_car_ipfs::single_file::read_single_file_and_reexport;
+///
+/// #[async_std::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut input = async_std::fs::File::open("tests/example.car").await?;
+///   let mut out = Cursor::new(Vec::new());
+///   let mut car_output = Vec::new();
+///
+///   let (summary, stats) =
+///       read_single_file_and_reexport(&mut input, &mut out, &mut car_output, None, &Default::default())
+///           .await?;
+///   assert_eq!(stats.bytes_written, car_output.len() as u64);
+///   println!("{} bytes in {} blocks", summary.bytes_written, stats.blocks_written);
+///   Ok(())
+/// }
+/// ```
+pub async fn read_single_file_and_reexport<R, W, C>(
+    car_input: &mut R,
+    out: &mut W,
+    car_output: &mut C,
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<(ReadSummary, SubgraphStats), ReadSingleFileError>
+where
+    R: AsyncRead + Send + Unpin,
+    W: AsyncSeek + AsyncRead + AsyncWrite + Unpin,
+    C: AsyncWrite + Unpin,
+{
+    let options = ReadSingleFileOptions {
+        on_block_error: None,
+        ..options.clone()
+    };
+    let reader_failed = AtomicBool::new(false);
+    let (sections_tx, sections_rx) = mpsc::channel(0);
+    let mut tee = TeeOutput {
+        car_output,
+        sections_tx,
+        reader_failed: &reader_failed,
+        stopped: false,
+    };
+
+    let copy = async {
+        let res = extract_subgraph_car(car_input, &mut tee, root_cid, options.max_buffer).await;
+        // The end of the minimized CAR for the reader
+        tee.sections_tx.close_channel();
+        (res, tee.stopped)
+    };
+    let extract = async {
+        let mut car = sections_rx.map(Ok::<_, io::Error>).into_async_read();
+        let res = read_single_file_seek_with_options(&mut car, out, None, &options).await;
+        reader_failed.store(res.is_err(), Ordering::Relaxed);
+        res
+    };
+    match future::join(copy, extract).await {
+        ((Ok(stats), _), Ok(summary)) => Ok((summary, stats)),
+        // The copy was stopped by the reader failing
+        ((Err(_), true), Err(err)) => Err(err),
+        // The reader fails on the end of the minimized CAR if the copy failed first
+        ((Err(err), _), _) | ((Ok(_), _), Err(err)) => Err(err),
+    }
+}
+
+/// Output of [`read_single_file_and_reexport`] writing the minimized CAR to `car_output` and
+/// passing each write to the reader of the file
+struct TeeOutput<'a, C> {
+    car_output: &'a mut C,
+    sections_tx: mpsc::Sender<Vec<u8>>,
+    /// Set once the reader failed, the copy is stopped on its next write
+    reader_failed: &'a AtomicBool,
+    stopped: bool,
+}
+
+impl<C: AsyncWrite + Unpin> AsyncWrite for TeeOutput<'_, C> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        // A closed channel is a reader done with the file, or failed
+        let send = ready!(Pin::new(&mut self.sections_tx).poll_ready(cx)).is_ok();
+        if !send && self.reader_failed.load(Ordering::Relaxed) {
+            self.stopped = true;
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the read of the file failed",
+            )));
+        }
+        let written = ready!(Pin::new(&mut *self.car_output).poll_write(cx, buf))?;
+        if send {
+            // Only fails if the reader was dropped since `poll_ready`
+            let _ = Pin::new(&mut self.sections_tx).start_send(buf[..written].to_vec());
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.car_output).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.car_output).poll_close(cx)
+    }
+}
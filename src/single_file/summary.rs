@@ -0,0 +1,80 @@
+use multihash::Multihash;
+use rs_car::Cid;
+
+use super::Finalize;
+
+/// Summary of a completed single file read, returned by the readers on success
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadSummary {
+    /// Root CID of the file read, the single root of the CAR header if none was requested. A
+    /// directory root read with
+    /// [`auto_descend_single_entry`](super::ReadSingleFileOptions::auto_descend_single_entry) or a
+    /// `Metadata` root is the CID returned, not the node of the file under it. None only for
+    /// [`read_single_file_prefix`](super::read_single_file_prefix) of 0 bytes without a root CID,
+    /// which does not read the CAR header
+    pub root_cid: Option<Cid>,
+    /// Total length of the file written to `out`, including regions of skipped blocks
+    pub bytes_written: u64,
+    /// Bytes of `bytes_written` not physically written to `out` because they belong to a run of
+    /// zeros skipped with a seek, leaving a hole. Always 0 for readers that do not write sparsely.
+    pub sparse_bytes: u64,
+    /// Number of holes the `sparse_bytes` were skipped in. Adjacent holes of consecutive writes
+    /// are counted separately.
+    pub holes_count: u64,
+    /// Count of blocks read from `car_input`, including blocks not part of the file. The readers
+    /// stop reading once the whole file DAG is known, so blocks after it are not counted. Set by
+    /// [`read_single_file_seek`](super::read_single_file_seek),
+    /// [`read_single_file_buffer`](super::read_single_file_buffer),
+    /// [`read_single_file_indexed`](super::read_single_file_indexed) and
+    /// [`extract_many`](super::extract_many). The indexed reader only reads the blocks of the
+    /// file, a block linked more than once is read and counted once per link
+    pub blocks_read: usize,
+    /// Length of the CAR read from `car_input`, up to the end of the last block read. Set by
+    /// [`read_single_file_seek`](super::read_single_file_seek),
+    /// [`read_single_file_buffer`](super::read_single_file_buffer) and
+    /// [`extract_many`](super::extract_many)
+    pub car_bytes_read: u64,
+    /// Blocks that failed to validate or decode and were skipped by
+    /// [`on_block_error`](super::ReadSingleFileOptions::on_block_error), in stream order
+    pub skipped_blocks: Vec<Cid>,
+    /// Finalize level completed on `out`, lower than
+    /// [`finalize`](super::ReadSingleFileOptions::finalize) if the reader can not sync `out`
+    pub finalized: Finalize,
+    /// MIME type declared by a UnixFS `Metadata` node wrapping the root, see
+    /// [`reject_metadata_root`](super::ReadSingleFileOptions::reject_metadata_root)
+    pub mime_type: Option<String>,
+    /// Name of the single entry of a directory root read as the file, see
+    /// [`auto_descend_single_entry`](super::ReadSingleFileOptions::auto_descend_single_entry)
+    pub descended_entry: Option<String>,
+    /// MIME type sniffed from the magic bytes at the start of the file, if
+    /// [`sniff_content_type`](super::ReadSingleFileOptions::sniff_content_type) is set and the
+    /// type is recognized
+    #[cfg(feature = "sniff")]
+    pub content_type: Option<&'static str>,
+    /// Digest of the file written, with
+    /// [`hash_output`](super::ReadSingleFileOptions::hash_output)
+    pub output_digest: Option<Multihash>,
+}
+
+impl ReadSummary {
+    /// True if at least one hole was left in `out` instead of writing zeros
+    pub fn is_sparse(&self) -> bool {
+        self.sparse_bytes > 0
+    }
+
+    /// The summary of a read returned by a reader, logged at debug level with the `log` feature
+    pub(crate) fn completed(self) -> Self {
+        log_event!(
+            debug,
+            "read root {}: {} bytes written, {} sparse, {} blocks read of {} CAR bytes, {} skipped",
+            self.root_cid
+                .map_or("none".to_string(), |cid| cid.to_string()),
+            self.bytes_written,
+            self.sparse_bytes,
+            self.blocks_read,
+            self.car_bytes_read,
+            self.skipped_blocks.len()
+        );
+        self
+    }
+}
@@ -0,0 +1,126 @@
+use futures::{future::BoxFuture, AsyncRead, FutureExt};
+use std::{
+    fmt, io,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// Read a CAR that is still being appended to, see
+/// [`tail`](super::ReadSingleFileOptions::tail).
+///
+/// The readers treat a read of 0 bytes from `car_input` as the end of the CAR. In tail mode such
+/// a read is retried every `poll_interval` instead, until no new byte arrived for `timeout`, and
+/// the read stops as soon as the whole file DAG is written without waiting for the end of the
+/// CAR. `car_input` must return 0 bytes, not `Pending`, while no new data is available, like a
+/// file being written to.
+///
+/// ```
+/// use rs_car_ipfs::single_file::{ReadSingleFileOptions, SleepHook, TailOptions};
+/// use std::time::Duration;
+///
+/// let options = ReadSingleFileOptions {
+///     tail: Some(TailOptions::new(
+///         Duration::from_secs(30),
+///         SleepHook::new(|duration| Box::pin(async_std::task::sleep(duration))),
+///     )),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct TailOptions {
+    /// Time without new data after which an EOF is the end of the CAR
+    pub timeout: Duration,
+    /// Time waited before reading again after an EOF
+    pub poll_interval: Duration,
+    pub sleep: SleepHook,
+}
+
+impl TailOptions {
+    /// Tail with `timeout`, polling every 100 ms
+    pub fn new(timeout: Duration, sleep: SleepHook) -> Self {
+        Self {
+            timeout,
+            poll_interval: Duration::from_millis(100),
+            sleep,
+        }
+    }
+}
+
+/// Timer of the async runtime used to wait between reads in tail mode, as the crate does not
+/// depend on one
+#[derive(Clone)]
+pub struct SleepHook(Arc<SleepFn>);
+
+type SleepFn = dyn Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync;
+
+impl SleepHook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Duration) -> BoxFuture<'static, ()> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        (self.0)(duration)
+    }
+
+    /// Sleep with `async_std::task::sleep`
+    #[cfg(feature = "async-std")]
+    pub fn async_std() -> Self {
+        Self::new(|duration| Box::pin(async_std::task::sleep(duration)))
+    }
+}
+
+impl fmt::Debug for SleepHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SleepHook")
+    }
+}
+
+/// `car_input` retrying reads at EOF as set by `tail`, or passed through without
+pub(crate) struct TailInput<R> {
+    inner: R,
+    tail: Option<TailOptions>,
+    last_data: Instant,
+    sleeping: Option<BoxFuture<'static, ()>>,
+}
+
+impl<R> TailInput<R> {
+    pub(crate) fn new(inner: R, tail: Option<TailOptions>) -> Self {
+        Self {
+            inner,
+            tail,
+            last_data: Instant::now(),
+            sleeping: None,
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for TailInput<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let me = self.get_mut();
+        loop {
+            if let Some(sleeping) = me.sleeping.as_mut() {
+                ready!(sleeping.poll_unpin(cx));
+                me.sleeping = None;
+            }
+
+            let n = ready!(Pin::new(&mut me.inner).poll_read(cx, buf))?;
+            let Some(tail) = me.tail.as_ref().filter(|_| n == 0 && !buf.is_empty()) else {
+                me.last_data = Instant::now();
+                return Poll::Ready(Ok(n));
+            };
+            if me.last_data.elapsed() >= tail.timeout {
+                return Poll::Ready(Ok(0));
+            }
+            me.sleeping = Some(tail.sleep.call(tail.poll_interval));
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    fmt,
+    num::NonZeroU64,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use super::{ReadSingleFileOptions, SleepHook};
+
+/// Shortest time over which [`Throughput::bytes_per_sec`] is measured
+const THROUGHPUT_WINDOW: Duration = Duration::from_millis(100);
+
+/// Max rate of the bytes written to `out`, see
+/// [`rate_limit`](super::ReadSingleFileOptions::rate_limit).
+///
+/// Before writing more, the read sleeps until the bytes written so far are within
+/// `max_bytes_per_sec` of the time elapsed since it started. Writes are paced between blocks, so
+/// a block of the CAR is written at once whatever its length. Dropping the read future drops the
+/// pending sleep, cancelling the read as usual.
+///
+/// ```
+/// use rs_car_ipfs::single_file::{RateLimit, ReadSingleFileOptions, SleepHook};
+/// use std::num::NonZeroU64;
+///
+/// let options = ReadSingleFileOptions {
+///     rate_limit: Some(RateLimit::new(
+///         NonZeroU64::new(10 * 1024 * 1024).unwrap(),
+///         SleepHook::new(|duration| Box::pin(async_std::task::sleep(duration))),
+///     )),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub max_bytes_per_sec: NonZeroU64,
+    pub sleep: SleepHook,
+}
+
+impl RateLimit {
+    pub fn new(max_bytes_per_sec: NonZeroU64, sleep: SleepHook) -> Self {
+        Self {
+            max_bytes_per_sec,
+            sleep,
+        }
+    }
+}
+
+/// Throughput of the writes to `out`, see
+/// [`on_throughput`](super::ReadSingleFileOptions::on_throughput)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Throughput {
+    /// Bytes physically written to `out` so far, without the holes of runs of zeros
+    pub bytes_written: u64,
+    /// Time since the read started
+    pub elapsed: Duration,
+    /// Rate of the bytes written since the previous report
+    pub bytes_per_sec: f64,
+}
+
+/// Callback called with the [`Throughput`] of a read, see
+/// [`on_throughput`](super::ReadSingleFileOptions::on_throughput).
+///
+/// ```
+/// use rs_car_ipfs::single_file::{ReadSingleFileOptions, ThroughputHook};
+///
+/// let options = ReadSingleFileOptions {
+///     on_throughput: Some(ThroughputHook::new(|throughput| {
+///         eprintln!("{:.0} bytes/s", throughput.bytes_per_sec);
+///     })),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone)]
+pub struct ThroughputHook(Arc<ThroughputFn>);
+
+type ThroughputFn = dyn Fn(&Throughput) + Send + Sync;
+
+impl ThroughputHook {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&Throughput) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, throughput: &Throughput) {
+        (self.0)(throughput)
+    }
+}
+
+impl fmt::Debug for ThroughputHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ThroughputHook")
+    }
+}
+
+/// Pacing and throughput reports of the writes of a read, as set by its options
+pub(crate) struct Pacer<'a> {
+    rate_limit: Option<&'a RateLimit>,
+    on_throughput: Option<&'a ThroughputHook>,
+    start: Instant,
+    /// Time and bytes written of the previous report
+    reported: (Instant, u64),
+}
+
+impl<'a> Pacer<'a> {
+    pub(crate) fn new(options: &'a ReadSingleFileOptions) -> Self {
+        let start = Instant::now();
+        Self {
+            rate_limit: options.rate_limit.as_ref(),
+            on_throughput: options.on_throughput.as_ref(),
+            start,
+            reported: (start, 0),
+        }
+    }
+
+    /// Called with the bytes physically written so far before writing more, sleeping as long as
+    /// they are ahead of the rate limit
+    pub(crate) async fn pace(&mut self, written: u64) {
+        self.report(written, false);
+        let Some(rate_limit) = self.rate_limit else {
+            return;
+        };
+        let due =
+            Duration::from_secs_f64(written as f64 / rate_limit.max_bytes_per_sec.get() as f64);
+        let elapsed = self.start.elapsed();
+        if due > elapsed {
+            rate_limit.sleep.call(due - elapsed).await;
+        }
+    }
+
+    /// Called once the file is written, with the bytes physically written
+    pub(crate) fn finish(&mut self, written: u64) {
+        self.report(written, true);
+    }
+
+    fn report(&mut self, written: u64, last: bool) {
+        let Some(hook) = self.on_throughput else {
+            return;
+        };
+        let now = Instant::now();
+        let (reported_at, reported_bytes) = self.reported;
+        let window = now - reported_at;
+        if window < THROUGHPUT_WINDOW && !last {
+            return;
+        }
+        let bytes_per_sec = match window.as_secs_f64() {
+            secs if secs > 0.0 => written.saturating_sub(reported_bytes) as f64 / secs,
+            _ => 0.0,
+        };
+        hook.call(&Throughput {
+            bytes_written: written,
+            elapsed: now - self.start,
+            bytes_per_sec,
+        });
+        self.reported = (now, written);
+    }
+}
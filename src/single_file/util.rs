@@ -1,28 +1,74 @@
-use rs_car::{CarHeader, Cid};
+use futures::{AsyncWrite, AsyncWriteExt};
+use multihash::{Code, MultihashDigest};
+use rs_car::{CarDecodeError, CarHeader, Cid};
+use std::{borrow::Cow, collections::HashMap, ops::Range};
 
-use crate::pb::PBLink;
+use crate::{
+    cid_version::block_key,
+    layout::FileLayout,
+    pb::{FlatUnixFs, PBLink, UnixFs, UnixFsType},
+};
 
 use super::ReadSingleFileError;
 
+/// The root of the read, `root_cid` if provided whatever the roots of `header`, which may be empty
+/// for CARs written without roots, or any block of the CAR such as a file nested in the directory
+/// of the header. Otherwise the single root of `header`
 pub fn assert_header_single_file(
     header: &CarHeader,
     root_cid: Option<&Cid>,
 ) -> Result<Cid, ReadSingleFileError> {
+    let root = single_root(&header.roots, root_cid)?;
+    log_event!(
+        debug,
+        "CAR {:?} header with roots {:?}, reading root {}",
+        header.version,
+        header.roots,
+        root
+    );
+    Ok(root)
+}
+
+/// `root_cid`, or the single root of `roots` of a CAR header if None
+pub fn single_root(roots: &[Cid], root_cid: Option<&Cid>) -> Result<Cid, ReadSingleFileError> {
     Ok(match root_cid {
         Some(root_cid) => *root_cid,
         None => {
             // If not root CID is provided, assume header contains the single root_cid for this file
-            if header.roots.len() == 1 {
-                header.roots[0]
-            } else {
-                return Err(ReadSingleFileError::NotSingleRoot {
-                    roots: header.roots.clone(),
-                });
+            match roots {
+                [root] => *root,
+                [] => return Err(ReadSingleFileError::NoRootsInHeader),
+                _ => {
+                    return Err(ReadSingleFileError::NotSingleRoot {
+                        roots: roots.to_vec(),
+                    })
+                }
             }
         }
     })
 }
 
+/// Length of the file under the root node `inner`: its declared `filesize`, or else its inline
+/// data and the `blocksizes` of its children, None if it declares neither
+pub(crate) fn declared_file_size(inner: &FlatUnixFs<'_>, links: &[Cid]) -> Option<u64> {
+    let data_len = inner.data.Data.as_ref().map_or(0, |data| data.len() as u64);
+    match inner.data.filesize {
+        Some(filesize) => Some(filesize),
+        None if links.is_empty() => Some(data_len),
+        None if inner.data.blocksizes.len() == links.len() => {
+            // Declared sizes, possibly overflowing
+            Some(
+                inner
+                    .data
+                    .blocksizes
+                    .iter()
+                    .fold(data_len, |sum, size| sum.saturating_add(*size)),
+            )
+        }
+        None => None,
+    }
+}
+
 pub fn links_to_cids(links: &[PBLink<'_>]) -> Result<Vec<Cid>, ReadSingleFileError> {
     links
         .iter()
@@ -36,5 +82,206 @@ pub fn links_to_cids(links: &[PBLink<'_>]) -> Result<Vec<Cid>, ReadSingleFileErr
 }
 
 fn hash_to_cid(hash: &[u8]) -> Result<Cid, ReadSingleFileError> {
-    Cid::try_from(hash).map_err(|err| ReadSingleFileError::InvalidUnixFsHash(err.to_string()))
+    let cid = Cid::try_from(hash)
+        .map_err(|err| ReadSingleFileError::InvalidUnixFsHash(err.to_string()))?;
+    check_digest_len(&cid)?;
+    Ok(cid)
+}
+
+/// Reject CIDs with a digest that can not have been produced by their hash function, which would
+/// otherwise only fail as a missing block. Identity and unsupported hash functions are not checked.
+fn check_digest_len(cid: &Cid) -> Result<(), ReadSingleFileError> {
+    let code = cid.hash().code();
+    if code == CODE_IDENTITY {
+        return Ok(());
+    }
+    let Ok(hasher) = Code::try_from(code) else {
+        return Ok(());
+    };
+    let expected = hasher.digest(&[]).size() as usize;
+    if cid.hash().size() as usize != expected {
+        return Err(ReadSingleFileError::InvalidLinkDigestLength {
+            cid: *cid,
+            expected,
+        });
+    }
+    Ok(())
+}
+
+const CODE_IDENTITY: u64 = 0x00;
+
+/// Block of an identity CID, carried inline by the CID instead of in the CAR, e.g. a tiny file
+/// linked from a directory
+pub fn inline_block(cid: &Cid) -> Option<&[u8]> {
+    (cid.hash().code() == CODE_IDENTITY).then(|| cid.hash().digest())
+}
+pub(crate) const CODEC_DAG_PB: u64 = 0x70;
+pub(crate) const CODEC_RAW: u64 = 0x55;
+
+/// Source of zeros to fill regions of skipped blocks
+pub static ZEROS: [u8; 65536] = [0; 65536];
+
+/// Verify that `block` hashes to the digest of `cid`. Used on blocks that are not read through
+/// `CarReader`, which already validates the blocks it streams.
+pub fn assert_block_cid(cid: &Cid, block: &[u8]) -> Result<(), ReadSingleFileError> {
+    let code = cid.hash().code();
+    let matches = if code == CODE_IDENTITY {
+        cid.hash().digest() == block
+    } else {
+        let code = Code::try_from(code).map_err(|err| {
+            ReadSingleFileError::CarDecodeError(CarDecodeError::InvalidMultihash(err.to_string()))
+        })?;
+        code.digest(block).digest() == cid.hash().digest()
+    };
+
+    if matches {
+        Ok(())
+    } else {
+        Err(ReadSingleFileError::CarDecodeError(
+            CarDecodeError::BlockDigestMismatch(format!("digest mismatch cid {:?}", cid)),
+        ))
+    }
+}
+
+/// Decode a block as a UnixFS node and the CIDs of its links. Verifies the block hash first if
+/// `validate_hash`, for readers that do not let `CarReader` validate blocks. The errors returned
+/// are the block errors that can be skipped with `on_block_error`.
+///
+/// Blocks with the raw codec are returned as a UnixFS `Raw` leaf with the whole block as data.
+/// Other codecs than dag-pb and raw can not be part of a UnixFS file DAG.
+pub fn decode_block<'a>(
+    cid: &Cid,
+    block: &'a [u8],
+    validate_hash: bool,
+) -> Result<(FlatUnixFs<'a>, Vec<Cid>), ReadSingleFileError> {
+    if validate_hash {
+        assert_block_cid(cid, block)?;
+    }
+
+    let inner = match cid.codec() {
+        CODEC_DAG_PB => FlatUnixFs::try_from(block)
+            .map_err(|err| ReadSingleFileError::InvalidUnixFs(err.to_string()))?,
+        CODEC_RAW => FlatUnixFs {
+            links: vec![],
+            data: UnixFs {
+                Type: UnixFsType::Raw,
+                Data: Some(Cow::Borrowed(block)),
+                filesize: Some(block.len() as u64),
+                ..Default::default()
+            },
+        },
+        codec => return Err(ReadSingleFileError::UnsupportedCodec(codec)),
+    };
+    let links = links_to_cids(&inner.links)?;
+    log_event!(
+        trace,
+        "decoded {:?} block {} with {} links",
+        inner.data.Type,
+        cid,
+        links.len()
+    );
+
+    Ok((inner, links))
+}
+
+/// Fail if the leaf `cid` of `len` bytes has another length in the `blocksizes` of its parent,
+/// collected in `sizes` by [`block_key`], which would shift the rest of the file
+pub fn check_blocksize(
+    sizes: &HashMap<Cid, u64>,
+    cid: &Cid,
+    len: u64,
+) -> Result<(), ReadSingleFileError> {
+    match sizes.get(&block_key(cid)) {
+        Some(declared) if *declared != len => Err(ReadSingleFileError::BlocksizeMismatch {
+            cid: *cid,
+            declared: *declared,
+            actual: len,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Fail if the links node `cid`, the next node of `layout`, is being expanded already
+pub fn check_cycle(layout: &FileLayout, cid: &Cid) -> Result<(), ReadSingleFileError> {
+    if layout.is_ancestor(cid) {
+        return Err(ReadSingleFileError::CycleDetected(*cid));
+    }
+    Ok(())
+}
+
+/// Error of a read that reached the end of the CAR without seeing the block of `root`. An
+/// identity CID carries its block inline instead of in the CAR, so it is only a missing node.
+pub fn root_not_found(root: Cid, blocks_seen: usize, header_roots: &[Cid]) -> ReadSingleFileError {
+    if root.hash().code() == CODE_IDENTITY {
+        return ReadSingleFileError::MissingNode(root);
+    }
+    ReadSingleFileError::RootCidNotFound {
+        root,
+        blocks_seen,
+        header_roots: header_roots.into(),
+    }
+}
+
+/// Whether a node can be the root of a file DAG: a UnixFS `File`, a UnixFS `Raw` node accepted
+/// as a leaf only, or a single raw block as added with raw leaves
+pub fn is_file_root(cid: &Cid, unixfs_type: UnixFsType) -> bool {
+    matches!(unixfs_type, UnixFsType::File | UnixFsType::Raw) || cid.codec() == CODEC_RAW
+}
+
+/// Data of a leaf node. Empty files are a leaf without `Data` and a `filesize` of 0, as encoded by
+/// kubo
+pub fn leaf_data<'a>(
+    block: &'a [u8],
+    data: Option<Cow<'a, [u8]>>,
+    filesize: Option<u64>,
+) -> Result<Cow<'a, [u8]>, ReadSingleFileError> {
+    match (data, filesize) {
+        (Some(data), _) => Ok(data),
+        (None, Some(0)) => Ok(Cow::Borrowed(&block[..0])),
+        (None, _) => Err(ReadSingleFileError::InvalidUnixFs(
+            "unixfs data node has not Data field".to_string(),
+        )),
+    }
+}
+
+/// Range of `data` within `block`, which it was decoded from
+pub fn data_range(block: &[u8], data: &[u8]) -> Result<Range<usize>, ReadSingleFileError> {
+    let start = (data.as_ptr() as usize).wrapping_sub(block.as_ptr() as usize);
+    if start > block.len() || data.len() > block.len() - start {
+        return Err(ReadSingleFileError::InternalError(
+            "node data is not a slice of its block".to_string(),
+        ));
+    }
+    Ok(start..start + data.len())
+}
+
+/// Flushes a writer once at least `every` bytes were written to it since the last flush
+pub struct PeriodicFlush {
+    every: Option<usize>,
+    unflushed: usize,
+}
+
+impl PeriodicFlush {
+    pub fn new(every: Option<usize>) -> Self {
+        Self {
+            every,
+            unflushed: 0,
+        }
+    }
+
+    /// Record `len` bytes written to `out`, flushing it if due
+    pub async fn wrote<W: AsyncWrite + Unpin>(
+        &mut self,
+        out: &mut W,
+        len: usize,
+    ) -> Result<(), ReadSingleFileError> {
+        if let Some(every) = self.every {
+            self.unflushed += len;
+            if self.unflushed >= every {
+                out.flush().await?;
+                self.unflushed = 0;
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,143 @@
+use futures::{channel::oneshot, Future, Stream};
+use rs_car::Cid;
+use std::{
+    collections::VecDeque,
+    io,
+    num::NonZeroUsize,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll},
+    thread,
+};
+
+use super::{util::assert_block_cid, ReadSingleFileError};
+
+type Validated = Result<(Cid, Vec<u8>), ReadSingleFileError>;
+
+struct Job {
+    cid: Cid,
+    block: Vec<u8>,
+    done: oneshot::Sender<Validated>,
+}
+
+/// Worker threads hashing blocks for a read, see
+/// [`validation_concurrency`](super::ReadSingleFileOptions::validation_concurrency). The workers
+/// exit once the pool and the streams using it are dropped.
+pub(crate) struct ValidationPool {
+    jobs: mpsc::Sender<Job>,
+    in_flight: usize,
+}
+
+impl ValidationPool {
+    pub(crate) fn new(threads: NonZeroUsize) -> io::Result<Self> {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..threads.get() {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("car-validate-{}", i))
+                .spawn(move || loop {
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    let Ok(job) = job else {
+                        return;
+                    };
+                    let result =
+                        assert_block_cid(&job.cid, &job.block).map(|_| (job.cid, job.block));
+                    // The read may have been dropped in the meantime
+                    let _ = job.done.send(result);
+                })?;
+        }
+        Ok(Self {
+            jobs,
+            in_flight: 2 * threads.get(),
+        })
+    }
+
+    /// `blocks` validated on the workers if there is a pool, yielded in their order in `blocks`
+    pub(crate) fn validate<S>(pool: Option<&Self>, blocks: S) -> ValidatedBlocks<S> {
+        ValidatedBlocks {
+            inner: blocks,
+            ended: false,
+            jobs: pool.map(|pool| (pool.jobs.clone(), pool.in_flight)),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// Blocks of a stream read ahead while the blocks before them are hashed, or passed through
+/// without a pool, see [`ValidationPool::validate`]. Blocks are only read ahead as long as the
+/// inner stream has them ready, an error of the inner stream is yielded after the blocks before
+/// it.
+pub(crate) struct ValidatedBlocks<S> {
+    inner: S,
+    /// Set once the inner stream ended or failed, nothing is read past an error
+    ended: bool,
+    /// Jobs of the pool, with the max count of blocks in flight
+    jobs: Option<(mpsc::Sender<Job>, usize)>,
+    pending: VecDeque<oneshot::Receiver<Validated>>,
+}
+
+impl<S> ValidatedBlocks<S> {
+    pub(crate) fn get_ref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S, E> Stream for ValidatedBlocks<S>
+where
+    S: Stream<Item = Result<(Cid, Vec<u8>), E>> + Unpin,
+    E: Into<ReadSingleFileError>,
+{
+    type Item = Validated;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let me = &mut *self;
+        let Some((jobs, in_flight)) = &me.jobs else {
+            return Pin::new(&mut me.inner)
+                .poll_next(cx)
+                .map(|item| item.map(|item| item.map_err(Into::into)));
+        };
+        while me.pending.len() < *in_flight && !me.ended {
+            let (done, receiver) = oneshot::channel();
+            match Pin::new(&mut me.inner).poll_next(cx) {
+                Poll::Pending => break,
+                Poll::Ready(None) => {
+                    me.ended = true;
+                    break;
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    let _ = done.send(Err(err.into()));
+                    me.ended = true;
+                }
+                Poll::Ready(Some(Ok((cid, block)))) => {
+                    if let Err(mpsc::SendError(job)) = jobs.send(Job { cid, block, done }) {
+                        let _ =
+                            job.done.send(Err(
+                                io::Error::other("block validation workers exited").into()
+                            ));
+                    }
+                }
+            }
+            me.pending.push_back(receiver);
+        }
+
+        let Some(next) = me.pending.front_mut() else {
+            return match me.ended {
+                false => Poll::Pending,
+                true => Poll::Ready(None),
+            };
+        };
+        match Pin::new(next).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(result) => {
+                me.pending.pop_front();
+                Poll::Ready(Some(result.unwrap_or_else(|_| {
+                    Err(io::Error::other("block validation worker panicked").into())
+                })))
+            }
+        }
+    }
+}
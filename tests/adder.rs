@@ -0,0 +1,145 @@
+use futures::io::Cursor;
+use rs_car_ipfs::{
+    adder::{
+        compute_file_cid, compute_file_cid_with_options, verify_reconstructs_root, ChunkerProfile,
+        PackOptions,
+    },
+    import::write_file_car,
+    single_file::{
+        read_single_file_buffer_with_options, read_single_file_seek_with_options,
+        ReadSingleFileError, ReadSingleFileOptions,
+    },
+    Cid,
+};
+use std::fs;
+
+const TEST_DATA_DIR: &str = "tests/data";
+
+/// Root CIDs of the fixtures created with kubo's default chunker
+fn default_chunker_fixtures() -> Vec<(Vec<u8>, String, Cid)> {
+    fs::read_dir(TEST_DATA_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter_map(|path| {
+            let filename = path.file_name().unwrap().to_str().unwrap().to_string();
+            let source = filename.strip_suffix(".size-262144.normal.car")?;
+            let source = fs::read(format!("{}/{}", TEST_DATA_DIR, source)).unwrap();
+            let car = fs::read(&path).unwrap();
+            Some((source, filename, root_cid(&car)))
+        })
+        .collect()
+}
+
+fn root_cid(car: &[u8]) -> Cid {
+    futures::executor::block_on(async {
+        rs_car::CarReader::new(&mut Cursor::new(car), false)
+            .await
+            .unwrap()
+            .header
+            .roots[0]
+    })
+}
+
+#[async_std::test]
+async fn verify_reconstructs_root_default_chunker() {
+    let fixtures = default_chunker_fixtures();
+    assert!(!fixtures.is_empty());
+
+    for (source, filename, root_cid) in fixtures {
+        let ok = verify_reconstructs_root(
+            &mut Cursor::new(&source),
+            &root_cid,
+            ChunkerProfile::KuboDefaultV0,
+        )
+        .await
+        .unwrap();
+        assert!(ok, "not canonical {}", filename);
+
+        // Any change to the content must produce a different root
+        let mut modified = source.clone();
+        match modified.first_mut() {
+            Some(byte) => *byte ^= 1,
+            None => modified.push(0),
+        }
+        let ok = verify_reconstructs_root(
+            &mut Cursor::new(&modified),
+            &root_cid,
+            ChunkerProfile::KuboDefaultV0,
+        )
+        .await
+        .unwrap();
+        assert!(!ok, "modified content reconstructs root {}", filename);
+    }
+}
+
+#[async_std::test]
+async fn compute_file_cid_fixtures() {
+    let mut count = 0;
+    for entry in fs::read_dir(TEST_DATA_DIR).unwrap() {
+        let path = entry.unwrap().path();
+        let filename = path.file_name().unwrap().to_str().unwrap().to_string();
+        // Created with `ipfs add --chunker=size-<n>`
+        let Some((source, chunk_size)) = filename
+            .strip_suffix(".normal.car")
+            .and_then(|name| name.rsplit_once(".size-"))
+        else {
+            continue;
+        };
+        let source = fs::read(format!("{}/{}", TEST_DATA_DIR, source)).unwrap();
+        let options = PackOptions {
+            chunk_size: chunk_size.parse().unwrap(),
+            ..Default::default()
+        };
+        let (cid, size) = compute_file_cid_with_options(&mut Cursor::new(&source), &options)
+            .await
+            .unwrap();
+        assert_eq!(cid, root_cid(&fs::read(&path).unwrap()), "{}", filename);
+        assert_eq!(size, source.len() as u64, "{}", filename);
+        count += 1;
+    }
+    assert!(count > 0);
+
+    // Same root as the packer
+    let path = format!("{}/rand_100K.bin", TEST_DATA_DIR);
+    for profile in [ChunkerProfile::KuboDefaultV0, ChunkerProfile::KuboDefaultV1] {
+        let source = fs::read(&path).unwrap();
+        let (cid, size) = compute_file_cid(&mut Cursor::new(&source), profile)
+            .await
+            .unwrap();
+        let packed = write_file_car(&path, &mut Vec::new(), &profile.into()).unwrap();
+        assert_eq!((cid, size), (packed, source.len() as u64), "{:?}", profile);
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_verify_canonical() {
+    let options = ReadSingleFileOptions {
+        verify_canonical: Some(ChunkerProfile::KuboDefaultV0),
+        ..Default::default()
+    };
+
+    // Chunked with the default chunker, canonical
+    let car = fs::read("tests/data/seq_1000.txt.size-262144.normal.car").unwrap();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+
+    // Chunked with size-32, valid but not canonical for the default chunker
+    let car = fs::read("tests/data/seq_1000.txt.size-32.normal.car").unwrap();
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options).await {
+        Err(ReadSingleFileError::NotCanonical { computed }) => assert_ne!(computed, root_cid(&car)),
+        x => panic!("other result {:?}", x),
+    }
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options).await
+    {
+        Err(ReadSingleFileError::NotCanonical { computed }) => assert_ne!(computed, root_cid(&car)),
+        x => panic!("other result {:?}", x),
+    }
+}
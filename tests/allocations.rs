@@ -0,0 +1,94 @@
+//! Counts heap allocations of reads, in its own test binary to own the global allocator
+
+mod common;
+
+use common::{car_v1, cid_v1, generate::pseudo_random, PbNode, RAW};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{read_single_file_buffer, read_single_file_seek};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fs,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+/// Allocations of at least [`LARGE`] bytes
+static LARGE_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+const LARGE: usize = 1 << 20;
+
+/// Tests count the allocations of all threads, so they must not run concurrently
+static SERIAL: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        if layout.size() >= LARGE {
+            LARGE_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[test]
+fn read_single_file_buffer_allocations() {
+    let _serial = SERIAL.lock().unwrap();
+    // 3200 unique leaves of 32 bytes
+    let car = fs::read("tests/data/rand_100K.bin.size-32.normal.car").unwrap();
+    let leaves = fs::read("tests/data/rand_100K.bin").unwrap().len() / 32;
+
+    let mut out = Cursor::new(Vec::with_capacity(leaves));
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    futures::executor::block_on(read_single_file_buffer(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        None,
+    ))
+    .unwrap();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    println!("{} allocations for {} leaves", allocations, leaves);
+    // Reading the CAR stream costs ~5 allocations per block, copying leaf data out of their
+    // blocks would add one more per leaf
+    assert!(allocations < leaves * 6, "{} allocations", allocations);
+}
+
+#[test]
+fn read_single_file_seek_replay_allocations() {
+    let _serial = SERIAL.lock().unwrap();
+    // A 2 MiB leaf, the largest block by default, linked 3 times, written once and replayed twice
+    // from `out`
+    let leaf = pseudo_random(2 * LARGE, 1);
+    let leaf_cid = cid_v1(RAW, &leaf);
+    let len = leaf.len() as u64;
+    let (root_cid, root) =
+        PbNode::file_branch(&[(leaf_cid, len), (leaf_cid, len), (leaf_cid, len)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (leaf_cid, leaf)]);
+
+    let mut out = Cursor::new(Vec::with_capacity(3 * len as usize));
+    let before = LARGE_ALLOCATIONS.load(Ordering::Relaxed);
+    futures::executor::block_on(read_single_file_seek(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        None,
+    ))
+    .unwrap();
+    let large_allocations = LARGE_ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+    // Only the block read from the CAR stream, replays are copied in small chunks
+    assert_eq!(large_allocations, 1);
+    assert_eq!(out.into_inner().len(), 3 * len as usize);
+}
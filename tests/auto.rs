@@ -0,0 +1,166 @@
+//! Backend selection of `read_single_file_auto` from the declared size of the file
+
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec, GeneratedCar, Order},
+};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_auto, ReadSingleFileError, ReadSingleFileOptions, ReadSummary, RootSelector,
+};
+use std::fs;
+
+async fn read_auto(
+    car: &[u8],
+    budget: usize,
+    options: &ReadSingleFileOptions,
+) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_auto(&mut &car[..], &mut out, None, budget, options).await?;
+    Ok((summary, out.into_inner()))
+}
+
+fn generated(order: Order) -> (GeneratedCar, Vec<u8>) {
+    let data = pseudo_random(2000, 15);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        fanout: 4,
+        order,
+        ..Default::default()
+    }
+    .build();
+    (generated, data)
+}
+
+#[async_std::test]
+async fn auto_fixtures() {
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let Some((file, _)) = name.split_once(".size-") else {
+            continue;
+        };
+        // Fixtures chunked by 1 byte have thousands of blocks
+        if name.contains(".size-1.") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+        let expected = fs::read(path.with_file_name(file)).unwrap();
+
+        // Whichever reader is chosen, the replayed CAR is read whole
+        for budget in [0, 100, expected.len(), 1 << 30] {
+            let (summary, out) = read_auto(&car, budget, &Default::default())
+                .await
+                .unwrap_or_else(|err| panic!("{:?} {}: {:?}", path, budget, err));
+            assert!(out == expected, "{:?} {}", path, budget);
+            assert_eq!(summary.car_bytes_read, car.len() as u64, "{:?}", path);
+        }
+    }
+}
+
+#[async_std::test]
+async fn auto_selects_by_declared_size() {
+    // Leaves before their parents are only read by the buffer reader
+    let (generated, data) = generated(Order::Random(4));
+    assert_eq!(
+        read_auto(&generated.car, 2000, &Default::default())
+            .await
+            .unwrap()
+            .1,
+        data
+    );
+    assert!(read_auto(&generated.car, 1999, &Default::default())
+        .await
+        .is_err());
+
+    // Runs of zeros are only written as holes by the seek reader
+    let data = [pseudo_random(300, 16), vec![0; 4000]].concat();
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    let (summary, out) = read_auto(&car, 4299, &Default::default()).await.unwrap();
+    assert!(out == data);
+    assert!(summary.is_sparse());
+    let (summary, out) = read_auto(&car, 4300, &Default::default()).await.unwrap();
+    assert!(out == data);
+    assert!(!summary.is_sparse());
+}
+
+#[async_std::test]
+async fn auto_root_last() {
+    // The size is not known before the root, the buffer reader is chosen if the CAR fits
+    let (generated, data) = generated(Order::ChildrenFirst);
+    let blocks_len: usize = generated.blocks.iter().map(|(_, block)| block.len()).sum();
+    assert_eq!(
+        read_auto(&generated.car, blocks_len, &Default::default())
+            .await
+            .unwrap()
+            .1,
+        data
+    );
+    assert!(
+        read_auto(&generated.car, blocks_len / 2, &Default::default())
+            .await
+            .is_err()
+    );
+
+    // Always the buffer reader, the root is only known once the CAR ends
+    let car = car_v1(&[], &generated.blocks);
+    let options = ReadSingleFileOptions {
+        root_selector: RootSelector::LastBlock,
+        ..Default::default()
+    };
+    assert_eq!(read_auto(&car, blocks_len, &options).await.unwrap().1, data);
+}
+
+#[async_std::test]
+async fn auto_caps_max_buffer() {
+    let (generated, _) = generated(Order::ChildrenFirst);
+    let car = car_v1(&[], &generated.blocks);
+    let blocks_len: usize = generated.blocks.iter().map(|(_, block)| block.len()).sum();
+
+    // The buffer reader is held to the budget, or to a smaller `max_buffer`
+    for (max_buffer, budget, limit) in [
+        (None, blocks_len / 2, blocks_len / 2),
+        (Some(blocks_len), blocks_len / 2, blocks_len / 2),
+        (Some(blocks_len / 4), blocks_len, blocks_len / 4),
+    ] {
+        let options = ReadSingleFileOptions {
+            root_selector: RootSelector::LastBlock,
+            max_buffer,
+            ..Default::default()
+        };
+        match read_auto(&car, budget, &options).await {
+            Err(ReadSingleFileError::MaxBufferedData(max_buffer)) => assert_eq!(max_buffer, limit),
+            x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+        }
+    }
+}
+
+#[async_std::test]
+async fn auto_errors() {
+    // Errors of the header are reported by the chosen reader
+    match read_auto(&car_v1(&[], &[]), 0, &Default::default()).await {
+        Err(ReadSingleFileError::NoRootsInHeader) => {}
+        x => panic!("other result {:?}", x),
+    }
+    let (generated, _) = generated(Order::Dfs);
+    let blocks = &generated.blocks[..generated.blocks.len() / 2];
+    match read_auto(
+        &car_v1(&[generated.root], blocks),
+        1 << 20,
+        &Default::default(),
+    )
+    .await
+    {
+        Err(ReadSingleFileError::MissingNode(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
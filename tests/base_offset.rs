@@ -0,0 +1,45 @@
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{read_single_file_seek_with_options, ReadSingleFileOptions};
+use std::fs;
+
+async fn read_at_offset(car: &[u8], out: Vec<u8>, base_offset: u64) -> Vec<u8> {
+    let options = ReadSingleFileOptions {
+        base_offset,
+        ..Default::default()
+    };
+    let mut out = Cursor::new(out);
+    read_single_file_seek_with_options(&mut Cursor::new(car), &mut out, None, &options)
+        .await
+        .unwrap();
+    out.into_inner()
+}
+
+#[async_std::test]
+async fn base_offset_preserves_surrounding_bytes() {
+    // Repeated leaves, reconstructed by copying from `out` itself
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bbbb").block();
+    let (root_cid, root) =
+        PbNode::file_branch(&[(a_cid, 4), (b_cid, 4), (a_cid, 4), (b_cid, 4)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    let out = read_at_offset(&car, vec![b'x'; 30], 10).await;
+    assert_eq!(
+        out,
+        [&[b'x'; 10][..], b"aaaabbbbaaaabbbb", &[b'x'; 4][..]].concat()
+    );
+}
+
+#[async_std::test]
+async fn base_offset_past_end() {
+    let car = fs::read("tests/data/seq_1000.txt.size-32.normal.car").unwrap();
+    let expected = fs::read("tests/data/seq_1000.txt").unwrap();
+
+    let out = read_at_offset(&car, b"header".to_vec(), 100).await;
+    assert_eq!(&out[..6], b"header");
+    assert_eq!(&out[6..100], &[0u8; 94][..]);
+    assert_eq!(&out[100..], &expected);
+}
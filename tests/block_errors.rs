@@ -0,0 +1,183 @@
+mod common;
+
+use common::{car_v1, cid_v0, PbNode};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, BlockErrorHook,
+    ErrorAction, ReadSingleFileError, ReadSingleFileOptions, SkipFill,
+};
+use std::sync::{Arc, Mutex};
+
+/// File "aaaa" + "bbbb" + "cccc" where the block of "bbbb" has `corrupt_block` applied. Returns
+/// the CAR and the CID of the middle block
+fn car_with_bad_block(corrupt_block: impl Fn(&mut [u8])) -> (Vec<u8>, Cid) {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, mut b) = PbNode::file_leaf(b"bbbb").block();
+    let (c_cid, c) = PbNode::file_leaf(b"cccc").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 4), (c_cid, 4)]).block();
+    corrupt_block(&mut b);
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (a_cid, a), (b_cid, b), (c_cid, c)],
+    );
+    (car, b_cid)
+}
+
+fn flip_last_byte(block: &mut [u8]) {
+    *block.last_mut().unwrap() ^= 0xff;
+}
+
+fn skip_all(calls: Arc<Mutex<Vec<Cid>>>) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new(move |cid, _| {
+            calls.lock().unwrap().push(*cid);
+            ErrorAction::Skip
+        })),
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn block_error_aborts_by_default() {
+    let (car, _) = car_with_bad_block(flip_last_byte);
+    let options = ReadSingleFileOptions::default();
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options).await;
+    assert!(matches!(res, Err(ReadSingleFileError::CarDecodeError(_))));
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options).await;
+    assert!(matches!(res, Err(ReadSingleFileError::CarDecodeError(_))));
+}
+
+#[async_std::test]
+async fn block_error_hook_abort() {
+    let (car, _) = car_with_bad_block(flip_last_byte);
+    let options = ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new(|_, _| ErrorAction::Abort)),
+        ..Default::default()
+    };
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options).await;
+    assert!(matches!(res, Err(ReadSingleFileError::CarDecodeError(_))));
+}
+
+#[async_std::test]
+async fn block_error_skip_zero_fill() {
+    // Digest mismatch and a block that hashes correctly but is not dag-pb
+    let bad_digest = car_with_bad_block(flip_last_byte);
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let garbage = vec![0xff; 8];
+    let garbage_cid = cid_v0(&garbage);
+    let (c_cid, c) = PbNode::file_leaf(b"cccc").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (garbage_cid, 4), (c_cid, 4)]).block();
+    let undecodable = (
+        car_v1(
+            &[root_cid],
+            &[
+                (root_cid, root),
+                (a_cid, a),
+                (garbage_cid, garbage),
+                (c_cid, c),
+            ],
+        ),
+        garbage_cid,
+    );
+
+    for (car, bad_cid) in [bad_digest, undecodable] {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let options = skip_all(calls.clone());
+
+        let mut out = Cursor::new(Vec::new());
+        let summary =
+            read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+                .await
+                .unwrap();
+        assert_eq!(out.into_inner(), b"aaaa\0\0\0\0cccc");
+        assert_eq!(summary.skipped_blocks, vec![bad_cid]);
+        assert_eq!(summary.bytes_written, 12);
+
+        let mut out = Cursor::new(Vec::new());
+        let summary =
+            read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+                .await
+                .unwrap();
+        assert_eq!(out.into_inner(), b"aaaa\0\0\0\0cccc");
+        assert_eq!(summary.skipped_blocks, vec![bad_cid]);
+
+        assert_eq!(*calls.lock().unwrap(), vec![bad_cid, bad_cid]);
+    }
+}
+
+#[async_std::test]
+async fn block_error_skip_unwritten() {
+    let (car, _) = car_with_bad_block(flip_last_byte);
+    let options = ReadSingleFileOptions {
+        skip_fill: SkipFill::Unwritten,
+        ..skip_all(Default::default())
+    };
+
+    // Region of the skipped block keeps the previous contents of `out`
+    let mut out = Cursor::new(b"xxxxxxxxxxxx".to_vec());
+    let summary = read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"aaaaxxxxcccc");
+    assert_eq!((summary.sparse_bytes, summary.holes_count), (4, 1));
+}
+
+#[async_std::test]
+async fn block_error_skip_unwritten_at_end() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, mut b) = PbNode::file_leaf(b"bbbb").block();
+    flip_last_byte(&mut b);
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 4)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+    let options = ReadSingleFileOptions {
+        skip_fill: SkipFill::Unwritten,
+        ..skip_all(Default::default())
+    };
+
+    // The last byte of a trailing skipped region is written, `out` has the length of the file
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"aaaa\0\0\0\0");
+    assert_eq!(summary.bytes_written, 8);
+    assert_eq!((summary.sparse_bytes, summary.holes_count), (3, 1));
+}
+
+#[async_std::test]
+async fn block_error_skip_unknown_size() {
+    // Parent without blocksizes, the length of the skipped region is unknown
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, mut b) = PbNode::file_leaf(b"bbbb").block();
+    flip_last_byte(&mut b);
+    let (root_cid, root) = PbNode {
+        blocksizes: vec![],
+        ..PbNode::file_branch(&[(a_cid, 4), (b_cid, 4)])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+    let options = skip_all(Default::default());
+
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options).await {
+        Err(ReadSingleFileError::SkippedBlockUnknownSize(cid)) => assert_eq!(cid, b_cid),
+        x => panic!("other result {:?}", x),
+    }
+
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options).await
+    {
+        Err(ReadSingleFileError::SkippedBlockUnknownSize(cid)) => assert_eq!(cid, b_cid),
+        x => panic!("other result {:?}", x),
+    }
+}
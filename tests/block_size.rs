@@ -0,0 +1,118 @@
+//! Blocks larger than `max_block_size`
+
+mod common;
+
+use common::{car_v1, cid_v1, generate::pseudo_random, write_varint, PbNode, RAW};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_with_options, CarIndex, ReadSingleFileError, ReadSingleFileOptions,
+    ReadSummary, DEFAULT_MAX_BLOCK_SIZE,
+};
+
+/// A file of one branch linking a raw leaf of `len` bytes, and the CID of the leaf
+fn car_with_leaf(len: usize) -> (Vec<u8>, Cid) {
+    let leaf = pseudo_random(len, 7);
+    let leaf_cid = cid_v1(RAW, &leaf);
+    let (root_cid, root) = PbNode::file_branch(&[(leaf_cid, len as u64)]).block();
+    (
+        car_v1(&[root_cid], &[(root_cid, root), (leaf_cid, leaf)]),
+        leaf_cid,
+    )
+}
+
+async fn read_both(
+    car: &[u8],
+    options: &ReadSingleFileOptions,
+) -> [Result<ReadSummary, ReadSingleFileError>; 2] {
+    let mut out = Cursor::new(Vec::new());
+    let buffer = read_single_file_buffer_with_options(&mut &car[..], &mut out, None, options).await;
+    let mut out = Cursor::new(Vec::new());
+    let seek = read_single_file_seek_with_options(&mut &car[..], &mut out, None, options).await;
+    [buffer, seek]
+}
+
+#[async_std::test]
+async fn block_too_large_default_limit() {
+    let (car, _) = car_with_leaf(DEFAULT_MAX_BLOCK_SIZE);
+    for res in read_both(&car, &Default::default()).await {
+        assert_eq!(res.unwrap().bytes_written, DEFAULT_MAX_BLOCK_SIZE as u64);
+    }
+
+    let (car, leaf_cid) = car_with_leaf(DEFAULT_MAX_BLOCK_SIZE + 1);
+    for res in read_both(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::BlockTooLarge { cid, size, limit }) => {
+                assert_eq!(cid, leaf_cid);
+                assert_eq!(size, DEFAULT_MAX_BLOCK_SIZE + 1);
+                assert_eq!(limit, DEFAULT_MAX_BLOCK_SIZE);
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+
+    // No limit
+    let options = ReadSingleFileOptions {
+        max_block_size: Some(usize::MAX),
+        ..Default::default()
+    };
+    for res in read_both(&car, &options).await {
+        assert_eq!(
+            res.unwrap().bytes_written,
+            DEFAULT_MAX_BLOCK_SIZE as u64 + 1
+        );
+    }
+}
+
+#[async_std::test]
+async fn block_too_large_custom_limit() {
+    let (car, leaf_cid) = car_with_leaf(1000);
+    let options = ReadSingleFileOptions {
+        max_block_size: Some(999),
+        ..Default::default()
+    };
+    for res in read_both(&car, &options).await {
+        assert!(matches!(
+            res,
+            Err(ReadSingleFileError::BlockTooLarge { cid, size: 1000, limit: 999 }) if cid == leaf_cid
+        ));
+    }
+}
+
+#[async_std::test]
+async fn block_too_large_declared_section() {
+    // The leaf section declares a block of about 1 GiB and ends after a few bytes
+    let leaf_cid = cid_v1(RAW, b"leaf");
+    let size = (1 << 30) - 100;
+    let (root_cid, root) = PbNode::file_branch(&[(leaf_cid, size as u64)]).block();
+    let mut car = car_v1(&[root_cid], &[(root_cid, root)]);
+    write_varint(&mut car, (leaf_cid.to_bytes().len() + size) as u64);
+    car.extend_from_slice(&leaf_cid.to_bytes());
+    car.extend_from_slice(&[0; 10]);
+
+    // Rejected from the section prefix, before the block is allocated
+    let is_too_large = |res: &Result<ReadSummary, ReadSingleFileError>| {
+        matches!(
+            res,
+            Err(ReadSingleFileError::BlockTooLarge { cid, size: s, limit })
+                if *cid == leaf_cid && *s == size && *limit == DEFAULT_MAX_BLOCK_SIZE
+        )
+    };
+    for res in read_both(&car, &Default::default()).await {
+        assert!(is_too_large(&res), "{:?}", res);
+    }
+
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        &Default::default(),
+    )
+    .await;
+    assert!(is_too_large(&res), "{:?}", res);
+}
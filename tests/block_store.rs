@@ -0,0 +1,121 @@
+//! Blocks served from a store instead of the CAR
+
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec},
+    RAW,
+};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, MemoryBlockStore,
+    ReadSingleFileError, ReadSingleFileOptions, ReadSummary, SharedBlockStore,
+};
+use std::sync::Arc;
+
+/// Results of the seek and buffer readers, which use the block store, each with a store of
+/// `stored` blocks, and the length of the store after the read
+async fn read_with_store(
+    car: &[u8],
+    stored: &[(Cid, Vec<u8>)],
+) -> Vec<(Result<(ReadSummary, Vec<u8>), ReadSingleFileError>, usize)> {
+    let mut results = vec![];
+    for seek in [true, false] {
+        let store = Arc::new(MemoryBlockStore::default());
+        for (cid, block) in stored {
+            store.insert(*cid, block.clone());
+        }
+        let options = ReadSingleFileOptions {
+            block_store: Some(SharedBlockStore::new(store.clone())),
+            ..Default::default()
+        };
+        let mut out = Cursor::new(Vec::new());
+        let res = if seek {
+            read_single_file_seek_with_options(&mut &car[..], &mut out, None, &options).await
+        } else {
+            read_single_file_buffer_with_options(&mut &car[..], &mut out, None, &options).await
+        };
+        results.push((res.map(|summary| (summary, out.into_inner())), store.len()));
+    }
+    results
+}
+
+#[async_std::test]
+async fn blocks_from_store() {
+    let data = pseudo_random(3000, 6);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        fanout: 4,
+        raw_leaves: true,
+        ..Default::default()
+    }
+    .build();
+    let (leaves, nodes): (Vec<_>, Vec<_>) = generated
+        .blocks
+        .iter()
+        .cloned()
+        .partition(|(cid, _)| cid.codec() == RAW);
+
+    // Every other leaf in the store, the CAR without them
+    let stored: Vec<_> = leaves.iter().step_by(2).cloned().collect();
+    let blocks: Vec<_> = generated
+        .blocks
+        .iter()
+        .filter(|block| !stored.contains(block))
+        .cloned()
+        .collect();
+    let car = car_v1(&[generated.root], &blocks);
+    for (res, store_len) in read_with_store(&car, &stored).await {
+        let (summary, out) = res.unwrap();
+        assert!(out == data);
+        assert_eq!(summary.blocks_read, blocks.len());
+        // Blocks read from the CAR were put into the store
+        assert_eq!(store_len, generated.blocks.len());
+    }
+
+    // Without the store the leaves are missing
+    for (res, _) in read_with_store(&car, &[]).await {
+        match res {
+            Err(ReadSingleFileError::PendingLinksAtEOF(_))
+            | Err(ReadSingleFileError::DataNodesNotSorted)
+            | Err(ReadSingleFileError::MissingNode(_)) => {}
+            x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+        }
+    }
+
+    // The next snapshot only has the nodes with links, the store the rest
+    let car = car_v1(&[generated.root], &nodes);
+    for (res, _) in read_with_store(&car, &leaves).await {
+        let (summary, out) = res.unwrap();
+        assert!(out == data);
+        assert_eq!(summary.blocks_read, nodes.len());
+    }
+}
+
+#[async_std::test]
+async fn invalid_block_from_store() {
+    let data = pseudo_random(1000, 7);
+    let generated = CarSpec {
+        data,
+        chunk_size: 100,
+        raw_leaves: true,
+        ..Default::default()
+    }
+    .build();
+    let (leaf_cid, _) = generated.blocks[1];
+    assert_eq!(leaf_cid.codec(), RAW);
+
+    // Blocks of the store are validated against their CID
+    let stored = [(leaf_cid, b"not the leaf".to_vec())];
+    for (res, _) in read_with_store(&generated.car, &stored).await {
+        match res {
+            Err(ReadSingleFileError::CarDecodeError(
+                rs_car::CarDecodeError::BlockDigestMismatch(_),
+            )) => {}
+            x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use rs_car_ipfs::{
+    blocking::{read_single_file_buffer, read_single_file_seek},
+    single_file::{ReadSingleFileError, ReadSummary},
+};
+use std::{fs, io::Cursor};
+
+const CAR_FILEPATH: &str = "tests/data/rand_100K.bin.size-512.normal.car";
+const EXPECTED_FILEPATH: &str = "tests/data/rand_100K.bin";
+
+fn read_both() -> Result<ReadSummary, ReadSingleFileError> {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek(&mut car.as_slice(), &mut out, None, &Default::default())?;
+    assert_eq!(out.into_inner(), expected);
+
+    let mut out = vec![];
+    let summary =
+        read_single_file_buffer(&mut car.as_slice(), &mut out, None, &Default::default())?;
+    assert_eq!(out, expected);
+    Ok(summary)
+}
+
+fn assert_blocking_in_async_context(res: Result<ReadSummary, ReadSingleFileError>) {
+    match res {
+        Err(ReadSingleFileError::BlockingInAsyncContext) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[test]
+fn blocking_plain_thread() {
+    read_both().unwrap();
+    std::thread::spawn(read_both).join().unwrap().unwrap();
+}
+
+#[test]
+fn blocking_in_futures_executor() {
+    assert_blocking_in_async_context(futures::executor::block_on(async { read_both() }));
+}
+
+#[cfg(feature = "async-std")]
+#[test]
+fn blocking_in_async_std() {
+    assert_blocking_in_async_context(async_std::task::block_on(async { read_both() }));
+
+    async_std::task::block_on(async_std::task::spawn_blocking(read_both)).unwrap();
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn blocking_in_tokio() {
+    let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+    runtime.block_on(async { read_both() }).unwrap();
+    runtime
+        .block_on(runtime.spawn(async { read_both() }))
+        .unwrap()
+        .unwrap();
+    runtime
+        .block_on(runtime.spawn_blocking(read_both))
+        .unwrap()
+        .unwrap();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    assert_blocking_in_async_context(runtime.block_on(async { read_both() }));
+}
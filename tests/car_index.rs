@@ -0,0 +1,213 @@
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_indexed, read_single_file_indexed_with_options, CarIndex, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+use std::{fs, path::PathBuf};
+
+const TEST_DATA_DIR: &str = "tests/data";
+
+#[async_std::test]
+async fn read_single_file_indexed_test_data() {
+    for (car_filepath, expected_filepath) in car_fixtures() {
+        let expected_data = fs::read(&expected_filepath).unwrap();
+        let mut car_input = Cursor::new(fs::read(&car_filepath).unwrap());
+
+        let index = CarIndex::build(&mut car_input).await.unwrap();
+        assert!(!index.is_empty(), "empty index {}", car_filepath.display());
+
+        let mut out = Cursor::new(Vec::new());
+        read_single_file_indexed(&mut car_input, &mut out, None, Some(&index))
+            .await
+            .unwrap_or_else(|err| panic!("error on {}: {:?}", car_filepath.display(), err));
+
+        assert_eq!(
+            hex::encode(out.get_ref()),
+            hex::encode(&expected_data),
+            "Different out data {}",
+            car_filepath.display()
+        );
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_indexed_no_index_fallback() {
+    let mut car_input = async_std::fs::File::open("tests/data/seq_1000.txt.size-32.trickle.car")
+        .await
+        .unwrap();
+    let mut out = Cursor::new(Vec::new());
+
+    read_single_file_indexed(&mut car_input, &mut out, None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(out.get_ref(), &fs::read("tests/data/seq_1000.txt").unwrap());
+}
+
+#[async_std::test]
+async fn read_single_file_indexed_missing_block() {
+    let mut car_input = async_std::fs::File::open("tests/data/seq_1000.txt.size-32.normal.car")
+        .await
+        .unwrap();
+    let full_index = CarIndex::build(&mut car_input).await.unwrap();
+
+    // Index with only the root block, first child lookup must fail
+    let mut car_input = async_std::fs::File::open("tests/data/seq_1000.txt.size-32.normal.car")
+        .await
+        .unwrap();
+    let root_cid = rs_car::CarReader::new(&mut car_input, false)
+        .await
+        .unwrap()
+        .header
+        .roots[0];
+    let mut index = CarIndex::new();
+    index.insert(root_cid, full_index.get(&root_cid).unwrap());
+
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_indexed(&mut car_input, &mut out, None, Some(&index)).await {
+        Err(rs_car_ipfs::single_file::ReadSingleFileError::MissingNode(cid)) => {
+            assert_ne!(cid, root_cid)
+        }
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_indexed_blocks_read() {
+    // Every block of the CAR is part of the file
+    let mut car_input =
+        Cursor::new(fs::read("tests/data/seq_1000.txt.size-32.normal.car").unwrap());
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_indexed(&mut car_input, &mut out, None, Some(&index))
+        .await
+        .unwrap();
+    assert_eq!(summary.blocks_read, index.len());
+
+    // The root linking the same leaf 20 times
+    let mut car_input =
+        Cursor::new(fs::read("tests/data/zero_10K.bin.size-512.normal.car").unwrap());
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_indexed(&mut car_input, &mut out, None, Some(&index))
+        .await
+        .unwrap();
+    assert_eq!((index.len(), summary.blocks_read), (2, 21));
+}
+
+fn car_fixtures() -> Vec<(PathBuf, PathBuf)> {
+    fs::read_dir(TEST_DATA_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map(|ext| ext == "car") == Some(true))
+        .map(|path| {
+            // tests/data/seq_1000.txt.size-32.normal.car -> tests/data/seq_1000.txt
+            let filename = path.file_name().unwrap().to_str().unwrap();
+            let source = filename.split(".size-").next().unwrap();
+            let source = PathBuf::from(TEST_DATA_DIR).join(source);
+            (path, source)
+        })
+        .collect()
+}
+
+#[async_std::test]
+async fn read_single_file_indexed_summary_root_cid() {
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let root = rs_car::CarReader::new(&mut car.as_slice(), false)
+        .await
+        .unwrap()
+        .header
+        .roots[0];
+
+    let mut car_input = Cursor::new(&car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_indexed(&mut car_input, &mut out, None, Some(&index))
+        .await
+        .unwrap();
+    assert_eq!(summary.root_cid, Some(root));
+}
+
+#[async_std::test]
+async fn read_single_file_indexed_limits() {
+    // A leaf linked 1000 times by a root that does not declare its size
+    let (leaf_cid, leaf) = PbNode::file_leaf(b"aaaa").block();
+    let (root_cid, root) = PbNode {
+        filesize: None,
+        ..PbNode::file_branch(&[(leaf_cid, 4); 1000])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (leaf_cid, leaf)]);
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+
+    // Each read of the de-duplicated leaf counts
+    let options = ReadSingleFileOptions {
+        max_blocks: Some(100),
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        &options,
+    )
+    .await
+    {
+        Err(ReadSingleFileError::MaxBlocksExceeded { limit, read }) => {
+            assert_eq!((limit, read), (100, 101))
+        }
+        x => panic!("other result {:?}", x),
+    }
+
+    let options = ReadSingleFileOptions {
+        write_limit: Some(1000),
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        &options,
+    )
+    .await
+    {
+        Err(ReadSingleFileError::WriteLimitExceeded {
+            limit, attempted, ..
+        }) => assert_eq!((limit, attempted), (1000, 1004)),
+        x => panic!("other result {:?}", x),
+    }
+    assert_eq!(out.into_inner().len(), 1000);
+
+    // A declared size over the limit fails before writing anything
+    let mut car_input =
+        Cursor::new(fs::read("tests/data/zero_10K.bin.size-512.normal.car").unwrap());
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let options = ReadSingleFileOptions {
+        write_limit: Some(10239),
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        &options,
+    )
+    .await
+    {
+        Err(ReadSingleFileError::DeclaredSizeExceedsLimit { declared, limit }) => {
+            assert_eq!((declared, limit), (10240, 10239))
+        }
+        x => panic!("other result {:?}", x),
+    }
+    assert!(out.into_inner().is_empty());
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use common::{car_v1, cid_v0, cid_v1, read_all, PbNode, DAG_PB, RAW, TYPE_DIRECTORY};
+use futures::{io::Cursor, AsyncReadExt};
+use multihash::{Code, MultihashDigest};
+use rs_car::Cid;
+use rs_car_ipfs::{
+    cid_version::{same_block, to_cidv0, to_cidv1, CidVersionError},
+    single_file::{extract_many, CarFileReader, ReadSingleFileError},
+};
+
+#[test]
+fn cid_version_round_trip() {
+    let v0 = cid_v0(b"block");
+    let v1 = to_cidv1(&v0);
+    assert_eq!(v1, cid_v1(DAG_PB, b"block"));
+    assert_eq!(to_cidv0(&v1), Ok(v0));
+
+    // Already in the requested version
+    assert_eq!(to_cidv1(&v1), v1);
+    assert_eq!(to_cidv0(&v0), Ok(v0));
+
+    // Only the version changes for other codecs
+    let raw = cid_v1(RAW, b"block");
+    assert_eq!(to_cidv1(&raw), raw);
+}
+
+#[test]
+fn cid_version_no_v0() {
+    assert_eq!(
+        to_cidv0(&cid_v1(RAW, b"block")),
+        Err(CidVersionError::NotDagPb { codec: RAW })
+    );
+
+    let blake2b = Cid::new_v1(DAG_PB, Code::Blake2b256.digest(b"block"));
+    assert_eq!(
+        to_cidv0(&blake2b),
+        Err(CidVersionError::NotSha256 {
+            code: u64::from(Code::Blake2b256),
+            size: 32
+        })
+    );
+
+    // Identity multihash of 32 bytes
+    let identity = Cid::new_v1(DAG_PB, multihash::Multihash::wrap(0x00, &[7; 32]).unwrap());
+    assert_eq!(
+        to_cidv0(&identity),
+        Err(CidVersionError::NotSha256 {
+            code: 0x00,
+            size: 32
+        })
+    );
+}
+
+#[test]
+fn cid_version_same_block() {
+    let v0 = cid_v0(b"block");
+    assert!(same_block(&v0, &v0));
+    assert!(same_block(&v0, &to_cidv1(&v0)));
+    assert!(same_block(&to_cidv1(&v0), &v0));
+    assert!(!same_block(&v0, &cid_v0(b"other")));
+    // Same multihash, other codec
+    assert!(!same_block(&v0, &Cid::new_v1(RAW, *v0.hash())));
+}
+
+#[async_std::test]
+async fn root_is_not_file_in_other_cid_version() {
+    let (v0, block) = PbNode {
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+    .block();
+    let v1 = to_cidv1(&v0);
+
+    // Header root and streamed block in different versions
+    for (root, streamed) in [(v0, v1), (v1, v0)] {
+        let car = car_v1(&[root], &[(streamed, block.clone())]);
+        for res in &read_all(&car, &Default::default()).await[..2] {
+            match res {
+                Err(ReadSingleFileError::RootCidIsNotFile) => {}
+                x => panic!("other result {:?}", x),
+            }
+        }
+
+        let mut car_input = car.as_slice();
+        let mut reader = CarFileReader::new(&mut car_input, None, &Default::default());
+        match reader.read_to_end(&mut vec![]).await {
+            Err(err) => assert!(
+                matches!(
+                    err.get_ref().and_then(|err| err.downcast_ref()),
+                    Some(ReadSingleFileError::RootCidIsNotFile)
+                ),
+                "{:?}",
+                err
+            ),
+            x => panic!("other result {:?}", x),
+        }
+
+        let results = extract_many(&mut car.as_slice(), vec![(root, Cursor::new(vec![]))])
+            .await
+            .unwrap();
+        match &results[..] {
+            [Err(ReadSingleFileError::RootCidIsNotFile)] => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn links_in_other_cid_version() {
+    let (a, a_block) = PbNode::file_leaf(b"hello ").block();
+    let (b, b_block) = PbNode::file_leaf(b"world").block();
+    // Links mix both versions, the same leaf is linked in both
+    let (root, root_block) =
+        PbNode::file_branch(&[(a, 6), (to_cidv1(&b), 5), (to_cidv1(&a), 6)]).block();
+
+    // Blocks streamed in the other version than linked
+    let car = car_v1(
+        &[to_cidv1(&root)],
+        &[(root, root_block), (to_cidv1(&a), a_block), (b, b_block)],
+    );
+    for res in read_all(&car, &Default::default()).await {
+        assert_eq!(res.unwrap(), b"hello worldhello ");
+    }
+
+    let mut car_input = car.as_slice();
+    let mut reader = CarFileReader::new(&mut car_input, None, &Default::default());
+    let mut out = vec![];
+    reader.read_to_end(&mut out).await.unwrap();
+    assert_eq!(out, b"hello worldhello ");
+}
@@ -0,0 +1,88 @@
+//! `cleanup_on_error` truncates the output of a failed read back to `base_offset`
+
+mod common;
+
+use common::{
+    generate::{pseudo_random, CarSpec, Corruption},
+    Recorder,
+};
+use rs_car_ipfs::single_file::{read_single_file_seek_file, ReadSingleFileOptions};
+
+/// CAR of 10 leaves whose last block is corrupted, read after the first 9 leaves are written
+fn corrupt_last_leaf_car() -> Vec<u8> {
+    CarSpec {
+        data: pseudo_random(1000, 21),
+        chunk_size: 100,
+        corruption: Some(Corruption::FlipByte(10)),
+        ..Default::default()
+    }
+    .build()
+    .car
+}
+
+#[async_std::test]
+async fn cleanup_on_error_truncates_output() {
+    let car = corrupt_last_leaf_car();
+    let options = ReadSingleFileOptions {
+        cleanup_on_error: true,
+        ..Default::default()
+    };
+
+    let mut out = Recorder::new(vec![]);
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap_err();
+    assert_eq!(out.written, 900);
+    assert_eq!(out.set_lens, vec![0]);
+    assert!(out.inner.into_inner().is_empty());
+
+    // By default the partial file is left in `out`
+    let mut out = Recorder::new(vec![]);
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &Default::default())
+        .await
+        .unwrap_err();
+    assert!(out.set_lens.is_empty());
+    assert_eq!(out.inner.into_inner().len(), 900);
+}
+
+#[async_std::test]
+async fn cleanup_on_error_keeps_base_offset() {
+    let car = corrupt_last_leaf_car();
+    let options = ReadSingleFileOptions {
+        cleanup_on_error: true,
+        base_offset: 10,
+        ..Default::default()
+    };
+
+    let mut out = Recorder::new(vec![0xff; 10]);
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap_err();
+    assert_eq!(out.inner.into_inner(), vec![0xff; 10]);
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn cleanup_on_error_file() {
+    let car = corrupt_last_leaf_car();
+    let path = std::env::temp_dir().join(format!("rs-car-ipfs-cleanup-{}", std::process::id()));
+
+    let mut out = async_std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .await
+        .unwrap();
+    let options = ReadSingleFileOptions {
+        cleanup_on_error: true,
+        ..Default::default()
+    };
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap_err();
+    drop(out);
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use common::{car_v1, cid_v1, read_all, PbNode, RAW};
+use rs_car_ipfs::single_file::ReadSingleFileError;
+
+const DAG_CBOR: u64 = 0x71;
+
+#[async_std::test]
+async fn raw_leaves() {
+    // As created by `ipfs add --cid-version=1`
+    let (a_cid, a) = (cid_v1(RAW, b"hello"), b"hello".to_vec());
+    let (b_cid, b) = (cid_v1(RAW, b"world"), b"world".to_vec());
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 5), (b_cid, 5)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    for res in read_all(&car, &Default::default()).await {
+        assert_eq!(res.unwrap(), b"helloworld");
+    }
+}
+
+#[async_std::test]
+async fn raw_block_root() {
+    // Files of a single chunk added with raw leaves are the raw block itself
+    let (root_cid, root) = (cid_v1(RAW, b"hello"), b"hello".to_vec());
+    let car = car_v1(&[root_cid], &[(root_cid, root)]);
+
+    for res in read_all(&car, &Default::default()).await {
+        assert_eq!(res.unwrap(), b"hello");
+    }
+}
+
+#[async_std::test]
+async fn unsupported_codec() {
+    // dag-cbor {"a": 1}
+    let cbor = vec![0xa1, 0x61, 0x61, 0x01];
+    let cbor_cid = cid_v1(DAG_CBOR, &cbor);
+    let (a_cid, a) = PbNode::file_leaf(b"hello").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 5), (cbor_cid, 4)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (a_cid, a), (cbor_cid, cbor)],
+    );
+
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::UnsupportedCodec(codec)) => assert_eq!(codec, DAG_CBOR),
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn corrupt_dag_pb_is_invalid_unixfs() {
+    let garbage = vec![0xff; 8];
+    let garbage_cid = common::cid_v0(&garbage);
+    let (root_cid, root) = PbNode::file_branch(&[(garbage_cid, 8)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (garbage_cid, garbage)]);
+
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::InvalidUnixFs(_)) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Synthetic UnixFS file CARs built from a [`CarSpec`], to cover DAG layouts, block orders and
+//! corruptions not present in the static fixtures of `tests/data`.
+
+use rs_car::Cid;
+use std::collections::HashSet;
+
+use super::{car_v1, cid_v1, PbNode, RAW};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Layout {
+    /// All leaves at the same depth, as `ipfs add` by default
+    Balanced,
+    /// `fanout` leaves then `repeat` subtrees of each increasing depth, as `ipfs add --trickle`
+    Trickle { repeat: usize },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Order {
+    /// Parents before their children, depth-first, as `ipfs dag export`
+    Dfs,
+    /// Level by level from the root
+    Bfs,
+    /// Children before their parents, depth-first
+    ChildrenFirst,
+    /// Shuffled with a seed
+    Random(u64),
+}
+
+/// Corruption of the block at an index of the emitted blocks
+#[derive(Clone, Copy, Debug)]
+pub enum Corruption {
+    /// Flip the last byte of the block, so it does not match its CID
+    FlipByte(usize),
+    Drop(usize),
+    /// Emit the block twice in a row
+    Duplicate(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct CarSpec {
+    pub data: Vec<u8>,
+    pub chunk_size: usize,
+    /// Max links per node
+    pub fanout: usize,
+    pub layout: Layout,
+    /// Leaves as raw blocks with CIDv1 instead of UnixFS File dag-pb nodes
+    pub raw_leaves: bool,
+    pub order: Order,
+    pub corruption: Option<Corruption>,
+}
+
+impl Default for CarSpec {
+    fn default() -> Self {
+        Self {
+            data: vec![],
+            chunk_size: 256,
+            fanout: 174,
+            layout: Layout::Balanced,
+            raw_leaves: false,
+            order: Order::Dfs,
+            corruption: None,
+        }
+    }
+}
+
+pub struct GeneratedCar {
+    pub car: Vec<u8>,
+    pub root: Cid,
+    /// Emitted blocks in CAR order, after corruption
+    pub blocks: Vec<(Cid, Vec<u8>)>,
+}
+
+/// Node of the DAG being built, with its block and the length of the file under it
+struct Node {
+    cid: Cid,
+    block: Vec<u8>,
+    size: u64,
+    children: Vec<Node>,
+}
+
+impl CarSpec {
+    pub fn build(&self) -> GeneratedCar {
+        assert!(self.chunk_size > 0 && self.fanout > 1);
+        let mut leaves = if self.data.is_empty() {
+            vec![self.leaf(&[])]
+        } else {
+            self.data
+                .chunks(self.chunk_size)
+                .map(|chunk| self.leaf(chunk))
+                .collect::<Vec<_>>()
+        }
+        .into_iter()
+        .peekable();
+
+        let root = match self.layout {
+            Layout::Balanced => self.balanced(leaves.collect()),
+            Layout::Trickle { repeat } => self.trickle(&mut leaves, None, repeat),
+        };
+        let root_cid = root.cid;
+
+        let mut blocks = vec![];
+        match self.order {
+            Order::Dfs => pre_order(root, &mut blocks),
+            Order::ChildrenFirst => post_order(root, &mut blocks),
+            Order::Bfs => {
+                let mut level = vec![root];
+                while !level.is_empty() {
+                    let mut next = vec![];
+                    for node in level {
+                        blocks.push((node.cid, node.block));
+                        next.extend(node.children);
+                    }
+                    level = next;
+                }
+            }
+            Order::Random(seed) => {
+                pre_order(root, &mut blocks);
+                let mut state = seed;
+                for i in (1..blocks.len()).rev() {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                    blocks.swap(i, (state >> 33) as usize % (i + 1));
+                }
+            }
+        }
+
+        // A CAR includes each block once, even if linked multiple times
+        let mut seen = HashSet::new();
+        blocks.retain(|(cid, _)| seen.insert(*cid));
+
+        match self.corruption {
+            Some(Corruption::FlipByte(k)) => *blocks[k].1.last_mut().unwrap() ^= 0xff,
+            Some(Corruption::Drop(k)) => {
+                blocks.remove(k);
+            }
+            Some(Corruption::Duplicate(k)) => blocks.insert(k, blocks[k].clone()),
+            None => {}
+        }
+
+        GeneratedCar {
+            car: car_v1(&[root_cid], &blocks),
+            root: root_cid,
+            blocks,
+        }
+    }
+
+    fn leaf(&self, chunk: &[u8]) -> Node {
+        let (cid, block) = if self.raw_leaves {
+            (cid_v1(RAW, chunk), chunk.to_vec())
+        } else {
+            PbNode::file_leaf(chunk).block()
+        };
+        Node {
+            cid,
+            block,
+            size: chunk.len() as u64,
+            children: vec![],
+        }
+    }
+
+    fn branch(&self, children: Vec<Node>) -> Node {
+        let links = children
+            .iter()
+            .map(|child| (child.cid, child.size))
+            .collect::<Vec<_>>();
+        let (cid, block) = PbNode::file_branch(&links).block();
+        Node {
+            cid,
+            block,
+            size: links.iter().map(|(_, size)| size).sum(),
+            children,
+        }
+    }
+
+    fn balanced(&self, mut nodes: Vec<Node>) -> Node {
+        while nodes.len() > 1 {
+            let mut parents = vec![];
+            let mut nodes_iter = nodes.into_iter().peekable();
+            while nodes_iter.peek().is_some() {
+                let children = nodes_iter.by_ref().take(self.fanout).collect();
+                parents.push(self.branch(children));
+            }
+            nodes = parents;
+        }
+        nodes.pop().unwrap()
+    }
+
+    /// Trickle node of at most `max_depth` levels of subtrees, unbounded for the root
+    fn trickle(
+        &self,
+        leaves: &mut std::iter::Peekable<std::vec::IntoIter<Node>>,
+        max_depth: Option<usize>,
+        repeat: usize,
+    ) -> Node {
+        let mut children = leaves.by_ref().take(self.fanout).collect::<Vec<_>>();
+        if max_depth.is_none() && children.len() == 1 && leaves.peek().is_none() {
+            return children.pop().unwrap();
+        }
+        let mut depth = 1;
+        while leaves.peek().is_some() && max_depth.is_none_or(|max| depth < max) {
+            for _ in 0..repeat {
+                if leaves.peek().is_none() {
+                    break;
+                }
+                children.push(self.trickle(leaves, Some(depth), repeat));
+            }
+            depth += 1;
+        }
+        self.branch(children)
+    }
+}
+
+fn pre_order(node: Node, blocks: &mut Vec<(Cid, Vec<u8>)>) {
+    blocks.push((node.cid, node.block));
+    for child in node.children {
+        pre_order(child, blocks);
+    }
+}
+
+fn post_order(node: Node, blocks: &mut Vec<(Cid, Vec<u8>)>) {
+    for child in node.children {
+        post_order(child, blocks);
+    }
+    blocks.push((node.cid, node.block));
+}
+
+/// Deterministic pseudo random bytes
+pub fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (state >> 33) as u8
+        })
+        .collect()
+}
@@ -0,0 +1,381 @@
+//! Helpers to craft CAR streams by hand in tests.
+
+#![allow(dead_code)]
+
+pub mod generate;
+pub mod rewrite;
+
+use futures::{future::BoxFuture, io::Cursor, AsyncRead, AsyncSeek, AsyncWrite};
+use multihash::{Code, MultihashDigest};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_with_options, CarIndex, ReadSingleFileError, ReadSingleFileOptions,
+    SetLen, SyncAll,
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pub const DAG_PB: u64 = 0x70;
+pub const RAW: u64 = 0x55;
+
+/// UnixFS Data.Type values
+pub const TYPE_RAW: u64 = 0;
+pub const TYPE_DIRECTORY: u64 = 1;
+pub const TYPE_FILE: u64 = 2;
+pub const TYPE_METADATA: u64 = 3;
+pub const TYPE_SYMLINK: u64 = 4;
+
+/// dag-pb block with a UnixFS Data payload, fields are written in dag-pb canonical order
+#[derive(Default, Clone)]
+pub struct PbNode {
+    pub links: Vec<PbLink>,
+    pub unixfs_type: u64,
+    pub data: Option<Vec<u8>>,
+    pub filesize: Option<u64>,
+    pub blocksizes: Vec<u64>,
+}
+
+#[derive(Clone)]
+pub struct PbLink {
+    pub cid: Cid,
+    pub name: Option<String>,
+    pub tsize: Option<u64>,
+}
+
+impl PbLink {
+    pub fn new(cid: Cid) -> Self {
+        Self {
+            cid,
+            name: None,
+            tsize: None,
+        }
+    }
+}
+
+impl PbNode {
+    /// UnixFS File leaf as emitted by kubo without raw leaves
+    pub fn file_leaf(data: &[u8]) -> Self {
+        Self {
+            unixfs_type: TYPE_FILE,
+            data: Some(data.to_vec()),
+            filesize: Some(data.len() as u64),
+            ..Default::default()
+        }
+    }
+
+    /// UnixFS File branch node linking `children` with their file sizes
+    pub fn file_branch(children: &[(Cid, u64)]) -> Self {
+        Self {
+            links: children.iter().map(|(cid, _)| PbLink::new(*cid)).collect(),
+            unixfs_type: TYPE_FILE,
+            data: None,
+            filesize: Some(children.iter().map(|(_, size)| size).sum()),
+            blocksizes: children.iter().map(|(_, size)| *size).collect(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut unixfs = vec![];
+        write_varint_field(&mut unixfs, 1, self.unixfs_type);
+        if let Some(data) = &self.data {
+            write_bytes_field(&mut unixfs, 2, data);
+        }
+        if let Some(filesize) = self.filesize {
+            write_varint_field(&mut unixfs, 3, filesize);
+        }
+        for blocksize in &self.blocksizes {
+            write_varint_field(&mut unixfs, 4, *blocksize);
+        }
+
+        let mut node = vec![];
+        for link in &self.links {
+            let mut link_buf = vec![];
+            write_bytes_field(&mut link_buf, 1, &link.cid.to_bytes());
+            if let Some(name) = &link.name {
+                write_bytes_field(&mut link_buf, 2, name.as_bytes());
+            }
+            if let Some(tsize) = link.tsize {
+                write_varint_field(&mut link_buf, 3, tsize);
+            }
+            write_bytes_field(&mut node, 2, &link_buf);
+        }
+        write_bytes_field(&mut node, 1, &unixfs);
+        node
+    }
+
+    /// Encode and compute its CIDv0
+    pub fn block(&self) -> (Cid, Vec<u8>) {
+        let block = self.encode();
+        (cid_v0(&block), block)
+    }
+}
+
+pub fn cid_v0(block: &[u8]) -> Cid {
+    Cid::new_v0(Code::Sha2_256.digest(block)).unwrap()
+}
+
+pub fn cid_v1(codec: u64, block: &[u8]) -> Cid {
+    Cid::new_v1(codec, Code::Sha2_256.digest(block))
+}
+
+/// CID carrying `block` inline with the identity hash
+pub fn cid_identity(codec: u64, block: &[u8]) -> Cid {
+    Cid::new_v1(codec, multihash::Multihash::wrap(0x00, block).unwrap())
+}
+
+/// Encode a CARv1 with `roots` in the header followed by `blocks` in order
+pub fn car_v1(roots: &[Cid], blocks: &[(Cid, Vec<u8>)]) -> Vec<u8> {
+    // dag-cbor {"roots": [..], "version": 1}
+    let mut header = vec![0xa2];
+    write_cbor_roots(&mut header, roots);
+    write_cbor_text(&mut header, "version");
+    header.push(0x01);
+    car_v1_with_header(&header, blocks)
+}
+
+/// Encode the `"roots": [..]` entry of a CAR header
+pub fn write_cbor_roots(header: &mut Vec<u8>, roots: &[Cid]) {
+    write_cbor_text(header, "roots");
+    write_cbor_head(header, 4, roots.len() as u64);
+    for root in roots {
+        // Tag 42 + bytes with the multibase identity prefix
+        header.extend_from_slice(&[0xd8, 0x2a]);
+        let mut cid_bytes = vec![0x00];
+        cid_bytes.extend_from_slice(&root.to_bytes());
+        write_cbor_head(header, 2, cid_bytes.len() as u64);
+        header.extend_from_slice(&cid_bytes);
+    }
+}
+
+/// Encode a CARv1 with the dag-cbor `header` as is followed by `blocks` in order
+pub fn car_v1_with_header(header: &[u8], blocks: &[(Cid, Vec<u8>)]) -> Vec<u8> {
+    let mut car = vec![];
+    write_varint(&mut car, header.len() as u64);
+    car.extend_from_slice(header);
+
+    for (cid, block) in blocks {
+        let cid_bytes = cid.to_bytes();
+        write_varint(&mut car, (cid_bytes.len() + block.len()) as u64);
+        car.extend_from_slice(&cid_bytes);
+        car.extend_from_slice(block);
+    }
+
+    car
+}
+
+/// Read `car` with the buffer, seek and indexed readers, in that order
+pub async fn read_all(
+    car: &[u8],
+    options: &ReadSingleFileOptions,
+) -> Vec<Result<Vec<u8>, ReadSingleFileError>> {
+    let mut results = vec![];
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_buffer_with_options(&mut Cursor::new(car), &mut out, None, options).await;
+    results.push(res.map(|_| out.into_inner()));
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_seek_with_options(&mut Cursor::new(car), &mut out, None, options).await;
+    results.push(res.map(|_| out.into_inner()));
+
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        options,
+    )
+    .await;
+    results.push(res.map(|_| out.into_inner()));
+
+    results
+}
+
+/// Cursor output recording the count of bytes written at each flush and sync call, and the
+/// lengths set with `SetLen`
+#[derive(Default)]
+pub struct Recorder {
+    pub inner: Cursor<Vec<u8>>,
+    pub written: usize,
+    pub flushes: Vec<usize>,
+    pub syncs: Vec<usize>,
+    pub set_lens: Vec<u64>,
+}
+
+impl Recorder {
+    pub fn new(inner: Vec<u8>) -> Self {
+        Self {
+            inner: Cursor::new(inner),
+            ..Default::default()
+        }
+    }
+}
+
+impl SetLen for Recorder {
+    fn set_len(&mut self, len: u64) -> BoxFuture<'_, io::Result<()>> {
+        self.set_lens.push(len);
+        self.inner.set_len(len)
+    }
+}
+
+impl SyncAll for Recorder {
+    fn sync_all(&mut self) -> BoxFuture<'_, io::Result<()>> {
+        self.syncs.push(self.written);
+        self.inner.sync_all()
+    }
+}
+
+impl AsyncWrite for Recorder {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = res {
+            self.written += n;
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let written = self.written;
+        self.flushes.push(written);
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+impl AsyncRead for Recorder {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncSeek for Recorder {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: io::SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.inner).poll_seek(cx, pos)
+    }
+}
+
+/// Cursor output accepting at most `max_write` bytes per write, and pending every other write as
+/// writers are allowed to
+pub struct ShortWriter {
+    pub inner: Cursor<Vec<u8>>,
+    pub max_write: usize,
+    pub writes: usize,
+}
+
+impl ShortWriter {
+    pub fn new(max_write: usize) -> Self {
+        Self {
+            inner: Cursor::new(vec![]),
+            max_write,
+            writes: 0,
+        }
+    }
+}
+
+impl AsyncWrite for ShortWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.writes += 1;
+        if self.writes.is_multiple_of(2) {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let len = buf.len().min(self.max_write);
+        Pin::new(&mut self.inner).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+impl AsyncRead for ShortWriter {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncSeek for ShortWriter {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: io::SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.inner).poll_seek(cx, pos)
+    }
+}
+
+pub fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+pub fn write_varint_field(buf: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(buf, field << 3);
+    write_varint(buf, value);
+}
+
+pub fn write_bytes_field(buf: &mut Vec<u8>, field: u64, value: &[u8]) {
+    write_varint(buf, (field << 3) | 2);
+    write_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+pub fn write_cbor_head(buf: &mut Vec<u8>, major: u8, len: u64) {
+    let major = major << 5;
+    match len {
+        0..=23 => buf.push(major | len as u8),
+        24..=0xff => buf.extend_from_slice(&[major | 24, len as u8]),
+        _ => {
+            buf.push(major | 25);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+}
+
+pub fn write_cbor_text(buf: &mut Vec<u8>, text: &str) {
+    write_cbor_head(buf, 3, text.len() as u64);
+    buf.extend_from_slice(text.as_bytes());
+}
@@ -0,0 +1,287 @@
+//! Parse the frames of a CARv1 and re-emit them modified, to derive corrupted variants of the
+//! fixtures of `tests/data`, with a model of the file DAG to compute the errors expected from
+//! the readers.
+
+use rs_car::Cid;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Cursor,
+};
+
+use super::{car_v1, PbLink, PbNode, DAG_PB, TYPE_DIRECTORY};
+
+/// Header roots and blocks of a CARv1, in CAR order
+#[derive(Clone, Debug)]
+pub struct CarFrames {
+    pub roots: Vec<Cid>,
+    pub blocks: Vec<(Cid, Vec<u8>)>,
+}
+
+impl CarFrames {
+    /// Parse a CARv1, panicking on anything else as fixtures are well formed
+    pub fn parse(car: &[u8]) -> Self {
+        let mut pos = 0;
+        let header_len = read_varint(car, &mut pos) as usize;
+        let header = &car[pos..pos + header_len];
+        pos += header_len;
+
+        let mut blocks = vec![];
+        while pos < car.len() {
+            let frame_len = read_varint(car, &mut pos) as usize;
+            let frame = &car[pos..pos + frame_len];
+            pos += frame_len;
+            let mut cursor = Cursor::new(frame);
+            let cid = Cid::read_bytes(&mut cursor).expect("frame CID");
+            let block = frame[cursor.position() as usize..].to_vec();
+            blocks.push((cid, block));
+        }
+
+        Self {
+            roots: header_roots(header),
+            blocks,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        car_v1(&self.roots, &self.blocks)
+    }
+
+    pub fn root(&self) -> Cid {
+        assert_eq!(self.roots.len(), 1, "fixtures have a single root");
+        self.roots[0]
+    }
+
+    /// Index of the block `cid` in the CAR
+    pub fn position(&self, cid: &Cid) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|(block_cid, _)| block_cid == cid)
+    }
+
+    /// Only the first `k` blocks
+    pub fn truncated(&self, k: usize) -> Self {
+        Self {
+            roots: self.roots.clone(),
+            blocks: self.blocks[..k].to_vec(),
+        }
+    }
+
+    /// Without the block at `k`
+    pub fn without(&self, k: usize) -> Self {
+        let mut frames = self.clone();
+        frames.blocks.remove(k);
+        frames
+    }
+
+    /// With the blocks at `i` and `j` swapped
+    pub fn swapped(&self, i: usize, j: usize) -> Self {
+        let mut frames = self.clone();
+        frames.blocks.swap(i, j);
+        frames
+    }
+
+    /// With the last byte of the block at `k` flipped, so it does not match its CID
+    pub fn flipped(&self, k: usize) -> Self {
+        let mut frames = self.clone();
+        *frames.blocks[k].1.last_mut().unwrap() ^= 0xff;
+        frames
+    }
+
+    /// With the root replaced by a UnixFS directory linking the children of the root as named
+    /// entries, in the header too. The directory is returned with the frames
+    pub fn directory_root(&self) -> (Self, Cid) {
+        let root = self.root();
+        let index = self.position(&root).expect("root block");
+        let links = links(&root, &self.blocks[index].1);
+        let (dir_cid, dir) = PbNode {
+            links: links
+                .iter()
+                .enumerate()
+                .map(|(i, cid)| PbLink {
+                    name: Some(format!("entry-{}", i)),
+                    ..PbLink::new(*cid)
+                })
+                .collect(),
+            unixfs_type: TYPE_DIRECTORY,
+            ..Default::default()
+        }
+        .block();
+
+        let mut frames = self.clone();
+        frames.roots = vec![dir_cid];
+        frames.blocks[index] = (dir_cid, dir);
+        (frames, dir_cid)
+    }
+
+    /// Links of each block of the CAR, empty for leaves
+    pub fn dag(&self) -> Dag {
+        Dag {
+            links: self
+                .blocks
+                .iter()
+                .map(|(cid, block)| (*cid, links(cid, block)))
+                .collect(),
+        }
+    }
+
+    /// Whether every block other than the root is linked by a block before it, which the seek
+    /// reader needs to know the block is part of the file
+    pub fn parents_first(&self) -> bool {
+        let root = self.root();
+        let mut linked = HashSet::new();
+        for (cid, block) in &self.blocks {
+            if *cid != root && !linked.contains(cid) {
+                return false;
+            }
+            linked.extend(links(cid, block));
+        }
+        true
+    }
+
+    /// Indexes of the leaves of the file in CAR order
+    pub fn leaves(&self) -> Vec<usize> {
+        let dag = self.dag();
+        let in_file = dag.reachable(&self.root());
+        (0..self.blocks.len())
+            .filter(|i| {
+                let cid = &self.blocks[*i].0;
+                in_file.contains(cid) && dag.links[cid].is_empty()
+            })
+            .collect()
+    }
+}
+
+/// Links of the nodes of a file DAG by CID, as read from a complete CAR
+pub struct Dag {
+    links: HashMap<Cid, Vec<Cid>>,
+}
+
+impl Dag {
+    /// CIDs of the nodes under `root`, itself included
+    pub fn reachable(&self, root: &Cid) -> HashSet<Cid> {
+        let mut seen = HashSet::new();
+        let mut stack = vec![*root];
+        while let Some(cid) = stack.pop() {
+            if seen.insert(cid) {
+                stack.extend(self.links.get(&cid).into_iter().flatten());
+            }
+        }
+        seen
+    }
+
+    pub fn is_leaf(&self, cid: &Cid) -> bool {
+        self.links[cid].is_empty()
+    }
+
+    /// Depth-first walk of the file `root` up to the first node in file order whose block is not
+    /// in `present`, with the nodes after it not expanded yet, next first
+    fn walk_to_missing(&self, root: &Cid, present: &HashSet<Cid>) -> Option<(Cid, Vec<Cid>)> {
+        let mut stack = vec![*root];
+        while let Some(cid) = stack.pop() {
+            if !present.contains(&cid) {
+                stack.reverse();
+                return Some((cid, stack));
+            }
+            stack.extend(self.links[&cid].iter().rev());
+        }
+        None
+    }
+
+    /// First node of the file in file order whose block is not in `present`, as reported by the
+    /// buffer reader once the CAR ends
+    pub fn first_missing(&self, root: &Cid, present: &HashSet<Cid>) -> Option<Cid> {
+        self.walk_to_missing(root, present).map(|(cid, _)| cid)
+    }
+
+    /// Nodes of the file not written by the seek reader once a CAR in depth-first order ends
+    /// without the blocks not in `present`: the first missing node, then the siblings after it
+    /// and after each of its ancestors, as nodes are only expanded once reached
+    pub fn pending_at_eof(&self, root: &Cid, present: &HashSet<Cid>) -> Vec<Cid> {
+        match self.walk_to_missing(root, present) {
+            Some((cid, next)) => [vec![cid], next].concat(),
+            None => vec![],
+        }
+    }
+}
+
+/// Links of a block, empty for raw blocks
+pub fn links(cid: &Cid, block: &[u8]) -> Vec<Cid> {
+    if cid.codec() != DAG_PB {
+        return vec![];
+    }
+    let mut links = vec![];
+    for (field, value) in pb_fields(block) {
+        // PBNode.Links, with PBLink.Hash as field 1
+        if field == 2 {
+            let hash = pb_fields(value)
+                .into_iter()
+                .find(|(field, _)| *field == 1)
+                .map(|(_, hash)| hash)
+                .expect("link hash");
+            links.push(Cid::try_from(hash).expect("link CID"));
+        }
+    }
+    links
+}
+
+/// Length delimited fields of a protobuf message, skipping varint fields
+fn pb_fields(message: &[u8]) -> Vec<(u64, &[u8])> {
+    let mut fields = vec![];
+    let mut pos = 0;
+    while pos < message.len() {
+        let key = read_varint(message, &mut pos);
+        match key & 7 {
+            0 => {
+                read_varint(message, &mut pos);
+            }
+            2 => {
+                let len = read_varint(message, &mut pos) as usize;
+                fields.push((key >> 3, &message[pos..pos + len]));
+                pos += len;
+            }
+            wire_type => panic!("unexpected wire type {}", wire_type),
+        }
+    }
+    fields
+}
+
+/// CIDs of the `"roots"` of a dag-cbor CAR header, encoded as tag 42 of the bytes of the CID
+/// with a multibase identity prefix
+fn header_roots(header: &[u8]) -> Vec<Cid> {
+    let mut roots = vec![];
+    let mut pos = 0;
+    while pos + 2 < header.len() {
+        if header[pos..pos + 2] != [0xd8, 0x2a] {
+            pos += 1;
+            continue;
+        }
+        pos += 2;
+        let len = match header[pos] & 0x1f {
+            len @ 0..=23 => len as usize,
+            24 => {
+                pos += 1;
+                header[pos] as usize
+            }
+            info => panic!("unexpected CID length encoding {}", info),
+        };
+        pos += 1;
+        assert_eq!(header[pos], 0x00, "multibase identity prefix");
+        roots.push(Cid::try_from(&header[pos + 1..pos + len]).expect("root CID"));
+        pos += len;
+    }
+    roots
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+    let mut n = 0;
+    let mut shift = 0;
+    loop {
+        let byte = buf[*pos];
+        *pos += 1;
+        n |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return n;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,111 @@
+//! Files compressed on the way to `out` with `compress_output`
+
+use futures::io::Cursor;
+use rs_car_ipfs::{
+    decompress::Compression,
+    single_file::{
+        read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+        read_single_file_seek_with_options, CarIndex, ReadSingleFileError, ReadSingleFileOptions,
+    },
+};
+use std::fs;
+
+const CAR_FILEPATH: &str = "tests/data/rand_100K.bin.size-512.normal.car";
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+const EXPECTED_FILEPATH: &str = "tests/data/rand_100K.bin";
+
+fn compressed(compression: Compression) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        compress_output: compression,
+        ..Default::default()
+    }
+}
+
+/// Compressed output of the buffer reader, with the summary length
+async fn read_compressed(compression: Compression) -> Result<(u64, Vec<u8>), ReadSingleFileError> {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_buffer_with_options(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        &compressed(compression),
+    )
+    .await?;
+    Ok((summary.bytes_written, out.into_inner()))
+}
+
+#[cfg(feature = "gzip")]
+#[async_std::test]
+async fn compress_output_gzip() {
+    use futures::AsyncReadExt;
+
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+    let (bytes_written, out) = read_compressed(Compression::Gzip).await.unwrap();
+    assert_eq!(bytes_written, expected.len() as u64);
+    assert_eq!(Compression::sniff(&out), Compression::Gzip);
+
+    let mut decompressed = vec![];
+    async_compression::futures::bufread::GzipDecoder::new(out.as_slice())
+        .read_to_end(&mut decompressed)
+        .await
+        .unwrap();
+    assert!(decompressed == expected);
+}
+
+#[cfg(feature = "zstd")]
+#[async_std::test]
+async fn compress_output_zstd() {
+    use futures::AsyncReadExt;
+
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+    let (bytes_written, out) = read_compressed(Compression::Zstd).await.unwrap();
+    assert_eq!(bytes_written, expected.len() as u64);
+    assert_eq!(Compression::sniff(&out), Compression::Zstd);
+
+    let mut decompressed = vec![];
+    async_compression::futures::bufread::ZstdDecoder::new(out.as_slice())
+        .read_to_end(&mut decompressed)
+        .await
+        .unwrap();
+    assert!(decompressed == expected);
+}
+
+#[cfg(not(feature = "gzip"))]
+#[async_std::test]
+async fn compress_output_feature_not_enabled() {
+    match read_compressed(Compression::Gzip).await {
+        Err(ReadSingleFileError::CompressedOutputUnsupported) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn compress_output_seek_unsupported() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let options = compressed(Compression::Gzip);
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options).await;
+    assert!(matches!(
+        res,
+        Err(ReadSingleFileError::CompressedOutputUnsupported)
+    ));
+
+    let mut car_input = Cursor::new(&car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let res = read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        &options,
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(ReadSingleFileError::CompressedOutputUnsupported)
+    ));
+    assert!(out.into_inner().is_empty());
+}
@@ -0,0 +1,314 @@
+//! The buffer and seek readers implement the same semantics: for every fixture and generated CAR,
+//! with every option both support, they write the same bytes and return the same summary, or fail
+//! with the same class of error. Both walk the file with [`FileLayout`](rs_car_ipfs::layout), the
+//! tests guard changes to the traversal shared by both.
+//!
+//! Differences are limited to what is documented:
+//! - The seek reader writes runs of zeros as holes, outputs are compared through a dense
+//!   `Cursor` and `sparse_bytes` and `holes_count` are not compared.
+//! - The seek reader discards leaves arriving before their parent, the buffer reader does not
+//!   depend on the order of the CAR.
+//! - Leaves under a skipped block are discarded by the seek reader as well.
+//! - A missing block is reported by the buffer reader as the first missing node once the CAR is
+//!   read, and by the seek reader as the nodes pending at EOF.
+
+mod common;
+
+use common::{
+    generate::{pseudo_random, CarSpec, Corruption, Layout, Order},
+    rewrite::CarFrames,
+};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::{
+    adder::ChunkerProfile,
+    single_file::{
+        read_single_file_buffer_with_options, read_single_file_seek_with_options, BlockErrorHook,
+        ErrorAction, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+    },
+};
+use std::{fs, path::PathBuf};
+
+type ReadResult = Result<(ReadSummary, Vec<u8>), ReadSingleFileError>;
+
+async fn read_buffer(car: &[u8], options: &ReadSingleFileOptions) -> ReadResult {
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_buffer_with_options(&mut &car[..], &mut out, None, options).await?;
+    Ok((summary, out.into_inner()))
+}
+
+async fn read_seek(car: &[u8], options: &ReadSingleFileOptions) -> ReadResult {
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_seek_with_options(&mut &car[..], &mut out, None, options).await?;
+    Ok((summary, out.into_inner()))
+}
+
+/// Class of an error, the same for both readers
+fn classify(err: &ReadSingleFileError) -> &'static str {
+    match err {
+        ReadSingleFileError::MissingNode(_) | ReadSingleFileError::PendingLinksAtEOF(_) => {
+            "missing_blocks"
+        }
+        err => err.code(),
+    }
+}
+
+/// The summary without the fields that are documented to differ
+fn comparable(summary: &ReadSummary) -> ReadSummary {
+    ReadSummary {
+        sparse_bytes: 0,
+        holes_count: 0,
+        blocks_read: 0,
+        car_bytes_read: 0,
+        ..summary.clone()
+    }
+}
+
+/// Option sets supported by both readers, with a file of `len` bytes
+fn option_sets(len: usize) -> Vec<(&'static str, ReadSingleFileOptions)> {
+    let skip = BlockErrorHook::new(|_, _| ErrorAction::Skip);
+    vec![
+        ("default", Default::default()),
+        (
+            "write_limit",
+            ReadSingleFileOptions {
+                write_limit: Some(len / 2),
+                ..Default::default()
+            },
+        ),
+        (
+            "write_limit exact",
+            ReadSingleFileOptions {
+                write_limit: Some(len),
+                ..Default::default()
+            },
+        ),
+        (
+            "expected_size",
+            ReadSingleFileOptions {
+                expected_size: Some(len as u64 + 1),
+                ..Default::default()
+            },
+        ),
+        (
+            "max_blocks",
+            ReadSingleFileOptions {
+                max_blocks: Some(2),
+                ..Default::default()
+            },
+        ),
+        (
+            "verify_canonical",
+            ReadSingleFileOptions {
+                verify_canonical: Some(ChunkerProfile::KuboDefaultV0),
+                ..Default::default()
+            },
+        ),
+        (
+            "on_block_error",
+            ReadSingleFileOptions {
+                on_block_error: Some(skip),
+                ..Default::default()
+            },
+        ),
+        (
+            "lenient_node_types",
+            ReadSingleFileOptions {
+                lenient_node_types: true,
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+/// Read `frames` with both readers and assert that they conform
+async fn assert_conform(case: &str, frames: &CarFrames, options: &ReadSingleFileOptions) {
+    let car = frames.encode();
+    let buffer = read_buffer(&car, options).await;
+    let seek = read_seek(&car, options).await;
+    match (buffer, seek) {
+        (Ok((buffer_summary, buffer_out)), Ok((seek_summary, seek_out))) => {
+            assert!(buffer_out == seek_out, "{}: outputs differ", case);
+            assert_eq!(
+                comparable(&buffer_summary),
+                comparable(&seek_summary),
+                "{}",
+                case
+            );
+            assert_eq!(buffer_summary.root_cid, Some(frames.root()), "{}", case);
+            assert_eq!(
+                buffer_summary.bytes_written,
+                buffer_out.len() as u64,
+                "{}",
+                case
+            );
+        }
+        // Leaves arriving before their parent, or under a skipped parent, are not known to the
+        // seek reader
+        (Ok((summary, _)), Err(ReadSingleFileError::PendingLinksAtEOF(_)))
+            if !without(frames, &summary.skipped_blocks).parents_first() => {}
+        (Err(_), Err(ReadSingleFileError::PendingLinksAtEOF(_))) if !frames.parents_first() => {}
+        (Err(buffer_err), Err(seek_err)) => assert_eq!(
+            classify(&buffer_err),
+            classify(&seek_err),
+            "{}: {:?} and {:?}",
+            case,
+            buffer_err,
+            seek_err
+        ),
+        (buffer, seek) => panic!(
+            "{}: buffer {:?}, seek {:?}",
+            case,
+            buffer.map(|(summary, _)| summary),
+            seek.map(|(summary, _)| summary)
+        ),
+    }
+}
+
+/// `frames` without the blocks `cids`
+fn without(frames: &CarFrames, cids: &[Cid]) -> CarFrames {
+    CarFrames {
+        roots: frames.roots.clone(),
+        blocks: frames
+            .blocks
+            .iter()
+            .filter(|(cid, _)| !cids.contains(cid))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Corrupted variants of `frames`, with a name
+fn corrupted(frames: &CarFrames) -> Vec<(String, CarFrames)> {
+    let n = frames.blocks.len();
+    let mut variants = vec![];
+    for k in [0, 1, n / 2, n - 1] {
+        variants.push((format!("truncated after {}", k), frames.truncated(k)));
+    }
+    for k in [0, n / 2, n - 1] {
+        variants.push((format!("without {}", k), frames.without(k)));
+        variants.push((format!("flipped {}", k), frames.flipped(k)));
+    }
+    if n > 1 {
+        variants.push(("swapped 0 1".to_string(), frames.swapped(0, 1)));
+        variants.push((
+            format!("swapped {} {}", n - 2, n - 1),
+            frames.swapped(n - 2, n - 1),
+        ));
+    }
+    variants.push(("directory root".to_string(), frames.directory_root().0));
+    variants
+}
+
+fn fixtures() -> Vec<(PathBuf, CarFrames, usize)> {
+    let mut fixtures = vec![];
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let Some((file, _)) = name.split_once(".size-") else {
+            continue;
+        };
+        let len = fs::metadata(path.with_file_name(file)).unwrap().len() as usize;
+        fixtures.push((
+            path.clone(),
+            CarFrames::parse(&fs::read(&path).unwrap()),
+            len,
+        ));
+    }
+    fixtures.sort_by(|a, b| a.0.cmp(&b.0));
+    fixtures
+}
+
+#[async_std::test]
+async fn conformance_fixtures() {
+    for (path, frames, len) in fixtures() {
+        // Fixtures chunked by 1 byte have thousands of blocks, only read with the defaults
+        let mut option_sets = option_sets(len);
+        if path.to_str().unwrap().contains(".size-1.") {
+            option_sets.truncate(1);
+        }
+        for (name, options) in option_sets {
+            assert_conform(&format!("{:?} {}", path, name), &frames, &options).await;
+        }
+    }
+}
+
+#[async_std::test]
+async fn conformance_fixtures_corrupted() {
+    for (path, frames, len) in fixtures() {
+        if path.to_str().unwrap().contains(".size-1.") {
+            continue;
+        }
+        for (variant, corrupted) in corrupted(&frames) {
+            for (name, options) in option_sets(len) {
+                let case = format!("{:?} {} {}", path, variant, name);
+                assert_conform(&case, &corrupted, &options).await;
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn conformance_generated() {
+    // Repeated content with runs of zeros, written as holes by the seek reader
+    let data = [
+        pseudo_random(900, 12),
+        vec![0; 1200],
+        pseudo_random(900, 12),
+        vec![0; 300],
+    ]
+    .concat();
+    for layout in [Layout::Balanced, Layout::Trickle { repeat: 2 }] {
+        for order in [
+            Order::Dfs,
+            Order::Bfs,
+            Order::ChildrenFirst,
+            Order::Random(3),
+        ] {
+            for raw_leaves in [false, true] {
+                let spec = CarSpec {
+                    data: data.clone(),
+                    chunk_size: 100,
+                    fanout: 3,
+                    layout,
+                    raw_leaves,
+                    order,
+                    ..Default::default()
+                };
+                let generated = spec.build();
+                let n = generated.blocks.len();
+                let mut variants = vec![(None, generated)];
+                for k in [0, 1, n / 2, n - 1] {
+                    for corruption in [
+                        Corruption::FlipByte(k),
+                        Corruption::Drop(k),
+                        Corruption::Duplicate(k),
+                    ] {
+                        let generated = CarSpec {
+                            corruption: Some(corruption),
+                            ..spec.clone()
+                        }
+                        .build();
+                        variants.push((Some(corruption), generated));
+                    }
+                }
+
+                for (corruption, generated) in variants {
+                    let frames = CarFrames {
+                        roots: vec![generated.root],
+                        blocks: generated.blocks,
+                    };
+                    for (name, options) in option_sets(data.len()) {
+                        let case = format!(
+                            "{:?} {:?} raw leaves {} {:?} {}",
+                            layout, order, raw_leaves, corruption, name
+                        );
+                        assert_conform(&case, &frames, &options).await;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+//! Corrupted variants of the fixtures of `tests/data`, derived by rewriting their frames, with
+//! the exact error returned by the buffer and seek readers
+
+mod common;
+
+use common::rewrite::CarFrames;
+use futures::io::Cursor;
+use rs_car::{CarDecodeError, Cid};
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+use std::{collections::HashSet, fs, path::PathBuf};
+
+/// Fixtures of a file in several blocks, with the file. Fixtures chunked by 1 byte have
+/// thousands of blocks and are only covered by the happy path
+fn fixtures() -> Vec<(PathBuf, CarFrames, Vec<u8>)> {
+    let mut fixtures = vec![];
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        let Some((file, _)) = name.split_once(".size-") else {
+            continue;
+        };
+        if name.contains(".size-1.") {
+            continue;
+        }
+        let frames = CarFrames::parse(&fs::read(&path).unwrap());
+        if frames.blocks.len() < 3 {
+            continue;
+        }
+        let data = fs::read(path.with_file_name(file)).unwrap();
+        fixtures.push((path, frames, data));
+    }
+    fixtures.sort_by(|a, b| a.0.cmp(&b.0));
+    assert!(fixtures.len() >= 10, "{}", fixtures.len());
+    fixtures
+}
+
+/// Results of the buffer and seek readers, in that order
+async fn read_both(
+    frames: &CarFrames,
+    options: &ReadSingleFileOptions,
+) -> [Result<Vec<u8>, ReadSingleFileError>; 2] {
+    let car = frames.encode();
+    read_both_bytes(&car, options).await
+}
+
+async fn read_both_bytes(
+    car: &[u8],
+    options: &ReadSingleFileOptions,
+) -> [Result<Vec<u8>, ReadSingleFileError>; 2] {
+    let mut out = Cursor::new(Vec::new());
+    let buffer = read_single_file_buffer_with_options(&mut &car[..], &mut out, None, options).await;
+    let buffer = buffer.map(|_| out.into_inner());
+
+    let mut out = Cursor::new(Vec::new());
+    let seek = read_single_file_seek_with_options(&mut &car[..], &mut out, None, options).await;
+    let seek = seek.map(|_| out.into_inner());
+
+    [buffer, seek]
+}
+
+fn present(frames: &CarFrames) -> HashSet<Cid> {
+    frames.blocks.iter().map(|(cid, _)| *cid).collect()
+}
+
+/// Results of a CAR missing blocks of the file: the first missing node for the buffer reader,
+/// the nodes not written yet for the seek reader
+fn assert_missing(
+    case: &str,
+    original: &CarFrames,
+    corrupted: &CarFrames,
+    [buffer, seek]: [Result<Vec<u8>, ReadSingleFileError>; 2],
+) {
+    let dag = original.dag();
+    let root = original.root();
+    let present = present(corrupted);
+    match buffer {
+        Err(ReadSingleFileError::MissingNode(cid)) => {
+            assert_eq!(Some(cid), dag.first_missing(&root, &present), "{}", case)
+        }
+        x => panic!("buffer {}: other result {:?}", case, x.map(|out| out.len())),
+    }
+    match seek {
+        Err(ReadSingleFileError::PendingLinksAtEOF(pending)) => {
+            assert_eq!(pending, dag.pending_at_eof(&root, &present), "{}", case)
+        }
+        x => panic!("seek {}: other result {:?}", case, x.map(|out| out.len())),
+    }
+}
+
+fn assert_digest_mismatch(
+    case: &str,
+    cid: &Cid,
+    results: [Result<Vec<u8>, ReadSingleFileError>; 2],
+) {
+    for res in results {
+        match res {
+            Err(ReadSingleFileError::CarDecodeError(CarDecodeError::BlockDigestMismatch(msg))) => {
+                assert!(msg.contains(&format!("{:?}", cid)), "{}: {}", case, msg)
+            }
+            x => panic!("{}: other result {:?}", case, x.map(|out| out.len())),
+        }
+    }
+}
+
+#[async_std::test]
+async fn corrupted_fixtures_truncated() {
+    for (path, frames, _) in fixtures() {
+        let n = frames.blocks.len();
+
+        match read_both(&frames.truncated(0), &Default::default()).await {
+            [Err(ReadSingleFileError::RootCidNotFound {
+                root: buffer_root,
+                blocks_seen: 0,
+                ..
+            }), Err(ReadSingleFileError::RootCidNotFound {
+                root: seek_root,
+                blocks_seen: 0,
+                ..
+            })] => assert_eq!([buffer_root, seek_root], [frames.root(); 2]),
+            [buffer, seek] => panic!(
+                "{:?} without blocks: {:?} {:?}",
+                path,
+                buffer.map(|out| out.len()),
+                seek.map(|out| out.len())
+            ),
+        }
+
+        for k in [1, n / 2, n - 1] {
+            let truncated = frames.truncated(k);
+            let case = format!("{:?} truncated after {} of {} blocks", path, k, n);
+            let results = read_both(&truncated, &Default::default()).await;
+            assert_missing(&case, &frames, &truncated, results);
+        }
+
+        // Within the last frame
+        let car = frames.encode();
+        for res in read_both_bytes(&car[..car.len() - 1], &Default::default()).await {
+            match res {
+                Err(ReadSingleFileError::IoError(err)) => {
+                    assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof, "{:?}", path)
+                }
+                x => panic!("{:?} cut: other result {:?}", path, x.map(|out| out.len())),
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn corrupted_fixtures_dropped() {
+    for (path, frames, _) in fixtures() {
+        let n = frames.blocks.len();
+        let leaves = frames.leaves();
+        // A middle block, the first leaf and the last leaf
+        for k in [n / 2, leaves[0], leaves[leaves.len() - 1]] {
+            let dropped = frames.without(k);
+            let case = format!("{:?} without block {} of {}", path, k, n);
+            let results = read_both(&dropped, &Default::default()).await;
+            assert_missing(&case, &frames, &dropped, results);
+        }
+    }
+}
+
+#[async_std::test]
+async fn corrupted_fixtures_swapped() {
+    for (path, frames, data) in fixtures() {
+        // Two leaves of the file, the second one held by the seek reader until its turn. Files
+        // of zeros have a single distinct leaf
+        let leaves = frames.leaves();
+        if let [i, j, ..] = leaves[..] {
+            let swapped = frames.swapped(i, j);
+            for res in read_both(&swapped, &Default::default()).await {
+                assert!(res.unwrap() == data, "{:?} swapped leaves", path);
+            }
+            let strict = ReadSingleFileOptions {
+                reorder_window: Some(0),
+                ..Default::default()
+            };
+            match read_both(&swapped, &strict).await {
+                [Ok(out), Err(ReadSingleFileError::DataNodesNotSorted)] => assert!(out == data),
+                [buffer, seek] => panic!(
+                    "{:?} swapped leaves: {:?} {:?}",
+                    path,
+                    buffer.map(|out| out.len()),
+                    seek.map(|out| out.len())
+                ),
+            }
+        }
+
+        // The root after its first child. The seek reader keeps nodes with links until they are
+        // reached, but discards a leaf arriving before its parent
+        let root = frames.position(&frames.root()).unwrap();
+        let swapped = frames.swapped(root, root + 1);
+        let [buffer, seek] = read_both(&swapped, &Default::default()).await;
+        assert!(buffer.unwrap() == data, "{:?} swapped root", path);
+        let first_child = frames.blocks[root + 1].0;
+        let dag = frames.dag();
+        match seek {
+            Ok(out) if !dag.is_leaf(&first_child) => assert!(out == data, "{:?}", path),
+            Err(ReadSingleFileError::PendingLinksAtEOF(pending)) if dag.is_leaf(&first_child) => {
+                let mut present = present(&frames);
+                present.remove(&first_child);
+                assert_eq!(
+                    pending,
+                    dag.pending_at_eof(&frames.root(), &present),
+                    "{:?}",
+                    path
+                )
+            }
+            x => panic!("{:?} swapped root: {:?}", path, x.map(|out| out.len())),
+        }
+    }
+}
+
+#[async_std::test]
+async fn corrupted_fixtures_flipped() {
+    for (path, frames, _) in fixtures() {
+        let leaves = frames.leaves();
+        let root = frames.position(&frames.root()).unwrap();
+        for k in [root, leaves[0], leaves[leaves.len() / 2]] {
+            let cid = frames.blocks[k].0;
+            let case = format!("{:?} flipped block {}", path, k);
+            assert_digest_mismatch(
+                &case,
+                &cid,
+                read_both(&frames.flipped(k), &Default::default()).await,
+            );
+        }
+    }
+}
+
+#[async_std::test]
+async fn corrupted_fixtures_directory_root() {
+    for (path, frames, _) in fixtures() {
+        let (directory, root) = frames.directory_root();
+        assert_ne!(root, frames.root());
+        for res in read_both(&directory, &Default::default()).await {
+            match res {
+                Err(ReadSingleFileError::RootCidIsNotFile) => {}
+                x => panic!("{:?}: other result {:?}", path, x.map(|out| out.len())),
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn corrupted_fixtures_limits() {
+    for (path, frames, data) in fixtures() {
+        let len = data.len();
+
+        // Distinct leaves are buffered by the buffer reader, the seek reader does not buffer
+        let options = ReadSingleFileOptions {
+            max_buffer: Some(0),
+            ..Default::default()
+        };
+        match read_both(&frames, &options).await {
+            [Err(ReadSingleFileError::MaxBufferedData(limit)), Ok(out)] => {
+                assert_eq!(limit, 0, "{:?}", path);
+                assert!(out == data, "{:?}", path);
+            }
+            [buffer, seek] => panic!(
+                "{:?} max_buffer: {:?} {:?}",
+                path,
+                buffer.map(|out| out.len()),
+                seek.map(|out| out.len())
+            ),
+        }
+
+        // The fixtures declare their size, checked as soon as the root is read
+        let options = ReadSingleFileOptions {
+            write_limit: Some(len - 1),
+            ..Default::default()
+        };
+        for res in read_both(&frames, &options).await {
+            match res {
+                Err(ReadSingleFileError::DeclaredSizeExceedsLimit { declared, limit }) => {
+                    assert_eq!((declared, limit), (len as u64, len - 1), "{:?}", path)
+                }
+                x => panic!("{:?} write_limit: {:?}", path, x.map(|out| out.len())),
+            }
+        }
+    }
+}
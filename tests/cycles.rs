@@ -0,0 +1,64 @@
+//! Content addressing makes cycles impossible with blocks matching their CID, a crafted CAR can
+//! still claim one
+
+mod common;
+
+use common::{car_v1, cid_v0, read_all, PbLink, PbNode};
+use futures::io::Cursor;
+use rs_car::CarDecodeError;
+use rs_car_ipfs::single_file::{
+    file_layout, read_single_file_buffer_with_options, BlockErrorHook, ErrorAction,
+    ReadSingleFileError, ReadSingleFileOptions,
+};
+
+/// Root linking the CID of a branch whose block links back to the root
+fn cyclic_car() -> Vec<u8> {
+    let (leaf_cid, leaf) = PbNode::file_leaf(b"aaaa").block();
+    let branch_cid = cid_v0(b"not the branch");
+    let (root_cid, root) = PbNode {
+        filesize: Some(4),
+        blocksizes: vec![4],
+        ..PbNode::file_branch(&[(branch_cid, 4)])
+    }
+    .block();
+    let (_, branch) = PbNode {
+        links: vec![PbLink::new(leaf_cid), PbLink::new(root_cid)],
+        ..PbNode::file_branch(&[(leaf_cid, 4)])
+    }
+    .block();
+    car_v1(
+        &[root_cid],
+        &[(root_cid, root), (branch_cid, branch), (leaf_cid, leaf)],
+    )
+}
+
+#[async_std::test]
+async fn cyclic_dag_rejected() {
+    let car = cyclic_car();
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::CarDecodeError(CarDecodeError::BlockDigestMismatch(_))) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    match file_layout(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::CarDecodeError(CarDecodeError::BlockDigestMismatch(_))) => {}
+        x => panic!("other result {:?}", x),
+    }
+
+    // Skipping the mismatching block breaks the cycle, its region is zero filled. The indexed
+    // reader does not skip blocks
+    let options = ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new(|_, _| ErrorAction::Skip)),
+        ..Default::default()
+    };
+    for res in &read_all(&car, &options).await[..2] {
+        assert_eq!(res.as_ref().unwrap(), &[0; 4]);
+    }
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_buffer_with_options(&mut Cursor::new(&car), &mut out, None, &options)
+            .await
+            .unwrap();
+    assert_eq!(summary.skipped_blocks.len(), 1);
+}
@@ -0,0 +1,151 @@
+//! CARs loaded into memory once with `load_dag`, files and subtrees written from the view
+
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::io::Cursor;
+use rs_car::{CarDecodeError, Cid};
+use rs_car_ipfs::{
+    single_file::{load_dag, DagView, ReadSingleFileError, ReadSingleFileOptions, ReadSummary},
+    UnixFsType,
+};
+
+async fn load(car: &[u8], options: &ReadSingleFileOptions) -> Result<DagView, ReadSingleFileError> {
+    let mut car_input = car;
+    load_dag(&mut car_input, options).await
+}
+
+async fn write(
+    dag: &DagView,
+    root_cid: Option<&Cid>,
+) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let summary = dag
+        .write_file(root_cid, &mut out, &Default::default())
+        .await?;
+    Ok((summary, out.into_inner()))
+}
+
+#[async_std::test]
+async fn dag_view_fixture() {
+    let car = std::fs::read("tests/data/rand_100K.bin.size-32.normal.car").unwrap();
+    let data = std::fs::read("tests/data/rand_100K.bin").unwrap();
+    let dag = load(&car, &Default::default()).await.unwrap();
+    assert_eq!(dag.car_bytes_read(), car.len() as u64);
+    assert_eq!(dag.cids().len(), dag.len());
+    let root = dag.roots()[0];
+
+    // Written as many times as needed
+    for _ in 0..2 {
+        let (summary, out) = write(&dag, None).await.unwrap();
+        assert!(out == data);
+        assert_eq!(summary.root_cid, Some(root));
+        assert_eq!(summary.blocks_read, dag.len());
+        assert_eq!(summary.car_bytes_read, 0);
+    }
+
+    // A subtree of the file is the range of the file under it
+    let root_node = dag.node(&root).unwrap().unwrap();
+    assert_eq!(root_node.unixfs_type, UnixFsType::File);
+    let links = dag.links(&root).unwrap();
+    assert_eq!(
+        links,
+        root_node
+            .links
+            .iter()
+            .map(|link| link.cid)
+            .collect::<Vec<_>>()
+    );
+    assert!(links.len() > 1);
+    let first_len = root_node.blocksizes[0] as usize;
+    let second_len = root_node.blocksizes[1] as usize;
+    let (summary, out) = write(&dag, Some(&links[1])).await.unwrap();
+    assert!(out == data[first_len..first_len + second_len]);
+    assert_eq!(summary.root_cid, Some(links[1]));
+    assert!(summary.blocks_read < dag.len());
+}
+
+#[async_std::test]
+async fn dag_view_several_files() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (c_cid, c) = PbNode::file_leaf(b"c").block();
+    let (first_cid, first) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let (second_cid, second) = PbNode::file_branch(&[(b_cid, 2), (c_cid, 1), (a_cid, 4)]).block();
+    let car = car_v1(
+        &[first_cid, second_cid],
+        &[
+            (first_cid, first),
+            (a_cid, a.clone()),
+            (b_cid, b),
+            (second_cid, second),
+            (c_cid, c),
+        ],
+    );
+    let dag = load(&car, &Default::default()).await.unwrap();
+    assert_eq!(dag.roots(), [first_cid, second_cid]);
+    assert_eq!(dag.len(), 5);
+    assert_eq!(dag.block(&a_cid), Some(&a[..]));
+    assert_eq!(dag.links(&a_cid), Some(&[][..]));
+    assert_eq!(dag.node(&a_cid).unwrap().unwrap().data_len, 4);
+
+    // Blocks shared by both files
+    assert_eq!(write(&dag, Some(&first_cid)).await.unwrap().1, b"aaaabb");
+    assert_eq!(write(&dag, Some(&second_cid)).await.unwrap().1, b"bbcaaaa");
+    assert_eq!(write(&dag, Some(&c_cid)).await.unwrap().1, b"c");
+
+    match write(&dag, None).await {
+        Err(ReadSingleFileError::NotSingleRoot { roots }) => {
+            assert_eq!(roots, [first_cid, second_cid])
+        }
+        x => panic!("other result {:?}", x),
+    }
+
+    // Not in the CAR
+    let (other_cid, _) = PbNode::file_leaf(b"other").block();
+    assert!(!dag.contains(&other_cid));
+    assert!(dag.links(&other_cid).is_none() && dag.node(&other_cid).is_none());
+    match write(&dag, Some(&other_cid)).await {
+        Err(ReadSingleFileError::RootCidNotFound {
+            root, header_roots, ..
+        }) => {
+            assert_eq!(root, other_cid);
+            assert_eq!(header_roots[..], [first_cid, second_cid]);
+        }
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn dag_view_incomplete() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, _) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+
+    // Loaded without the leaf, failing once written
+    let car = car_v1(&[root_cid], &[(root_cid, root.clone()), (a_cid, a.clone())]);
+    let dag = load(&car, &Default::default()).await.unwrap();
+    match write(&dag, None).await {
+        Err(ReadSingleFileError::MissingNode(cid)) => assert_eq!(cid, b_cid),
+        x => panic!("other result {:?}", x),
+    }
+
+    // Blocks are validated and bounded while loading
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root.clone()), (a_cid, root.clone())],
+    );
+    match load(&car, &Default::default()).await {
+        Err(ReadSingleFileError::CarDecodeError(CarDecodeError::BlockDigestMismatch(_))) => {}
+        x => panic!("other result {:?}", x.map(|dag| dag.len())),
+    }
+    let car = car_v1(&[root_cid], &[(root_cid, root.clone()), (a_cid, a)]);
+    let options = ReadSingleFileOptions {
+        max_buffer: Some(root.len()),
+        ..Default::default()
+    };
+    match load(&car, &options).await {
+        Err(ReadSingleFileError::MaxBufferedData(limit)) => assert_eq!(limit, root.len()),
+        x => panic!("other result {:?}", x.map(|dag| dag.len())),
+    }
+}
@@ -0,0 +1,97 @@
+use futures::{io::Cursor, AsyncReadExt};
+use rs_car_ipfs::{
+    decompress::{decompress_auto, Compression},
+    single_file::read_single_file_seek,
+};
+use std::fs;
+
+const CAR_FILEPATH: &str = "tests/data/seq_1000.txt.size-32.trickle.car";
+const EXPECTED_FILEPATH: &str = "tests/data/seq_1000.txt";
+
+async fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    async_compression::futures::bufread::GzipEncoder::new(data)
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+    out
+}
+
+async fn zstd(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    async_compression::futures::bufread::ZstdEncoder::new(data)
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+    out
+}
+
+async fn read_decompressed(input: &[u8]) -> (Compression, Vec<u8>) {
+    let mut input = decompress_auto(input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek(&mut input, &mut out, None, None)
+        .await
+        .unwrap();
+    (input.compression(), out.into_inner())
+}
+
+#[test]
+fn sniff_magic_bytes() {
+    assert_eq!(Compression::sniff(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+    assert_eq!(
+        Compression::sniff(&[0x28, 0xb5, 0x2f, 0xfd]),
+        Compression::Zstd
+    );
+    assert_eq!(Compression::sniff(&[0x28, 0xb5]), Compression::None);
+    assert_eq!(Compression::sniff(&[]), Compression::None);
+    assert_eq!(
+        Compression::sniff(&fs::read(CAR_FILEPATH).unwrap()),
+        Compression::None
+    );
+}
+
+#[async_std::test]
+async fn decompress_auto_plain_car() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let (compression, out) = read_decompressed(&car).await;
+    assert_eq!(compression, Compression::None);
+    assert_eq!(out, fs::read(EXPECTED_FILEPATH).unwrap());
+}
+
+#[cfg(feature = "gzip")]
+#[async_std::test]
+async fn decompress_auto_gzip_car() {
+    let car = gzip(&fs::read(CAR_FILEPATH).unwrap()).await;
+    let (compression, out) = read_decompressed(&car).await;
+    assert_eq!(compression, Compression::Gzip);
+    assert_eq!(out, fs::read(EXPECTED_FILEPATH).unwrap());
+}
+
+#[cfg(feature = "zstd")]
+#[async_std::test]
+async fn decompress_auto_zstd_car() {
+    let car = zstd(&fs::read(CAR_FILEPATH).unwrap()).await;
+    let (compression, out) = read_decompressed(&car).await;
+    assert_eq!(compression, Compression::Zstd);
+    assert_eq!(out, fs::read(EXPECTED_FILEPATH).unwrap());
+}
+
+#[cfg(not(feature = "gzip"))]
+#[async_std::test]
+async fn decompress_auto_gzip_feature_disabled() {
+    let car = gzip(&fs::read(CAR_FILEPATH).unwrap()).await;
+    match decompress_auto(car.as_slice()).await {
+        Err(err) => assert_eq!(err.kind(), std::io::ErrorKind::InvalidData),
+        Ok(_) => panic!("expected error"),
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+#[async_std::test]
+async fn decompress_auto_zstd_feature_disabled() {
+    let car = zstd(&fs::read(CAR_FILEPATH).unwrap()).await;
+    match decompress_auto(car.as_slice()).await {
+        Err(err) => assert_eq!(err.kind(), std::io::ErrorKind::InvalidData),
+        Ok(_) => panic!("expected error"),
+    }
+}
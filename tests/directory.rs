@@ -0,0 +1,470 @@
+mod common;
+
+use common::{car_v1, PbLink, PbNode, TYPE_DIRECTORY, TYPE_SYMLINK};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    check_symlink, decode_directory, plan_extraction, read_single_file_buffer,
+    read_single_file_indexed, read_single_file_seek, safe_entry_path, CarFileReader, CarIndex,
+    DirectoryEntry, EntryKind, PlannedEntry, ReadSingleFileError, SymlinkPolicy,
+};
+use std::{fs, path::PathBuf};
+
+fn directory(links: Vec<PbLink>) -> PbNode {
+    PbNode {
+        links,
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn decode_directory_named_entries() {
+    let (a_cid, _) = PbNode::file_leaf(b"a").block();
+    let (b_cid, _) = PbNode::file_leaf(b"b").block();
+    let (dir_cid, dir) = directory(vec![
+        PbLink {
+            name: Some("a.txt".to_string()),
+            tsize: Some(9),
+            ..PbLink::new(a_cid)
+        },
+        PbLink {
+            name: Some("b.txt".to_string()),
+            ..PbLink::new(b_cid)
+        },
+    ])
+    .block();
+
+    assert_eq!(
+        decode_directory(&dir_cid, &dir).unwrap(),
+        vec![
+            DirectoryEntry {
+                name: "a.txt".to_string(),
+                cid: a_cid,
+                tsize: Some(9),
+            },
+            DirectoryEntry {
+                name: "b.txt".to_string(),
+                cid: b_cid,
+                tsize: None,
+            },
+        ]
+    );
+}
+
+#[test]
+fn decode_directory_unnamed_entry() {
+    let (a_cid, _) = PbNode::file_leaf(b"a").block();
+    let (b_cid, _) = PbNode::file_leaf(b"b").block();
+
+    for name in [None, Some(String::new())] {
+        let (dir_cid, dir) = directory(vec![
+            PbLink {
+                name: Some("a.txt".to_string()),
+                ..PbLink::new(a_cid)
+            },
+            PbLink {
+                name: name.clone(),
+                ..PbLink::new(b_cid)
+            },
+        ])
+        .block();
+
+        match decode_directory(&dir_cid, &dir) {
+            Err(ReadSingleFileError::UnnamedDirectoryEntry { cid }) => assert_eq!(cid, b_cid),
+            x => panic!("other result for name {:?}: {:?}", name, x),
+        }
+    }
+}
+
+#[test]
+fn decode_directory_not_a_directory() {
+    let (cid, block) = PbNode::file_leaf(b"a").block();
+    match decode_directory(&cid, &block) {
+        Err(ReadSingleFileError::UnexpectedNodeType { cid: err_cid, .. }) => {
+            assert_eq!(err_cid, cid)
+        }
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_unnamed_file_links() {
+    // File nodes links have no Name, which must not be treated as an error
+    let (a_cid, a) = PbNode::file_leaf(b"hello ").block();
+    let (b_cid, b) = PbNode::file_leaf(b"world").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 6), (b_cid, 5)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek(&mut Cursor::new(car), &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"hello world");
+}
+
+#[async_std::test]
+async fn read_single_file_subtree_root() {
+    // A file nested in the directory of the header, extracted by its CID
+    let (a_cid, a) = PbNode::file_leaf(b"hello ").block();
+    let (b_cid, b) = PbNode::file_leaf(b"world").block();
+    let (file_cid, file) = PbNode::file_branch(&[(a_cid, 6), (b_cid, 5)]).block();
+    let (other_cid, other) = PbNode::file_leaf(b"other").block();
+    let (dir_cid, dir) =
+        directory(vec![named("a.txt", file_cid), named("b.txt", other_cid)]).block();
+    let car = car_v1(
+        &[dir_cid],
+        &[
+            (dir_cid, dir),
+            (file_cid, file),
+            (a_cid, a),
+            (b_cid, b),
+            (other_cid, other),
+        ],
+    );
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer(&mut Cursor::new(&car), &mut out, Some(&file_cid), None)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"hello world");
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek(&mut Cursor::new(&car), &mut out, Some(&file_cid), None)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"hello world");
+
+    let mut car_input = Cursor::new(&car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_indexed(&mut car_input, &mut out, Some(&file_cid), Some(&index))
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"hello world");
+
+    let mut car_input = Cursor::new(&car);
+    let mut out = vec![];
+    let reader = CarFileReader::new(&mut car_input, Some(&file_cid), &Default::default());
+    futures::io::copy(reader, &mut out).await.unwrap();
+    assert_eq!(out, b"hello world");
+}
+
+/// Empty extraction root with a `sub` directory, unique to the test
+fn extraction_root(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("rs-car-ipfs-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("sub")).unwrap();
+    root
+}
+
+fn assert_escape(res: Result<impl std::fmt::Debug, ReadSingleFileError>, expected: &str) {
+    match res {
+        Err(ReadSingleFileError::PathEscape { name }) => assert_eq!(name, expected),
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[test]
+fn safe_entry_path_rejects_traversal() {
+    let root = extraction_root("traversal");
+    let sub = root.join("sub");
+
+    assert_eq!(
+        safe_entry_path(&root, &sub, "a.txt").unwrap(),
+        sub.canonicalize().unwrap().join("a.txt")
+    );
+    for name in ["..", ".", "", "/etc", "../a", "a/b", "a\\b", "a\0"] {
+        assert_escape(safe_entry_path(&root, &sub, name), name);
+    }
+    // `dir` itself outside the root
+    assert_escape(safe_entry_path(&sub, &root, "a.txt"), "a.txt");
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn safe_entry_path_symlinks_on_disk() {
+    use std::os::unix::fs::symlink;
+
+    let root = extraction_root("symlinks-on-disk");
+    let outside = extraction_root("symlinks-on-disk-outside");
+    let sub = root.join("sub");
+    symlink(&outside, sub.join("out")).unwrap();
+    symlink(root.join("sub"), root.join("inside")).unwrap();
+    symlink(root.join("missing"), sub.join("dangling")).unwrap();
+
+    // Writing through an existing symlink or into a linked directory outside the root
+    assert_escape(safe_entry_path(&root, &sub, "out"), "out");
+    assert_escape(safe_entry_path(&root, &sub.join("out"), "a.txt"), "a.txt");
+    assert_escape(safe_entry_path(&root, &sub, "dangling"), "dangling");
+    // Symlinks within the root are followed
+    assert!(safe_entry_path(&root, &root.join("inside"), "a.txt").is_ok());
+    assert!(safe_entry_path(&root, &root, "inside").is_ok());
+
+    fs::remove_dir_all(&root).unwrap();
+    fs::remove_dir_all(&outside).unwrap();
+}
+
+#[test]
+fn check_symlink_targets() {
+    let root = extraction_root("symlink-targets");
+    let sub = root.join("sub");
+    let absolute_inside = root.join("a.txt");
+
+    for target in [
+        "a.txt",
+        "../a.txt",
+        "./x/../../a.txt",
+        absolute_inside.to_str().unwrap(),
+    ] {
+        for policy in [SymlinkPolicy::Reject, SymlinkPolicy::Skip] {
+            assert!(
+                check_symlink(&root, &sub, "link", target, policy).unwrap(),
+                "{}",
+                target
+            );
+        }
+    }
+    for target in ["../../a.txt", "../../../../../../etc/passwd", "/etc/passwd"] {
+        assert_escape(
+            check_symlink(&root, &sub, "link", target, SymlinkPolicy::Reject),
+            "link",
+        );
+        assert!(!check_symlink(&root, &sub, "link", target, SymlinkPolicy::Skip).unwrap());
+    }
+    // The name of the symlink is checked too
+    assert_escape(
+        check_symlink(&root, &sub, "../link", "a.txt", SymlinkPolicy::Skip),
+        "../link",
+    );
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+fn named(name: &str, cid: rs_car::Cid) -> PbLink {
+    PbLink {
+        name: Some(name.to_string()),
+        ..PbLink::new(cid)
+    }
+}
+
+#[async_std::test]
+async fn plan_extraction_manifest() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (link_cid, link) = PbNode {
+        unixfs_type: TYPE_SYMLINK,
+        data: Some(b"../a.txt".to_vec()),
+        ..Default::default()
+    }
+    .block();
+    let (sub_cid, sub) = directory(vec![named("b.txt", b_cid), named("link", link_cid)]).block();
+    let (root_cid, root) = directory(vec![named("a.txt", a_cid), named("sub", sub_cid)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[
+            (root_cid, root),
+            (sub_cid, sub),
+            (link_cid, link),
+            (a_cid, a),
+            (b_cid, b),
+        ],
+    );
+
+    let target = extraction_root("plan");
+    // `sub` already exists as a directory, only `a.txt` conflicts
+    fs::write(target.join("a.txt"), b"existing").unwrap();
+
+    let plan = plan_extraction(&mut car.as_slice(), None, &target)
+        .await
+        .unwrap();
+    let entry = |path: &str, kind, size, conflict| PlannedEntry {
+        path: path.into(),
+        kind,
+        size,
+        conflict,
+    };
+    assert_eq!(
+        plan.entries,
+        vec![
+            entry("", EntryKind::Directory, 0, false),
+            entry("a.txt", EntryKind::File, 4, true),
+            entry("sub", EntryKind::Directory, 0, false),
+            entry("sub/b.txt", EntryKind::File, 2, false),
+            entry("sub/link", EntryKind::Symlink, 8, false),
+        ]
+    );
+    assert_eq!((plan.total_bytes, plan.conflicts), (6, 1));
+    // Nothing written
+    assert_eq!(fs::read(target.join("a.txt")).unwrap(), b"existing");
+    assert!(!target.join("sub/b.txt").exists());
+
+    fs::remove_dir_all(&target).unwrap();
+}
+
+#[async_std::test]
+async fn plan_extraction_file_root() {
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let plan = plan_extraction(&mut car.as_slice(), None, "does-not-exist".as_ref())
+        .await
+        .unwrap();
+    assert_eq!(
+        plan.entries,
+        vec![PlannedEntry {
+            path: "".into(),
+            kind: EntryKind::File,
+            size: 10 * 1024,
+            conflict: false,
+        }]
+    );
+}
+
+#[async_std::test]
+async fn plan_extraction_rejects_traversal() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (root_cid, root) = directory(vec![named("..", a_cid)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    assert_escape(
+        plan_extraction(&mut car.as_slice(), None, "out".as_ref()).await,
+        "..",
+    );
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn extract_directory_link_names() {
+    use rs_car_ipfs::single_file::extract_directory;
+
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (sub_cid, sub) = directory(vec![named("b.txt", b_cid)]).block();
+    let (root_cid, root) = directory(vec![
+        named("a.txt", a_cid),
+        named("nested", sub_cid),
+        PbLink::new(b_cid),
+    ])
+    .block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (a_cid, a), (sub_cid, sub), (b_cid, b)],
+    );
+
+    let target = extraction_root("extract-directory");
+    let files = extract_directory(&mut car.as_slice(), None, &target, &Default::default())
+        .await
+        .unwrap();
+    let paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+    let unnamed = PathBuf::from(b_cid.to_string());
+    assert_eq!(
+        paths,
+        [
+            PathBuf::from("a.txt"),
+            PathBuf::from("nested/b.txt"),
+            unnamed.clone()
+        ]
+    );
+    assert_eq!(files[0].summary.bytes_written, 4);
+    assert_eq!(fs::read(target.join("a.txt")).unwrap(), b"aaaa");
+    assert_eq!(fs::read(target.join("nested/b.txt")).unwrap(), b"bb");
+    assert_eq!(fs::read(target.join(&unnamed)).unwrap(), b"bb");
+
+    // Nothing is written over
+    match extract_directory(&mut car.as_slice(), None, &target, &Default::default()).await {
+        Err(ReadSingleFileError::EntryExists { name }) => assert_eq!(name, "a.txt"),
+        x => panic!("other result {:?}", x),
+    }
+    assert_eq!(fs::read(target.join("a.txt")).unwrap(), b"aaaa");
+    fs::remove_dir_all(&target).unwrap();
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn extract_directory_name_collision() {
+    use rs_car_ipfs::single_file::extract_directory;
+
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = directory(vec![named("same", a_cid), named("same", b_cid)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    let target = extraction_root("extract-directory-collision");
+    match extract_directory(&mut car.as_slice(), None, &target, &Default::default()).await {
+        Err(ReadSingleFileError::EntryExists { name }) => assert_eq!(name, "same"),
+        x => panic!("other result {:?}", x),
+    }
+    assert_eq!(fs::read(target.join("same")).unwrap(), b"aaaa");
+    fs::remove_dir_all(&target).unwrap();
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn extract_directory_file_root() {
+    use rs_car_ipfs::single_file::extract_directory;
+
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let target = extraction_root("extract-directory-file-root");
+    let files = extract_directory(&mut car.as_slice(), None, &target, &Default::default())
+        .await
+        .unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, PathBuf::from(files[0].cid.to_string()));
+    assert!(
+        fs::read(target.join(&files[0].path)).unwrap()
+            == fs::read("tests/data/rand_10K.bin").unwrap()
+    );
+    fs::remove_dir_all(&target).unwrap();
+}
+
+/// Directory of a tiny file inlined with an identity CID and a file with its own block
+fn directory_with_inline_file() -> Vec<u8> {
+    let (_, inline_block) = PbNode::file_leaf(b"tiny").block();
+    let inline_cid = common::cid_identity(common::DAG_PB, &inline_block);
+    let (file_cid, file) = PbNode::file_leaf(b"normal file").block();
+    let (root_cid, root) = directory(vec![
+        named("tiny.txt", inline_cid),
+        named("file.txt", file_cid),
+    ])
+    .block();
+    car_v1(&[root_cid], &[(root_cid, root), (file_cid, file)])
+}
+
+#[async_std::test]
+async fn plan_extraction_inline_file() {
+    let car = directory_with_inline_file();
+    let plan = plan_extraction(&mut car.as_slice(), None, "does-not-exist".as_ref())
+        .await
+        .unwrap();
+    let files: Vec<_> = plan
+        .entries
+        .iter()
+        .filter(|entry| entry.kind == EntryKind::File)
+        .map(|entry| (entry.path.clone(), entry.size))
+        .collect();
+    assert_eq!(
+        files,
+        [
+            (PathBuf::from("tiny.txt"), 4),
+            (PathBuf::from("file.txt"), 11)
+        ]
+    );
+    assert_eq!(plan.total_bytes, 15);
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn extract_directory_inline_file() {
+    use rs_car_ipfs::single_file::extract_directory;
+
+    let car = directory_with_inline_file();
+    let target = extraction_root("extract-directory-inline");
+    let files = extract_directory(&mut car.as_slice(), None, &target, &Default::default())
+        .await
+        .unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!(files[0].summary.bytes_written, 4);
+    assert_eq!(fs::read(target.join("tiny.txt")).unwrap(), b"tiny");
+    assert_eq!(fs::read(target.join("file.txt")).unwrap(), b"normal file");
+    fs::remove_dir_all(&target).unwrap();
+}
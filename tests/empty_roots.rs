@@ -0,0 +1,219 @@
+//! CARs with an empty roots list in their header, e.g. written without wrapping the DAG
+
+mod common;
+
+use common::{car_v1, read_all, PbNode};
+use futures::{io::Cursor, AsyncReadExt};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    car_info, extract_many, file_size, inspect_root, read_single_file_buffer_with_options,
+    read_single_file_indexed_with_options, read_single_file_seek_with_options, CarFileReader,
+    CarIndex, ReadSingleFileError, ReadSingleFileOptions, ReadSummary, RootSelector,
+};
+
+/// A file of two leaves in a CAR without roots, with its root and its data
+fn car_without_roots() -> (Vec<u8>, Cid, Vec<u8>) {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let car = car_v1(&[], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+    (car, root_cid, b"aaaabb".to_vec())
+}
+
+/// Results of the readers of the file `root_cid` of `car`
+async fn read_root(
+    car: &[u8],
+    root_cid: Option<&Cid>,
+) -> Vec<Result<Vec<u8>, ReadSingleFileError>> {
+    let options = Default::default();
+    let mut results = vec![];
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_buffer_with_options(&mut &car[..], &mut out, root_cid, &options).await;
+    results.push(res.map(|_| out.into_inner()));
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_seek_with_options(&mut &car[..], &mut out, root_cid, &options).await;
+    results.push(res.map(|_| out.into_inner()));
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        root_cid,
+        Some(&index),
+        &options,
+    )
+    .await;
+    results.push(res.map(|_| out.into_inner()));
+
+    let mut car_input = car;
+    let mut file = vec![];
+    let res = CarFileReader::new(&mut car_input, root_cid, &options)
+        .read_to_end(&mut file)
+        .await
+        .map(|_| file)
+        .map_err(|err| *err.into_inner().unwrap().downcast().unwrap());
+    results.push(res);
+    results
+}
+
+#[async_std::test]
+async fn empty_roots_explicit_root() {
+    let (car, root_cid, data) = car_without_roots();
+
+    for res in read_root(&car, Some(&root_cid)).await {
+        assert_eq!(res.unwrap(), data);
+    }
+    let mut out = Cursor::new(Vec::new());
+    let results = extract_many(&mut car.as_slice(), vec![(root_cid, &mut out)])
+        .await
+        .unwrap();
+    assert_eq!(results[0].as_ref().unwrap().root_cid, Some(root_cid));
+    assert_eq!(out.into_inner(), data);
+
+    assert_eq!(
+        file_size(&mut car.as_slice(), Some(&root_cid))
+            .await
+            .unwrap(),
+        6
+    );
+    assert_eq!(
+        inspect_root(&mut car.as_slice(), Some(&root_cid))
+            .await
+            .unwrap()
+            .cid,
+        root_cid
+    );
+    let info = car_info(&mut car.as_slice(), true).await.unwrap();
+    assert_eq!((info.roots, info.block_count), (vec![], 3));
+
+    // Without the root block, the CAR claims to contain no root
+    let car = car_v1(&[], &[]);
+    for res in read_root(&car, Some(&root_cid)).await {
+        match res {
+            Err(ReadSingleFileError::RootCidNotFound {
+                root,
+                blocks_seen,
+                header_roots,
+            }) => assert_eq!((root, blocks_seen, header_roots.len()), (root_cid, 0, 0)),
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn empty_roots_no_root() {
+    let (car, _, _) = car_without_roots();
+
+    let results = [
+        read_all(&car, &Default::default()).await,
+        read_root(&car, None).await,
+    ];
+    for res in results.into_iter().flatten() {
+        match res {
+            Err(ReadSingleFileError::NoRootsInHeader) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    match file_size(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::NoRootsInHeader) => {}
+        x => panic!("other result {:?}", x),
+    }
+    match inspect_root(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::NoRootsInHeader) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn empty_roots_last_block() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let options = ReadSingleFileOptions {
+        root_selector: RootSelector::LastBlock,
+        ..Default::default()
+    };
+
+    // Children first, the root last, with or without roots in the header
+    let blocks = [(a_cid, a), (b_cid, b), (root_cid, root)];
+    for header_roots in [vec![], vec![a_cid]] {
+        let car = car_v1(&header_roots, &blocks);
+        for res in read_last_block(&car, &options).await {
+            let (summary, out) = res.unwrap();
+            assert_eq!(out, b"aaaabb");
+            assert_eq!(summary.root_cid, Some(root_cid));
+        }
+    }
+
+    // An explicit root CID is read whatever the selector
+    let car = car_v1(&[], &blocks);
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, Some(&a_cid), &options)
+            .await
+            .unwrap();
+    assert_eq!(
+        (summary.root_cid, out.into_inner()),
+        (Some(a_cid), b"aaaa".to_vec())
+    );
+
+    // Whatever the last block is, here a leaf after the root
+    let car = car_v1(&[], &[blocks[2].clone(), blocks[0].clone()]);
+    for res in read_last_block(&car, &options).await {
+        assert_eq!(res.unwrap().1, b"aaaa");
+    }
+    let car = car_v1(&[], &[]);
+    for res in read_last_block(&car, &options).await {
+        match res {
+            Err(ReadSingleFileError::NoRootsInHeader) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn last_block_max_buffer() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    // Every block is held until the CAR ends, the limit counts them all
+    let max_buffer = a.len() + b.len();
+    let car = car_v1(&[], &[(a_cid, a), (b_cid, b), (root_cid, root)]);
+    let options = ReadSingleFileOptions {
+        root_selector: RootSelector::LastBlock,
+        max_buffer: Some(max_buffer),
+        ..Default::default()
+    };
+
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options).await
+    {
+        Err(ReadSingleFileError::MaxBufferedData(limit)) => assert_eq!(limit, max_buffer),
+        x => panic!("other result {:?}", x),
+    }
+}
+
+/// Results of the buffer reader and of the indexed reader with and without an index
+async fn read_last_block(
+    car: &[u8],
+    options: &ReadSingleFileOptions,
+) -> Vec<Result<(ReadSummary, Vec<u8>), ReadSingleFileError>> {
+    let mut results = vec![];
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_buffer_with_options(&mut &car[..], &mut out, None, options).await;
+    results.push(res.map(|summary| (summary, out.into_inner())));
+
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    for index in [Some(&index), None] {
+        let mut out = Cursor::new(Vec::new());
+        let res =
+            read_single_file_indexed_with_options(&mut car_input, &mut out, None, index, options)
+                .await;
+        results.push(res.map(|summary| (summary, out.into_inner())));
+    }
+    results
+}
@@ -0,0 +1,292 @@
+//! Codes of the errors are matched by consumers in other languages, they must never change
+
+use rs_car::Cid;
+use rs_car_ipfs::{
+    single_file::{ErrorDetails, ReadSingleFileError},
+    UnixFsType,
+};
+use std::io;
+
+fn cid() -> Cid {
+    Cid::try_from("QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf").unwrap()
+}
+
+/// One error of each variant, with its code
+fn all_errors() -> Vec<(ReadSingleFileError, &'static str)> {
+    use ReadSingleFileError::*;
+    vec![
+        (IoError(io::ErrorKind::UnexpectedEof.into()), "io_error"),
+        (
+            CarDecodeError(rs_car::CarDecodeError::BlockStartEOF),
+            "car_decode_error",
+        ),
+        (NotSingleRoot { roots: vec![] }, "not_single_root"),
+        (InvalidUnixFs("".into()), "invalid_unixfs"),
+        (InvalidUnixFsHash("".into()), "invalid_unixfs_hash"),
+        (MissingNode(cid()), "missing_node"),
+        (MaxBufferedData(1), "max_buffered_data"),
+        (RootCidIsNotFile, "root_cid_is_not_file"),
+        (DataNodesNotSorted, "data_nodes_not_sorted"),
+        (PendingLinksAtEOF(vec![cid()]), "pending_links_at_eof"),
+        (PBLinkHasNoHash, "pblink_has_no_hash"),
+        (InternalError("".into()), "internal_error"),
+        (
+            WriteLimitExceeded {
+                limit: 1,
+                attempted: 2,
+                cid: Some(cid()),
+            },
+            "write_limit_exceeded",
+        ),
+        (InvalidCarIndex("".into()), "invalid_car_index"),
+        (
+            MaxBlocksExceeded { limit: 1, read: 2 },
+            "max_blocks_exceeded",
+        ),
+        (
+            UnexpectedNodeType {
+                cid: cid(),
+                found: UnixFsType::Directory,
+            },
+            "unexpected_node_type",
+        ),
+        (NotCanonical { computed: cid() }, "not_canonical"),
+        (
+            UnnamedDirectoryEntry { cid: cid() },
+            "unnamed_directory_entry",
+        ),
+        (SkippedBlockUnknownSize(cid()), "skipped_block_unknown_size"),
+        (UnsupportedCodec(0x71), "unsupported_codec"),
+        (
+            FileSizeMismatch {
+                declared: 1,
+                written: 2,
+            },
+            "file_size_mismatch",
+        ),
+        (
+            RootCidNotFound {
+                root: cid(),
+                blocks_seen: 3,
+                header_roots: Box::new([]),
+            },
+            "root_cid_not_found",
+        ),
+        (FileSizeUnknown(cid()), "file_size_unknown"),
+        (
+            SegmentRootMismatch {
+                segment: 1,
+                roots: vec![],
+            },
+            "segment_root_mismatch",
+        ),
+        (BlockingInAsyncContext, "blocking_in_async_context"),
+        (
+            SizeMismatch {
+                expected: 1,
+                actual: 2,
+            },
+            "size_mismatch",
+        ),
+        (
+            ExpectedSizeMismatch {
+                expected: 1,
+                declared: 2,
+            },
+            "expected_size_mismatch",
+        ),
+        (
+            InvalidLinkDigestLength {
+                cid: cid(),
+                expected: 32,
+            },
+            "invalid_link_digest_length",
+        ),
+        (PathEscape { name: "..".into() }, "path_escape"),
+        (
+            BlocksizesUnknown {
+                node: cid(),
+                link_index: 1,
+            },
+            "blocksizes_unknown",
+        ),
+        (
+            DeclaredSizeExceedsLimit {
+                declared: 2,
+                limit: 1,
+            },
+            "declared_size_exceeds_limit",
+        ),
+        (
+            MapLeaf {
+                cid: cid(),
+                error: "failed".into(),
+            },
+            "map_leaf",
+        ),
+        (MapLeafUnsupported, "map_leaf_unsupported"),
+        (CycleDetected(cid()), "cycle_detected"),
+        (
+            BlocksizeMismatch {
+                cid: cid(),
+                declared: 2,
+                actual: 1,
+            },
+            "blocksize_mismatch",
+        ),
+        (NoRootsInHeader, "no_roots_in_header"),
+        (MalformedHeader("".into()), "malformed_header"),
+        (
+            ReorderWindowExceeded {
+                needed: 2,
+                limit: 1,
+            },
+            "reorder_window_exceeded",
+        ),
+        (
+            VisitorError {
+                offset: 1,
+                error: "failed".into(),
+            },
+            "visitor_error",
+        ),
+        (
+            TsizeMismatch {
+                cid: cid(),
+                declared: 2,
+                actual: 1,
+            },
+            "tsize_mismatch",
+        ),
+        (CompressedOutputUnsupported, "compressed_output_unsupported"),
+        (
+            BlockTooLarge {
+                cid: cid(),
+                size: 2,
+                limit: 1,
+            },
+            "block_too_large",
+        ),
+        (EntryExists { name: "a".into() }, "entry_exists"),
+    ]
+}
+
+#[test]
+fn error_codes_stable() {
+    for (err, code) in all_errors() {
+        assert_eq!(err.code(), code, "{:?}", err);
+        assert_eq!(err.details().code, code);
+    }
+}
+
+#[test]
+fn error_details() {
+    let cid_str = cid().to_string();
+    let cases = [
+        (
+            ReadSingleFileError::PendingLinksAtEOF(vec![cid(), cid()]),
+            ErrorDetails {
+                code: "pending_links_at_eof",
+                cids: vec![cid_str.clone(), cid_str.clone()],
+                ..Default::default()
+            },
+        ),
+        (
+            ReadSingleFileError::RootCidNotFound {
+                root: cid(),
+                blocks_seen: 3,
+                header_roots: Box::new([cid()]),
+            },
+            ErrorDetails {
+                code: "root_cid_not_found",
+                cid: Some(cid_str.clone()),
+                roots: vec![cid_str.clone()],
+                actual: Some(3),
+                ..Default::default()
+            },
+        ),
+        (
+            ReadSingleFileError::UnexpectedNodeType {
+                cid: cid(),
+                found: UnixFsType::HAMTShard,
+            },
+            ErrorDetails {
+                code: "unexpected_node_type",
+                cid: Some(cid_str.clone()),
+                node_type: Some("HAMTShard".into()),
+                ..Default::default()
+            },
+        ),
+        (
+            ReadSingleFileError::DeclaredSizeExceedsLimit {
+                declared: 2,
+                limit: 1,
+            },
+            ErrorDetails {
+                code: "declared_size_exceeds_limit",
+                declared: Some(2),
+                limit: Some(1),
+                ..Default::default()
+            },
+        ),
+        (
+            ReadSingleFileError::MapLeaf {
+                cid: cid(),
+                error: "failed".into(),
+            },
+            ErrorDetails {
+                code: "map_leaf",
+                cid: Some(cid_str.clone()),
+                message: Some("failed".into()),
+                ..Default::default()
+            },
+        ),
+        (
+            ReadSingleFileError::WriteLimitExceeded {
+                limit: 1,
+                attempted: 2,
+                cid: Some(cid()),
+            },
+            ErrorDetails {
+                code: "write_limit_exceeded",
+                cid: Some(cid_str.clone()),
+                limit: Some(1),
+                actual: Some(2),
+                ..Default::default()
+            },
+        ),
+        (
+            ReadSingleFileError::WriteLimitExceeded {
+                limit: 1,
+                attempted: 2,
+                cid: None,
+            },
+            ErrorDetails {
+                code: "write_limit_exceeded",
+                limit: Some(1),
+                actual: Some(2),
+                ..Default::default()
+            },
+        ),
+        (
+            ReadSingleFileError::RootCidIsNotFile,
+            ErrorDetails {
+                code: "root_cid_is_not_file",
+                ..Default::default()
+            },
+        ),
+    ];
+    for (err, details) in cases {
+        assert_eq!(err.details(), details);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn errors_serialize() {
+    fn assert_serialize<T: serde::Serialize>(_: &T) {}
+    for (err, _) in all_errors() {
+        assert_serialize(&err);
+        assert_serialize(&err.details());
+    }
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use common::{car_v1, cid_v1, read_all, PbNode, RAW};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_seek_with_options, ReadSingleFileError, ReadSingleFileOptions,
+};
+use std::fs;
+
+fn expected_size(size: u64) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        expected_size: Some(size),
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn expected_size_match() {
+    let car = fs::read("tests/data/rand_100K.bin.size-512.normal.car").unwrap();
+    let expected = fs::read("tests/data/rand_100K.bin").unwrap();
+
+    for res in read_all(&car, &expected_size(expected.len() as u64)).await {
+        assert_eq!(res.unwrap(), expected);
+    }
+}
+
+#[async_std::test]
+async fn expected_size_mismatch_declared() {
+    let car = fs::read("tests/data/seq_1000.txt.size-32.normal.car").unwrap();
+    let len = fs::read("tests/data/seq_1000.txt").unwrap().len() as u64;
+
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_seek_with_options(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        &expected_size(len + 1),
+    )
+    .await;
+    assert!(res.is_err());
+    // Nothing written
+    assert_eq!(out.into_inner(), b"");
+
+    for size in [0, len - 1, len + 1] {
+        for res in read_all(&car, &expected_size(size)).await {
+            match res {
+                Err(ReadSingleFileError::ExpectedSizeMismatch { expected, declared }) => {
+                    assert_eq!((expected, declared), (size, len))
+                }
+                x => panic!("other result {:?}", x),
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn expected_size_raw_root() {
+    let car = car_v1(
+        &[cid_v1(RAW, b"aaaa")],
+        &[(cid_v1(RAW, b"aaaa"), b"aaaa".to_vec())],
+    );
+    for res in read_all(&car, &expected_size(5)).await {
+        match res {
+            Err(ReadSingleFileError::ExpectedSizeMismatch { expected, declared }) => {
+                assert_eq!((expected, declared), (5, 4))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn expected_size_undeclared() {
+    // Falls back to the written total if the root does not declare a size
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (root_cid, root) = PbNode {
+        filesize: None,
+        ..PbNode::file_branch(&[(a_cid, 4)])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    for res in read_all(&car, &expected_size(4)).await {
+        assert_eq!(res.unwrap(), b"aaaa");
+    }
+    for res in read_all(&car, &expected_size(5)).await {
+        match res {
+            Err(ReadSingleFileError::SizeMismatch { expected, actual }) => {
+                assert_eq!((expected, actual), (5, 4))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn expected_size_wrong_declared_filesize() {
+    // The DAG declares the same wrong size as the caller expects
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (root_cid, root) = PbNode {
+        filesize: Some(10),
+        ..PbNode::file_branch(&[(a_cid, 4)])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    for res in read_all(&car, &expected_size(10)).await {
+        match res {
+            Err(ReadSingleFileError::SizeMismatch { expected, actual }) => {
+                assert_eq!((expected, actual), (10, 4))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+mod common;
+
+use common::{car_v1, PbLink, PbNode, TYPE_DIRECTORY};
+use futures::{io::Cursor, StreamExt};
+use rs_car::{CarReader, Cid};
+use rs_car_ipfs::single_file::{
+    decode_directory, extract_many, read_single_file_seek, ReadSingleFileError,
+};
+use std::fs;
+
+const FIXTURES: [&str; 3] = [
+    "helloworld.txt.size-32.normal.car",
+    "rand_10K.bin.size-512.normal.car",
+    "config.toml.size-1.trickle.car",
+];
+
+/// Root and blocks of a CAR fixture
+async fn fixture_blocks(name: &str) -> (Cid, Vec<(Cid, Vec<u8>)>) {
+    let car = fs::read(format!("tests/data/{}", name)).unwrap();
+    let mut car_input = car.as_slice();
+    let mut reader = CarReader::new(&mut car_input, true).await.unwrap();
+    let root = reader.header.roots[0];
+    let mut blocks = vec![];
+    while let Some(item) = reader.next().await {
+        blocks.push(item.unwrap());
+    }
+    (root, blocks)
+}
+
+async fn extract(
+    car: &[u8],
+    roots: &[Cid],
+) -> Result<Vec<Result<Vec<u8>, ReadSingleFileError>>, ReadSingleFileError> {
+    let mut outs: Vec<_> = roots.iter().map(|_| Cursor::new(Vec::new())).collect();
+    let requests = roots.iter().copied().zip(outs.iter_mut()).collect();
+    let results = extract_many(&mut Cursor::new(car), requests).await?;
+    Ok(results
+        .into_iter()
+        .zip(outs)
+        .map(|(res, out)| {
+            let summary = res?;
+            assert_eq!(summary.bytes_written, out.get_ref().len() as u64);
+            Ok(out.into_inner())
+        })
+        .collect())
+}
+
+#[async_std::test]
+async fn extract_many_directory() {
+    // A directory of the files of the fixtures, with their blocks one file after the other
+    let mut links = vec![];
+    let mut blocks = vec![];
+    for name in FIXTURES {
+        let (root, file_blocks) = fixture_blocks(name).await;
+        links.push(PbLink {
+            name: Some(name.to_string()),
+            ..PbLink::new(root)
+        });
+        blocks.extend(file_blocks);
+    }
+    let (dir_cid, dir) = PbNode {
+        links,
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+    .block();
+    blocks.insert(0, (dir_cid, dir.clone()));
+    let car = car_v1(&[dir_cid], &blocks);
+
+    let roots: Vec<_> = decode_directory(&dir_cid, &dir)
+        .unwrap()
+        .iter()
+        .map(|entry| entry.cid)
+        .collect();
+    let results = extract(&car, &roots).await.unwrap();
+    for (name, res) in FIXTURES.iter().zip(results) {
+        let expected = fs::read(format!(
+            "tests/data/{}",
+            name.split(".size").next().unwrap()
+        ));
+        assert!(res.unwrap() == expected.unwrap(), "{}", name);
+    }
+
+    // Same as the single file reader, and each file as often as requested
+    let roots = [roots[1], roots[0], roots[1]];
+    let results = extract(&car, &roots).await.unwrap();
+    for (root, res) in roots.iter().zip(results) {
+        let mut expected = Cursor::new(Vec::new());
+        read_single_file_seek(&mut car.as_slice(), &mut expected, Some(root), None)
+            .await
+            .unwrap();
+        assert!(res.unwrap() == expected.into_inner());
+    }
+}
+
+#[async_std::test]
+async fn extract_many_shared_blocks() {
+    // Files sharing leaves, interleaved in one CAR
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (c_cid, c) = PbNode::file_leaf(b"c").block();
+    let (one_cid, one) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let (two_cid, two) =
+        PbNode::file_branch(&[(a_cid, 4), (a_cid, 4), (b_cid, 2), (c_cid, 1)]).block();
+    let car = car_v1(
+        &[one_cid, two_cid],
+        &[
+            (one_cid, one),
+            (two_cid, two),
+            (a_cid, a),
+            (b_cid, b),
+            (c_cid, c),
+        ],
+    );
+
+    let results = extract(&car, &[one_cid, two_cid, b_cid]).await.unwrap();
+    let results: Vec<_> = results.into_iter().map(Result::unwrap).collect();
+    assert_eq!(results, [&b"aaaabb"[..], &b"aaaaaaaabbc"[..], &b"bb"[..]]);
+}
+
+#[async_std::test]
+async fn extract_many_errors_per_request() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (file_cid, file) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    // Valid block, not UnixFS
+    let invalid = vec![0x0a, 0x05, 0xff];
+    let invalid_cid = common::cid_v0(&invalid);
+    let (broken_cid, broken) = PbNode::file_branch(&[(a_cid, 4), (invalid_cid, 1)]).block();
+    let (dir_cid, dir) = PbNode {
+        links: vec![PbLink {
+            name: Some("file".to_string()),
+            ..PbLink::new(file_cid)
+        }],
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+    .block();
+    let (missing_cid, _) = PbNode::file_leaf(b"missing").block();
+    let (pending_cid, pending) = PbNode::file_branch(&[(a_cid, 4), (missing_cid, 7)]).block();
+
+    let car = car_v1(
+        &[dir_cid],
+        &[
+            (dir_cid, dir),
+            (file_cid, file),
+            (broken_cid, broken),
+            (pending_cid, pending),
+            (a_cid, a),
+            (invalid_cid, invalid),
+            (b_cid, b),
+        ],
+    );
+    let results = extract(
+        &car,
+        &[missing_cid, file_cid, dir_cid, broken_cid, pending_cid],
+    )
+    .await
+    .unwrap();
+    match &results[..] {
+        [Err(ReadSingleFileError::RootCidNotFound { root, .. }), Ok(file), Err(ReadSingleFileError::RootCidIsNotFile), Err(ReadSingleFileError::InvalidUnixFs(_)), Err(ReadSingleFileError::PendingLinksAtEOF(pending))] =>
+        {
+            assert_eq!(*root, missing_cid);
+            assert_eq!(file, b"aaaabb");
+            assert_eq!(pending, &[missing_cid]);
+        }
+        x => panic!("other results {:?}", x),
+    }
+
+    // A failure of the stream fails every request
+    let mut car = car;
+    let last = car.len() - 1;
+    car[last] ^= 1;
+    match extract(&car, &[file_cid, a_cid]).await {
+        Err(ReadSingleFileError::CarDecodeError(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn read_all_files_two_roots() {
+    use rs_car_ipfs::single_file::read_all_files;
+
+    let (one_root, mut blocks) = fixture_blocks(FIXTURES[0]).await;
+    let (two_root, two_blocks) = fixture_blocks(FIXTURES[1]).await;
+    blocks.extend(two_blocks);
+    let (dir_cid, dir) = PbNode {
+        links: vec![PbLink::new(one_root)],
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+    .block();
+    blocks.push((dir_cid, dir));
+    let car = car_v1(&[one_root, two_root, one_root, dir_cid], &blocks);
+
+    let out_dir = std::env::temp_dir().join(format!("rs-car-ipfs-read-all-{}", std::process::id()));
+    fs::create_dir_all(&out_dir).unwrap();
+    let results = read_all_files(&mut car.as_slice(), &out_dir).await.unwrap();
+
+    let roots: Vec<_> = results.iter().map(|(root_cid, _)| *root_cid).collect();
+    assert_eq!(roots, [one_root, two_root, dir_cid]);
+    for ((root_cid, res), name) in results.iter().zip(&FIXTURES[..2]) {
+        let expected = fs::read(format!(
+            "tests/data/{}",
+            name.split(".size").next().unwrap()
+        ))
+        .unwrap();
+        assert_eq!(
+            res.as_ref().unwrap().bytes_written,
+            expected.len() as u64,
+            "{}",
+            name
+        );
+        let written = fs::read(out_dir.join(root_cid.to_string())).unwrap();
+        assert!(written == expected, "{}", name);
+    }
+    assert!(matches!(
+        results[2].1,
+        Err(ReadSingleFileError::RootCidIsNotFile)
+    ));
+    fs::remove_dir_all(&out_dir).unwrap();
+}
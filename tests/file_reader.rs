@@ -0,0 +1,195 @@
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec, Order},
+    PbNode,
+};
+use futures::{
+    io::{copy, Cursor},
+    AsyncReadExt,
+};
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer, read_single_file_prefix, CarFileReader, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+use std::{fs, io};
+
+/// File read through `copy`, or the `ReadSingleFileError` wrapped in the `io::Error`
+async fn copy_file(car: &[u8], options: &ReadSingleFileOptions) -> Result<Vec<u8>, io::Error> {
+    let mut car_input = Cursor::new(car);
+    let mut out = vec![];
+    copy(CarFileReader::new(&mut car_input, None, options), &mut out).await?;
+    Ok(out)
+}
+
+fn into_read_error(err: io::Error) -> ReadSingleFileError {
+    *err.into_inner()
+        .expect("wrapped error")
+        .downcast::<ReadSingleFileError>()
+        .expect("ReadSingleFileError")
+}
+
+#[async_std::test]
+async fn car_file_reader_fixtures() {
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "car") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+
+        let mut buffered = Cursor::new(Vec::new());
+        read_single_file_buffer(&mut car.as_slice(), &mut buffered, None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            copy_file(&car, &Default::default()).await.unwrap(),
+            buffered.into_inner(),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[async_std::test]
+async fn car_file_reader_any_order() {
+    let data = pseudo_random(10_000, 1);
+    for order in [
+        Order::Dfs,
+        Order::Bfs,
+        Order::ChildrenFirst,
+        Order::Random(5),
+    ] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 4,
+            order,
+            ..Default::default()
+        }
+        .build();
+        assert_eq!(
+            copy_file(&generated.car, &Default::default())
+                .await
+                .unwrap(),
+            data,
+            "{:?}",
+            order
+        );
+    }
+}
+
+#[async_std::test]
+async fn car_file_reader_small_reads() {
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let expected = fs::read("tests/data/rand_10K.bin").unwrap();
+
+    let mut car_input = Cursor::new(&car);
+    let mut reader = CarFileReader::new(&mut car_input, None, &Default::default());
+    let mut out = vec![];
+    let mut buf = [0; 7];
+    loop {
+        let n = reader.read(&mut buf).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(out, expected);
+    // EOF again after the end
+    assert_eq!(reader.read(&mut buf).await.unwrap(), 0);
+}
+
+#[async_std::test]
+async fn car_file_reader_missing_block() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, _) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    // The bytes before the missing block are read, EOF is never reported
+    let mut car_input = Cursor::new(&car);
+    let mut reader = CarFileReader::new(&mut car_input, None, &Default::default());
+    let mut out = vec![];
+    let err = reader.read_to_end(&mut out).await.unwrap_err();
+    assert_eq!(out, b"aaaa");
+    match into_read_error(err) {
+        ReadSingleFileError::PendingLinksAtEOF(cids) => assert_eq!(cids, vec![b_cid]),
+        x => panic!("other error {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn car_file_reader_max_buffer() {
+    let data = pseudo_random(1000, 2);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        order: Order::ChildrenFirst,
+        ..Default::default()
+    }
+    .build();
+
+    let options = |max_buffer| ReadSingleFileOptions {
+        max_buffer: Some(max_buffer),
+        ..Default::default()
+    };
+    assert_eq!(
+        copy_file(&generated.car, &options(1000)).await.unwrap(),
+        data
+    );
+    let err = copy_file(&generated.car, &options(999)).await.unwrap_err();
+    match into_read_error(err) {
+        ReadSingleFileError::MaxBufferedData(999) => {}
+        x => panic!("other error {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn read_prefix() {
+    let car = fs::read("tests/data/rand_100K.bin.size-512.normal.car").unwrap();
+    let expected = fs::read("tests/data/rand_100K.bin").unwrap();
+
+    for n in [0, 1, 4096, 5000, expected.len() as u64] {
+        let mut car_input = Cursor::new(&car);
+        let mut out = Cursor::new(Vec::new());
+        let summary = read_single_file_prefix(&mut car_input, &mut out, None, n)
+            .await
+            .unwrap();
+        assert_eq!(summary.bytes_written, n);
+        assert_eq!(out.into_inner(), &expected[..n as usize]);
+        // Stops reading the CAR once the prefix is written
+        if n < 10_000 {
+            assert!(car_input.position() < car.len() as u64 / 4, "n {}", n);
+        }
+    }
+
+    // Longer than the file
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_prefix(&mut car.as_slice(), &mut out, None, u64::MAX)
+        .await
+        .unwrap();
+    assert_eq!(summary.bytes_written, expected.len() as u64);
+    assert_eq!(out.into_inner(), expected);
+}
+
+#[async_std::test]
+async fn read_prefix_missing_block() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, _) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_prefix(&mut car.as_slice(), &mut out, None, 4)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"aaaa");
+
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_prefix(&mut car.as_slice(), &mut out, None, 5).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(cids)) => assert_eq!(cids, vec![b_cid]),
+        x => panic!("other result {:?}", x),
+    }
+}
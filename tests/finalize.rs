@@ -0,0 +1,224 @@
+mod common;
+
+use common::{car_v1, PbNode, Recorder};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_file, read_single_file_seek_with_options, CarIndex, Finalize,
+    ReadSingleFileError, ReadSingleFileOptions,
+};
+use std::fs;
+
+const CAR_FILEPATH: &str = "tests/data/seq_1000.txt.size-32.normal.car";
+const EXPECTED_FILEPATH: &str = "tests/data/seq_1000.txt";
+
+fn finalize(finalize: Finalize) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        finalize,
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn finalize_levels() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+
+    let mut out = Recorder::default();
+    let summary = read_single_file_seek_file(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        &finalize(Finalize::FlushAndSync),
+    )
+    .await
+    .unwrap();
+    assert_eq!(summary.finalized, Finalize::FlushAndSync);
+    assert_eq!(out.flushes, vec![expected.len()]);
+    assert_eq!(out.syncs, vec![expected.len()]);
+
+    for level in [Finalize::None, Finalize::Flush, Finalize::FlushAndSync] {
+        // Without SyncAll the readers flush at most
+        let completed = level.min(Finalize::Flush);
+        let flushes = if completed == Finalize::None {
+            vec![]
+        } else {
+            vec![expected.len()]
+        };
+
+        let mut out = Recorder::default();
+        let summary = read_single_file_seek_with_options(
+            &mut car.as_slice(),
+            &mut out,
+            None,
+            &finalize(level),
+        )
+        .await
+        .unwrap();
+        assert_eq!((summary.finalized, &out.flushes), (completed, &flushes));
+
+        let mut out = Recorder::default();
+        let summary = read_single_file_buffer_with_options(
+            &mut car.as_slice(),
+            &mut out,
+            None,
+            &finalize(level),
+        )
+        .await
+        .unwrap();
+        assert_eq!((summary.finalized, &out.flushes), (completed, &flushes));
+
+        let mut car_input = Cursor::new(&car);
+        let index = CarIndex::build(&mut car_input).await.unwrap();
+        let mut out = Recorder::default();
+        let summary = read_single_file_indexed_with_options(
+            &mut car_input,
+            &mut out,
+            None,
+            Some(&index),
+            &finalize(level),
+        )
+        .await
+        .unwrap();
+        assert_eq!((summary.finalized, &out.flushes), (completed, &flushes));
+        assert!(out.syncs.is_empty());
+    }
+}
+
+#[async_std::test]
+async fn error_state_write_limit_exceeded() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+
+    // Checked against the filesize declared by the root before writing
+    for write_limit in [0, 31, expected.len() - 1] {
+        let options = ReadSingleFileOptions {
+            write_limit: Some(write_limit),
+            ..Default::default()
+        };
+        let mut out = Cursor::new(Vec::new());
+        match read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+            .await
+        {
+            Err(ReadSingleFileError::DeclaredSizeExceedsLimit { declared, limit }) => {
+                assert_eq!((declared, limit), (expected.len() as u64, write_limit))
+            }
+            x => panic!("other result {:?}", x),
+        }
+        assert!(out.into_inner().is_empty());
+
+        let mut out = Cursor::new(Vec::new());
+        match read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+            .await
+        {
+            Err(ReadSingleFileError::DeclaredSizeExceedsLimit { .. }) => {}
+            x => panic!("other result {:?}", x),
+        }
+        assert!(out.into_inner().is_empty());
+    }
+    let options = ReadSingleFileOptions {
+        write_limit: Some(expected.len()),
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), expected);
+
+    // Without a declared filesize, the read fails once the limit is crossed
+    let expected = b"aaaabbbbcccc";
+    let leaves: Vec<_> = expected
+        .chunks(4)
+        .map(|data| PbNode::file_leaf(data).block())
+        .collect();
+    let (root_cid, root) = PbNode {
+        filesize: None,
+        ..PbNode::file_branch(&leaves.iter().map(|(cid, _)| (*cid, 4)).collect::<Vec<_>>())
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[&[(root_cid, root)][..], &leaves].concat());
+
+    for write_limit in [0, 3, 4, 5, expected.len() - 1] {
+        let options = ReadSingleFileOptions {
+            write_limit: Some(write_limit),
+            ..Default::default()
+        };
+        let mut out = Cursor::new(Vec::new());
+        match read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+            .await
+        {
+            Err(ReadSingleFileError::WriteLimitExceeded { .. }) => {}
+            x => panic!("other result {:?}", x),
+        }
+        let out = out.into_inner();
+        assert!(out.len() <= write_limit, "{} > {}", out.len(), write_limit);
+        assert_eq!(out, &expected[..out.len()]);
+
+        let mut out = Cursor::new(Vec::new());
+        match read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+            .await
+        {
+            Err(ReadSingleFileError::WriteLimitExceeded { .. }) => {}
+            x => panic!("other result {:?}", x),
+        }
+        let out = out.into_inner();
+        assert!(out.len() <= write_limit, "{} > {}", out.len(), write_limit);
+        assert_eq!(out, &expected[..out.len()]);
+    }
+
+    // The whole file fits
+    let options = ReadSingleFileOptions {
+        write_limit: Some(expected.len()),
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), expected);
+}
+
+#[async_std::test]
+async fn error_state_incomplete_dag() {
+    // Last leaf missing
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bbbb").block();
+    let (c_cid, _) = PbNode::file_leaf(b"cccc").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 4), (c_cid, 4)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+    let options = ReadSingleFileOptions::default();
+
+    // Nothing written
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap_err();
+    assert_eq!(out.into_inner(), b"");
+
+    // Prefix written
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap_err();
+    assert_eq!(out.into_inner(), b"aaaabbbb");
+
+    let mut car_input = Cursor::new(&car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_indexed_with_options(&mut car_input, &mut out, None, Some(&index), &options)
+        .await
+        .unwrap_err();
+    assert_eq!(out.into_inner(), b"aaaabbbb");
+
+    // Zeros past the prefix when preallocated
+    let options = ReadSingleFileOptions {
+        preallocate: true,
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap_err();
+    assert_eq!(out.into_inner(), b"aaaabbbb\0\0\0\0");
+}
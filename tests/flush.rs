@@ -0,0 +1,73 @@
+mod common;
+
+use common::Recorder;
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_with_options, CarIndex, ReadSingleFileOptions,
+};
+use std::fs;
+
+const CAR_FILEPATH: &str = "tests/data/seq_1000.txt.size-32.normal.car";
+const EXPECTED_FILEPATH: &str = "tests/data/seq_1000.txt";
+
+/// Check `out` flushes happen at most every `flush_every` + one leaf bytes and end with a flush
+fn assert_flushes(out: &Recorder, flush_every: Option<usize>) {
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+    assert_eq!(out.inner.get_ref(), &expected);
+
+    // Always flushed once at the end
+    assert_eq!(out.flushes.last(), Some(&out.written));
+
+    match flush_every {
+        None => assert_eq!(out.flushes.len(), 1),
+        Some(flush_every) => {
+            let mut prev = 0;
+            for flush in &out.flushes[..out.flushes.len() - 1] {
+                // Leaves are 32 bytes, a flush happens on the first write that crosses the limit
+                assert!(flush - prev >= flush_every, "{:?}", out.flushes);
+                assert!(flush - prev < flush_every + 32, "{:?}", out.flushes);
+                prev = *flush;
+            }
+            assert!(out.flushes.len() > 1);
+        }
+    }
+}
+
+#[async_std::test]
+async fn flush_every() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+
+    for flush_every in [None, Some(1), Some(100), Some(1000)] {
+        let options = ReadSingleFileOptions {
+            flush_every,
+            ..Default::default()
+        };
+
+        let mut out = Recorder::default();
+        read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options)
+            .await
+            .unwrap();
+        assert_flushes(&out, flush_every);
+
+        let mut out = Recorder::default();
+        read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+            .await
+            .unwrap();
+        assert_flushes(&out, flush_every);
+
+        let mut car_input = Cursor::new(&car);
+        let index = CarIndex::build(&mut car_input).await.unwrap();
+        let mut out = Recorder::default();
+        read_single_file_indexed_with_options(
+            &mut car_input,
+            &mut out,
+            None,
+            Some(&index),
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_flushes(&out, flush_every);
+    }
+}
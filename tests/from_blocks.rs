@@ -0,0 +1,98 @@
+//! Files read from blocks in memory, without CAR framing
+
+mod common;
+
+use common::{
+    generate::{pseudo_random, CarSpec, Layout, Order},
+    read_all, PbNode,
+};
+use futures::io::Cursor;
+use rs_car::{CarDecodeError, Cid};
+use rs_car_ipfs::single_file::{
+    read_single_file_from_blocks, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
+
+async fn read_blocks(
+    blocks: &[(Cid, Vec<u8>)],
+    root_cid: &Cid,
+    options: &ReadSingleFileOptions,
+) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_from_blocks(blocks, &mut out, root_cid, options).await?;
+    Ok((summary, out.into_inner()))
+}
+
+#[async_std::test]
+async fn from_blocks_matches_car() {
+    // Repeated content, leaves linked several times
+    let data = pseudo_random(1500, 8).repeat(2);
+    for order in [Order::Dfs, Order::ChildrenFirst, Order::Random(5)] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 3,
+            layout: Layout::Trickle { repeat: 2 },
+            order,
+            ..Default::default()
+        }
+        .build();
+
+        let (summary, out) = read_blocks(&generated.blocks, &generated.root, &Default::default())
+            .await
+            .unwrap();
+        let [buffer, ..]: [_; 3] = read_all(&generated.car, &Default::default())
+            .await
+            .try_into()
+            .unwrap();
+        assert_eq!(out, buffer.unwrap(), "{:?}", order);
+        assert!(out == data);
+        assert_eq!(
+            (
+                summary.root_cid,
+                summary.bytes_written,
+                summary.car_bytes_read
+            ),
+            (Some(generated.root), data.len() as u64, 0)
+        );
+    }
+}
+
+#[async_std::test]
+async fn from_blocks_manual() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2), (a_cid, 4)]).block();
+    let (other_cid, other) = PbNode::file_leaf(b"other").block();
+
+    // Any order, blocks not part of the file are ignored
+    let blocks = vec![
+        (b_cid, b.clone()),
+        (other_cid, other),
+        (a_cid, a.clone()),
+        (root_cid, root.clone()),
+    ];
+    let (summary, out) = read_blocks(&blocks, &root_cid, &Default::default())
+        .await
+        .unwrap();
+    assert_eq!(out, b"aaaabbaaaa");
+    assert_eq!(summary.blocks_read, 4);
+
+    // Missing root or leaf
+    match read_blocks(&blocks[..3], &root_cid, &Default::default()).await {
+        Err(ReadSingleFileError::RootCidNotFound { header_roots, .. }) => {
+            assert!(header_roots.is_empty())
+        }
+        x => panic!("other result {:?}", x),
+    }
+    match read_blocks(&blocks[1..], &root_cid, &Default::default()).await {
+        Err(ReadSingleFileError::MissingNode(cid)) => assert_eq!(cid, b_cid),
+        x => panic!("other result {:?}", x),
+    }
+
+    // Blocks are validated against their CID
+    let blocks = vec![(a_cid, b), (root_cid, root)];
+    match read_blocks(&blocks, &root_cid, &Default::default()).await {
+        Err(ReadSingleFileError::CarDecodeError(CarDecodeError::BlockDigestMismatch(_))) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
@@ -0,0 +1,489 @@
+use futures::io::Cursor;
+use multihash::{Code, MultihashDigest};
+use rs_car_ipfs::{
+    adder::{Chunker, ChunkerHook, ChunkerProfile, PackOptions},
+    hamt::{find_shard_link, ShardLink, HASH_MURMUR3},
+    import::{write_directory_car, write_directory_car_with_options, write_file_car},
+    single_file::{
+        file_layout, ls, read_single_file_indexed, read_single_file_seek, CarIndex, LeafExtent,
+        UnixFsNodeInfo,
+    },
+    Cid, UnixFsType,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Content {
+    File(Vec<u8>),
+    Directory,
+    Symlink,
+}
+
+/// Empty directory under the temp dir, removed on drop
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "rs-car-ipfs-import-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Contents of the tree under `dir` by relative path
+fn list_dir(dir: &Path, prefix: &str, listing: &mut BTreeMap<String, Content>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let path = format!("{}{}", prefix, entry.file_name().to_str().unwrap());
+        let file_type = entry.file_type().unwrap();
+        if file_type.is_symlink() {
+            listing.insert(path, Content::Symlink);
+        } else if file_type.is_dir() {
+            list_dir(&entry.path(), &format!("{}/", path), listing);
+            listing.insert(path, Content::Directory);
+        } else {
+            listing.insert(path, Content::File(fs::read(entry.path()).unwrap()));
+        }
+    }
+}
+
+/// Contents of the directory DAG `cid` of `car` by relative path, reading files with the seek
+/// reader streaming the whole CAR, or else with the indexed reader
+struct Extractor<'a> {
+    car: &'a [u8],
+    nodes: HashMap<Cid, UnixFsNodeInfo>,
+    index: CarIndex,
+    seek: bool,
+}
+
+impl<'a> Extractor<'a> {
+    async fn new(car: &'a [u8], seek: bool) -> Extractor<'a> {
+        let nodes = ls(&mut Cursor::new(car)).await.unwrap();
+        let count = nodes.len();
+        let nodes = nodes.into_iter().collect::<HashMap<_, _>>();
+        assert_eq!(nodes.len(), count, "duplicated blocks");
+        let index = CarIndex::build(&mut Cursor::new(car)).await.unwrap();
+        Extractor {
+            car,
+            nodes,
+            index,
+            seek,
+        }
+    }
+
+    async fn list(&self, cid: &Cid, prefix: &str, listing: &mut BTreeMap<String, Content>) {
+        let node = &self.nodes[cid];
+        for link in &node.links {
+            let name = link.name.clone().unwrap();
+            let name = match node.unixfs_type {
+                UnixFsType::Directory => name,
+                // Entries of HAMT shards are prefixed with their slot, sub-shards only have a slot
+                UnixFsType::HAMTShard if name.len() > 2 => name[2..].to_string(),
+                UnixFsType::HAMTShard => {
+                    Box::pin(self.list(&link.cid, prefix, listing)).await;
+                    continue;
+                }
+                x => panic!("not a directory {:?}", x),
+            };
+            let path = format!("{}{}", prefix, name);
+
+            let content = match self.nodes[&link.cid].unixfs_type {
+                UnixFsType::Directory | UnixFsType::HAMTShard => {
+                    Box::pin(self.list(&link.cid, &format!("{}/", path), listing)).await;
+                    Content::Directory
+                }
+                UnixFsType::Symlink => Content::Symlink,
+                _ => Content::File(self.read_file(&link.cid).await),
+            };
+            listing.insert(path, content);
+        }
+    }
+
+    async fn read_file(&self, cid: &Cid) -> Vec<u8> {
+        let mut out = Cursor::new(Vec::new());
+        if self.seek {
+            read_single_file_seek(&mut Cursor::new(self.car), &mut out, Some(cid), None)
+                .await
+                .unwrap();
+        } else {
+            read_single_file_indexed(
+                &mut Cursor::new(self.car),
+                &mut out,
+                Some(cid),
+                Some(&self.index),
+            )
+            .await
+            .unwrap();
+        }
+        out.into_inner()
+    }
+}
+
+async fn assert_round_trip(dir: &Path, profile: ChunkerProfile, seek: bool) -> (Cid, Vec<u8>) {
+    let mut car = vec![];
+    let root_cid = write_directory_car(dir, &mut car, profile).unwrap();
+
+    let header = rs_car::CarReader::new(&mut car.as_slice(), true)
+        .await
+        .unwrap()
+        .header;
+    assert_eq!(header.roots, vec![root_cid]);
+
+    let mut expected = BTreeMap::new();
+    list_dir(dir, "", &mut expected);
+    let mut extracted = BTreeMap::new();
+    Extractor::new(&car, seek)
+        .await
+        .list(&root_cid, "", &mut extracted)
+        .await;
+    assert_eq!(extracted, expected);
+
+    (root_cid, car)
+}
+
+fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (state >> 33) as u8
+        })
+        .collect()
+}
+
+#[async_std::test]
+async fn write_directory_car_round_trip() {
+    let dir = TempDir::new("tree");
+    let root = &dir.0;
+    fs::write(root.join("a.txt"), b"hello").unwrap();
+    fs::write(root.join("empty"), b"").unwrap();
+    // Multiple chunks with the default chunker, duplicated
+    let big = pseudo_random(600_000, 1);
+    fs::write(root.join("big.bin"), &big).unwrap();
+    fs::write(root.join("dup.bin"), &big).unwrap();
+    fs::create_dir_all(root.join("sub/nested")).unwrap();
+    fs::write(root.join("sub/nested/c.txt"), b"c").unwrap();
+    fs::write(root.join("sub/.hidden"), b"hidden").unwrap();
+    fs::create_dir(root.join("emptydir")).unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("a.txt", root.join("link")).unwrap();
+
+    for (profile, cid_prefix) in [
+        (ChunkerProfile::KuboDefaultV0, "Qm"),
+        (ChunkerProfile::KuboDefaultV1, "bafy"),
+    ] {
+        let (root_cid, car) = assert_round_trip(root, profile, true).await;
+        assert!(root_cid.to_string().starts_with(cid_prefix), "{}", root_cid);
+
+        // Deterministic
+        let mut again = vec![];
+        write_directory_car(root, &mut again, profile).unwrap();
+        assert_eq!(again, car);
+    }
+}
+
+#[async_std::test]
+async fn write_directory_car_empty_directory() {
+    let dir = TempDir::new("empty");
+    let mut car = vec![];
+    let root_cid = write_directory_car(&dir.0, &mut car, ChunkerProfile::KuboDefaultV0).unwrap();
+    // `ipfs add -r` of an empty directory
+    assert_eq!(
+        root_cid.to_string(),
+        "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+    );
+
+    // Not a directory
+    let file = dir.0.join("file");
+    fs::write(&file, b"file").unwrap();
+    write_directory_car(&file, &mut vec![], ChunkerProfile::KuboDefaultV0).unwrap_err();
+}
+
+#[async_std::test]
+async fn write_directory_car_hamt_sharded() {
+    let dir = TempDir::new("hamt");
+    // ~60 bytes per link, above the 256 KiB sharding threshold
+    for i in 0..5000 {
+        let name = format!("file-with-a-long-enough-name-{:04}", i);
+        fs::write(dir.0.join(&name), name.as_bytes()).unwrap();
+    }
+
+    let (root_cid, car) = assert_round_trip(&dir.0, ChunkerProfile::KuboDefaultV0, false).await;
+    let extractor = Extractor::new(&car, false).await;
+    let root = &extractor.nodes[&root_cid];
+    assert_eq!(root.unixfs_type, UnixFsType::HAMTShard);
+    assert_eq!(
+        (root.hash_type, root.fanout),
+        (Some(HASH_MURMUR3), Some(256))
+    );
+    assert!(root.links.len() <= 256);
+
+    // Every entry is found in the bucket of its name, of the shard linking it
+    let entries: HashMap<_, _> = extractor
+        .nodes
+        .values()
+        .filter(|node| node.unixfs_type == UnixFsType::HAMTShard)
+        .flat_map(|node| &node.links)
+        .filter_map(|link| Some((link.name.as_ref()?.get(2..)?, link.cid)))
+        .filter(|(name, _)| !name.is_empty())
+        .collect();
+    assert_eq!(entries.len(), 5000);
+    let lookup = |name: &str| {
+        let mut node = root;
+        for depth in 0.. {
+            match find_shard_link(node, name, depth).unwrap() {
+                Some(ShardLink::Entry(link)) => return Some(link.cid),
+                Some(ShardLink::Shard(link)) => node = &extractor.nodes[&link.cid],
+                None => return None,
+            }
+        }
+        unreachable!()
+    };
+    for i in 0..5000 {
+        let name = format!("file-with-a-long-enough-name-{:04}", i);
+        assert_eq!(lookup(&name), Some(entries[name.as_str()]), "{}", name);
+    }
+    assert_eq!(lookup("file-with-a-long-enough-name-5000"), None);
+}
+
+fn read_car_file(car: &[u8]) -> Vec<u8> {
+    let mut out = Cursor::new(Vec::new());
+    futures::executor::block_on(read_single_file_seek(
+        &mut Cursor::new(car),
+        &mut out,
+        None,
+        None,
+    ))
+    .unwrap();
+    out.into_inner()
+}
+
+fn leaf_extents(car: &[u8]) -> Vec<LeafExtent> {
+    futures::executor::block_on(file_layout(&mut Cursor::new(car), None)).unwrap()
+}
+
+/// All `tests/data/*.normal.car` fixtures are created with `ipfs add --chunker=size-N`
+#[test]
+fn write_file_car_matches_kubo_fixtures() {
+    let mut count = 0;
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        let Some((source, chunker)) = filename
+            .strip_suffix(".normal.car")
+            .and_then(|name| name.split_once(".size-"))
+        else {
+            continue;
+        };
+        let options = PackOptions {
+            chunk_size: chunker.parse().unwrap(),
+            ..Default::default()
+        };
+
+        let fixture = fs::read(&path).unwrap();
+        let expected_root = futures::executor::block_on(async {
+            rs_car::CarReader::new(&mut fixture.as_slice(), false)
+                .await
+                .unwrap()
+                .header
+                .roots[0]
+        });
+        let source = format!("tests/data/{}", source);
+        let mut car = vec![];
+        let root_cid = write_file_car(&source, &mut car, &options).unwrap();
+        assert_eq!(root_cid, expected_root, "{}", filename);
+        assert_eq!(read_car_file(&car), fs::read(&source).unwrap());
+        count += 1;
+    }
+    assert!(count > 0);
+}
+
+#[test]
+fn write_file_car_profiles() {
+    let dir = TempDir::new("profiles");
+    let empty = dir.0.join("empty");
+    fs::write(&empty, b"").unwrap();
+    let hello = dir.0.join("helloworld.txt");
+    fs::copy("tests/data/helloworld.txt", &hello).unwrap();
+
+    // `ipfs add` and `ipfs add --cid-version=1`
+    for (path, profile, expected) in [
+        (
+            &empty,
+            ChunkerProfile::KuboDefaultV0,
+            "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH",
+        ),
+        (
+            &empty,
+            ChunkerProfile::KuboDefaultV1,
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku",
+        ),
+        (
+            &hello,
+            ChunkerProfile::KuboDefaultV0,
+            "QmUU2HcUBVSXkfWPUc3WUSeCMrWWeEJTuAgR9uyWBhh9Nf",
+        ),
+    ] {
+        let root_cid = write_file_car(path, &mut vec![], &profile.into()).unwrap();
+        assert_eq!(root_cid.to_string(), expected);
+    }
+
+    let data = fs::read(&hello).unwrap();
+    // A single raw leaf, its CID is the CIDv1 of the file content
+    let options = PackOptions {
+        chunk_size: 1048576,
+        raw_leaves: true,
+        ..Default::default()
+    };
+    let mut car = vec![];
+    let root_cid = write_file_car(&hello, &mut car, &options).unwrap();
+    assert_eq!(root_cid.codec(), 0x55);
+    assert_eq!(*root_cid.hash(), Code::Sha2_256.digest(&data));
+    assert_eq!(read_car_file(&car), data);
+
+    // Raw leaves under dag-pb nodes of the CID version
+    for (cid_version, prefix) in [(0, "Qm"), (1, "bafy")] {
+        let options = PackOptions {
+            chunk_size: 4,
+            raw_leaves: true,
+            cid_version,
+            ..Default::default()
+        };
+        let mut car = vec![];
+        let root_cid = write_file_car(&hello, &mut car, &options).unwrap();
+        assert!(root_cid.to_string().starts_with(prefix), "{}", root_cid);
+        assert_eq!(read_car_file(&car), data);
+        let extents = leaf_extents(&car);
+        assert_eq!(extents.len(), data.len().div_ceil(4));
+        assert!(extents.iter().all(|extent| extent.cid.codec() == 0x55));
+    }
+
+    // CIDv1 dag-pb leaves
+    let options = PackOptions {
+        chunk_size: 4,
+        cid_version: 1,
+        ..Default::default()
+    };
+    let mut car = vec![];
+    write_file_car(&hello, &mut car, &options).unwrap();
+    assert!(leaf_extents(&car)
+        .iter()
+        .all(|extent| extent.cid.codec() == 0x70 && u64::from(extent.cid.version()) == 1));
+}
+
+#[test]
+fn write_file_car_max_links() {
+    let data = pseudo_random(1000, 2);
+    let dir = TempDir::new("max-links");
+    let path = dir.0.join("file");
+    fs::write(&path, &data).unwrap();
+
+    let mut roots = HashSet::new();
+    for max_links in [2, 3, 174] {
+        let options = PackOptions {
+            chunk_size: 10,
+            max_links,
+            ..Default::default()
+        };
+        let mut car = vec![];
+        roots.insert(write_file_car(&path, &mut car, &options).unwrap());
+        assert_eq!(read_car_file(&car), data);
+        assert_eq!(leaf_extents(&car).len(), 100);
+
+        let nodes = futures::executor::block_on(ls(&mut Cursor::new(&car))).unwrap();
+        assert!(nodes.iter().all(|(_, node)| node.links.len() <= max_links));
+    }
+    assert_eq!(roots.len(), 3);
+
+    // Not a file
+    write_file_car(&dir.0, &mut vec![], &Default::default()).unwrap_err();
+}
+
+/// Ends chunks after each new line
+struct Lines;
+
+impl Chunker for Lines {
+    fn next_chunk(&mut self, data: &[u8]) -> Option<usize> {
+        data.iter().position(|byte| *byte == b'\n').map(|i| i + 1)
+    }
+}
+
+#[async_std::test]
+async fn write_car_custom_chunker() {
+    let dir = TempDir::new("chunker");
+    let data = b"first line\nsecond\n\nlast without new line".to_vec();
+    fs::write(dir.0.join("lines.txt"), &data).unwrap();
+    let options = PackOptions {
+        chunker: Some(ChunkerHook::new(|| Lines)),
+        ..Default::default()
+    };
+
+    let mut car = vec![];
+    write_file_car(dir.0.join("lines.txt"), &mut car, &options).unwrap();
+    assert_eq!(read_car_file(&car), data);
+    let lens = leaf_extents(&car)
+        .iter()
+        .map(|extent| extent.len)
+        .collect::<Vec<_>>();
+    assert_eq!(lens, [11, 7, 1, 21]);
+
+    // A new chunker for each file of a directory
+    fs::write(dir.0.join("other.txt"), b"a\nb\n").unwrap();
+    let mut car = vec![];
+    let root_cid = write_directory_car_with_options(&dir.0, &mut car, &options).unwrap();
+    let mut extracted = BTreeMap::new();
+    Extractor::new(&car, true)
+        .await
+        .list(&root_cid, "", &mut extracted)
+        .await;
+    assert_eq!(extracted["lines.txt"], Content::File(data));
+    assert_eq!(extracted["other.txt"], Content::File(b"a\nb\n".to_vec()));
+}
+
+/// Returns a chunk length past the data given
+struct Overlong;
+
+impl Chunker for Overlong {
+    fn next_chunk(&mut self, data: &[u8]) -> Option<usize> {
+        Some(data.len() + 1)
+    }
+}
+
+/// Returns an empty chunk
+struct Empty;
+
+impl Chunker for Empty {
+    fn next_chunk(&mut self, _data: &[u8]) -> Option<usize> {
+        Some(0)
+    }
+}
+
+#[test]
+fn write_car_misbehaving_chunker() {
+    let dir = TempDir::new("bad_chunker");
+    fs::write(dir.0.join("data.txt"), b"some data").unwrap();
+
+    for chunker in [ChunkerHook::new(|| Overlong), ChunkerHook::new(|| Empty)] {
+        let options = PackOptions {
+            chunker: Some(chunker),
+            ..Default::default()
+        };
+        let err = write_file_car(dir.0.join("data.txt"), &mut vec![], &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer, read_single_file_indexed, read_single_file_seek, CarIndex,
+};
+
+/// File node with `data` inline followed by `children`
+fn branch_with_data(data: &[u8], children: &[(Cid, u64)]) -> PbNode {
+    let branch = PbNode::file_branch(children);
+    PbNode {
+        data: Some(data.to_vec()),
+        filesize: branch.filesize.map(|size| size + data.len() as u64),
+        ..branch
+    }
+}
+
+async fn assert_all_readers(car: &[u8], expected: &[u8]) {
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer(&mut Cursor::new(car), &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), expected, "buffer");
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek(&mut Cursor::new(car), &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), expected, "seek");
+
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_indexed(&mut car_input, &mut out, None, Some(&index))
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), expected, "indexed");
+}
+
+#[async_std::test]
+async fn root_inline_data_before_links() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bbbb").block();
+    let (root_cid, root) = branch_with_data(b"0123456789", &[(a_cid, 4), (b_cid, 4)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    assert_all_readers(&car, b"0123456789aaaabbbb").await;
+}
+
+#[async_std::test]
+async fn nested_inline_data_deduplicated() {
+    // Intermediary node with inline data referenced twice, after a regular leaf
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (x_cid, x) = branch_with_data(b"xx", &[(a_cid, 4)]).block();
+    let (root_cid, root) = branch_with_data(b"r", &[(a_cid, 4), (x_cid, 6), (x_cid, 6)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (x_cid, x)]);
+
+    assert_all_readers(&car, b"raaaaxxaaaaxxaaaa").await;
+}
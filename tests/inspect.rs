@@ -0,0 +1,281 @@
+mod common;
+
+use common::{car_v1, cid_v1, PbLink, PbNode, DAG_PB, RAW, TYPE_FILE};
+use rs_car_ipfs::{
+    single_file::{
+        car_info, decode_unixfs_node, file_layout, file_size, inspect_root, ls, CarInfo,
+        LeafExtent, LinkInfo, ReadSingleFileError, RootInfo, UnixFsNodeInfo,
+    },
+    UnixFsType,
+};
+
+#[test]
+fn decode_unixfs_node_links_metadata() {
+    let (a_cid, _) = PbNode::file_leaf(b"aaaa").block();
+    let b_cid = cid_v1(RAW, b"bbbb");
+    let node = PbNode {
+        links: vec![
+            PbLink {
+                name: Some("".to_string()),
+                tsize: Some(14),
+                ..PbLink::new(a_cid)
+            },
+            PbLink::new(b_cid),
+        ],
+        ..PbNode::file_branch(&[(a_cid, 4), (b_cid, 4)])
+    };
+
+    assert_eq!(
+        decode_unixfs_node(&node.encode()).unwrap(),
+        UnixFsNodeInfo {
+            unixfs_type: UnixFsType::File,
+            data_len: 0,
+            filesize: Some(8),
+            blocksizes: vec![4, 4],
+            hash_type: None,
+            fanout: None,
+            links: vec![
+                LinkInfo {
+                    cid: a_cid,
+                    name: Some("".to_string()),
+                    tsize: Some(14),
+                },
+                LinkInfo {
+                    cid: b_cid,
+                    name: None,
+                    tsize: None,
+                },
+            ],
+        }
+    );
+}
+
+#[async_std::test]
+async fn ls_lists_all_blocks() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let b_cid = cid_v1(RAW, b"bbbb");
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 4)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (a_cid, a), (b_cid, b"bbbb".to_vec())],
+    );
+
+    let nodes = ls(&mut car.as_slice()).await.unwrap();
+    let summary = nodes
+        .iter()
+        .map(|(cid, node)| (*cid, node.unixfs_type, node.data_len, node.links.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (root_cid, UnixFsType::File, 0, 2),
+            (a_cid, UnixFsType::File, 4, 0),
+            (b_cid, UnixFsType::Raw, 4, 0),
+        ]
+    );
+}
+
+#[async_std::test]
+async fn car_info_counts_codecs() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let b_cid = cid_v1(RAW, b"bbbb");
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 4)]).block();
+    // dag-cbor block, not UnixFS
+    let cbor = vec![0xa1, 0x61, 0x61, 0x01];
+    let cbor_cid = cid_v1(0x71, &cbor);
+    let car = car_v1(
+        &[root_cid],
+        &[
+            (root_cid, root.clone()),
+            (cbor_cid, cbor.clone()),
+            (a_cid, a.clone()),
+            (b_cid, b"bbbb".to_vec()),
+        ],
+    );
+
+    assert_eq!(
+        car_info(&mut car.as_slice(), true).await.unwrap(),
+        CarInfo {
+            version: 1,
+            roots: vec![root_cid],
+            block_count: 4,
+            block_bytes: (root.len() + cbor.len() + a.len() + 4) as u64,
+            dag_pb_blocks: 2,
+            raw_blocks: 1,
+            other_blocks: 1,
+            root_filesize: Some(8),
+        }
+    );
+
+    // Root not a UnixFS file
+    let car = car_v1(&[cbor_cid], &[(cbor_cid, cbor)]);
+    let info = car_info(&mut car.as_slice(), true).await.unwrap();
+    assert_eq!((info.other_blocks, info.root_filesize), (1, None));
+}
+
+#[async_std::test]
+async fn file_size_from_root() {
+    let car = std::fs::read("tests/data/rand_100K.bin.size-512.normal.car").unwrap();
+    assert_eq!(
+        file_size(&mut car.as_slice(), None).await.unwrap(),
+        100 * 1024
+    );
+
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let b_cid = cid_v1(RAW, b"bbbb");
+    let branch = PbNode {
+        data: Some(b"xy".to_vec()),
+        filesize: None,
+        ..PbNode::file_branch(&[(a_cid, 4), (b_cid, 4)])
+    };
+    let blocks = |root: (_, Vec<u8>)| {
+        car_v1(
+            &[root.0],
+            &[root, (a_cid, a.clone()), (b_cid, b"bbbb".to_vec())],
+        )
+    };
+
+    // Without filesize, from the inline data and blocksizes
+    let car = blocks(branch.block());
+    assert_eq!(file_size(&mut car.as_slice(), None).await.unwrap(), 10);
+
+    // Raw block root
+    let car = car_v1(&[b_cid], &[(b_cid, b"bbbb".to_vec())]);
+    assert_eq!(file_size(&mut car.as_slice(), None).await.unwrap(), 4);
+
+    // Neither filesize nor blocksizes
+    let (root_cid, root) = PbNode {
+        blocksizes: vec![],
+        ..branch
+    }
+    .block();
+    let car = blocks((root_cid, root));
+    match file_size(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::FileSizeUnknown(cid)) => assert_eq!(cid, root_cid),
+        x => panic!("other result {:?}", x),
+    }
+
+    // Root not in the CAR
+    let car = car_v1(&[root_cid], &[(a_cid, a.clone())]);
+    match file_size(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::RootCidNotFound {
+            root,
+            blocks_seen,
+            header_roots,
+        }) => assert_eq!(
+            (root, blocks_seen, header_roots.to_vec()),
+            (root_cid, 1, vec![root_cid])
+        ),
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn inspect_root_codec_and_version() {
+    let car = std::fs::read("tests/example.car").unwrap();
+    let root = inspect_root(&mut car.as_slice(), None).await.unwrap();
+    assert_eq!(
+        (root.codec, root.cid_version, root.car_version),
+        (DAG_PB, 0, 1)
+    );
+
+    // Header only, the root block does not need to be in the CAR
+    let cbor_cid = cid_v1(0x71, &[0xa0]);
+    let raw_cid = cid_v1(RAW, b"bbbb");
+    let car = car_v1(&[cbor_cid, raw_cid], &[]);
+    assert_eq!(
+        inspect_root(&mut car.as_slice(), Some(&raw_cid))
+            .await
+            .unwrap(),
+        RootInfo {
+            cid: raw_cid,
+            codec: RAW,
+            cid_version: 1,
+            car_version: 1,
+        }
+    );
+    let root = inspect_root(&mut car.as_slice(), Some(&cbor_cid))
+        .await
+        .unwrap();
+    assert_eq!(root.codec, 0x71);
+
+    match inspect_root(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::NotSingleRoot { roots }) => {
+            assert_eq!(roots, vec![cbor_cid, raw_cid])
+        }
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn file_layout_extents() {
+    let car = std::fs::read("tests/data/rand_100K.bin.size-512.normal.car").unwrap();
+    let extents = file_layout(&mut car.as_slice(), None).await.unwrap();
+    let mut offset = 0;
+    for extent in &extents {
+        assert_eq!(extent.offset, offset);
+        offset += extent.len;
+    }
+    assert_eq!(offset, 100 * 1024);
+
+    // Only `a` is in the CAR, the root declares the length of `b` and the branch linked twice
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let b_cid = cid_v1(RAW, b"bbb");
+    let (branch_cid, branch) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 3)]).block();
+    let (root_cid, root) = PbNode {
+        data: Some(b"xy".to_vec()),
+        ..PbNode::file_branch(&[(branch_cid, 7), (a_cid, 4), (branch_cid, 7)])
+    }
+    .block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (branch_cid, branch), (a_cid, a)],
+    );
+    let extent = |cid, offset, len| LeafExtent { cid, offset, len };
+    assert_eq!(
+        file_layout(&mut car.as_slice(), None).await.unwrap(),
+        vec![
+            extent(root_cid, 0, 2),
+            extent(a_cid, 2, 4),
+            extent(b_cid, 6, 3),
+            extent(a_cid, 9, 4),
+            extent(a_cid, 13, 4),
+            extent(b_cid, 17, 3),
+        ]
+    );
+
+    // Without blocksizes nor the block of `b`
+    let (root_cid, root) = PbNode {
+        links: vec![PbLink::new(a_cid), PbLink::new(b_cid)],
+        unixfs_type: TYPE_FILE,
+        ..Default::default()
+    }
+    .block();
+    let car = car_v1(
+        &[root_cid],
+        &[
+            (root_cid, root),
+            (a_cid, PbNode::file_leaf(b"aaaa").block().1),
+        ],
+    );
+    match file_layout(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::BlocksizesUnknown { node, link_index }) => {
+            assert_eq!((node, link_index), (root_cid, 1))
+        }
+        x => panic!("other result {:?}", x),
+    }
+
+    // Blocksizes not matching the leaf in the CAR
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 5)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[
+            (root_cid, root),
+            (a_cid, PbNode::file_leaf(b"aaaa").block().1),
+        ],
+    );
+    match file_layout(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::InvalidUnixFs(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{car_v1, read_all, PbNode, DAG_PB, RAW};
+use multihash::{Code, Multihash, MultihashDigest};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{decode_unixfs_node, ReadSingleFileError};
+
+#[async_std::test]
+async fn truncated_link_digest() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let digest = Code::Sha2_256.digest(b"bb");
+    let truncated = Cid::new_v1(
+        DAG_PB,
+        Multihash::wrap(digest.code(), &digest.digest()[..20]).unwrap(),
+    );
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (truncated, 2)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root.clone()), (a_cid, a)]);
+
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::InvalidLinkDigestLength { cid, expected }) => {
+                assert_eq!((cid, expected), (truncated, 32))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+    assert!(matches!(
+        decode_unixfs_node(&root),
+        Err(ReadSingleFileError::InvalidLinkDigestLength { .. })
+    ));
+}
+
+#[async_std::test]
+async fn identity_link_any_length() {
+    // Identity CIDs inline their block, so their digest has no fixed length
+    let identity = Cid::new_v1(RAW, Multihash::wrap(0x00, b"inline data").unwrap());
+    let (root_cid, root) = PbNode::file_branch(&[(identity, 11)]).block();
+    decode_unixfs_node(&root).unwrap();
+
+    let car = car_v1(&[root_cid], &[(root_cid, root)]);
+    for res in read_all(&car, &Default::default()).await {
+        assert!(!matches!(
+            res,
+            Err(ReadSingleFileError::InvalidLinkDigestLength { .. })
+        ));
+    }
+}
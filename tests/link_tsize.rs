@@ -0,0 +1,130 @@
+//! `Tsize` of links checked against the blocks under them with `check_link_tsize`
+
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec, Corruption},
+    PbLink, PbNode,
+};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, BlockErrorHook, ErrorAction, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+use std::fs;
+
+fn check() -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        check_link_tsize: true,
+        ..Default::default()
+    }
+}
+
+async fn read(car: &[u8], options: &ReadSingleFileOptions) -> Result<Vec<u8>, ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(&mut Cursor::new(car), &mut out, None, options).await?;
+    Ok(out.into_inner())
+}
+
+#[async_std::test]
+async fn link_tsize_fixtures() {
+    // Written by kubo, with the exact cumulative size of the blocks under each link
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if !name.ends_with(".car") || name.contains(".size-1.") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+        let expected = read(&car, &Default::default()).await.unwrap();
+        assert!(
+            read(&car, &check()).await.unwrap() == expected,
+            "{:?}",
+            path
+        );
+    }
+}
+
+#[async_std::test]
+async fn link_tsize_duplicated_leaves() {
+    // The same leaf linked several times counts once per link
+    let data = [vec![0; 1000], pseudo_random(300, 1), vec![0; 1000]].concat();
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        fanout: 3,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    assert!(read(&car, &check()).await.unwrap() == data);
+}
+
+/// CAR of a file of two leaves, the second linked with `tsize`
+fn car_with_tsize(tsize: u64) -> (Vec<u8>, rs_car::Cid, usize) {
+    let (a_cid, a) = PbNode::file_leaf(b"hello ").block();
+    let (b_cid, b) = PbNode::file_leaf(b"world").block();
+    let b_len = b.len();
+    let mut root = PbNode::file_branch(&[(a_cid, 6), (b_cid, 5)]);
+    root.links = vec![
+        PbLink {
+            tsize: Some(a.len() as u64),
+            ..PbLink::new(a_cid)
+        },
+        PbLink {
+            tsize: Some(tsize),
+            ..PbLink::new(b_cid)
+        },
+    ];
+    let (root_cid, root) = root.block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+    (car, b_cid, b_len)
+}
+
+#[async_std::test]
+async fn link_tsize_out_of_bounds() {
+    let (_, _, b_len) = car_with_tsize(0);
+    for tsize in [5, b_len as u64] {
+        let (car, _, _) = car_with_tsize(tsize);
+        assert_eq!(read(&car, &check()).await.unwrap(), b"hello world");
+    }
+
+    // Fewer bytes than the data of the leaf, or more than its block
+    for (tsize, actual) in [(4, 5), (b_len as u64 + 1, b_len as u64)] {
+        let (car, b_cid, _) = car_with_tsize(tsize);
+        assert_eq!(
+            read(&car, &Default::default()).await.unwrap(),
+            b"hello world"
+        );
+        match read(&car, &check()).await {
+            Err(ReadSingleFileError::TsizeMismatch {
+                cid,
+                declared,
+                actual: found,
+            }) => {
+                assert_eq!(cid, b_cid);
+                assert_eq!(declared, tsize);
+                assert_eq!(found, actual);
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn link_tsize_skipped_block() {
+    // The subtree of a skipped block has an unknown size
+    let generated = CarSpec {
+        data: pseudo_random(1000, 2),
+        chunk_size: 100,
+        corruption: Some(Corruption::FlipByte(3)),
+        ..Default::default()
+    }
+    .build();
+    let options = ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new(|_, _| ErrorAction::Skip)),
+        ..check()
+    };
+    assert_eq!(read(&generated.car, &options).await.unwrap().len(), 1000);
+}
@@ -0,0 +1,71 @@
+//! Events of the readers through the `log` facade
+
+#![cfg(feature = "log")]
+
+use futures::io::Cursor;
+use log::{Level, Log, Metadata, Record};
+use rs_car_ipfs::single_file::{read_single_file_seek_with_options, ReadSingleFileOptions};
+use std::{fs, sync::Mutex};
+
+/// Logger keeping the events of every level
+struct Collect(Mutex<Vec<(Level, String)>>);
+
+impl Log for Collect {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let event = (record.level(), record.args().to_string());
+        // Events of a background thread after a failed assertion
+        let mut events = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        events.push(event);
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Collect = Collect(Mutex::new(vec![]));
+
+#[async_std::test]
+async fn log_read_events() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    let car = fs::read("tests/example.car").unwrap();
+    let options = ReadSingleFileOptions {
+        write_limit: Some(10),
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options).await;
+    assert!(res.is_err());
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_seek_with_options(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+    let root = summary.root_cid.unwrap();
+
+    let events = LOGGER.0.lock().unwrap();
+    let logged = |level: Level, pattern: &str| {
+        events
+            .iter()
+            .any(|(l, message)| *l == level && message.contains(pattern))
+    };
+    assert!(logged(Level::Debug, &format!("reading root {}", root)));
+    assert!(logged(Level::Trace, &format!("block {} with", root)));
+    assert!(logged(Level::Debug, "exceeds the write limit of 10 bytes"));
+    assert!(logged(
+        Level::Debug,
+        &format!(
+            "read root {}: {} bytes written",
+            root, summary.bytes_written
+        )
+    ));
+}
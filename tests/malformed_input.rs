@@ -0,0 +1,293 @@
+//! Readers must return errors, never panic, on truncated or corrupted CAR streams
+
+mod common;
+
+use common::{
+    car_v1, car_v1_with_header, generate::pseudo_random, write_cbor_roots, write_cbor_text, PbNode,
+};
+use futures::io::Cursor;
+use rs_car::CarDecodeError;
+use rs_car_ipfs::single_file::ReadSingleFileError;
+use rs_car_ipfs::single_file::{
+    car_info, ls, read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_with_options, BlockErrorHook, CarFileReader, CarIndex, ErrorAction,
+    ReadSingleFileOptions,
+};
+use std::{fs, io};
+
+/// Small fixtures covering balanced and trickle DAGs, with and without raw leaves
+const FIXTURES: &[&str] = &[
+    "tests/data/helloworld.txt.size-1.normal.car",
+    "tests/data/helloworld.txt.size-1.trickle.car",
+    "tests/data/config.toml.size-512.trickle.car",
+    "tests/data/zero_1K.bin.size-512.normal.car",
+    "tests/example.car",
+];
+
+/// Run every reader over `car`, ignoring the results
+async fn read_everything(car: &[u8]) {
+    let skip_errors = ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new(|_, _| ErrorAction::Skip)),
+        lenient_node_types: true,
+        ..Default::default()
+    };
+    for options in [ReadSingleFileOptions::default(), skip_errors] {
+        let mut out = Cursor::new(Vec::new());
+        let _ =
+            read_single_file_buffer_with_options(&mut Cursor::new(car), &mut out, None, &options)
+                .await;
+        let mut out = Cursor::new(Vec::new());
+        let _ = read_single_file_seek_with_options(&mut Cursor::new(car), &mut out, None, &options)
+            .await;
+
+        let mut car_input = Cursor::new(car);
+        if let Ok(index) = CarIndex::build(&mut car_input).await {
+            let mut out = Cursor::new(Vec::new());
+            let _ = read_single_file_indexed_with_options(
+                &mut car_input,
+                &mut out,
+                None,
+                Some(&index),
+                &options,
+            )
+            .await;
+        }
+
+        let mut car_input = Cursor::new(car);
+        let mut out = vec![];
+        let _ =
+            futures::io::copy(CarFileReader::new(&mut car_input, None, &options), &mut out).await;
+    }
+    let _ = ls(&mut Cursor::new(car)).await;
+    let _ = car_info(&mut Cursor::new(car), true).await;
+}
+
+#[async_std::test]
+async fn malformed_truncated() {
+    for path in FIXTURES {
+        let car = fs::read(path).unwrap();
+        for len in 0..car.len() {
+            read_everything(&car[..len]).await;
+        }
+    }
+}
+
+#[async_std::test]
+async fn malformed_bit_flips() {
+    for (seed, path) in FIXTURES.iter().enumerate() {
+        let car = fs::read(path).unwrap();
+        // Every byte of the header and first sections, then pseudo random positions
+        let random = pseudo_random(512, seed as u64);
+        let positions = (0..car.len().min(256)).chain(
+            random
+                .chunks(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]) as usize % car.len()),
+        );
+        for position in positions {
+            for mask in [0x01, 0x80, 0xff] {
+                let mut corrupted = car.clone();
+                corrupted[position] ^= mask;
+                read_everything(&corrupted).await;
+            }
+        }
+    }
+}
+
+/// Inputs of the fuzz target in `fuzz/`, run without a fuzzer: random bytes, alone and after a
+/// valid header
+#[async_std::test]
+async fn malformed_random_bytes() {
+    for seed in 0..64 {
+        let random = pseudo_random(seed as usize * 16, seed);
+        read_everything(&random).await;
+        read_everything(&car_with_sections(&random)).await;
+    }
+}
+
+/// Header of a CARv1 with a single root followed by the raw bytes of `sections`
+fn car_with_sections(sections: &[u8]) -> Vec<u8> {
+    let (root_cid, _) = PbNode::file_leaf(b"aaaa").block();
+    let mut car = car_v1(&[root_cid], &[]);
+    car.extend_from_slice(sections);
+    car
+}
+
+/// CARv2 wrapping `inner` with the given data offset and size
+fn car_v2(inner: &[u8], data_offset: u64, data_size: u64) -> Vec<u8> {
+    let mut car = hex_literal::hex!("0aa16776657273696f6e02").to_vec();
+    car.extend_from_slice(&[0; 16]);
+    car.extend_from_slice(&data_offset.to_le_bytes());
+    car.extend_from_slice(&data_size.to_le_bytes());
+    car.extend_from_slice(&0u64.to_le_bytes());
+    car.extend_from_slice(inner);
+    car
+}
+
+async fn read_err(car: &[u8]) -> CarDecodeError {
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_seek_with_options(
+        &mut Cursor::new(car),
+        &mut out,
+        None,
+        &Default::default(),
+    )
+    .await;
+    match res {
+        Err(ReadSingleFileError::CarDecodeError(err)) => err,
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn malformed_framing() {
+    let (a_cid, _) = PbNode::file_leaf(b"aaaa").block();
+    let cid = a_cid.to_bytes();
+
+    // Section length shorter than its CID
+    let mut section = vec![10];
+    section.extend_from_slice(&cid);
+    assert!(matches!(
+        read_err(&car_with_sections(&section)).await,
+        CarDecodeError::InvalidBlockHeader(_)
+    ));
+    // CID version 2
+    assert!(matches!(
+        read_err(&car_with_sections(&[40, 0x02, 0x70, 0x12, 0x20])).await,
+        CarDecodeError::InvalidCid(_)
+    ));
+    // Digest of 100 bytes
+    assert!(matches!(
+        read_err(&car_with_sections(&[120, 0x01, 0x70, 0x12, 100])).await,
+        CarDecodeError::InvalidMultihash(_)
+    ));
+    // Invalid varint of a block section length, rejected by rs-car itself
+    assert!(matches!(
+        read_err(&car_with_sections(&[0xff; 12])).await,
+        CarDecodeError::InvalidBlockHeader(_)
+    ));
+
+    let inner = car_v1(&[a_cid], &[]);
+    // Data offset within the CARv2 header
+    assert!(matches!(
+        read_err(&car_v2(&inner, 20, inner.len() as u64)).await,
+        CarDecodeError::InvalidCarV2Header(_)
+    ));
+    // Data size shorter than the inner header
+    assert!(matches!(
+        read_err(&car_v2(&inner, 51, 3)).await,
+        CarDecodeError::InvalidCarV2Header(_)
+    ));
+
+    // Truncated in the middle of a block
+    let (root_cid, root) = PbNode::file_leaf(b"aaaa").block();
+    let car = car_v1(&[root_cid], &[(root_cid, root)]);
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_seek_with_options(
+        &mut &car[..car.len() - 1],
+        &mut out,
+        None,
+        &Default::default(),
+    )
+    .await
+    {
+        Err(ReadSingleFileError::IoError(err)) => {
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof)
+        }
+        x => panic!("other result {:?}", x),
+    }
+
+    for car in [
+        car_with_sections(&section),
+        car_v2(&inner, 20, inner.len() as u64),
+    ] {
+        read_everything(&car).await;
+    }
+}
+
+#[async_std::test]
+async fn malformed_header_keys() {
+    let (root_cid, root) = PbNode::file_leaf(b"aaaa").block();
+    let blocks = [(root_cid, root)];
+    // dag-cbor map of `len` entries, "roots" and "version" first
+    let header = |len: u8, extra: &dyn Fn(&mut Vec<u8>)| {
+        let mut header = vec![0xa0 + len];
+        write_cbor_roots(&mut header, &[root_cid]);
+        write_cbor_text(&mut header, "version");
+        header.push(0x01);
+        extra(&mut header);
+        header
+    };
+
+    let headers = [
+        // Unexpected key, and the same with a nested value rs-car ignores
+        header(3, &|header| {
+            write_cbor_text(header, "extra");
+            header.push(0x00);
+        }),
+        header(3, &|header| {
+            write_cbor_text(header, "extra");
+            header.extend_from_slice(&[0x82, 0xa1, 0x61, b'a', 0x01, 0x40]);
+        }),
+        // Second roots, rs-car keeps the last
+        header(3, &|header| write_cbor_roots(header, &[])),
+        // Bytes after the map
+        header(2, &|header| header.push(0x00)),
+    ];
+    for header in &headers {
+        let car = car_v1_with_header(header, &blocks);
+        let mut out = Cursor::new(Vec::new());
+        let results = [
+            read_single_file_buffer_with_options(
+                &mut car.as_slice(),
+                &mut out,
+                None,
+                &Default::default(),
+            )
+            .await
+            .map(|_| ()),
+            read_single_file_seek_with_options(
+                &mut car.as_slice(),
+                &mut out,
+                None,
+                &Default::default(),
+            )
+            .await
+            .map(|_| ()),
+            CarIndex::build(&mut Cursor::new(&car)).await.map(|_| ()),
+            car_info(&mut car.as_slice(), true).await.map(|_| ()),
+            ls(&mut car.as_slice()).await.map(|_| ()),
+        ];
+        for res in results {
+            match res {
+                Err(ReadSingleFileError::MalformedHeader(_)) => {}
+                x => panic!("other result {:?}", x),
+            }
+        }
+        read_everything(&car).await;
+    }
+
+    // Within a CARv2, in the inner header or the pragma
+    let inner = car_v1_with_header(&headers[0], &blocks);
+    let car = car_v2(&inner, 51, inner.len() as u64);
+    match car_info(&mut car.as_slice(), true).await {
+        Err(ReadSingleFileError::MalformedHeader(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+    let inner = car_v1(&[root_cid], &blocks);
+    let mut car = car_v2(&inner, 58, inner.len() as u64);
+    // {"version": 2, "extra": 0} instead of {"version": 2}
+    car.splice(
+        ..11,
+        hex_literal::hex!("11a26776657273696f6e0265657874726100"),
+    );
+    match car_info(&mut car.as_slice(), true).await {
+        Err(ReadSingleFileError::MalformedHeader(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+
+    // The same header without the extra key is read
+    let car = car_v1_with_header(&header(2, &|_| {}), &blocks);
+    for res in common::read_all(&car, &Default::default()).await {
+        assert_eq!(res.unwrap(), b"aaaa");
+    }
+}
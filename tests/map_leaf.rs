@@ -0,0 +1,136 @@
+mod common;
+
+use common::{car_v1, read_all, PbNode};
+use futures::{io::Cursor, AsyncReadExt};
+use rs_car_ipfs::{
+    adder::ChunkerProfile,
+    single_file::{
+        read_single_file_buffer_with_options, read_single_file_indexed_with_options, CarFileReader,
+        MapLeafHook, ReadSingleFileError, ReadSingleFileOptions,
+    },
+};
+use std::{borrow::Cow, fs};
+
+fn map_leaf(hook: MapLeafHook) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        map_leaf: Some(hook),
+        ..Default::default()
+    }
+}
+
+fn xor_hook() -> MapLeafHook {
+    MapLeafHook::new(|data| Ok(Cow::Owned(data.iter().map(|byte| byte ^ 0x5a).collect())))
+}
+
+async fn read_buffer(
+    car: &[u8],
+    options: &ReadSingleFileOptions,
+) -> Result<(u64, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_buffer_with_options(&mut Cursor::new(car), &mut out, None, options)
+            .await?;
+    Ok((summary.bytes_written, out.into_inner()))
+}
+
+#[async_std::test]
+async fn map_leaf_transforms_each_leaf() {
+    let car = fs::read("tests/data/rand_10K.bin.size-262144.normal.car").unwrap();
+    let source = fs::read("tests/data/rand_10K.bin").unwrap();
+
+    let options = ReadSingleFileOptions {
+        // The DAG is still checked against the original data
+        verify_canonical: Some(ChunkerProfile::KuboDefaultV0),
+        ..map_leaf(xor_hook())
+    };
+    let (bytes_written, out) = read_buffer(&car, &options).await.unwrap();
+    let expected: Vec<u8> = source.iter().map(|byte| byte ^ 0x5a).collect();
+    assert_eq!(bytes_written, expected.len() as u64);
+    assert!(out == expected);
+
+    // The length of the file is the length of the mapped data
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let first_byte = MapLeafHook::new(|data| Ok(Cow::Borrowed(&data[..1])));
+    let (bytes_written, out) = read_buffer(&car, &map_leaf(first_byte)).await.unwrap();
+    let expected: Vec<u8> = source.chunks(512).map(|chunk| chunk[0]).collect();
+    assert_eq!(bytes_written, expected.len() as u64);
+    assert_eq!(out, expected);
+}
+
+#[async_std::test]
+async fn map_leaf_inline_data() {
+    // Data inline in the root is mapped before its children
+    let (a_cid, a) = PbNode::file_leaf(b"aa").block();
+    let (root_cid, root) = PbNode {
+        data: Some(b"rr".to_vec()),
+        ..PbNode::file_branch(&[(a_cid, 2)])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    let upper = MapLeafHook::new(|data| Ok(Cow::Owned(data.to_ascii_uppercase())));
+    let (_, out) = read_buffer(&car, &map_leaf(upper)).await.unwrap();
+    assert_eq!(out, b"RRAA");
+}
+
+#[async_std::test]
+async fn map_leaf_error() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bad").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 3)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    let reject = MapLeafHook::new(|data| match data {
+        b"bad" => Err("bad leaf".into()),
+        data => Ok(Cow::Borrowed(data)),
+    });
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_buffer_with_options(
+        &mut Cursor::new(&car),
+        &mut out,
+        None,
+        &map_leaf(reject),
+    )
+    .await;
+    match res {
+        Err(err @ ReadSingleFileError::MapLeaf { .. }) => {
+            assert_eq!(
+                std::error::Error::source(&err).unwrap().to_string(),
+                "bad leaf"
+            );
+            let ReadSingleFileError::MapLeaf { cid, .. } = err else {
+                unreachable!()
+            };
+            assert_eq!(cid, b_cid);
+        }
+        x => panic!("other result {:?}", x),
+    }
+    // Written up to the failing leaf
+    assert_eq!(out.into_inner(), b"aaaa");
+}
+
+#[async_std::test]
+async fn map_leaf_unsupported() {
+    let car = fs::read("tests/example.car").unwrap();
+    let options = map_leaf(xor_hook());
+
+    // The seek and indexed readers
+    for res in &read_all(&car, &options).await[1..] {
+        match res {
+            Err(ReadSingleFileError::MapLeafUnsupported) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    let mut car_input = car.as_slice();
+    let mut reader = CarFileReader::new(&mut car_input, None, &options);
+    let err = reader.read_to_end(&mut vec![]).await.unwrap_err();
+    assert!(err.to_string().contains("MapLeafUnsupported"), "{}", err);
+
+    // Without an index the indexed reader buffers the DAG
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_indexed_with_options(&mut Cursor::new(&car), &mut out, None, None, &options)
+        .await
+        .unwrap();
+    let (_, expected) = read_buffer(&car, &options).await.unwrap();
+    assert_eq!(out.into_inner(), expected);
+}
@@ -0,0 +1,222 @@
+//! Files written into a memory region of a fixed length with `MappedOutput`
+
+mod common;
+
+use common::generate::{pseudo_random, CarSpec};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    file_size, read_single_file_seek_file, read_single_file_seek_with_options, Finalize,
+    MappedOutput, MappedRegion, ReadSingleFileError, ReadSingleFileOptions,
+};
+use std::{fs, io};
+
+/// Region counting calls to `flush`, as a memory map of a file would write back its pages
+struct CountingRegion {
+    memory: Vec<u8>,
+    flushes: usize,
+}
+
+impl AsRef<[u8]> for CountingRegion {
+    fn as_ref(&self) -> &[u8] {
+        &self.memory
+    }
+}
+
+impl AsMut<[u8]> for CountingRegion {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+}
+
+impl MappedRegion for CountingRegion {
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+#[async_std::test]
+async fn mapped_same_output_as_seek() {
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if !name.ends_with(".car") || name.contains(".size-1.") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+        let len = file_size(&mut car.as_slice(), None).await.unwrap();
+
+        let mut expected = Cursor::new(Vec::new());
+        read_single_file_seek_with_options(
+            &mut car.as_slice(),
+            &mut expected,
+            None,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+
+        for preallocate in [false, true] {
+            let options = ReadSingleFileOptions {
+                preallocate,
+                finalize: Finalize::FlushAndSync,
+                ..Default::default()
+            };
+            let mut out = MappedOutput::new(CountingRegion {
+                memory: vec![0; len as usize],
+                flushes: 0,
+            });
+            let summary = read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+                .await
+                .unwrap();
+            assert_eq!(summary.finalized, Finalize::FlushAndSync, "{:?}", path);
+            assert_eq!(out.position(), len, "{:?}", path);
+            let region = out.into_inner();
+            assert_eq!(region.flushes, 1, "{:?}", path);
+            assert!(region.memory == expected.get_ref()[..], "{:?}", path);
+        }
+    }
+}
+
+#[async_std::test]
+async fn mapped_sparse() {
+    // Runs of zeros are seeked over, the region is zero already
+    let data = [
+        pseudo_random(300, 8),
+        vec![0; 1000],
+        pseudo_random(200, 9),
+        vec![0; 500],
+    ]
+    .concat();
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    let mut out = MappedOutput::new(vec![0; data.len()]);
+    let summary =
+        read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &Default::default())
+            .await
+            .unwrap();
+    assert!(summary.holes_count > 0);
+    assert!(out.into_inner() == data);
+}
+
+#[async_std::test]
+async fn mapped_truncate() {
+    let data = [
+        pseudo_random(300, 12),
+        vec![0; 1000],
+        pseudo_random(200, 13),
+    ]
+    .concat();
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build()
+    .car;
+
+    // A region reused from a longer file keeps nothing of it, in holes or past the file
+    let options = ReadSingleFileOptions {
+        truncate: true,
+        ..Default::default()
+    };
+    let mut out = MappedOutput::new(vec![0xff; data.len() + 500]);
+    let summary = read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert!(summary.holes_count > 0);
+    let region = out.into_inner();
+    assert!(region[..data.len()] == data);
+    assert!(region[data.len()..].iter().all(|&byte| byte == 0));
+
+    // A failed read leaves a zeroed region
+    let options = ReadSingleFileOptions {
+        cleanup_on_error: true,
+        write_limit: Some(data.len() - 1),
+        ..Default::default()
+    };
+    let mut out = MappedOutput::new(vec![0xff; data.len()]);
+    assert!(
+        read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+            .await
+            .is_err()
+    );
+    assert!(out.into_inner().iter().all(|&byte| byte == 0));
+}
+
+#[async_std::test]
+async fn mapped_region_too_small() {
+    let data = pseudo_random(1000, 10);
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build()
+    .car;
+
+    // Writes past the end of the region fail
+    let mut region = vec![0; 999];
+    let mut out = MappedOutput::new(&mut region[..]);
+    match read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &Default::default()).await
+    {
+        Err(ReadSingleFileError::IoError(err)) => assert_eq!(err.kind(), io::ErrorKind::WriteZero),
+        x => panic!("other result {:?}", x),
+    }
+    assert!(region == data[..999]);
+
+    // The region can not be extended to the declared size
+    let options = ReadSingleFileOptions {
+        preallocate: true,
+        ..Default::default()
+    };
+    let mut out = MappedOutput::new(vec![0; 999]);
+    match read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options).await {
+        Err(ReadSingleFileError::IoError(err)) => {
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput)
+        }
+        x => panic!("other result {:?}", x),
+    }
+    assert!(out.into_inner().iter().all(|&byte| byte == 0));
+}
+
+#[cfg(feature = "memmap2")]
+#[async_std::test]
+async fn mapped_memmap2() {
+    let data = pseudo_random(5000, 11);
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    let path = std::env::temp_dir().join(format!("rs-car-ipfs-mapped-{}", std::process::id()));
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    file.set_len(data.len() as u64).unwrap();
+    let map = unsafe { memmap2::MmapMut::map_mut(&file) }.unwrap();
+
+    // Pages are written back to the file on sync
+    let options = ReadSingleFileOptions {
+        finalize: Finalize::FlushAndSync,
+        ..Default::default()
+    };
+    let mut out = MappedOutput::new(map);
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    drop(out);
+    assert!(fs::read(&path).unwrap() == data);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use common::{car_v1, read_all, PbLink, PbNode, TYPE_METADATA};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::{
+    single_file::{
+        read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+        read_single_file_seek_with_options, CarIndex, ReadSingleFileError, ReadSingleFileOptions,
+    },
+    UnixFsType,
+};
+
+/// `Metadata` node linking `links`, with a protobuf `Metadata { MimeType }` as data
+fn metadata_node(links: &[Cid], mime_type: &str) -> (Cid, Vec<u8>) {
+    let mut data = vec![0x0a, mime_type.len() as u8];
+    data.extend_from_slice(mime_type.as_bytes());
+    PbNode {
+        links: links.iter().copied().map(PbLink::new).collect(),
+        unixfs_type: TYPE_METADATA,
+        data: Some(data),
+        ..Default::default()
+    }
+    .block()
+}
+
+/// File `aaaabb` wrapped in a `Metadata` root
+fn wrapped_file() -> Vec<u8> {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (file_cid, file) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let (root_cid, root) = metadata_node(&[file_cid], "text/plain");
+    car_v1(
+        &[root_cid],
+        &[(root_cid, root), (file_cid, file), (a_cid, a), (b_cid, b)],
+    )
+}
+
+#[async_std::test]
+async fn metadata_root_descends() {
+    let car = wrapped_file();
+    for res in read_all(&car, &Default::default()).await {
+        assert_eq!(res.unwrap(), b"aaaabb");
+    }
+
+    let options = ReadSingleFileOptions::default();
+    let mut out = Cursor::new(Vec::new());
+    let buffer =
+        read_single_file_buffer_with_options(&mut Cursor::new(&car), &mut out, None, &options)
+            .await
+            .unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let seek = read_single_file_seek_with_options(&mut Cursor::new(&car), &mut out, None, &options)
+        .await
+        .unwrap();
+    let mut car_input = Cursor::new(&car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let indexed = read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        &options,
+    )
+    .await
+    .unwrap();
+    for summary in [buffer, seek, indexed] {
+        assert_eq!(summary.mime_type.as_deref(), Some("text/plain"));
+        assert_eq!(summary.bytes_written, 6);
+    }
+}
+
+#[async_std::test]
+async fn metadata_root_rejected() {
+    let options = ReadSingleFileOptions {
+        reject_metadata_root: true,
+        ..Default::default()
+    };
+    for res in read_all(&wrapped_file(), &options).await {
+        match res {
+            Err(ReadSingleFileError::RootCidIsNotFile) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn metadata_root_multiple_links() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = metadata_node(&[a_cid, b_cid], "text/plain");
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::InvalidUnixFs(_)) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn metadata_node_not_root() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (meta_cid, meta) = metadata_node(&[a_cid], "text/plain");
+    let (root_cid, root) = PbNode::file_branch(&[(meta_cid, 4)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (meta_cid, meta), (a_cid, a)],
+    );
+
+    // Even with lenient node types
+    let options = ReadSingleFileOptions {
+        lenient_node_types: true,
+        ..Default::default()
+    };
+    for res in read_all(&car, &options).await {
+        match res {
+            Err(ReadSingleFileError::UnexpectedNodeType { cid, found }) => {
+                assert_eq!((cid, found), (meta_cid, UnixFsType::Metadata))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
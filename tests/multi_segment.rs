@@ -0,0 +1,138 @@
+mod common;
+
+use common::car_v1;
+use futures::{io::Cursor, stream};
+use rs_car::car_read_all;
+use rs_car_ipfs::single_file::{
+    read_single_file_multi, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
+use std::fs;
+
+/// Blocks of the fixture CAR `name` split in `count` CAR segments, each with the fixture roots
+async fn segments(name: &str, count: usize) -> Vec<Vec<u8>> {
+    let car = fs::read(format!("tests/data/{}", name)).unwrap();
+    let (blocks, header) = car_read_all(&mut car.as_slice(), true).await.unwrap();
+    let per_segment = blocks.len().div_ceil(count);
+    blocks
+        .chunks(per_segment)
+        .map(|blocks| car_v1(&header.roots, blocks))
+        .collect()
+}
+
+async fn read_multi(segments: &[Vec<u8>]) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let segments = stream::iter(segments.iter().map(|segment| segment.as_slice()));
+    let summary = read_single_file_multi(segments, &mut out, None, &Default::default()).await?;
+    Ok((summary, out.into_inner()))
+}
+
+#[async_std::test]
+async fn read_single_file_multi_segments() {
+    let expected = fs::read("tests/data/rand_100K.bin").unwrap();
+
+    for name in [
+        "rand_100K.bin.size-512.normal.car",
+        "rand_100K.bin.size-512.trickle.car",
+    ] {
+        for count in [1, 2, 5] {
+            let segments = segments(name, count).await;
+            assert_eq!(segments.len(), count);
+            let (summary, out) = read_multi(&segments).await.unwrap();
+            assert_eq!(out, expected, "{} in {} segments", name, count);
+            assert_eq!(summary.bytes_written, expected.len() as u64);
+        }
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_multi_incomplete() {
+    let mut segments = segments("rand_100K.bin.size-512.normal.car", 3).await;
+
+    // Only pending after the last segment
+    segments.pop();
+    match read_multi(&segments).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+
+    // No segments at all
+    match read_multi(&[]).await {
+        Err(ReadSingleFileError::IoError(err)) => {
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof)
+        }
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_multi_root_mismatch() {
+    let mut segments = segments("rand_100K.bin.size-512.normal.car", 3).await;
+    let other = fs::read("tests/data/helloworld.txt.size-1.normal.car").unwrap();
+    let (blocks, header) = car_read_all(&mut segments[2].as_slice(), true)
+        .await
+        .unwrap();
+    let (_, other_header) = car_read_all(&mut other.as_slice(), true).await.unwrap();
+    segments[2] = car_v1(&other_header.roots, &blocks);
+
+    match read_multi(&segments).await {
+        Err(ReadSingleFileError::SegmentRootMismatch { segment, roots }) => {
+            assert_eq!((segment, roots), (2, other_header.roots.clone()));
+        }
+        x => panic!("other result {:?}", x),
+    }
+
+    // Extra roots are accepted
+    let roots = [other_header.roots[0], header.roots[0]];
+    segments[2] = car_v1(&roots, &blocks);
+    read_multi(&segments).await.unwrap();
+}
+
+#[async_std::test]
+async fn read_single_file_multi_split_files() {
+    let car = fs::read("tests/data/rand_100K.bin.size-512.normal.car").unwrap();
+    let expected = fs::read("tests/data/rand_100K.bin").unwrap();
+    let (blocks, header) = car_read_all(&mut car.as_slice(), true).await.unwrap();
+
+    // Split in two files, the second rooted at its first block as split by other tools
+    let (first, second) = blocks.split_at(blocks.len() / 2);
+    let paths = [
+        (car_v1(&header.roots, first), "first"),
+        (car_v1(&[second[0].0], second), "second"),
+    ]
+    .map(|(car, name)| {
+        let path = std::env::temp_dir().join(format!(
+            "rs-car-ipfs-split-{}-{}.car",
+            std::process::id(),
+            name
+        ));
+        fs::write(&path, car).unwrap();
+        path
+    });
+    let open = || async {
+        let mut files = vec![];
+        for path in &paths {
+            files.push(async_std::fs::File::open(path).await.unwrap());
+        }
+        stream::iter(files)
+    };
+
+    let options = ReadSingleFileOptions {
+        ignore_segment_roots: true,
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_multi(open().await, &mut out, None, &options)
+        .await
+        .unwrap();
+    assert!(out.into_inner() == expected);
+
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_multi(open().await, &mut out, None, &Default::default()).await {
+        Err(ReadSingleFileError::SegmentRootMismatch { segment: 1, .. }) => {}
+        x => panic!("other result {:?}", x),
+    }
+
+    for path in paths {
+        fs::remove_file(path).unwrap();
+    }
+}
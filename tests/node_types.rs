@@ -0,0 +1,279 @@
+mod common;
+
+use common::{
+    car_v1, read_all, PbLink, PbNode, TYPE_DIRECTORY, TYPE_METADATA, TYPE_RAW, TYPE_SYMLINK,
+};
+use futures::AsyncReadExt;
+use rs_car::Cid;
+use rs_car_ipfs::{
+    single_file::{file_size, CarFileReader, ReadSingleFileError, ReadSingleFileOptions},
+    UnixFsType,
+};
+
+/// Root File node linking a regular File leaf followed by `child`
+fn file_with_child(child: PbNode) -> (Vec<u8>, Cid) {
+    let (leaf_cid, leaf) = PbNode::file_leaf(b"hello").block();
+    let (child_cid, child) = child.block();
+    let (root_cid, root) = PbNode::file_branch(&[(leaf_cid, 5), (child_cid, 5)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (leaf_cid, leaf), (child_cid, child)],
+    );
+    (car, child_cid)
+}
+
+fn leaf_of_type(unixfs_type: u64) -> PbNode {
+    PbNode {
+        unixfs_type,
+        ..PbNode::file_leaf(b"world")
+    }
+}
+
+#[async_std::test]
+async fn reject_unexpected_leaf_types() {
+    for (unixfs_type, expected_found) in [
+        (TYPE_DIRECTORY, UnixFsType::Directory),
+        (TYPE_SYMLINK, UnixFsType::Symlink),
+    ] {
+        let (car, child_cid) = file_with_child(leaf_of_type(unixfs_type));
+
+        for res in read_all(&car, &Default::default()).await {
+            match res {
+                Err(ReadSingleFileError::UnexpectedNodeType { cid, found }) => {
+                    assert_eq!((cid, found), (child_cid, expected_found))
+                }
+                x => panic!("other result {:?}", x),
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn accept_raw_leaf_type() {
+    let (car, _) = file_with_child(leaf_of_type(TYPE_RAW));
+
+    for res in read_all(&car, &Default::default()).await {
+        assert_eq!(res.unwrap(), b"helloworld");
+    }
+}
+
+#[async_std::test]
+async fn accept_raw_root_type() {
+    // A file of a single Raw node, with or without `filesize`
+    for filesize in [Some(5), None] {
+        let (root_cid, root) = PbNode {
+            filesize,
+            ..leaf_of_type(TYPE_RAW)
+        }
+        .block();
+        let car = car_v1(&[root_cid], &[(root_cid, root)]);
+
+        for res in read_all(&car, &Default::default()).await {
+            assert_eq!(res.unwrap(), b"world");
+        }
+        let mut car_input = car.as_slice();
+        let mut out = vec![];
+        CarFileReader::new(&mut car_input, None, &Default::default())
+            .read_to_end(&mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, b"world");
+        assert_eq!(file_size(&mut car.as_slice(), None).await.unwrap(), 5);
+    }
+
+    // Not as a branch, unless lenient
+    let (leaf_cid, leaf) = PbNode::file_leaf(b"hello").block();
+    let (root_cid, root) = PbNode {
+        unixfs_type: TYPE_RAW,
+        ..PbNode::file_branch(&[(leaf_cid, 5)])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (leaf_cid, leaf)]);
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::UnexpectedNodeType { cid, found }) => {
+                assert_eq!((cid, found), (root_cid, UnixFsType::Raw))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+    let options = ReadSingleFileOptions {
+        lenient_node_types: true,
+        ..Default::default()
+    };
+    for res in read_all(&car, &options).await {
+        assert_eq!(res.unwrap(), b"hello");
+    }
+}
+
+#[async_std::test]
+async fn reject_unexpected_branch_type() {
+    // Intermediary node of type Raw with links
+    let (inner_leaf_cid, inner_leaf) = PbNode::file_leaf(b"world").block();
+    let branch = PbNode {
+        unixfs_type: TYPE_RAW,
+        ..PbNode::file_branch(&[(inner_leaf_cid, 5)])
+    };
+    let (leaf_cid, leaf) = PbNode::file_leaf(b"hello").block();
+    let (branch_cid, branch) = branch.block();
+    let (root_cid, root) = PbNode::file_branch(&[(leaf_cid, 5), (branch_cid, 5)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[
+            (root_cid, root),
+            (leaf_cid, leaf),
+            (branch_cid, branch),
+            (inner_leaf_cid, inner_leaf),
+        ],
+    );
+
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::UnexpectedNodeType { cid, found }) => {
+                assert_eq!((cid, found), (branch_cid, UnixFsType::Raw))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+
+    let options = ReadSingleFileOptions {
+        lenient_node_types: true,
+        ..Default::default()
+    };
+    for res in read_all(&car, &options).await {
+        assert_eq!(res.unwrap(), b"helloworld");
+    }
+}
+
+#[async_std::test]
+async fn reject_directory_under_file() {
+    // A directory with entries linked by the file, e.g. a CAR holding another DAG than expected
+    let (entry_cid, entry) = PbNode::file_leaf(b"world").block();
+    let (dir_cid, dir) = PbNode {
+        links: vec![PbLink {
+            name: Some("world.txt".to_string()),
+            ..PbLink::new(entry_cid)
+        }],
+        unixfs_type: TYPE_DIRECTORY,
+        data: None,
+        filesize: None,
+        blocksizes: vec![],
+    }
+    .block();
+    let (leaf_cid, leaf) = PbNode::file_leaf(b"hello").block();
+    let (root_cid, root) = PbNode::file_branch(&[(leaf_cid, 5), (dir_cid, 5)]).block();
+    // The directory before the leaf preceding it, only an error once reached
+    let car = car_v1(
+        &[root_cid],
+        &[
+            (root_cid, root),
+            (dir_cid, dir),
+            (leaf_cid, leaf),
+            (entry_cid, entry),
+        ],
+    );
+
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::UnexpectedNodeType { cid, found }) => {
+                assert_eq!((cid, found), (dir_cid, UnixFsType::Directory))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+
+    let mut car_input = car.as_slice();
+    let mut reader = CarFileReader::new(&mut car_input, None, &Default::default());
+    let err = reader.read_to_end(&mut vec![]).await.unwrap_err();
+    match err
+        .into_inner()
+        .map(|err| err.downcast::<ReadSingleFileError>())
+    {
+        Some(Ok(err)) => match *err {
+            ReadSingleFileError::UnexpectedNodeType { cid, found } => {
+                assert_eq!((cid, found), (dir_cid, UnixFsType::Directory))
+            }
+            x => panic!("other error {:?}", x),
+        },
+        x => panic!("other error {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn lenient_node_types_writes_unexpected_leaf() {
+    let (car, _) = file_with_child(leaf_of_type(TYPE_SYMLINK));
+    let options = ReadSingleFileOptions {
+        lenient_node_types: true,
+        ..Default::default()
+    };
+
+    for res in read_all(&car, &options).await {
+        assert_eq!(res.unwrap(), b"helloworld");
+    }
+}
+
+#[async_std::test]
+async fn require_file_root() {
+    let (hello_cid, hello) = PbNode::file_leaf(b"hello").block();
+    let (world_cid, world) = PbNode::file_leaf(b"world").block();
+    let (dir_cid, dir) = PbNode {
+        links: vec![
+            PbLink {
+                name: Some("a".to_string()),
+                ..PbLink::new(hello_cid)
+            },
+            PbLink {
+                name: Some("b".to_string()),
+                ..PbLink::new(world_cid)
+            },
+        ],
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+    .block();
+    let car = car_v1(
+        &[dir_cid],
+        &[(dir_cid, dir), (hello_cid, hello), (world_cid, world)],
+    );
+    let options = ReadSingleFileOptions {
+        require_file_root: false,
+        ..Default::default()
+    };
+
+    for res in read_all(&car, &Default::default()).await {
+        assert!(matches!(res, Err(ReadSingleFileError::RootCidIsNotFile)));
+    }
+    // The entries are read as the chunks of a file
+    for res in read_all(&car, &options).await {
+        assert_eq!(res.unwrap(), b"helloworld");
+    }
+    let mut car_input = car.as_slice();
+    let mut out = vec![];
+    CarFileReader::new(&mut car_input, None, &options)
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+    assert_eq!(out, b"helloworld");
+
+    // The data of a root leaf is the file
+    let (root_cid, root) = leaf_of_type(TYPE_SYMLINK).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root)]);
+    for res in read_all(&car, &options).await {
+        assert_eq!(res.unwrap(), b"world");
+    }
+
+    // Unless rejected by `reject_metadata_root`
+    let (root_cid, root) = PbNode {
+        unixfs_type: TYPE_METADATA,
+        ..PbNode::file_branch(&[(hello_cid, 5)])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root)]);
+    let options = ReadSingleFileOptions {
+        reject_metadata_root: true,
+        ..options
+    };
+    for res in read_all(&car, &options).await {
+        assert!(matches!(res, Err(ReadSingleFileError::RootCidIsNotFile)));
+    }
+}
@@ -0,0 +1,82 @@
+//! Random reads of a file through a `FileOffsetIndex` built from its `blocksizes`
+
+mod common;
+
+use common::generate::{pseudo_random, CarSpec, Layout};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{CarIndex, FileOffsetIndex, LeafRange};
+
+#[async_std::test]
+async fn offset_index_query() {
+    let generated = CarSpec {
+        data: pseudo_random(1000, 3),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build();
+    let offsets = FileOffsetIndex::build(&mut generated.car.as_slice(), None)
+        .await
+        .unwrap();
+    assert_eq!(offsets.len(), 1000);
+    // Root first, then the leaves in file order
+    let leaves: Vec<_> = generated.blocks[1..].iter().map(|(cid, _)| *cid).collect();
+
+    let range = |index: usize, file_offset, leaf_offset, len| LeafRange {
+        cid: leaves[index],
+        file_offset,
+        leaf_offset,
+        len,
+    };
+    assert_eq!(
+        offsets.query(250..520),
+        vec![
+            range(2, 250, 50, 50),
+            range(3, 300, 0, 100),
+            range(4, 400, 0, 100),
+            range(5, 500, 0, 20),
+        ]
+    );
+    // Within a single leaf, and on its boundaries
+    assert_eq!(offsets.query(310..320), vec![range(3, 310, 10, 10)]);
+    assert_eq!(offsets.query(300..400), vec![range(3, 300, 0, 100)]);
+    // Clamped to the file
+    assert_eq!(offsets.query(950..5000), vec![range(9, 950, 50, 50)]);
+    assert!(offsets.query(1000..1100).is_empty());
+    assert!(offsets.query(400..400).is_empty());
+}
+
+#[async_std::test]
+async fn offset_index_read_range() {
+    let data = pseudo_random(5000, 4);
+    for layout in [Layout::Balanced, Layout::Trickle { repeat: 2 }] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 64,
+            fanout: 4,
+            layout,
+            ..Default::default()
+        }
+        .build();
+        let mut car_input = Cursor::new(generated.car);
+        let offsets = FileOffsetIndex::build(&mut car_input, None).await.unwrap();
+        let index = CarIndex::build(&mut car_input).await.unwrap();
+
+        // Several reads against the same index
+        for (start, end) in [(0, 5000), (1, 2), (63, 65), (1000, 3333), (4990, 6000)] {
+            let mut out = Cursor::new(Vec::new());
+            let written = offsets
+                .read_range(&mut car_input, &index, start..end, &mut out)
+                .await
+                .unwrap();
+            let expected = &data[start as usize..(end as usize).min(data.len())];
+            assert_eq!(written, expected.len() as u64);
+            assert!(
+                out.into_inner() == expected,
+                "{:?} {}..{}",
+                layout,
+                start,
+                end
+            );
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Reads through `ReadSingleFileOptions::read_seek` and `read_buffered`
+
+use futures::io::Cursor;
+use rs_car_ipfs::{
+    decompress::Compression,
+    single_file::{ReadSingleFileError, ReadSingleFileOptions},
+};
+use std::fs;
+
+const CAR_FILEPATH: &str = "tests/data/rand_100K.bin.size-512.normal.car";
+const EXPECTED_FILEPATH: &str = "tests/data/rand_100K.bin";
+
+#[async_std::test]
+async fn options_read_seek_and_buffered() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+    let options = ReadSingleFileOptions {
+        expected_size: Some(expected.len() as u64),
+        ..Default::default()
+    };
+
+    let mut out = Cursor::new(Vec::new());
+    let summary = options
+        .read_seek(&mut car.as_slice(), &mut out, None)
+        .await
+        .unwrap();
+    assert_eq!(summary.bytes_written, expected.len() as u64);
+    assert!(out.into_inner() == expected);
+
+    let mut out = vec![];
+    let summary = options
+        .read_buffered(&mut car.as_slice(), &mut out, None)
+        .await
+        .unwrap();
+    assert_eq!(summary.bytes_written, expected.len() as u64);
+    assert!(out == expected);
+}
+
+#[async_std::test]
+async fn options_check_seek() {
+    assert!(ReadSingleFileOptions::default().check_seek().is_ok());
+
+    let options = ReadSingleFileOptions {
+        compress_output: Compression::Gzip,
+        ..Default::default()
+    };
+    assert!(matches!(
+        options.check_seek(),
+        Err(ReadSingleFileError::CompressedOutputUnsupported)
+    ));
+
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let res = options.read_seek(&mut car.as_slice(), &mut out, None).await;
+    assert!(matches!(
+        res,
+        Err(ReadSingleFileError::CompressedOutputUnsupported)
+    ));
+    assert!(out.into_inner().is_empty());
+}
@@ -0,0 +1,99 @@
+//! Digest of the file written with `hash_output`
+
+use futures::io::Cursor;
+use multihash::{Code, MultihashDigest};
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, OutputHash,
+    ReadSingleFileOptions,
+};
+use std::fs;
+
+const CAR_FILEPATH: &str = "tests/data/rand_100K.bin.size-512.normal.car";
+const EXPECTED_FILEPATH: &str = "tests/data/rand_100K.bin";
+/// `sha256sum tests/data/rand_100K.bin`
+const EXPECTED_SHA256: &str = "a6feedb9f7164e0aa75a2d2cd26ea5df5069d768e198cc2512ba8286fcdef931";
+
+fn hashed(hash: OutputHash) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        hash_output: Some(hash),
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn hash_output_sha256() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let mut out = vec![];
+    let summary = read_single_file_buffer_with_options(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        &hashed(OutputHash::Sha2_256),
+    )
+    .await
+    .unwrap();
+
+    let digest = summary.output_digest.unwrap();
+    assert_eq!(digest.code(), u64::from(Code::Sha2_256));
+    assert_eq!(hex::encode(digest.digest()), EXPECTED_SHA256);
+}
+
+#[async_std::test]
+async fn hash_output_functions() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let expected = fs::read(EXPECTED_FILEPATH).unwrap();
+    for hash in [
+        OutputHash::Sha2_256,
+        OutputHash::Sha2_512,
+        OutputHash::Blake2b256,
+        OutputHash::Blake2b512,
+    ] {
+        let mut out = vec![];
+        let summary = read_single_file_buffer_with_options(
+            &mut car.as_slice(),
+            &mut out,
+            None,
+            &hashed(hash),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            summary.output_digest,
+            Some(hash.code().digest(&expected)),
+            "{:?}",
+            hash
+        );
+    }
+}
+
+#[cfg(feature = "gzip")]
+#[async_std::test]
+async fn hash_output_before_compression() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let options = ReadSingleFileOptions {
+        compress_output: rs_car_ipfs::decompress::Compression::Gzip,
+        ..hashed(OutputHash::Sha2_256)
+    };
+    let mut out = vec![];
+    let summary =
+        read_single_file_buffer_with_options(&mut car.as_slice(), &mut out, None, &options)
+            .await
+            .unwrap();
+    let digest = summary.output_digest.unwrap();
+    assert_eq!(hex::encode(digest.digest()), EXPECTED_SHA256);
+}
+
+#[async_std::test]
+async fn hash_output_not_used_by_seek() {
+    let car = fs::read(CAR_FILEPATH).unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_seek_with_options(
+        &mut car.as_slice(),
+        &mut out,
+        None,
+        &hashed(OutputHash::Sha2_256),
+    )
+    .await
+    .unwrap();
+    assert_eq!(summary.output_digest, None);
+}
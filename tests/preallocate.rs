@@ -0,0 +1,90 @@
+mod common;
+
+use common::{car_v1, PbNode, Recorder};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_seek_file, read_single_file_seek_with_options, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+use std::fs;
+
+fn preallocate() -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        preallocate: true,
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn preallocate_write_last_byte() {
+    let car = fs::read("tests/data/rand_100K.bin.size-32.normal.car").unwrap();
+    let expected = fs::read("tests/data/rand_100K.bin").unwrap();
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &preallocate())
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), expected);
+}
+
+#[async_std::test]
+async fn preallocate_set_len() {
+    let car = fs::read("tests/data/seq_1000.txt.size-32.normal.car").unwrap();
+    let expected = fs::read("tests/data/seq_1000.txt").unwrap();
+
+    let options = ReadSingleFileOptions {
+        base_offset: 10,
+        ..preallocate()
+    };
+    let mut out = Recorder::default();
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(out.set_lens, vec![10 + expected.len() as u64]);
+    assert_eq!(&out.inner.into_inner()[10..], &expected);
+
+    // Never shortens `out`
+    let mut out = Recorder::new(vec![b'x'; expected.len() + 20]);
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(out.set_lens, Vec::<u64>::new());
+    let out = out.inner.into_inner();
+    assert_eq!(&out[10..10 + expected.len()], &expected);
+    assert_eq!(&out[10 + expected.len()..], &[b'x'; 10]);
+
+    // Disabled by default
+    let mut out = Recorder::default();
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &Default::default())
+        .await
+        .unwrap();
+    assert_eq!(out.set_lens, Vec::<u64>::new());
+}
+
+#[async_std::test]
+async fn preallocate_declared_size_mismatch() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (root_cid, root) = PbNode {
+        filesize: Some(10),
+        ..PbNode::file_branch(&[(a_cid, 4)])
+    }
+    .block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &preallocate())
+        .await
+    {
+        Err(ReadSingleFileError::FileSizeMismatch { declared, written }) => {
+            assert_eq!((declared, written), (10, 4))
+        }
+        x => panic!("other result {:?}", x),
+    }
+
+    // Not enforced without preallocating
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &Default::default())
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"aaaa");
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec},
+    PbNode,
+};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_handle,
+    read_single_file_seek_with_options, Progress, ProgressHandle, ProgressHook,
+    ReadSingleFileError, ReadSingleFileOptions,
+};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Options recording every reported progress
+fn recording() -> (ReadSingleFileOptions, Arc<Mutex<Vec<Progress>>>) {
+    let reported = Arc::new(Mutex::new(vec![]));
+    let recorder = reported.clone();
+    let options = ReadSingleFileOptions {
+        on_progress: Some(ProgressHook::new(move |progress| {
+            recorder.lock().unwrap().push(*progress)
+        })),
+        ..Default::default()
+    };
+    (options, reported)
+}
+
+fn assert_progress(reported: &[Progress], file_len: u64, blocks: usize) {
+    assert_eq!(reported[0], Progress::default());
+    for pair in reported.windows(2) {
+        assert!(pair[0].bytes_written <= pair[1].bytes_written);
+        assert!(pair[0].blocks_read <= pair[1].blocks_read);
+    }
+    // The declared size is known once the root, first in the CAR, was read
+    assert!(reported[1..]
+        .iter()
+        .all(|progress| progress.declared_size == Some(file_len)));
+    assert_eq!(
+        reported.last(),
+        Some(&Progress {
+            bytes_written: file_len,
+            blocks_read: blocks,
+            declared_size: Some(file_len),
+        })
+    );
+}
+
+#[async_std::test]
+async fn progress_reported() {
+    let data = pseudo_random(5000, 4);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        fanout: 8,
+        ..Default::default()
+    }
+    .build();
+    let file_len = data.len() as u64;
+
+    let (options, reported) = recording();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut generated.car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    let reported = reported.lock().unwrap().clone();
+    assert_progress(&reported, file_len, generated.blocks.len());
+    // Bytes are written while the CAR is read
+    assert!(reported.iter().any(
+        |progress| progress.bytes_written > 0 && progress.blocks_read < generated.blocks.len()
+    ));
+
+    let (options, reported) = recording();
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(&mut generated.car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_progress(&reported.lock().unwrap(), file_len, generated.blocks.len());
+}
+
+#[async_std::test]
+async fn progress_handle_polled() {
+    let data = pseudo_random(5000, 5);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        fanout: 8,
+        ..Default::default()
+    }
+    .build();
+
+    // Polled from the progress hook, while the read runs
+    let handle = Arc::new(OnceLock::<ProgressHandle>::new());
+    let polled = Arc::new(Mutex::new(vec![]));
+    let (poll_handle, recorder) = (handle.clone(), polled.clone());
+    let options = ReadSingleFileOptions {
+        on_progress: Some(ProgressHook::new(move |_| {
+            let handle = poll_handle.get().unwrap();
+            recorder.lock().unwrap().push((
+                handle.bytes_written(),
+                handle.blocks_read(),
+                handle.pending_links_len(),
+                handle.pending_head(),
+            ));
+        })),
+        ..Default::default()
+    };
+    let mut car_input = generated.car.as_slice();
+    let mut out = Cursor::new(Vec::new());
+    let (read, read_handle) =
+        read_single_file_seek_with_handle(&mut car_input, &mut out, None, &options);
+    handle.set(read_handle.clone()).unwrap();
+    read.await.unwrap();
+    assert!(out.into_inner() == data);
+
+    let polled = polled.lock().unwrap().clone();
+    assert_eq!(polled[0], (0, 0, 0, None));
+    for pair in polled.windows(2) {
+        assert!(pair[0].0 <= pair[1].0 && pair[0].1 <= pair[1].1);
+    }
+    // Waiting for the root, then for nodes of the file
+    assert_eq!(polled[1], (0, 0, 1, Some(generated.root)));
+    assert!(polled[1..]
+        .iter()
+        .all(|(_, _, len, head)| *len > 0 && head.is_some()));
+    assert_eq!(
+        (
+            read_handle.bytes_written(),
+            read_handle.blocks_read(),
+            read_handle.pending_links_len(),
+            read_handle.pending_head()
+        ),
+        (data.len() as u64, generated.blocks.len(), 0, None)
+    );
+}
+
+#[async_std::test]
+async fn progress_handle_after_failure() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, _) = PbNode::file_leaf(b"bb").block();
+    let (c_cid, _) = PbNode::file_leaf(b"c").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2), (c_cid, 1)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a)]);
+
+    let options = Default::default();
+    let mut car_input = car.as_slice();
+    let mut out = Cursor::new(Vec::new());
+    let (read, handle) =
+        read_single_file_seek_with_handle(&mut car_input, &mut out, None, &options);
+    match read.await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+    // What the read was waiting for when the CAR ended
+    assert_eq!(handle.bytes_written(), 4);
+    assert_eq!(handle.blocks_read(), 2);
+    assert_eq!(handle.pending_links_len(), 2);
+    assert_eq!(handle.pending_head(), Some(b_cid));
+}
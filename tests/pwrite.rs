@@ -0,0 +1,96 @@
+#![cfg(all(unix, feature = "pwrite"))]
+
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_pwrite, read_single_file_seek_with_options, Finalize, ReadSingleFileOptions,
+};
+use std::{
+    fs,
+    io::{Read, Seek, SeekFrom},
+};
+
+#[async_std::test]
+async fn pwrite_same_output_as_seek() {
+    let dir = std::env::temp_dir().join(format!("rs-car-ipfs-pwrite-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "car") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+
+        let mut expected = Cursor::new(Vec::new());
+        let expected_res = read_single_file_seek_with_options(
+            &mut car.as_slice(),
+            &mut expected,
+            None,
+            &Default::default(),
+        )
+        .await;
+
+        for preallocate in [false, true] {
+            let options = ReadSingleFileOptions {
+                preallocate,
+                finalize: Finalize::FlushAndSync,
+                ..Default::default()
+            };
+            let out_path = dir.join(path.file_name().unwrap());
+            let file = fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&out_path)
+                .unwrap();
+            let res = read_single_file_pwrite(&mut car.as_slice(), &file, None, &options).await;
+            match (&res, &expected_res) {
+                (Ok(summary), Ok(expected_summary)) => {
+                    assert_eq!(summary.bytes_written, expected_summary.bytes_written);
+                    assert_eq!(summary.finalized, Finalize::FlushAndSync);
+                    assert!(
+                        fs::read(&out_path).unwrap() == expected.get_ref()[..],
+                        "{}",
+                        path.display()
+                    );
+                }
+                (Err(err), Err(expected_err)) => {
+                    assert_eq!(format!("{:?}", err), format!("{:?}", expected_err))
+                }
+                _ => panic!("{}: {:?} != {:?}", path.display(), res, expected_res),
+            }
+        }
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[async_std::test]
+async fn pwrite_keeps_descriptor_offset() {
+    let car = fs::read("tests/data/rand_100K.bin.size-1.normal.car").unwrap();
+    let path =
+        std::env::temp_dir().join(format!("rs-car-ipfs-pwrite-offset-{}", std::process::id()));
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+    file.seek(SeekFrom::Start(10)).unwrap();
+
+    read_single_file_pwrite(&mut car.as_slice(), &file, None, &Default::default())
+        .await
+        .unwrap();
+
+    // Another user of the descriptor reads from where it was
+    assert_eq!(file.stream_position().unwrap(), 10);
+    let mut rest = vec![];
+    file.read_to_end(&mut rest).unwrap();
+    let written = fs::read(&path).unwrap();
+    assert_eq!(written.len(), 100 * 1024);
+    assert!(rest == written[10..]);
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::generate::{pseudo_random, CarSpec};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, RateLimit,
+    ReadSingleFileOptions, SleepHook, Throughput, ThroughputHook,
+};
+use std::{
+    num::NonZeroU64,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+fn rate_limit(max_bytes_per_sec: u64) -> Option<RateLimit> {
+    Some(RateLimit::new(
+        NonZeroU64::new(max_bytes_per_sec).unwrap(),
+        SleepHook::new(|duration| Box::pin(async_std::task::sleep(duration))),
+    ))
+}
+
+#[async_std::test]
+async fn rate_limited() {
+    let data = pseudo_random(20_000, 8);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 1000,
+        ..Default::default()
+    }
+    .build();
+
+    let reported = Arc::new(Mutex::new(Vec::<Throughput>::new()));
+    let recorder = reported.clone();
+    let options = ReadSingleFileOptions {
+        rate_limit: rate_limit(40_000),
+        on_throughput: Some(ThroughputHook::new(move |throughput| {
+            recorder.lock().unwrap().push(*throughput)
+        })),
+        ..Default::default()
+    };
+
+    for seek in [true, false] {
+        reported.lock().unwrap().clear();
+        let start = Instant::now();
+        let mut out = Cursor::new(Vec::new());
+        if seek {
+            read_single_file_seek_with_options(
+                &mut generated.car.as_slice(),
+                &mut out,
+                None,
+                &options,
+            )
+            .await
+            .unwrap();
+        } else {
+            read_single_file_buffer_with_options(
+                &mut generated.car.as_slice(),
+                &mut out,
+                None,
+                &options,
+            )
+            .await
+            .unwrap();
+        }
+        assert!(out.into_inner() == data);
+        // All but the last leaf are paced
+        assert!(start.elapsed() >= Duration::from_millis(19_000 * 1000 / 40_000));
+
+        let reported = reported.lock().unwrap();
+        assert!(reported.len() >= 2);
+        for pair in reported.windows(2) {
+            assert!(pair[0].bytes_written <= pair[1].bytes_written);
+            assert!(pair[0].elapsed <= pair[1].elapsed);
+        }
+        assert_eq!(reported.last().unwrap().bytes_written, data.len() as u64);
+    }
+}
+
+#[async_std::test]
+async fn rate_limited_cancelled() {
+    let data = pseudo_random(20_000, 9);
+    let generated = CarSpec {
+        data,
+        chunk_size: 1000,
+        ..Default::default()
+    }
+    .build();
+    // Would take 20 s
+    let options = ReadSingleFileOptions {
+        rate_limit: rate_limit(1000),
+        ..Default::default()
+    };
+
+    let start = Instant::now();
+    let mut car_input = generated.car.as_slice();
+    let mut out = Cursor::new(Vec::new());
+    let read = read_single_file_seek_with_options(&mut car_input, &mut out, None, &options);
+    assert!(async_std::future::timeout(Duration::from_millis(200), read)
+        .await
+        .is_err());
+    assert!(start.elapsed() < Duration::from_secs(2));
+    // Less than a second of data was written
+    assert!(out.into_inner().len() <= 2000);
+}
@@ -0,0 +1,109 @@
+//! `read_buffer_capacity` sets the buffer `car_input` is read through
+
+mod common;
+
+use common::generate::{pseudo_random, CarSpec};
+use futures::{
+    io::{AsyncReadExt, Cursor},
+    AsyncRead,
+};
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, CarFileReader,
+    ReadSingleFileOptions, DEFAULT_READ_BUFFER_CAPACITY,
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Input counting the reads made to it
+struct CountReads<'a> {
+    inner: &'a [u8],
+    reads: usize,
+}
+
+impl AsyncRead for CountReads<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.reads += 1;
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+/// Output and count of reads of `car` by the seek and buffer readers and [`CarFileReader`]
+async fn read_counted(car: &[u8], options: &ReadSingleFileOptions) -> Vec<(Vec<u8>, usize)> {
+    let mut results = vec![];
+
+    let mut input = CountReads {
+        inner: car,
+        reads: 0,
+    };
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(&mut input, &mut out, None, options)
+        .await
+        .unwrap();
+    results.push((out.into_inner(), input.reads));
+
+    let mut input = CountReads {
+        inner: car,
+        reads: 0,
+    };
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(&mut input, &mut out, None, options)
+        .await
+        .unwrap();
+    results.push((out.into_inner(), input.reads));
+
+    let mut input = CountReads {
+        inner: car,
+        reads: 0,
+    };
+    let mut out = vec![];
+    CarFileReader::new(&mut input, None, options)
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+    results.push((out, input.reads));
+
+    results
+}
+
+#[async_std::test]
+async fn read_buffer_capacity() {
+    let data = pseudo_random(200_000, 7);
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 1000,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    let options = |capacity| ReadSingleFileOptions {
+        read_buffer_capacity: capacity,
+        ..Default::default()
+    };
+
+    let unbuffered = read_counted(&car, &options(Some(0))).await;
+    let buffered = read_counted(&car, &options(None)).await;
+    let large = read_counted(&car, &options(Some(1 << 20))).await;
+    for ((unbuffered, buffered), large) in unbuffered.iter().zip(&buffered).zip(&large) {
+        assert!(unbuffered.0 == data && buffered.0 == data && large.0 == data);
+        // Each block is read in a few reads without a buffer, the default buffer holds many
+        assert!(unbuffered.1 > 200, "{}", unbuffered.1);
+        assert!(
+            buffered.1 <= car.len() / DEFAULT_READ_BUFFER_CAPACITY + 2,
+            "{}",
+            buffered.1
+        );
+        assert!(large.1 <= 2, "{}", large.1);
+    }
+
+    // A buffer of 1 byte still reads the whole file
+    for (out, _) in read_counted(&car, &options(Some(1))).await {
+        assert!(out == data);
+    }
+}
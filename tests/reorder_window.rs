@@ -0,0 +1,97 @@
+//! Leaves arriving before the leaves preceding them in the file, held by the seek reader within
+//! its reorder window
+
+mod common;
+
+use common::generate::{pseudo_random, CarSpec, GeneratedCar, Layout, Order};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_seek_with_options, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
+
+async fn read_seek(
+    car: &[u8],
+    reorder_window: Option<usize>,
+) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let options = ReadSingleFileOptions {
+        reorder_window,
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_seek_with_options(&mut &car[..], &mut out, None, &options).await?;
+    Ok((summary, out.into_inner()))
+}
+
+/// Level by level, the leaves of a trickle DAG arrive before the deeper leaves preceding them
+fn bfs_trickle(data: &[u8]) -> GeneratedCar {
+    CarSpec {
+        data: data.to_vec(),
+        chunk_size: 100,
+        fanout: 3,
+        layout: Layout::Trickle { repeat: 2 },
+        order: Order::Bfs,
+        ..Default::default()
+    }
+    .build()
+}
+
+#[async_std::test]
+async fn reorder_window_bfs() {
+    // Repeated content, leaves held in the window are also copied once written
+    let data = pseudo_random(2000, 6).repeat(3);
+    let generated = bfs_trickle(&data);
+
+    let (summary, out) = read_seek(&generated.car, None).await.unwrap();
+    assert!(out == data);
+    assert_eq!(summary.bytes_written, data.len() as u64);
+    assert_eq!(summary.blocks_read, generated.blocks.len());
+
+    match read_seek(&generated.car, Some(0)).await {
+        Err(ReadSingleFileError::DataNodesNotSorted) => {}
+        x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+    }
+
+    // Grown to the bytes needed until the window is large enough
+    let mut window = 1;
+    let out = loop {
+        match read_seek(&generated.car, Some(window)).await {
+            Ok((_, out)) => break out,
+            Err(ReadSingleFileError::ReorderWindowExceeded { needed, limit }) => {
+                assert_eq!(limit, window);
+                assert!(needed > window && needed <= data.len(), "{}", needed);
+                window = needed;
+            }
+            Err(err) => panic!("window {}: {:?}", window, err),
+        }
+    };
+    assert!(out == data);
+    assert!(window > 1 && window < data.len(), "{}", window);
+    match read_seek(&generated.car, Some(window - 1)).await {
+        Err(ReadSingleFileError::ReorderWindowExceeded { limit, .. }) => {
+            assert_eq!(limit, window - 1)
+        }
+        x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+    }
+}
+
+#[async_std::test]
+async fn reorder_window_other_orders() {
+    // Leaves arriving before their parent are still discarded
+    let data = pseudo_random(3000, 7);
+    for order in [Order::ChildrenFirst, Order::Random(4)] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 3,
+            order,
+            ..Default::default()
+        }
+        .build();
+        match read_seek(&generated.car, None).await {
+            Ok((_, out)) => assert!(out == data, "{:?}", order),
+            Err(ReadSingleFileError::PendingLinksAtEOF(_)) => {}
+            Err(err) => panic!("{:?}: {:?}", order, err),
+        }
+    }
+}
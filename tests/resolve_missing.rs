@@ -0,0 +1,198 @@
+//! Blocks missing from the CAR fetched with `resolve_missing`
+
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec, Corruption},
+    PbNode,
+};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_seek_with_options, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+    ResolveHook, ResolveMissing, SleepHook,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+type Blocks = Vec<(Cid, Vec<u8>)>;
+
+/// File of 10 leaves, the CAR without its 4th leaf and the blocks of the whole DAG
+fn missing_leaf() -> (Vec<u8>, Vec<u8>, Blocks) {
+    let data = pseudo_random(1000, 5);
+    let spec = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        ..Default::default()
+    };
+    let car = CarSpec {
+        corruption: Some(Corruption::Drop(4)),
+        ..spec.clone()
+    }
+    .build()
+    .car;
+    (data, car, spec.build().blocks)
+}
+
+fn sleep() -> SleepHook {
+    SleepHook::new(|duration| Box::pin(async_std::task::sleep(duration)))
+}
+
+async fn read(
+    car: &[u8],
+    resolver: ResolveMissing,
+) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let options = ReadSingleFileOptions {
+        resolve_missing: Some(resolver),
+        ..Default::default()
+    };
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_seek_with_options(&mut &car[..], &mut out, None, &options).await?;
+    Ok((summary, out.into_inner()))
+}
+
+#[async_std::test]
+async fn resolve_missing_leaf() {
+    let (data, car, blocks) = missing_leaf();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let resolve = ResolveHook::new(move |cid| {
+        counted.fetch_add(1, Ordering::Relaxed);
+        let block = blocks
+            .iter()
+            .find(|(block_cid, _)| *block_cid == cid)
+            .map(|(_, block)| block.clone());
+        Box::pin(async move { block })
+    });
+
+    let (summary, out) = read(
+        &car,
+        ResolveMissing::new(resolve, Duration::from_secs(5), sleep()),
+    )
+    .await
+    .unwrap();
+    assert!(out == data);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    assert_eq!(summary.blocks_read, 10);
+}
+
+#[async_std::test]
+async fn resolve_missing_retries() {
+    let (data, car, blocks) = missing_leaf();
+    let missing = blocks[4].clone();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    // Fails twice, then returns the block
+    let resolve = ResolveHook::new(move |_| {
+        let block = (counted.fetch_add(1, Ordering::Relaxed) == 2).then(|| missing.1.clone());
+        Box::pin(async move { block })
+    });
+
+    let (_, out) = read(
+        &car,
+        ResolveMissing::new(resolve.clone(), Duration::from_secs(5), sleep()),
+    )
+    .await
+    .unwrap();
+    assert!(out == data);
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+    // Out of attempts
+    calls.store(0, Ordering::Relaxed);
+    let resolver = ResolveMissing {
+        attempts: 2,
+        ..ResolveMissing::new(resolve, Duration::from_secs(5), sleep())
+    };
+    match read(&car, resolver).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(pending)) => {
+            assert_eq!(pending[0], missing.0)
+        }
+        x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+    }
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+}
+
+#[async_std::test]
+async fn resolve_missing_timeout() {
+    let (_, car, _) = missing_leaf();
+    // Never completes
+    let resolve = ResolveHook::new(|_| Box::pin(futures::future::pending()));
+
+    let resolver = ResolveMissing::new(resolve, Duration::from_millis(10), sleep());
+    match read(&car, resolver).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(_)) => {}
+        x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+    }
+}
+
+#[async_std::test]
+async fn resolve_missing_wrong_block() {
+    let (_, car, blocks) = missing_leaf();
+    let other = blocks[5].1.clone();
+    let resolve = ResolveHook::new(move |_| {
+        let block = other.clone();
+        Box::pin(async move { Some(block) })
+    });
+
+    let resolver = ResolveMissing::new(resolve, Duration::from_secs(5), sleep());
+    match read(&car, resolver).await {
+        // Validated against its CID like the blocks of the CAR
+        Err(ReadSingleFileError::CarDecodeError(_)) => {}
+        x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+    }
+}
+
+#[async_std::test]
+async fn resolve_missing_limits() {
+    // The CAR holds the root and a small leaf, the large leaf is resolved
+    let large = pseudo_random(1000, 6);
+    let (small_cid, small) = PbNode::file_leaf(b"aaaa").block();
+    let (large_cid, large) = PbNode::file_leaf(&large).block();
+    let (root_cid, root) = PbNode::file_branch(&[(small_cid, 4), (large_cid, 1000)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (small_cid, small)]);
+    let resolver = || {
+        let large = large.clone();
+        let resolve = ResolveHook::new(move |_| {
+            let block = large.clone();
+            Box::pin(async move { Some(block) })
+        });
+        ResolveMissing::new(resolve, Duration::from_secs(5), sleep())
+    };
+
+    let read_with = |options: ReadSingleFileOptions| {
+        let car = car.clone();
+        async move {
+            let mut out = Cursor::new(Vec::new());
+            read_single_file_seek_with_options(&mut car.as_slice(), &mut out, None, &options).await
+        }
+    };
+    let options = ReadSingleFileOptions {
+        resolve_missing: Some(resolver()),
+        max_block_size: Some(500),
+        ..Default::default()
+    };
+    match read_with(options).await {
+        Err(ReadSingleFileError::BlockTooLarge { cid, limit, .. }) => {
+            assert_eq!((cid, limit), (large_cid, 500))
+        }
+        x => panic!("other result {:?}", x),
+    }
+    let options = ReadSingleFileOptions {
+        resolve_missing: Some(resolver()),
+        max_blocks: Some(2),
+        ..Default::default()
+    };
+    match read_with(options).await {
+        Err(ReadSingleFileError::MaxBlocksExceeded { limit, read }) => {
+            assert_eq!((limit, read), (2, 3))
+        }
+        x => panic!("other result {:?}", x),
+    }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use common::{car_v1, read_all, PbNode};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    inspect_root, read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_with_options, CarFileReader, CarIndex, ReadSingleFileError,
+};
+use std::fs;
+
+#[async_std::test]
+async fn root_block_missing() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (root_cid, _) = PbNode::file_branch(&[(a_cid, 4)]).block();
+    let car = car_v1(&[root_cid], &[(a_cid, a)]);
+
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::RootCidNotFound {
+                root,
+                blocks_seen,
+                header_roots,
+            }) => assert_eq!(
+                (root, blocks_seen, header_roots.to_vec()),
+                (root_cid, 1, vec![root_cid])
+            ),
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
+
+#[async_std::test]
+async fn root_block_present_dag_incomplete() {
+    let (a_cid, _) = PbNode::file_leaf(b"aaaa").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root)]);
+
+    let results = read_all(&car, &Default::default()).await;
+    assert!(
+        matches!(&results[..], [
+            Err(ReadSingleFileError::MissingNode(buffer)),
+            Err(ReadSingleFileError::PendingLinksAtEOF(seek)),
+            Err(ReadSingleFileError::MissingNode(indexed)),
+        ] if *buffer == a_cid && *seek == vec![a_cid] && *indexed == a_cid),
+        "{:?}",
+        results
+    );
+}
+
+#[async_std::test]
+async fn root_cid_of_other_car() {
+    let car_a = fs::read("tests/data/helloworld.txt.size-32.normal.car").unwrap();
+    let car_b = fs::read("tests/data/rand_1K.bin.size-32.normal.car").unwrap();
+    let root_a = inspect_root(&mut car_a.as_slice(), None).await.unwrap().cid;
+    let root_b = inspect_root(&mut car_b.as_slice(), None).await.unwrap().cid;
+    let blocks_a = rs_car::car_read_all(&mut car_a.as_slice(), true)
+        .await
+        .unwrap()
+        .0
+        .len();
+    let root = Some(&root_b);
+    let options = Default::default();
+
+    let mut results = vec![];
+    let mut out = Cursor::new(Vec::new());
+    results.push(
+        read_single_file_buffer_with_options(&mut car_a.as_slice(), &mut out, root, &options)
+            .await
+            .map(|_| ()),
+    );
+    let mut out = Cursor::new(Vec::new());
+    results.push(
+        read_single_file_seek_with_options(&mut car_a.as_slice(), &mut out, root, &options)
+            .await
+            .map(|_| ()),
+    );
+    let mut car_input = Cursor::new(&car_a);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    results.push(
+        read_single_file_indexed_with_options(
+            &mut car_input,
+            &mut out,
+            root,
+            Some(&index),
+            &options,
+        )
+        .await
+        .map(|_| ()),
+    );
+    let mut car_input = car_a.as_slice();
+    let mut out = vec![];
+    results.push(
+        futures::io::copy(CarFileReader::new(&mut car_input, root, &options), &mut out)
+            .await
+            .map(|_| ())
+            .map_err(|err| *err.into_inner().unwrap().downcast().unwrap()),
+    );
+
+    for res in results {
+        match res {
+            Err(ReadSingleFileError::RootCidNotFound {
+                root,
+                blocks_seen,
+                header_roots,
+            }) => assert_eq!(
+                (root, blocks_seen, header_roots.to_vec()),
+                (root_b, blocks_a, vec![root_a])
+            ),
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
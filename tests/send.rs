@@ -0,0 +1,148 @@
+//! The futures of the readers must be `Send` to be spawned on a multi-threaded executor
+
+mod common;
+
+use futures::{io::Cursor, stream};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    car_info, extract_many, extract_subgraph_car, file_layout, file_size, inspect_root, load_dag,
+    ls, plan_extraction, read_single_file_and_reexport, read_single_file_auto,
+    read_single_file_buffer, read_single_file_buffer_verified,
+    read_single_file_buffer_with_options, read_single_file_from_blocks, read_single_file_indexed,
+    read_single_file_multi, read_single_file_prefix, read_single_file_seek,
+    read_single_file_seek_split, read_single_file_seek_verified, read_single_file_seek_with_handle,
+    read_single_file_seek_with_options, visit_file_leaves, CarFileReader, CarIndex, DagView,
+    FileOffsetIndex, ReadSingleFileOptions,
+};
+use std::path::Path;
+
+fn assert_send<T: Send>(_: &T) {}
+
+#[test]
+fn reader_futures_are_send() {
+    let options = ReadSingleFileOptions::default();
+    let mut input = async_std::io::empty();
+    let mut out = Cursor::new(Vec::new());
+    let mut read_back = Cursor::new(Vec::new());
+    let mut file = Cursor::new(Vec::new());
+    let index = CarIndex::default();
+
+    assert_send(&read_single_file_buffer(&mut input, &mut out, None, None));
+    assert_send(&read_single_file_buffer_with_options(
+        &mut input, &mut out, None, &options,
+    ));
+    assert_send(&read_single_file_buffer_verified(
+        &mut input, &mut out, None, &options,
+    ));
+    assert_send(&read_single_file_from_blocks(
+        &[],
+        &mut out,
+        &Cid::default(),
+        &options,
+    ));
+    assert_send(&read_single_file_seek(&mut input, &mut out, None, None));
+    assert_send(&read_single_file_seek_with_options(
+        &mut input, &mut out, None, &options,
+    ));
+    assert_send(&options.read_seek(&mut input, &mut out, None));
+    assert_send(&options.read_buffered(&mut input, &mut out, None));
+    assert_send(&read_single_file_auto(
+        &mut input,
+        &mut out,
+        None,
+        1 << 20,
+        &options,
+    ));
+    assert_send(&read_single_file_seek_verified(
+        &mut input, &mut out, None, &options,
+    ));
+    let (read, handle) = read_single_file_seek_with_handle(&mut input, &mut out, None, &options);
+    assert_send(&read);
+    assert_send(&handle);
+    drop(read);
+    assert_send(&read_single_file_seek_split(
+        &mut input,
+        &mut out,
+        &mut read_back,
+        None,
+        &options,
+    ));
+    assert_send(&read_single_file_multi(
+        stream::iter([Cursor::new(vec![])]),
+        &mut out,
+        None,
+        &options,
+    ));
+    assert_send(&read_single_file_indexed(
+        &mut file,
+        &mut out,
+        None,
+        Some(&index),
+    ));
+    assert_send(&read_single_file_prefix(&mut input, &mut out, None, 10));
+    assert_send(&CarFileReader::new(&mut input, None, &options));
+    assert_send(&visit_file_leaves(
+        &mut input,
+        None,
+        &options,
+        |_, _| async { Ok::<_, std::io::Error>(()) },
+    ));
+
+    assert_send(&ls(&mut input));
+    assert_send(&car_info(&mut input, true));
+    assert_send(&inspect_root(&mut input, None));
+    assert_send(&file_size(&mut input, None));
+    assert_send(&file_layout(&mut input, None));
+    assert_send(&FileOffsetIndex::build(&mut input, None));
+    let offsets = FileOffsetIndex::default();
+    assert_send(&offsets.read_range(&mut file, &index, 0..1, &mut out));
+    assert_send(&plan_extraction(&mut input, None, Path::new("out")));
+    assert_send(&extract_subgraph_car(&mut input, &mut out, None, None));
+    assert_send(&read_single_file_and_reexport(
+        &mut input, &mut out, &mut file, None, &options,
+    ));
+    assert_send(&extract_many(&mut input, vec![(Cid::default(), &mut file)]));
+    #[cfg(feature = "async-std")]
+    assert_send(&rs_car_ipfs::single_file::read_all_files(
+        &mut input,
+        Path::new("out"),
+    ));
+    #[cfg(feature = "async-std")]
+    assert_send(&rs_car_ipfs::single_file::extract_directory(
+        &mut input,
+        None,
+        Path::new("out"),
+        &options,
+    ));
+    assert_send(&load_dag(&mut input, &options));
+}
+
+#[allow(dead_code)]
+fn dag_view_futures_are_send(dag: &DagView, out: &mut Cursor<Vec<u8>>) {
+    assert_send(&dag.write_file(None, out, &Default::default()));
+}
+
+#[async_std::test]
+async fn reader_spawned() {
+    let car = std::fs::read("tests/example.car").unwrap();
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let car = car.clone();
+            async_std::task::spawn(async move {
+                let mut out = Cursor::new(Vec::new());
+                read_single_file_seek(&mut car.as_slice(), &mut out, None, None)
+                    .await
+                    .unwrap();
+                out.into_inner()
+            })
+        })
+        .collect();
+
+    let mut expected = Cursor::new(Vec::new());
+    read_single_file_buffer(&mut car.as_slice(), &mut expected, None, None)
+        .await
+        .unwrap();
+    for handle in handles {
+        assert!(handle.await == expected.get_ref()[..]);
+    }
+}
@@ -0,0 +1,139 @@
+//! Outputs accepting fewer bytes than requested per write, and pending between writes, get the
+//! complete file, holes and zero filled blocks included
+
+mod common;
+
+use common::{
+    generate::{pseudo_random, CarSpec, Corruption, Order},
+    ShortWriter,
+};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_with_options, BlockErrorHook, CarIndex, ErrorAction, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+
+/// Read `car` with the buffer, seek and indexed readers into outputs writing at most
+/// `max_write` bytes at once
+async fn read_short(
+    car: &[u8],
+    max_write: usize,
+    options: &ReadSingleFileOptions,
+) -> Vec<Result<Vec<u8>, ReadSingleFileError>> {
+    let mut results = vec![];
+
+    let mut out = ShortWriter::new(max_write);
+    let res =
+        read_single_file_buffer_with_options(&mut Cursor::new(car), &mut out, None, options).await;
+    results.push(res.map(|_| out.inner.into_inner()));
+
+    let mut out = ShortWriter::new(max_write);
+    let res =
+        read_single_file_seek_with_options(&mut Cursor::new(car), &mut out, None, options).await;
+    results.push(res.map(|_| out.inner.into_inner()));
+
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = ShortWriter::new(max_write);
+    let res = read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        options,
+    )
+    .await;
+    results.push(res.map(|_| out.inner.into_inner()));
+
+    results
+}
+
+/// Data with runs of zeros written as holes, the last one ending the file
+fn sparse_data() -> Vec<u8> {
+    [
+        pseudo_random(300, 4),
+        vec![0; 500],
+        pseudo_random(250, 5),
+        vec![0; 350],
+    ]
+    .concat()
+}
+
+#[async_std::test]
+async fn short_writes_sparse() {
+    let data = sparse_data();
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    for max_write in [1, 7, 64] {
+        for preallocate in [false, true] {
+            let options = ReadSingleFileOptions {
+                preallocate,
+                ..Default::default()
+            };
+            for res in read_short(&car, max_write, &options).await {
+                assert!(res.unwrap() == data, "{} {}", max_write, preallocate);
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn short_writes_ahead() {
+    let data = sparse_data();
+    let car = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        order: Order::Bfs,
+        ..Default::default()
+    }
+    .build()
+    .car;
+    let options = ReadSingleFileOptions {
+        write_ahead: true,
+        ..Default::default()
+    };
+    for res in read_short(&car, 3, &options).await {
+        assert!(res.unwrap() == data);
+    }
+}
+
+#[async_std::test]
+async fn short_writes_skipped_block() {
+    let data = pseudo_random(1000, 6);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        corruption: Some(Corruption::FlipByte(3)),
+        ..Default::default()
+    }
+    .build();
+    let options = ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new(|_, _| ErrorAction::Skip)),
+        ..Default::default()
+    };
+    // `on_block_error` is not used by the indexed reader
+    let results = read_short(&generated.car, 5, &options).await;
+
+    // The corrupted leaf is zero filled
+    let mut expected = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(
+        &mut Cursor::new(&generated.car),
+        &mut expected,
+        None,
+        &options,
+    )
+    .await
+    .unwrap();
+    let expected = expected.into_inner();
+    assert_eq!(expected.len(), data.len());
+    assert!(expected != data);
+    for res in &results[..2] {
+        assert!(*res.as_ref().unwrap() == expected);
+    }
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::{car_v1, read_all, PbLink, PbNode, TYPE_DIRECTORY};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::{
+    single_file::{
+        read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+        read_single_file_seek_with_options, CarIndex, ReadSingleFileError, ReadSingleFileOptions,
+    },
+    UnixFsType,
+};
+
+const TYPE_HAMT_SHARD: u64 = 5;
+
+fn descend() -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        auto_descend_single_entry: true,
+        ..Default::default()
+    }
+}
+
+/// Directory node of `unixfs_type` with the named `links`
+fn directory(unixfs_type: u64, links: &[(&str, Cid)]) -> (Cid, Vec<u8>) {
+    PbNode {
+        links: links
+            .iter()
+            .map(|(name, cid)| PbLink {
+                name: Some(name.to_string()),
+                ..PbLink::new(*cid)
+            })
+            .collect(),
+        unixfs_type,
+        ..Default::default()
+    }
+    .block()
+}
+
+/// File `aaaabb` as the single entry `name` of a directory root of `unixfs_type`
+fn single_entry(unixfs_type: u64, name: &str) -> Vec<u8> {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (file_cid, file) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let (root_cid, root) = directory(unixfs_type, &[(name, file_cid)]);
+    car_v1(
+        &[root_cid],
+        &[(root_cid, root), (file_cid, file), (a_cid, a), (b_cid, b)],
+    )
+}
+
+#[async_std::test]
+async fn single_entry_root_descends() {
+    for car in [
+        single_entry(TYPE_DIRECTORY, "file.txt"),
+        single_entry(TYPE_HAMT_SHARD, "A3file.txt"),
+    ] {
+        for res in read_all(&car, &descend()).await {
+            assert_eq!(res.unwrap(), b"aaaabb");
+        }
+
+        let options = descend();
+        let mut out = Cursor::new(Vec::new());
+        let buffer =
+            read_single_file_buffer_with_options(&mut Cursor::new(&car), &mut out, None, &options)
+                .await
+                .unwrap();
+        let mut out = Cursor::new(Vec::new());
+        let seek =
+            read_single_file_seek_with_options(&mut Cursor::new(&car), &mut out, None, &options)
+                .await
+                .unwrap();
+        let mut car_input = Cursor::new(&car);
+        let index = CarIndex::build(&mut car_input).await.unwrap();
+        let mut out = Cursor::new(Vec::new());
+        let indexed = read_single_file_indexed_with_options(
+            &mut car_input,
+            &mut out,
+            None,
+            Some(&index),
+            &options,
+        )
+        .await
+        .unwrap();
+        for summary in [buffer, seek, indexed] {
+            assert_eq!(summary.descended_entry.as_deref(), Some("file.txt"));
+            assert_eq!(summary.bytes_written, 6);
+        }
+
+        // Opt-in
+        for res in read_all(&car, &Default::default()).await {
+            match res {
+                Err(ReadSingleFileError::RootCidIsNotFile) => {}
+                x => panic!("other result {:?}", x),
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn single_entry_root_rejected() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+
+    // Several entries
+    let (root_cid, root) = directory(TYPE_DIRECTORY, &[("a", a_cid), ("b", b_cid)]);
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (a_cid, a.clone()), (b_cid, b)],
+    );
+    for res in read_all(&car, &descend()).await {
+        match res {
+            Err(ReadSingleFileError::RootCidIsNotFile) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+
+    // A shard linking a nested shard
+    let (nested_cid, nested) = directory(TYPE_HAMT_SHARD, &[("F1a", a_cid)]);
+    let (root_cid, root) = directory(TYPE_HAMT_SHARD, &[("0C", nested_cid)]);
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (nested_cid, nested), (a_cid, a.clone())],
+    );
+    for res in read_all(&car, &descend()).await {
+        match res {
+            Err(ReadSingleFileError::RootCidIsNotFile) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+
+    // A nested directory
+    let (dir_cid, dir) = directory(TYPE_DIRECTORY, &[("a", a_cid)]);
+    let (root_cid, root) = directory(TYPE_DIRECTORY, &[("dir", dir_cid)]);
+    let car = car_v1(&[root_cid], &[(root_cid, root), (dir_cid, dir), (a_cid, a)]);
+    for res in read_all(&car, &descend()).await {
+        match res {
+            Err(ReadSingleFileError::UnexpectedNodeType { cid, found }) => {
+                assert_eq!((cid, found), (dir_cid, UnixFsType::Directory))
+            }
+            x => panic!("other result {:?}", x),
+        }
+    }
+}
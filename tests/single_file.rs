@@ -1,6 +1,17 @@
+mod common;
+
 use async_std::io::ReadExt;
+use common::{
+    generate::{pseudo_random, CarSpec, Corruption, Layout, Order},
+    read_all,
+};
 use futures::io::Cursor;
-use rs_car_ipfs::single_file::{read_single_file_buffer, read_single_file_seek};
+use rs_car::{CarReader, Cid};
+use rs_car_ipfs::single_file::{
+    file_layout, read_single_file_buffer, read_single_file_buffer_with_options,
+    read_single_file_prefix, read_single_file_seek, read_single_file_seek_with_options,
+    CarFileReader, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
 use std::env;
 use std::{fs, path::PathBuf};
 
@@ -96,6 +107,303 @@ async fn read_single_file_test_data() {
     }
 }
 
+#[async_std::test]
+async fn read_single_file_max_blocks() {
+    let car_filepath = "tests/data/seq_1000.txt.size-32.normal.car";
+    let (blocks, _) = rs_car::car_read_all(
+        &mut async_std::fs::File::open(car_filepath).await.unwrap(),
+        false,
+    )
+    .await
+    .unwrap();
+    let block_count = blocks.len();
+
+    for (limit, should_pass) in [(block_count, true), (block_count - 1, false)] {
+        let options = ReadSingleFileOptions {
+            max_blocks: Some(limit),
+            ..Default::default()
+        };
+
+        let mut car_input = async_std::fs::File::open(car_filepath).await.unwrap();
+        let mut out = Cursor::new(Vec::new());
+        let res =
+            read_single_file_buffer_with_options(&mut car_input, &mut out, None, &options).await;
+        assert_max_blocks_result(res, limit, should_pass);
+
+        let mut car_input = async_std::fs::File::open(car_filepath).await.unwrap();
+        let mut out = Cursor::new(Vec::new());
+        let res =
+            read_single_file_seek_with_options(&mut car_input, &mut out, None, &options).await;
+        assert_max_blocks_result(res, limit, should_pass);
+    }
+}
+
+fn assert_max_blocks_result(
+    res: Result<ReadSummary, ReadSingleFileError>,
+    limit: usize,
+    should_pass: bool,
+) {
+    match res {
+        Ok(_) if should_pass => {}
+        Err(ReadSingleFileError::MaxBlocksExceeded { limit: l, read }) if !should_pass => {
+            assert_eq!((l, read), (limit, limit + 1))
+        }
+        x => panic!("limit {} unexpected result {:?}", limit, x),
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_summary_sparse() {
+    let car_filepath = "tests/data/zero_10K.bin.size-512.normal.car";
+    let file_len = fs::metadata("tests/data/zero_10K.bin").unwrap().len();
+    let car_len = fs::metadata(car_filepath).unwrap().len();
+    let root_cid = Some(header_root(&fs::read(car_filepath).unwrap()).await);
+
+    let mut car_input = async_std::fs::File::open(car_filepath).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_buffer(&mut car_input, &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        summary,
+        ReadSummary {
+            root_cid,
+            bytes_written: file_len,
+            sparse_bytes: 0,
+            blocks_read: 2,
+            car_bytes_read: car_len,
+            ..Default::default()
+        }
+    );
+
+    // 20 leaves of 512 zero bytes, each written as a hole of 511 bytes + a single zero byte. The
+    // leaves share a CID, all but the first are holes left while copying the first one
+    let mut car_input = async_std::fs::File::open(car_filepath).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_seek(&mut car_input, &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        summary,
+        ReadSummary {
+            root_cid,
+            bytes_written: file_len,
+            sparse_bytes: 20 * 511,
+            holes_count: 20,
+            blocks_read: 2,
+            car_bytes_read: car_len,
+            ..Default::default()
+        }
+    );
+    assert!(summary.is_sparse());
+    assert_eq!(
+        out.into_inner(),
+        fs::read("tests/data/zero_10K.bin").unwrap()
+    );
+}
+
+async fn header_root(car: &[u8]) -> Cid {
+    let mut car_input = car;
+    let reader = CarReader::new(&mut car_input, true).await.unwrap();
+    reader.header.roots[0]
+}
+
+#[async_std::test]
+async fn read_single_file_summary_root_cid() {
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let root = header_root(&car).await;
+
+    // The single root of the header
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_buffer(&mut car.as_slice(), &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(summary.root_cid, Some(root));
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_seek(&mut car.as_slice(), &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(summary.root_cid, Some(root));
+
+    let mut car_input = car.as_slice();
+    let mut reader = CarFileReader::new(&mut car_input, None, &Default::default());
+    assert_eq!(reader.root_cid(), None);
+    reader.read_to_end(&mut vec![]).await.unwrap();
+    assert_eq!(reader.root_cid(), Some(root));
+
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_prefix(&mut car.as_slice(), &mut out, None, 10)
+        .await
+        .unwrap();
+    assert_eq!(summary.root_cid, Some(root));
+    // The header is not read for 0 bytes
+    let summary = read_single_file_prefix(&mut car.as_slice(), &mut out, None, 0)
+        .await
+        .unwrap();
+    assert_eq!(summary.root_cid, None);
+
+    // The requested root
+    let leaf = file_layout(&mut car.as_slice(), None).await.unwrap()[0].cid;
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_seek(&mut car.as_slice(), &mut out, Some(&leaf), None)
+        .await
+        .unwrap();
+    assert_eq!(summary.root_cid, Some(leaf));
+}
+
+#[async_std::test]
+async fn read_single_file_stops_once_complete() {
+    // Repeated content, leaves are linked several times and arrive before their parents
+    let data = pseudo_random(1000, 3).repeat(4);
+    for order in [
+        Order::Dfs,
+        Order::Bfs,
+        Order::ChildrenFirst,
+        Order::Random(2),
+    ] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 3,
+            order,
+            ..Default::default()
+        }
+        .build();
+        // Followed by a block failing hash validation, never read
+        let mut blocks = generated.blocks.clone();
+        blocks.push((generated.root, b"not the root".to_vec()));
+        let car = common::car_v1(&[generated.root], &blocks);
+
+        let mut out = Cursor::new(Vec::new());
+        let summary = read_single_file_buffer(&mut car.as_slice(), &mut out, None, None)
+            .await
+            .unwrap();
+        assert_eq!(out.into_inner(), data, "{:?}", order);
+        assert_eq!(
+            (summary.blocks_read, summary.car_bytes_read),
+            (generated.blocks.len(), generated.car.len() as u64),
+            "{:?}",
+            order
+        );
+
+        // The seek reader discards leaves arriving before their parent
+        if !matches!(order, Order::Dfs | Order::Bfs) {
+            continue;
+        }
+        let mut out = Cursor::new(Vec::new());
+        let summary = read_single_file_seek(&mut car.as_slice(), &mut out, None, None)
+            .await
+            .unwrap();
+        assert_eq!(out.into_inner(), data, "{:?}", order);
+        assert_eq!(
+            (summary.blocks_read, summary.car_bytes_read),
+            (generated.blocks.len(), generated.car.len() as u64),
+            "{:?}",
+            order
+        );
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_generated() {
+    let random = pseudo_random(10_000, 1);
+    // Chunks repeated within the file, de-duplicated in the CAR
+    let repeated = pseudo_random(1000, 2).repeat(8);
+
+    for data in [vec![], vec![7], random, repeated] {
+        for chunk_size in [10, 250] {
+            for fanout in [3, 174] {
+                for layout in [Layout::Balanced, Layout::Trickle { repeat: 4 }] {
+                    for raw_leaves in [false, true] {
+                        for order in [
+                            Order::Dfs,
+                            Order::Bfs,
+                            Order::ChildrenFirst,
+                            Order::Random(3),
+                        ] {
+                            let spec = CarSpec {
+                                data: data.clone(),
+                                chunk_size,
+                                fanout,
+                                layout,
+                                raw_leaves,
+                                order,
+                                corruption: None,
+                            };
+                            assert_generated_case(&spec).await;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The buffer and indexed readers accept any block order, the seek reader requires parents
+/// before their children and holds leaves arriving before their turn
+async fn assert_generated_case(spec: &CarSpec) {
+    let generated = spec.build();
+    let case = format!(
+        "{} bytes, chunk {}, fanout {}, {:?}, raw leaves {}, {:?}, {} blocks",
+        spec.data.len(),
+        spec.chunk_size,
+        spec.fanout,
+        spec.layout,
+        spec.raw_leaves,
+        spec.order,
+        generated.blocks.len()
+    );
+    let [buffer, seek, indexed]: [_; 3] = read_all(&generated.car, &Default::default())
+        .await
+        .try_into()
+        .unwrap();
+
+    assert_eq!(buffer.unwrap(), spec.data, "buffer {}", case);
+    assert_eq!(indexed.unwrap(), spec.data, "indexed {}", case);
+    // Leaves of nodes not yet expanded are discarded by the seek reader, which never happens
+    // with a breadth-first order
+    match (spec.order, seek) {
+        (Order::Dfs | Order::Bfs, seek) => {
+            assert_eq!(seek.expect(&case), spec.data, "seek {}", case)
+        }
+        (_, Ok(out)) => assert_eq!(out, spec.data, "seek {}", case),
+        (_, Err(_)) => assert!(generated.blocks.len() > 1, "seek {}", case),
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_generated_corrupted() {
+    let spec = CarSpec {
+        data: pseudo_random(5000, 4),
+        chunk_size: 100,
+        fanout: 7,
+        ..Default::default()
+    };
+    let block_count = spec.build().blocks.len();
+
+    for k in [0, 1, block_count / 2, block_count - 1] {
+        for corruption in [Corruption::FlipByte(k), Corruption::Drop(k)] {
+            let generated = CarSpec {
+                corruption: Some(corruption),
+                ..spec.clone()
+            }
+            .build();
+            for res in read_all(&generated.car, &Default::default()).await {
+                assert!(res.is_err(), "{:?} accepted", corruption);
+            }
+        }
+
+        let generated = CarSpec {
+            corruption: Some(Corruption::Duplicate(k)),
+            ..spec.clone()
+        }
+        .build();
+        for res in read_all(&generated.car, &Default::default()).await {
+            assert_eq!(res.unwrap(), spec.data, "block {} duplicated", k);
+        }
+    }
+}
+
 async fn read_file_to_end_hex(path: &PathBuf) -> String {
     let mut data = vec![];
     let mut file = async_std::fs::File::open(path).await.unwrap();
@@ -103,12 +411,14 @@ async fn read_file_to_end_hex(path: &PathBuf) -> String {
     hex::encode(data)
 }
 
+#[allow(clippy::ptr_arg)]
 fn path_starts_with(path: &PathBuf, starts_with_path: &PathBuf) -> bool {
     path.to_str()
         .unwrap()
         .starts_with(starts_with_path.to_str().unwrap())
 }
 
+#[allow(clippy::ptr_arg)]
 fn is_car_filepath(filepath: &PathBuf) -> bool {
     filepath.extension().map(|ext| ext.to_str().unwrap()) == Some("car")
 }
@@ -0,0 +1,191 @@
+//! Files of a single leaf, the root block is the whole file
+
+mod common;
+
+use common::{car_v1, cid_v1, read_all, PbNode, RAW, TYPE_DIRECTORY};
+use futures::{io::Cursor, AsyncReadExt};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    file_layout, file_size, read_single_file_buffer_with_options, read_single_file_prefix,
+    read_single_file_seek_with_options, CarFileReader, LeafExtent, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+
+/// The single leaf roots of `data`: a dag-pb leaf with inline data, the same without filesize,
+/// and a raw block
+fn leaf_roots(data: &[u8]) -> Vec<(Cid, Vec<u8>)> {
+    vec![
+        PbNode::file_leaf(data).block(),
+        PbNode {
+            filesize: None,
+            ..PbNode::file_leaf(data)
+        }
+        .block(),
+        (cid_v1(RAW, data), data.to_vec()),
+    ]
+}
+
+async fn read_file_reader(car: &[u8], root_cid: Option<&Cid>) -> Vec<u8> {
+    let mut car_input = car;
+    let mut reader = CarFileReader::new(&mut car_input, root_cid, &Default::default());
+    let mut file = vec![];
+    reader.read_to_end(&mut file).await.unwrap();
+    file
+}
+
+#[async_std::test]
+async fn single_leaf_file() {
+    let data = b"hello world";
+    let (other_cid, other) = PbNode::file_leaf(b"other").block();
+
+    for (cid, block) in leaf_roots(data) {
+        // Alone, and after an unrelated block
+        for car in [
+            car_v1(&[cid], &[(cid, block.clone())]),
+            car_v1(&[cid], &[(other_cid, other.clone()), (cid, block.clone())]),
+        ] {
+            for res in read_all(&car, &Default::default()).await {
+                assert_eq!(res.unwrap(), data);
+            }
+            assert_eq!(read_file_reader(&car, None).await, data);
+
+            let mut out = Cursor::new(Vec::new());
+            let summary = read_single_file_prefix(&mut car.as_slice(), &mut out, None, 5)
+                .await
+                .unwrap();
+            assert_eq!(
+                (summary.bytes_written, out.get_ref()),
+                (5, &data[..5].to_vec())
+            );
+
+            assert_eq!(
+                file_size(&mut car.as_slice(), None).await.unwrap(),
+                data.len() as u64
+            );
+            assert_eq!(
+                file_layout(&mut car.as_slice(), None).await.unwrap(),
+                vec![LeafExtent {
+                    cid,
+                    offset: 0,
+                    len: data.len() as u64
+                }]
+            );
+        }
+
+        // Blocks after the root are not read
+        let car = car_v1(&[cid], &[(cid, block.clone()), (other_cid, other.clone())]);
+        let mut out = Cursor::new(Vec::new());
+        let summary = read_single_file_seek_with_options(
+            &mut car.as_slice(),
+            &mut out,
+            None,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((summary.bytes_written, summary.blocks_read), (11, 1));
+        let mut out = Cursor::new(Vec::new());
+        let summary = read_single_file_buffer_with_options(
+            &mut car.as_slice(),
+            &mut out,
+            None,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!((summary.bytes_written, summary.blocks_read), (11, 1));
+
+        // Preallocated and checked against the expected size
+        let options = ReadSingleFileOptions {
+            preallocate: true,
+            expected_size: Some(data.len() as u64),
+            ..Default::default()
+        };
+        for res in read_all(&car, &options).await {
+            assert_eq!(res.unwrap(), data);
+        }
+        let options = ReadSingleFileOptions {
+            expected_size: Some(3),
+            ..Default::default()
+        };
+        for res in read_all(&car, &options).await {
+            match res {
+                Err(ReadSingleFileError::SizeMismatch { .. })
+                | Err(ReadSingleFileError::ExpectedSizeMismatch { .. }) => {}
+                x => panic!("other result {:?}", x),
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn single_leaf_empty_file() {
+    // As encoded by kubo, without Data
+    let kubo = PbNode {
+        data: None,
+        filesize: Some(0),
+        ..PbNode::file_leaf(b"")
+    }
+    .block();
+
+    for (cid, block) in [&leaf_roots(b"")[..], &[kubo]].concat() {
+        let car = car_v1(&[cid], &[(cid, block)]);
+        for res in read_all(&car, &Default::default()).await {
+            assert_eq!(res.unwrap(), b"");
+        }
+        assert_eq!(read_file_reader(&car, None).await, b"");
+        assert_eq!(file_size(&mut car.as_slice(), None).await.unwrap(), 0);
+    }
+}
+
+#[async_std::test]
+async fn single_leaf_root_within_dag() {
+    // The root is a leaf of another file in the CAR
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (file_cid, file) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let car = car_v1(&[file_cid], &[(file_cid, file), (a_cid, a), (b_cid, b)]);
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_with_options(
+        &mut car.as_slice(),
+        &mut out,
+        Some(&b_cid),
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(out.into_inner(), b"bb");
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_with_options(
+        &mut car.as_slice(),
+        &mut out,
+        Some(&b_cid),
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(out.into_inner(), b"bb");
+    assert_eq!(read_file_reader(&car, Some(&b_cid)).await, b"bb");
+}
+
+#[async_std::test]
+async fn single_leaf_not_file() {
+    // A directory without entries is a leaf too
+    let (cid, block) = PbNode {
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+    .block();
+    let car = car_v1(&[cid], &[(cid, block)]);
+    for res in read_all(&car, &Default::default()).await {
+        match res {
+            Err(ReadSingleFileError::RootCidIsNotFile) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    match file_size(&mut car.as_slice(), None).await {
+        Err(ReadSingleFileError::RootCidIsNotFile) => {}
+        x => panic!("other result {:?}", x),
+    }
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "sniff")]
+
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_indexed_with_options,
+    read_single_file_seek_with_options, sniff_content_type, CarIndex, ReadSingleFileOptions,
+};
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+/// Content types sniffed by the buffer, seek and indexed readers
+async fn content_types(car: &[u8], options: &ReadSingleFileOptions) -> Vec<Option<&'static str>> {
+    let mut out = Cursor::new(Vec::new());
+    let buffer =
+        read_single_file_buffer_with_options(&mut Cursor::new(car), &mut out, None, options)
+            .await
+            .unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let seek = read_single_file_seek_with_options(&mut Cursor::new(car), &mut out, None, options)
+        .await
+        .unwrap();
+    let mut car_input = Cursor::new(car);
+    let index = CarIndex::build(&mut car_input).await.unwrap();
+    let mut out = Cursor::new(Vec::new());
+    let indexed = read_single_file_indexed_with_options(
+        &mut car_input,
+        &mut out,
+        None,
+        Some(&index),
+        options,
+    )
+    .await
+    .unwrap();
+    [buffer, seek, indexed]
+        .into_iter()
+        .map(|summary| summary.content_type)
+        .collect()
+}
+
+#[async_std::test]
+async fn sniff_content_type_across_leaves() {
+    // Magic bytes split across the first leaves
+    let (a_cid, a) = PbNode::file_leaf(&PNG[..3]).block();
+    let (b_cid, b) = PbNode::file_leaf(&PNG[3..]).block();
+    let (root_cid, root) =
+        PbNode::file_branch(&[(a_cid, 3), (b_cid, PNG.len() as u64 - 3)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+
+    let options = ReadSingleFileOptions {
+        sniff_content_type: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        content_types(&car, &options).await,
+        vec![Some("image/png"); 3]
+    );
+
+    // Disabled by default
+    assert_eq!(
+        content_types(&car, &Default::default()).await,
+        vec![None; 3]
+    );
+}
+
+#[async_std::test]
+async fn sniff_content_type_unknown() {
+    let car = std::fs::read("tests/data/helloworld.txt.size-1.normal.car").unwrap();
+    let options = ReadSingleFileOptions {
+        sniff_content_type: true,
+        ..Default::default()
+    };
+    assert_eq!(content_types(&car, &options).await, vec![None; 3]);
+
+    assert_eq!(sniff_content_type(PNG), Some("image/png"));
+    assert_eq!(sniff_content_type(b""), None);
+}
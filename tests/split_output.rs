@@ -0,0 +1,144 @@
+mod common;
+
+use common::generate::{pseudo_random, CarSpec};
+use futures::{AsyncRead, AsyncSeek, AsyncWrite};
+use rs_car_ipfs::single_file::{read_single_file_seek_split, ReadSingleFileOptions};
+use std::{
+    io::{self, SeekFrom},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+/// Handle with its own position onto storage shared with other handles
+#[derive(Default)]
+struct SharedHandle {
+    storage: Arc<Mutex<Vec<u8>>>,
+    position: u64,
+    /// Positions of all seeks
+    seeks: Vec<u64>,
+}
+
+impl SharedHandle {
+    fn other(&self) -> Self {
+        Self {
+            storage: self.storage.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl AsyncSeek for SharedHandle {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        let len = self.storage.lock().unwrap().len() as i64;
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(delta) => (self.position as i64 + delta) as u64,
+            SeekFrom::End(delta) => (len + delta) as u64,
+        };
+        let position = self.position;
+        self.seeks.push(position);
+        Poll::Ready(Ok(position))
+    }
+}
+
+impl AsyncRead for SharedHandle {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let storage = self.storage.lock().unwrap();
+        let start = (self.position as usize).min(storage.len());
+        let n = buf.len().min(storage.len() - start);
+        buf[..n].copy_from_slice(&storage[start..start + n]);
+        drop(storage);
+        self.position += n as u64;
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// Only writes and seeks, it can not read back
+struct WriteOnly(SharedHandle);
+
+impl AsyncWrite for WriteOnly {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let start = self.0.position as usize;
+        let mut storage = self.0.storage.lock().unwrap();
+        if storage.len() < start + buf.len() {
+            storage.resize(start + buf.len(), 0);
+        }
+        storage[start..start + buf.len()].copy_from_slice(buf);
+        drop(storage);
+        self.0.position += buf.len() as u64;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for WriteOnly {
+    fn poll_seek(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        pos: SeekFrom,
+    ) -> Poll<io::Result<u64>> {
+        Pin::new(&mut self.0).poll_seek(cx, pos)
+    }
+}
+
+#[async_std::test]
+async fn read_seek_split_handles() {
+    // Repeated content, most leaves are copied from already written ones
+    let data = pseudo_random(1000, 1).repeat(8);
+    for (chunk_size, base_offset) in [(250, 0), (100, 0), (250, 7)] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size,
+            fanout: 3,
+            ..Default::default()
+        }
+        .build();
+
+        let mut out_write = WriteOnly(SharedHandle::default());
+        let mut out_read = out_write.0.other();
+        let options = ReadSingleFileOptions {
+            base_offset,
+            ..Default::default()
+        };
+        let summary = read_single_file_seek_split(
+            &mut generated.car.as_slice(),
+            &mut out_write,
+            &mut out_read,
+            None,
+            &options,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(summary.bytes_written, data.len() as u64);
+        let storage = out_write.0.storage.lock().unwrap().clone();
+        assert_eq!(&storage[base_offset as usize..], &data);
+        // Copies only moved the read handle, the write handle only seeked to the start and
+        // reported its position at the end
+        assert!(!out_read.seeks.is_empty());
+        assert_eq!(
+            out_write.0.seeks,
+            vec![base_offset, base_offset + data.len() as u64]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use bytes::Bytes;
+use futures::{io::Cursor, stream};
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_stream, read_single_file_seek_stream, ReadSingleFileOptions,
+};
+use std::fs;
+
+fn chunked_stream(
+    data: &[u8],
+    chunk_size: usize,
+) -> impl futures::Stream<Item = Result<Bytes, std::io::Error>> + Unpin {
+    let chunks = data
+        .chunks(chunk_size)
+        .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+        .collect::<Vec<_>>();
+    stream::iter(chunks)
+}
+
+#[async_std::test]
+async fn read_single_file_from_chunked_stream() {
+    let car = fs::read("tests/data/seq_1000.txt.size-32.trickle.car").unwrap();
+    let expected = fs::read("tests/data/seq_1000.txt").unwrap();
+    let options = ReadSingleFileOptions::default();
+
+    // Chunk sizes that split varints, CIDs and blocks at different points
+    for chunk_size in [1, 7, 100, 4096] {
+        let mut out = Cursor::new(Vec::new());
+        read_single_file_seek_stream(chunked_stream(&car, chunk_size), &mut out, None, &options)
+            .await
+            .unwrap();
+        assert_eq!(out.get_ref(), &expected, "seek chunk_size {}", chunk_size);
+
+        let mut out = Cursor::new(Vec::new());
+        read_single_file_buffer_stream(chunked_stream(&car, chunk_size), &mut out, None, &options)
+            .await
+            .unwrap();
+        assert_eq!(out.get_ref(), &expected, "buffer chunk_size {}", chunk_size);
+    }
+}
+
+#[async_std::test]
+async fn read_single_file_stream_error() {
+    let car = fs::read("tests/data/seq_1000.txt.size-32.trickle.car").unwrap();
+    let chunks = vec![
+        Ok(Bytes::copy_from_slice(&car[..200])),
+        Err(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "reset",
+        )),
+    ];
+
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_seek_stream(
+        stream::iter(chunks),
+        &mut out,
+        None,
+        &ReadSingleFileOptions::default(),
+    )
+    .await;
+    assert!(res.is_err(), "expected error, got {:?}", res);
+}
@@ -0,0 +1,343 @@
+mod common;
+
+use common::{car_v1, PbLink, PbNode, TYPE_DIRECTORY};
+use futures::io::Cursor;
+use rs_car::{CarReader, Cid};
+use rs_car_ipfs::single_file::{
+    extract_subgraph_car, ls, read_single_file_and_reexport, read_single_file_buffer,
+    read_single_file_seek, ReadSingleFileError, ReadSingleFileOptions, ReadSummary, SubgraphStats,
+};
+use std::fs;
+
+async fn extract(
+    car: &[u8],
+    root_cid: Option<&Cid>,
+    max_buffer: Option<usize>,
+) -> Result<(SubgraphStats, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let stats = extract_subgraph_car(&mut Cursor::new(car), &mut out, root_cid, max_buffer).await?;
+    Ok((stats, out.into_inner()))
+}
+
+/// Summary and output of the file `root_cid` of `car`, with the stats of its minimized CAR and
+/// the CAR
+async fn reexport(
+    car: &[u8],
+    root_cid: Option<&Cid>,
+    options: &ReadSingleFileOptions,
+) -> Result<(ReadSummary, Vec<u8>, SubgraphStats, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let mut car_output = Cursor::new(Vec::new());
+    let (summary, stats) = read_single_file_and_reexport(
+        &mut Cursor::new(car),
+        &mut out,
+        &mut car_output,
+        root_cid,
+        options,
+    )
+    .await?;
+    Ok((summary, out.into_inner(), stats, car_output.into_inner()))
+}
+
+/// Roots and block CIDs of `car` in order
+async fn car_blocks(car: &[u8]) -> (Vec<Cid>, Vec<Cid>) {
+    let roots = CarReader::new(&mut Cursor::new(car), true)
+        .await
+        .unwrap()
+        .header
+        .roots;
+    let blocks = ls(&mut Cursor::new(car))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(cid, _)| cid)
+        .collect();
+    (roots, blocks)
+}
+
+/// Directory with the files `aaaabb` and `cc`, sharing no block
+struct Tree {
+    car: Vec<u8>,
+    file_cid: Cid,
+    file_blocks: Vec<Cid>,
+    other_cid: Cid,
+}
+
+fn tree() -> Tree {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (file_cid, file) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let (other_cid, other) = PbNode::file_leaf(b"cc").block();
+    let (dir_cid, dir) = PbNode {
+        links: vec![
+            PbLink {
+                name: Some("file".into()),
+                ..PbLink::new(file_cid)
+            },
+            PbLink {
+                name: Some("other".into()),
+                ..PbLink::new(other_cid)
+            },
+        ],
+        unixfs_type: TYPE_DIRECTORY,
+        ..Default::default()
+    }
+    .block();
+    let car = car_v1(
+        &[dir_cid],
+        &[
+            (dir_cid, dir),
+            (file_cid, file),
+            (a_cid, a),
+            (b_cid, b),
+            (other_cid, other),
+        ],
+    );
+    Tree {
+        car,
+        file_cid,
+        file_blocks: vec![file_cid, a_cid, b_cid],
+        other_cid,
+    }
+}
+
+#[async_std::test]
+async fn subgraph_of_one_file() {
+    let tree = tree();
+    let (stats, sub) = extract(&tree.car, Some(&tree.file_cid), None)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        car_blocks(&sub).await,
+        (vec![tree.file_cid], tree.file_blocks.clone())
+    );
+    assert_eq!(
+        stats,
+        SubgraphStats {
+            blocks_written: 3,
+            bytes_written: sub.len() as u64,
+            // Stops after the last block of the file
+            blocks_read: 4,
+            blocks_skipped: 1,
+        }
+    );
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek(&mut Cursor::new(&sub), &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"aaaabb");
+
+    let (_, sub) = extract(&tree.car, Some(&tree.other_cid), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        car_blocks(&sub).await,
+        (vec![tree.other_cid], vec![tree.other_cid])
+    );
+}
+
+#[async_std::test]
+async fn subgraph_whole_car() {
+    // Re-exporting the root of a CAR of a single file copies all its blocks verbatim
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        if !path.to_str().unwrap().ends_with(".normal.car") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+        let (stats, sub) = extract(&car, None, None).await.unwrap();
+        assert_eq!(car_blocks(&sub).await, car_blocks(&car).await);
+        assert_eq!(stats.blocks_skipped, 0);
+        let mut expected = Cursor::new(Vec::new());
+        read_single_file_seek(&mut Cursor::new(&car), &mut expected, None, None)
+            .await
+            .unwrap();
+        let mut out = Cursor::new(Vec::new());
+        read_single_file_seek(&mut Cursor::new(&sub), &mut out, None, None)
+            .await
+            .unwrap();
+        assert!(
+            out.into_inner() == expected.into_inner(),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[async_std::test]
+async fn subgraph_blocks_before_links() {
+    // Leaves and an unrelated block before the root linking them, and a duplicated leaf
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (c_cid, c) = PbNode::file_leaf(b"c").block();
+    let (other_cid, other) = PbNode::file_leaf(b"other").block();
+    let (root_cid, root) =
+        PbNode::file_branch(&[(a_cid, 4), (b_cid, 2), (a_cid, 4), (c_cid, 1)]).block();
+    let car = car_v1(
+        &[other_cid],
+        &[
+            (c_cid, c.clone()),
+            (other_cid, other),
+            (a_cid, a.clone()),
+            (root_cid, root),
+            (a_cid, a),
+            (b_cid, b),
+        ],
+    );
+
+    let (stats, sub) = extract(&car, Some(&root_cid), None).await.unwrap();
+    // Buffered leaves follow the root in link order, then the rest in the order of the CAR
+    assert_eq!(
+        car_blocks(&sub).await,
+        (vec![root_cid], vec![root_cid, a_cid, c_cid, b_cid])
+    );
+    assert_eq!(
+        (
+            stats.blocks_written,
+            stats.blocks_read,
+            stats.blocks_skipped
+        ),
+        (4, 6, 2)
+    );
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer(&mut Cursor::new(&sub), &mut out, None, None)
+        .await
+        .unwrap();
+    assert_eq!(out.into_inner(), b"aaaabbaaaac");
+
+    // Bounded buffering
+    match extract(&car, Some(&root_cid), Some(8)).await {
+        Err(ReadSingleFileError::MaxBufferedData(8)) => {}
+        x => panic!("other result {:?}", x),
+    }
+    extract(&car, Some(&root_cid), Some(c.len() + 100))
+        .await
+        .unwrap();
+}
+
+#[async_std::test]
+async fn subgraph_incomplete() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, _) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a.clone())]);
+    match extract(&car, None, None).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(pending)) => assert_eq!(pending, [b_cid]),
+        x => panic!("other result {:?}", x),
+    }
+
+    let car = car_v1(&[root_cid], &[(a_cid, a)]);
+    match extract(&car, None, None).await {
+        Err(ReadSingleFileError::RootCidNotFound { root, .. }) => assert_eq!(root, root_cid),
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn reexport_one_file() {
+    let tree = tree();
+    let (summary, out, stats, sub) = reexport(&tree.car, Some(&tree.file_cid), &Default::default())
+        .await
+        .unwrap();
+    assert_eq!(out, b"aaaabb");
+    assert_eq!(summary.root_cid, Some(tree.file_cid));
+    // Counts of the minimized CAR
+    assert_eq!(summary.blocks_read, 3);
+    assert_eq!(summary.car_bytes_read, sub.len() as u64);
+
+    // The same CAR as copied alone
+    let (expected_stats, expected_sub) = extract(&tree.car, Some(&tree.file_cid), None)
+        .await
+        .unwrap();
+    assert_eq!((stats, &sub), (expected_stats, &expected_sub));
+}
+
+#[async_std::test]
+async fn reexport_fixtures() {
+    // The minimized CAR re-extracts to the same file
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.to_str().unwrap();
+        if !name.ends_with(".car") || name.contains(".size-1.") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+        let (summary, out, stats, sub) = reexport(&car, None, &Default::default()).await.unwrap();
+        assert_eq!(summary.bytes_written, out.len() as u64, "{}", name);
+        assert_eq!(stats.bytes_written, sub.len() as u64, "{}", name);
+        let mut again = Cursor::new(Vec::new());
+        read_single_file_seek(&mut Cursor::new(&sub), &mut again, None, None)
+            .await
+            .unwrap();
+        assert!(again.into_inner() == out, "{}", name);
+    }
+}
+
+#[async_std::test]
+async fn reexport_blocks_before_links() {
+    // Leaves before the root linking them and an unrelated block, discarded by the seek reader
+    // alone, are written to the reader after the root
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (other_cid, other) = PbNode::file_leaf(b"other").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2), (a_cid, 4)]).block();
+    let car = car_v1(
+        &[root_cid, other_cid],
+        &[(b_cid, b), (other_cid, other), (a_cid, a), (root_cid, root)],
+    );
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_seek(&mut Cursor::new(&car), &mut out, Some(&root_cid), None).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(_)) => {}
+        x => panic!("other result {:?}", x),
+    }
+
+    let (_, out, stats, sub) = reexport(&car, Some(&root_cid), &Default::default())
+        .await
+        .unwrap();
+    assert_eq!(out, b"aaaabbaaaa");
+    assert_eq!((stats.blocks_written, stats.blocks_skipped), (3, 1));
+    assert_eq!(
+        car_blocks(&sub).await,
+        (vec![root_cid], vec![root_cid, a_cid, b_cid])
+    );
+}
+
+#[async_std::test]
+async fn reexport_errors() {
+    let tree = tree();
+
+    // Failing the read of the file stops the copy
+    let options = ReadSingleFileOptions {
+        write_limit: Some(5),
+        ..Default::default()
+    };
+    match reexport(&tree.car, Some(&tree.file_cid), &options).await {
+        Err(ReadSingleFileError::DeclaredSizeExceedsLimit { declared, limit }) => {
+            assert_eq!((declared, limit), (6, 5))
+        }
+        x => panic!("other result {:?}", x.map(|(summary, ..)| summary)),
+    }
+
+    // The error of the copy, not of the reader of the truncated minimized CAR
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, _) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a.clone())]);
+    match reexport(&car, None, &Default::default()).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(pending)) => assert_eq!(pending, [b_cid]),
+        x => panic!("other result {:?}", x.map(|(summary, ..)| summary)),
+    }
+    let car = car_v1(&[root_cid], &[(a_cid, a)]);
+    match reexport(&car, None, &Default::default()).await {
+        Err(ReadSingleFileError::RootCidNotFound { root, .. }) => assert_eq!(root, root_cid),
+        x => panic!("other result {:?}", x.map(|(summary, ..)| summary)),
+    }
+
+    // Not a file
+    match reexport(&tree.car, None, &Default::default()).await {
+        Err(ReadSingleFileError::RootCidIsNotFile) => {}
+        x => panic!("other result {:?}", x.map(|(summary, ..)| summary)),
+    }
+}
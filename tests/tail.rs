@@ -0,0 +1,136 @@
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::{io::Cursor, AsyncRead, AsyncReadExt};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_seek_with_options, CarFileReader, ReadSingleFileError, ReadSingleFileOptions,
+    SleepHook, TailOptions,
+};
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+/// Reader of a file being appended to, a read of 0 bytes means no data is available yet
+#[derive(Clone, Default)]
+struct Growing {
+    data: Arc<Mutex<Vec<u8>>>,
+    position: usize,
+}
+
+impl AsyncRead for Growing {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let data = self.data.lock().unwrap();
+        let n = buf.len().min(data.len() - self.position);
+        buf[..n].copy_from_slice(&data[self.position..self.position + n]);
+        drop(data);
+        self.position += n;
+        Poll::Ready(Ok(n))
+    }
+}
+
+/// CAR of a file of two leaves, and how long its first part is without the second leaf
+fn two_leaves_car() -> (Vec<u8>, usize, Cid) {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2)]).block();
+    let first = car_v1(&[root_cid], &[(root_cid, root.clone()), (a_cid, a.clone())]);
+    let car = car_v1(&[root_cid], &[(root_cid, root), (a_cid, a), (b_cid, b)]);
+    (car, first.len(), b_cid)
+}
+
+/// Input holding the first `written` bytes of `car`, the rest is appended in two steps after
+/// `delay`, the second one splitting the last block section
+fn append_later(car: Vec<u8>, written: usize, delay: Duration) -> Growing {
+    let input = Growing::default();
+    input
+        .data
+        .lock()
+        .unwrap()
+        .extend_from_slice(&car[..written]);
+    let data = input.data.clone();
+    async_std::task::spawn(async move {
+        let middle = written + (car.len() - written) / 2;
+        for part in [&car[written..middle], &car[middle..]] {
+            async_std::task::sleep(delay).await;
+            data.lock().unwrap().extend_from_slice(part);
+        }
+    });
+    input
+}
+
+fn tail_options(timeout: Duration) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        tail: Some(TailOptions {
+            poll_interval: Duration::from_millis(5),
+            ..TailOptions::new(
+                timeout,
+                SleepHook::new(|duration| Box::pin(async_std::task::sleep(duration))),
+            )
+        }),
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn tail_seek_waits_for_appended_blocks() {
+    let (car, written, b_cid) = two_leaves_car();
+
+    // Without tail, the first EOF ends the CAR
+    let mut input = append_later(car.clone(), written, Duration::from_millis(50));
+    let mut out = Cursor::new(Vec::new());
+    match read_single_file_seek_with_options(&mut input, &mut out, None, &Default::default()).await
+    {
+        Err(ReadSingleFileError::PendingLinksAtEOF(cids)) => assert_eq!(cids, vec![b_cid]),
+        x => panic!("other result {:?}", x),
+    }
+
+    // Returns once the file is written, without waiting for the timeout
+    let timeout = Duration::from_secs(30);
+    let start = Instant::now();
+    let mut input = append_later(car.clone(), written, Duration::from_millis(50));
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_seek_with_options(&mut input, &mut out, None, &tail_options(timeout))
+            .await
+            .unwrap();
+    assert_eq!(summary.bytes_written, 6);
+    assert_eq!(out.into_inner(), b"aaaabb");
+    assert!(start.elapsed() < timeout);
+}
+
+#[async_std::test]
+async fn tail_timeout() {
+    let (car, written, b_cid) = two_leaves_car();
+
+    // Nothing is appended within the timeout
+    let mut input = append_later(car, written, Duration::from_secs(30));
+    let mut out = Cursor::new(Vec::new());
+    let options = tail_options(Duration::from_millis(50));
+    match read_single_file_seek_with_options(&mut input, &mut out, None, &options).await {
+        Err(ReadSingleFileError::PendingLinksAtEOF(cids)) => assert_eq!(cids, vec![b_cid]),
+        x => panic!("other result {:?}", x),
+    }
+}
+
+#[async_std::test]
+async fn tail_car_file_reader() {
+    let (car, written, _) = two_leaves_car();
+
+    let mut input = append_later(car, written, Duration::from_millis(50));
+    let options = tail_options(Duration::from_secs(30));
+    let mut out = vec![];
+    CarFileReader::new(&mut input, None, &options)
+        .read_to_end(&mut out)
+        .await
+        .unwrap();
+    assert_eq!(out, b"aaaabb");
+}
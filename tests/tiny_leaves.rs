@@ -0,0 +1,121 @@
+//! Leaves around the 32 bytes from which runs of zeros are written as holes
+
+mod common;
+
+use common::{car_v1, cid_v1, read_all, PbNode, RAW};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, ReadSingleFileError,
+    ReadSummary,
+};
+
+async fn read_seek(car: &[u8]) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let summary = read_single_file_seek_with_options(
+        &mut Cursor::new(car),
+        &mut out,
+        None,
+        &Default::default(),
+    )
+    .await?;
+    Ok((summary, out.into_inner()))
+}
+
+/// File of `leaf` between two leaves of data, and `leaf` again, as dag-pb and raw leaves
+fn files_with(leaf: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let (a_cid, a) = PbNode::file_leaf(b"a").block();
+    let leaves: [(Cid, Vec<u8>); 2] = [
+        PbNode::file_leaf(leaf).block(),
+        (cid_v1(RAW, leaf), leaf.to_vec()),
+    ];
+    leaves
+        .into_iter()
+        .map(|(leaf_cid, block)| {
+            let len = leaf.len() as u64;
+            let (root_cid, root) =
+                PbNode::file_branch(&[(a_cid, 1), (leaf_cid, len), (a_cid, 1), (leaf_cid, len)])
+                    .block();
+            let car = car_v1(
+                &[root_cid],
+                &[(root_cid, root), (a_cid, a.clone()), (leaf_cid, block)],
+            );
+            let expected = [b"a", leaf, b"a", leaf].concat();
+            (car, expected)
+        })
+        .collect()
+}
+
+#[async_std::test]
+async fn tiny_leaves_written_densely() {
+    for leaf in [&[][..], &[0], &[0; 31], &[1; 32]] {
+        for (car, expected) in files_with(leaf) {
+            for res in read_all(&car, &Default::default()).await {
+                assert_eq!(res.unwrap(), expected);
+            }
+            let (summary, out) = read_seek(&car).await.unwrap();
+            assert_eq!(out, expected);
+            assert_eq!(summary.bytes_written, expected.len() as u64);
+            assert_eq!((summary.sparse_bytes, summary.holes_count), (0, 0));
+        }
+    }
+}
+
+#[async_std::test]
+async fn zero_leaf_of_32_bytes_is_a_hole() {
+    for (car, expected) in files_with(&[0; 32]) {
+        for res in read_all(&car, &Default::default()).await {
+            assert_eq!(res.unwrap(), expected);
+        }
+        // All but the last byte, once written and once copied
+        let (summary, out) = read_seek(&car).await.unwrap();
+        assert_eq!(out, expected);
+        assert_eq!((summary.sparse_bytes, summary.holes_count), (2 * 31, 2));
+    }
+}
+
+#[async_std::test]
+async fn leaf_length_not_blocksize() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    for (leaf, declared) in [(&b""[..], 4), (b"bb", 0), (b"bb", 3), (&[0; 32][..], 33)] {
+        let (leaf_cid, block) = PbNode::file_leaf(leaf).block();
+        let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (leaf_cid, declared)]).block();
+        let car = car_v1(
+            &[root_cid],
+            &[(root_cid, root), (a_cid, a.clone()), (leaf_cid, block)],
+        );
+
+        // Failing before writing the leaf
+        let (res, out) = {
+            let mut out = Cursor::new(Vec::new());
+            let res = read_single_file_seek_with_options(
+                &mut car.as_slice(),
+                &mut out,
+                None,
+                &Default::default(),
+            )
+            .await;
+            (res, out.into_inner())
+        };
+        assert_eq!(out, b"aaaa");
+        let mut out = Cursor::new(Vec::new());
+        let buffer_res = read_single_file_buffer_with_options(
+            &mut car.as_slice(),
+            &mut out,
+            None,
+            &Default::default(),
+        )
+        .await;
+        assert!(out.into_inner().is_empty());
+        for res in [res, buffer_res] {
+            match res {
+                Err(ReadSingleFileError::BlocksizeMismatch {
+                    cid,
+                    declared: d,
+                    actual,
+                }) => assert_eq!((cid, d, actual), (leaf_cid, declared, leaf.len() as u64)),
+                x => panic!("other result {:?}", x),
+            }
+        }
+    }
+}
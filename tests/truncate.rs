@@ -0,0 +1,106 @@
+//! Outputs reused from a longer file, truncated before the file is written
+
+mod common;
+
+use common::{
+    generate::{pseudo_random, CarSpec},
+    Recorder,
+};
+use rs_car_ipfs::single_file::{read_single_file_seek_file, ReadSingleFileOptions};
+
+/// Data with runs of zeros written as holes, the last one ending the file
+fn sparse_data() -> Vec<u8> {
+    [
+        pseudo_random(300, 13),
+        vec![0; 500],
+        pseudo_random(250, 14),
+        vec![0; 350],
+    ]
+    .concat()
+}
+
+fn sparse_car(data: &[u8]) -> Vec<u8> {
+    CarSpec {
+        data: data.to_vec(),
+        chunk_size: 100,
+        ..Default::default()
+    }
+    .build()
+    .car
+}
+
+#[async_std::test]
+async fn truncate_reused_output() {
+    let data = sparse_data();
+    let car = sparse_car(&data);
+    let options = ReadSingleFileOptions {
+        truncate: true,
+        ..Default::default()
+    };
+
+    let mut out = Recorder::new(vec![0xff; 5000]);
+    let summary = read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert!(summary.holes_count > 0);
+    assert_eq!(out.set_lens[0], 0);
+    assert!(out.inner.into_inner() == data);
+
+    // By default the previous contents are left in the holes and past the end of the file
+    let mut out = Recorder::new(vec![0xff; 5000]);
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &Default::default())
+        .await
+        .unwrap();
+    assert!(out.set_lens.is_empty());
+    let out = out.inner.into_inner();
+    assert_eq!(out.len(), 5000);
+    assert!(out[..data.len()] != data);
+}
+
+#[async_std::test]
+async fn truncate_base_offset() {
+    let data = sparse_data();
+    let car = sparse_car(&data);
+    let options = ReadSingleFileOptions {
+        truncate: true,
+        base_offset: 10,
+        preallocate: true,
+        ..Default::default()
+    };
+
+    // Bytes before `base_offset` are kept
+    let mut out = Recorder::new(vec![0xff; 5000]);
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    assert_eq!(out.set_lens, vec![10, 10 + data.len() as u64]);
+    let out = out.inner.into_inner();
+    assert_eq!(out[..10], [0xff; 10]);
+    assert!(out[10..] == data);
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn truncate_file() {
+    let data = sparse_data();
+    let car = sparse_car(&data);
+    let path = std::env::temp_dir().join(format!("rs-car-ipfs-truncate-{}", std::process::id()));
+    std::fs::write(&path, vec![0xff; 5000]).unwrap();
+
+    let mut out = async_std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .await
+        .unwrap();
+    let options = ReadSingleFileOptions {
+        truncate: true,
+        ..Default::default()
+    };
+    read_single_file_seek_file(&mut car.as_slice(), &mut out, None, &options)
+        .await
+        .unwrap();
+    drop(out);
+    assert!(std::fs::read(&path).unwrap() == data);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,158 @@
+//! Unknown protobuf fields, e.g. UnixFS extensions of newer tools, are skipped as protobuf
+//! parsers do
+
+mod common;
+
+use common::{
+    car_v1, cid_v0, read_all, write_bytes_field, write_varint, write_varint_field, PbNode,
+};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    decode_unixfs_node, file_layout, file_size, LeafExtent, ReadSingleFileError,
+};
+
+const TYPE_FILE: u64 = 2;
+
+/// Unknown fields of every wire type, a group with a nested group included
+fn extensions() -> Vec<u8> {
+    let mut buf = vec![];
+    write_varint_field(&mut buf, 15, 300);
+    write_bytes_field(&mut buf, 100, b"future extension");
+    write_varint(&mut buf, 16 << 3 | 5);
+    buf.extend_from_slice(&[1, 2, 3, 4]);
+    write_varint(&mut buf, 17 << 3 | 1);
+    buf.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    write_varint(&mut buf, 18 << 3 | 3);
+    write_varint_field(&mut buf, 1, 1);
+    write_varint(&mut buf, 2 << 3 | 3);
+    write_bytes_field(&mut buf, 3, b"nested");
+    write_varint(&mut buf, 2 << 3 | 4);
+    write_varint(&mut buf, 18 << 3 | 4);
+    buf
+}
+
+/// dag-pb block of a File leaf with `extra` appended to its UnixFS Data payload
+fn leaf_with(data: &[u8], extra: &[u8]) -> (Cid, Vec<u8>) {
+    let mut unixfs = vec![];
+    write_varint_field(&mut unixfs, 1, TYPE_FILE);
+    write_bytes_field(&mut unixfs, 2, data);
+    write_varint_field(&mut unixfs, 3, data.len() as u64);
+    unixfs.extend_from_slice(extra);
+    let mut block = vec![];
+    write_bytes_field(&mut block, 1, &unixfs);
+    (cid_v0(&block), block)
+}
+
+/// dag-pb block of a File branch linking `children`, with packed `blocksizes` and unknown fields
+/// in the links, the Data payload and the node
+fn packed_branch(children: &[(Cid, u64)]) -> (Cid, Vec<u8>) {
+    let mut unixfs = vec![];
+    write_varint_field(&mut unixfs, 1, TYPE_FILE);
+    write_varint_field(&mut unixfs, 3, children.iter().map(|(_, size)| size).sum());
+    let mut packed = vec![];
+    for (_, size) in children {
+        write_varint(&mut packed, *size);
+    }
+    write_bytes_field(&mut unixfs, 4, &packed);
+    unixfs.extend_from_slice(&extensions());
+
+    let mut block = vec![];
+    for (cid, _) in children {
+        let mut link = vec![];
+        write_bytes_field(&mut link, 1, &cid.to_bytes());
+        write_bytes_field(&mut link, 7, b"link extension");
+        write_bytes_field(&mut block, 2, &link);
+    }
+    write_bytes_field(&mut block, 1, &unixfs);
+    write_varint_field(&mut block, 9, 1);
+    (cid_v0(&block), block)
+}
+
+#[async_std::test]
+async fn unknown_fields_skipped() {
+    let (a_cid, a) = leaf_with(b"aaaa", &extensions());
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (root_cid, root) = packed_branch(&[(a_cid, 4), (b_cid, 2)]);
+
+    let info = decode_unixfs_node(&a).unwrap();
+    assert_eq!((info.data_len, info.filesize), (4, Some(4)));
+    let info = decode_unixfs_node(&root).unwrap();
+    assert_eq!(info.blocksizes, [4, 2]);
+    assert_eq!(info.links.len(), 2);
+
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root.clone()), (a_cid, a), (b_cid, b)],
+    );
+    for res in read_all(&car, &Default::default()).await {
+        assert_eq!(res.unwrap(), b"aaaabb");
+    }
+    assert_eq!(file_size(&mut car.as_slice(), None).await.unwrap(), 6);
+
+    // Packed blocksizes give the extent of a missing leaf
+    let car = car_v1(
+        &[root_cid],
+        &[
+            (root_cid, root),
+            (b_cid, PbNode::file_leaf(b"bb").block().1),
+        ],
+    );
+    assert_eq!(
+        file_layout(&mut car.as_slice(), None).await.unwrap(),
+        [
+            LeafExtent {
+                cid: a_cid,
+                offset: 0,
+                len: 4
+            },
+            LeafExtent {
+                cid: b_cid,
+                offset: 4,
+                len: 2
+            }
+        ]
+    );
+}
+
+#[async_std::test]
+async fn malformed_unknown_fields() {
+    let mut truncated = vec![];
+    write_varint(&mut truncated, 100 << 3 | 2);
+    write_varint(&mut truncated, 64);
+    truncated.extend_from_slice(b"shorter than declared");
+
+    let mut unmatched_end = vec![];
+    write_varint(&mut unmatched_end, 18 << 3 | 4);
+
+    let mut unterminated = vec![];
+    write_varint(&mut unterminated, 18 << 3 | 3);
+    write_varint_field(&mut unterminated, 1, 1);
+
+    let mut mismatched = vec![];
+    write_varint(&mut mismatched, 18 << 3 | 3);
+    write_varint(&mut mismatched, 19 << 3 | 4);
+
+    let mut wire_type = vec![];
+    write_varint(&mut wire_type, 100 << 3 | 6);
+
+    for extra in [
+        truncated,
+        unmatched_end,
+        unterminated,
+        mismatched,
+        wire_type,
+    ] {
+        let (cid, block) = leaf_with(b"aaaa", &extra);
+        match decode_unixfs_node(&block) {
+            Err(ReadSingleFileError::InvalidUnixFs(_)) => {}
+            x => panic!("other result {:?}", x),
+        }
+        let car = car_v1(&[cid], &[(cid, block)]);
+        for res in read_all(&car, &Default::default()).await {
+            match res {
+                Err(ReadSingleFileError::InvalidUnixFs(_)) => {}
+                x => panic!("other result {:?}", x),
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::io::Cursor;
+use rs_car_ipfs::{
+    adder::ChunkerProfile,
+    single_file::{
+        read_single_file_buffer_with_options, MapLeafHook, ReadSingleFileError,
+        ReadSingleFileOptions,
+    },
+};
+use std::{borrow::Cow, fs};
+
+/// Reads `car` with and without `validate_before_write`, returning what was written to `out`
+async fn read_both(
+    car: &[u8],
+    options: ReadSingleFileOptions,
+) -> [(Result<u64, ReadSingleFileError>, Vec<u8>); 2] {
+    let mut results = vec![];
+    for validate_before_write in [false, true] {
+        let options = ReadSingleFileOptions {
+            validate_before_write,
+            ..options.clone()
+        };
+        let mut out = Cursor::new(Vec::new());
+        let res =
+            read_single_file_buffer_with_options(&mut Cursor::new(car), &mut out, None, &options)
+                .await
+                .map(|summary| summary.bytes_written);
+        results.push((res, out.into_inner()));
+    }
+    results.try_into().unwrap()
+}
+
+/// File `aaaabbbbcccc` of three leaves, the root not declaring its filesize
+fn undeclared_size() -> Vec<u8> {
+    let leaves: Vec<_> = [b"aaaa", b"bbbb", b"cccc"]
+        .iter()
+        .map(|data| PbNode::file_leaf(*data).block())
+        .collect();
+    let (root_cid, root) = PbNode {
+        filesize: None,
+        blocksizes: vec![],
+        ..PbNode::file_branch(&leaves.iter().map(|(cid, _)| (*cid, 4)).collect::<Vec<_>>())
+    }
+    .block();
+    car_v1(&[root_cid], &[&[(root_cid, root)], &leaves[..]].concat())
+}
+
+#[async_std::test]
+async fn validate_before_write_same_output() {
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let expected = fs::read("tests/data/rand_10K.bin").unwrap();
+    let options = ReadSingleFileOptions {
+        verify_canonical: Some(ChunkerProfile::KuboDefaultV0),
+        ..Default::default()
+    };
+    let car_canonical = fs::read("tests/data/rand_10K.bin.size-262144.normal.car").unwrap();
+    for (car, options) in [(&car, Default::default()), (&car_canonical, options)] {
+        for (res, out) in read_both(car, options).await {
+            assert_eq!(res.unwrap(), expected.len() as u64);
+            assert!(out == expected);
+        }
+    }
+}
+
+#[async_std::test]
+async fn validate_before_write_leaves_out_untouched() {
+    let car = undeclared_size();
+
+    // Crossing the write limit
+    let options = ReadSingleFileOptions {
+        write_limit: Some(6),
+        ..Default::default()
+    };
+    let [(partial, written), (res, untouched)] = read_both(&car, options).await;
+    for res in [partial, res] {
+        match res {
+            Err(ReadSingleFileError::WriteLimitExceeded {
+                limit: 6,
+                attempted: 8,
+                ..
+            }) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    assert_eq!(
+        (written.as_slice(), untouched.as_slice()),
+        (&b"aaaa"[..], &b""[..])
+    );
+
+    // Not the expected size
+    let options = ReadSingleFileOptions {
+        expected_size: Some(10),
+        ..Default::default()
+    };
+    let [(partial, written), (res, untouched)] = read_both(&car, options).await;
+    for res in [partial, res] {
+        match res {
+            Err(ReadSingleFileError::SizeMismatch { .. }) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    assert_eq!(
+        (written.as_slice(), untouched.as_slice()),
+        (&b"aaaabbbbcccc"[..], &b""[..])
+    );
+
+    // A hook failing on the last leaf
+    let options = ReadSingleFileOptions {
+        map_leaf: Some(MapLeafHook::new(|data| match data {
+            b"cccc" => Err("failed".into()),
+            data => Ok(Cow::Borrowed(data)),
+        })),
+        ..Default::default()
+    };
+    let [(partial, written), (res, untouched)] = read_both(&car, options).await;
+    for res in [partial, res] {
+        match res {
+            Err(ReadSingleFileError::MapLeaf { .. }) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    assert_eq!(
+        (written.as_slice(), untouched.as_slice()),
+        (&b"aaaabbbb"[..], &b""[..])
+    );
+
+    // Not built with the profile
+    let car = fs::read("tests/data/rand_10K.bin.size-512.normal.car").unwrap();
+    let options = ReadSingleFileOptions {
+        verify_canonical: Some(ChunkerProfile::KuboDefaultV0),
+        ..Default::default()
+    };
+    let [(partial, written), (res, untouched)] = read_both(&car, options).await;
+    for res in [partial, res] {
+        match res {
+            Err(ReadSingleFileError::NotCanonical { .. }) => {}
+            x => panic!("other result {:?}", x),
+        }
+    }
+    assert_eq!((written.len(), untouched.len()), (10 * 1024, 0));
+}
@@ -0,0 +1,148 @@
+//! Block hashes validated on worker threads with `validation_concurrency`, the file is still
+//! written in order and corrupted blocks still fail the read
+
+mod common;
+
+use common::generate::{pseudo_random, CarSpec, Corruption, GeneratedCar, Layout, Order};
+use futures::io::Cursor;
+use rs_car::CarDecodeError;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_from_blocks,
+    read_single_file_seek_with_options, BlockErrorHook, ErrorAction, ReadSingleFileError,
+    ReadSingleFileOptions,
+};
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+/// Output of the seek, buffer and from blocks readers
+async fn read_concurrent(
+    generated: &GeneratedCar,
+    options: &ReadSingleFileOptions,
+) -> Vec<Result<Vec<u8>, ReadSingleFileError>> {
+    let mut results = vec![];
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_seek_with_options(&mut generated.car.as_slice(), &mut out, None, options)
+            .await;
+    results.push(res.map(|_| out.into_inner()));
+
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_buffer_with_options(
+        &mut generated.car.as_slice(),
+        &mut out,
+        None,
+        options,
+    )
+    .await;
+    results.push(res.map(|_| out.into_inner()));
+
+    let mut out = Cursor::new(Vec::new());
+    let res =
+        read_single_file_from_blocks(&generated.blocks, &mut out, &generated.root, options).await;
+    results.push(res.map(|_| out.into_inner()));
+
+    results
+}
+
+fn concurrency(threads: usize) -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        validation_concurrency: NonZeroUsize::new(threads),
+        ..Default::default()
+    }
+}
+
+#[async_std::test]
+async fn validation_concurrency_output() {
+    // Repeated content, leaves copied from `out` by the seek reader
+    let data = pseudo_random(5000, 9).repeat(2);
+    for (layout, order) in [
+        (Layout::Balanced, Order::Dfs),
+        (Layout::Trickle { repeat: 2 }, Order::Bfs),
+        (Layout::Balanced, Order::ChildrenFirst),
+    ] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 4,
+            layout,
+            order,
+            ..Default::default()
+        }
+        .build();
+        let expected = read_concurrent(&generated, &Default::default()).await;
+        for threads in [1, 3, 8] {
+            let results = read_concurrent(&generated, &concurrency(threads)).await;
+            for (res, expected) in results.into_iter().zip(&expected) {
+                match (res, expected) {
+                    (Ok(out), Ok(expected)) => assert!(out == *expected && out == data),
+                    (Err(err), Err(expected)) => assert_eq!(err.code(), expected.code()),
+                    (res, expected) => panic!(
+                        "{:?} {:?} threads {}: {:?} instead of {:?}",
+                        layout,
+                        order,
+                        threads,
+                        res.map(|out| out.len()),
+                        expected.as_ref().map(|out| out.len())
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn validation_concurrency_corrupted() {
+    let data = pseudo_random(3000, 10);
+    for k in [0, 1, 7, 30] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 4,
+            corruption: Some(Corruption::FlipByte(k)),
+            ..Default::default()
+        }
+        .build();
+        for res in read_concurrent(&generated, &concurrency(4)).await {
+            match res {
+                Err(ReadSingleFileError::CarDecodeError(CarDecodeError::BlockDigestMismatch(
+                    _,
+                ))) => {}
+                x => panic!("block {}: other result {:?}", k, x.map(|out| out.len())),
+            }
+        }
+    }
+}
+
+#[async_std::test]
+async fn validation_concurrency_block_error_hook() {
+    // Blocks are validated inline to skip the failing one
+    let data = pseudo_random(3000, 11);
+    let generated = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        fanout: 4,
+        corruption: Some(Corruption::FlipByte(5)),
+        ..Default::default()
+    }
+    .build();
+    let skipped = Arc::new(Mutex::new(vec![]));
+    let options = ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new({
+            let skipped = skipped.clone();
+            move |cid, _| {
+                skipped.lock().unwrap().push(*cid);
+                ErrorAction::Skip
+            }
+        })),
+        ..concurrency(4)
+    };
+    for res in read_concurrent(&generated, &options).await {
+        assert_eq!(res.unwrap().len(), data.len());
+    }
+    let skipped = skipped.lock().unwrap();
+    assert_eq!(skipped.len(), 3);
+    assert!(skipped.iter().all(|cid| *cid == generated.blocks[5].0));
+}
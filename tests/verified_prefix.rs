@@ -0,0 +1,113 @@
+mod common;
+
+use common::{car_v1, cid_v0, PbNode};
+use futures::io::Cursor;
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_verified, read_single_file_seek_verified, BlockErrorHook, ErrorAction,
+    ReadSingleFileError, ReadSingleFileOptions, VerifiedPrefixError,
+};
+
+/// CAR of the file "aaaa" + "bbbb" + "cccc" with the blocks in `order` of root, a, b, c. The block
+/// of "bbbb" is replaced by `b` if some
+fn car(order: &[usize], b: Option<(Cid, Vec<u8>)>) -> Vec<u8> {
+    let a = PbNode::file_leaf(b"aaaa").block();
+    let b = b.unwrap_or_else(|| PbNode::file_leaf(b"bbbb").block());
+    let c = PbNode::file_leaf(b"cccc").block();
+    let root = PbNode::file_branch(&[(a.0, 4), (b.0, 4), (c.0, 4)]).block();
+    let blocks = [root, a, b, c];
+    car_v1(
+        &[blocks[0].0],
+        &order.iter().map(|i| blocks[*i].clone()).collect::<Vec<_>>(),
+    )
+}
+
+async fn read_seek(car: &[u8], options: &ReadSingleFileOptions) -> VerifiedPrefixError {
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_seek_verified(&mut &car[..], &mut out, None, options)
+        .await
+        .unwrap_err()
+}
+
+#[async_std::test]
+async fn verified_prefix_seek() {
+    // Leaf out of order, without a reorder window
+    let strict = ReadSingleFileOptions {
+        reorder_window: Some(0),
+        ..Default::default()
+    };
+    let err = read_seek(&car(&[0, 1, 3, 2], None), &strict).await;
+    assert!(matches!(err.error, ReadSingleFileError::DataNodesNotSorted));
+    assert_eq!(err.verified_prefix_bytes, 4);
+
+    // Block not matching its hash
+    let (b_cid, _) = PbNode::file_leaf(b"bbbb").block();
+    let corrupt = PbNode::file_leaf(b"bbbx").block().1;
+    let err = read_seek(
+        &car(&[0, 1, 2, 3], Some((b_cid, corrupt))),
+        &Default::default(),
+    )
+    .await;
+    assert!(matches!(err.error, ReadSingleFileError::CarDecodeError(_)));
+    assert_eq!(err.verified_prefix_bytes, 4);
+
+    // Malformed node
+    let garbage = vec![0xff; 8];
+    let bad = car(&[0, 1, 2, 3], Some((cid_v0(&garbage), garbage)));
+    let err = read_seek(&bad, &Default::default()).await;
+    assert!(matches!(err.error, ReadSingleFileError::InvalidUnixFs(_)));
+    assert_eq!(err.verified_prefix_bytes, 4);
+
+    // Incomplete DAG, all leaves before the missing one are verified
+    let err = read_seek(&car(&[0, 1, 2], None), &Default::default()).await;
+    assert!(matches!(
+        err.error,
+        ReadSingleFileError::PendingLinksAtEOF(_)
+    ));
+    assert_eq!(err.verified_prefix_bytes, 8);
+}
+
+#[async_std::test]
+async fn verified_prefix_seek_stops_at_skipped_block() {
+    let garbage = vec![0xff; 8];
+    let car = car(&[0, 1, 2], Some((cid_v0(&garbage), garbage)));
+    let options = ReadSingleFileOptions {
+        on_block_error: Some(BlockErrorHook::new(|_, _| ErrorAction::Skip)),
+        ..Default::default()
+    };
+
+    // "bbbb" is zero filled, and "cccc" is missing
+    let err = read_seek(&car, &options).await;
+    assert!(matches!(
+        err.error,
+        ReadSingleFileError::PendingLinksAtEOF(_)
+    ));
+    assert_eq!(err.verified_prefix_bytes, 4);
+}
+
+#[async_std::test]
+async fn verified_prefix_buffer() {
+    // Nothing is written before the whole CAR is read
+    let mut out = Cursor::new(Vec::new());
+    let err = read_single_file_buffer_verified(
+        &mut &car(&[0, 1, 2], None)[..],
+        &mut out,
+        None,
+        &Default::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err.error, ReadSingleFileError::MissingNode(_)));
+    assert_eq!(err.verified_prefix_bytes, 0);
+
+    let mut out = Cursor::new(Vec::new());
+    read_single_file_buffer_verified(
+        &mut &car(&[0, 1, 2, 3], None)[..],
+        &mut out,
+        None,
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(out.into_inner(), b"aaaabbbbcccc");
+}
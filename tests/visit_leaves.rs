@@ -0,0 +1,132 @@
+//! Bytes of a file passed to a visitor in file order instead of written
+
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec, Order},
+    PbNode,
+};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer, visit_file_leaves, ReadSingleFileError, ReadSingleFileOptions,
+};
+use std::{fmt, fs};
+
+#[derive(Debug)]
+struct Found(u64);
+
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "found at {}", self.0)
+    }
+}
+
+impl std::error::Error for Found {}
+
+/// Visited bytes of `car` with their offsets
+async fn visit(
+    car: &[u8],
+    options: &ReadSingleFileOptions,
+) -> Result<(Vec<u8>, Vec<u64>), ReadSingleFileError> {
+    let mut data = vec![];
+    let mut offsets = vec![];
+    let mut car_input = car;
+    let summary = visit_file_leaves(&mut car_input, None, options, |offset, bytes| {
+        offsets.push(offset);
+        data.extend_from_slice(bytes);
+        async { Ok::<_, Found>(()) }
+    })
+    .await?;
+    assert_eq!(summary.bytes_written, data.len() as u64);
+    assert!(summary.car_bytes_read > 0 && summary.car_bytes_read <= car.len() as u64);
+    Ok((data, offsets))
+}
+
+#[async_std::test]
+async fn visit_file_leaves_fixtures() {
+    for name in [
+        "seq_1000.txt.size-32.trickle.car",
+        "rand_10K.bin.size-512.normal.car",
+    ] {
+        let car = fs::read(format!("tests/data/{}", name)).unwrap();
+        let mut expected = Cursor::new(Vec::new());
+        read_single_file_buffer(&mut car.as_slice(), &mut expected, None, None)
+            .await
+            .unwrap();
+
+        let (data, offsets) = visit(&car, &Default::default()).await.unwrap();
+        assert!(data == expected.get_ref()[..], "{}", name);
+        assert_eq!(offsets[0], 0);
+        assert!(offsets.windows(2).all(|pair| pair[0] < pair[1]), "{}", name);
+    }
+}
+
+#[async_std::test]
+async fn visit_file_leaves_any_order() {
+    let data = pseudo_random(3000, 9).repeat(2);
+    for order in [Order::Bfs, Order::ChildrenFirst, Order::Random(6)] {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 4,
+            order,
+            ..Default::default()
+        }
+        .build();
+        let (visited, offsets) = visit(&generated.car, &Default::default()).await.unwrap();
+        assert!(visited == data, "{:?}", order);
+        assert_eq!(offsets, (0..60).map(|i| i * 100).collect::<Vec<_>>());
+
+        // Out of order data is buffered within `max_buffer`
+        let options = ReadSingleFileOptions {
+            max_buffer: Some(300),
+            ..Default::default()
+        };
+        match visit(&generated.car, &options).await {
+            Err(ReadSingleFileError::MaxBufferedData(300)) => {}
+            x => panic!(
+                "{:?}: other result {:?}",
+                order,
+                x.map(|(_, offsets)| offsets)
+            ),
+        }
+    }
+}
+
+#[async_std::test]
+async fn visit_file_leaves_stops_on_error() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+    let (c_cid, c) = PbNode::file_leaf(b"cccc").block();
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 4), (b_cid, 2), (c_cid, 4)]).block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (a_cid, a), (b_cid, b), (c_cid, c)],
+    );
+
+    let mut visited = vec![];
+    let mut car_input = car.as_slice();
+    let res = visit_file_leaves(
+        &mut car_input,
+        None,
+        &Default::default(),
+        |offset, bytes| {
+            visited.push(offset);
+            let res = match bytes {
+                b"bb" => Err(Found(offset)),
+                _ => Ok(()),
+            };
+            async move { res }
+        },
+    )
+    .await;
+    match res {
+        Err(ReadSingleFileError::VisitorError { offset, error }) => {
+            assert_eq!(offset, 4);
+            assert_eq!(error.downcast::<Found>().unwrap().0, 4);
+        }
+        x => panic!("other result {:?}", x),
+    }
+    assert_eq!(visited, vec![0, 4]);
+}
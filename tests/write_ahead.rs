@@ -0,0 +1,159 @@
+mod common;
+
+use common::{
+    car_v1,
+    generate::{pseudo_random, CarSpec, Layout, Order},
+    PbLink, PbNode, RAW, TYPE_FILE,
+};
+use futures::io::Cursor;
+use rs_car_ipfs::single_file::{
+    read_single_file_seek_with_options, ReadSingleFileError, ReadSingleFileOptions, ReadSummary,
+};
+use std::fs;
+
+fn write_ahead() -> ReadSingleFileOptions {
+    ReadSingleFileOptions {
+        write_ahead: true,
+        ..Default::default()
+    }
+}
+
+async fn read_seek(
+    car: &[u8],
+    options: &ReadSingleFileOptions,
+) -> Result<(ReadSummary, Vec<u8>), ReadSingleFileError> {
+    let mut out = Cursor::new(Vec::new());
+    let summary =
+        read_single_file_seek_with_options(&mut &car[..], &mut out, None, options).await?;
+    Ok((summary, out.into_inner()))
+}
+
+#[async_std::test]
+async fn write_ahead_same_output_on_fixtures() {
+    for entry in fs::read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "car") {
+            continue;
+        }
+        let car = fs::read(&path).unwrap();
+
+        let expected = read_seek(&car, &Default::default()).await;
+        let actual = read_seek(&car, &write_ahead()).await;
+        assert_eq!(
+            format!("{:?}", actual),
+            format!("{:?}", expected),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[async_std::test]
+async fn write_ahead_near_sorted() {
+    // Repeated content, some leaves are copied from already written ones. Starts with the magic
+    // bytes of a PNG, sniffed from a first leaf written ahead
+    let data = [&b"\x89PNG\r\n\x1a\n"[..], &pseudo_random(1992, 5)]
+        .concat()
+        .repeat(3);
+
+    // Level by level, leaves of a trickle DAG arrive before deeper leaves preceding them
+    let trickle = CarSpec {
+        data: data.clone(),
+        chunk_size: 100,
+        fanout: 3,
+        layout: Layout::Trickle { repeat: 2 },
+        order: Order::Bfs,
+        ..Default::default()
+    }
+    .build();
+
+    // Depth first with pairs of leaves swapped
+    let swapped = {
+        let generated = CarSpec {
+            data: data.clone(),
+            chunk_size: 100,
+            fanout: 4,
+            raw_leaves: true,
+            ..Default::default()
+        }
+        .build();
+        let mut blocks = generated.blocks.clone();
+        let mut i = 0;
+        while i + 1 < blocks.len() {
+            if [&blocks[i], &blocks[i + 1]]
+                .iter()
+                .all(|(cid, _)| cid.codec() == RAW)
+            {
+                blocks.swap(i, i + 1);
+                i += 5;
+            } else {
+                i += 1;
+            }
+        }
+        car_v1(&[generated.root], &blocks)
+    };
+
+    for car in [trickle.car, swapped] {
+        let strict = ReadSingleFileOptions {
+            reorder_window: Some(0),
+            ..Default::default()
+        };
+        match read_seek(&car, &strict).await {
+            Err(ReadSingleFileError::DataNodesNotSorted) => {}
+            x => panic!("other result {:?}", x.map(|(summary, _)| summary)),
+        }
+        // Written ahead without holding leaves in memory, even without a reorder window
+        let options = ReadSingleFileOptions {
+            reorder_window: Some(0),
+            ..write_ahead()
+        };
+        let (summary, out) = read_seek(&car, &options).await.unwrap();
+        assert_eq!(summary.bytes_written, data.len() as u64);
+        assert!(out == data);
+
+        #[cfg(feature = "sniff")]
+        {
+            let options = ReadSingleFileOptions {
+                sniff_content_type: true,
+                ..write_ahead()
+            };
+            let (summary, _) = read_seek(&car, &options).await.unwrap();
+            assert_eq!(summary.content_type, Some("image/png"));
+        }
+    }
+}
+
+#[async_std::test]
+async fn write_ahead_unknown_or_wrong_sizes() {
+    let (a_cid, a) = PbNode::file_leaf(b"aaaa").block();
+    let (b_cid, b) = PbNode::file_leaf(b"bb").block();
+
+    // Without blocksizes the offset of `b` is unknown
+    let (root_cid, root) = PbNode {
+        links: vec![PbLink::new(a_cid), PbLink::new(b_cid)],
+        unixfs_type: TYPE_FILE,
+        filesize: Some(6),
+        ..Default::default()
+    }
+    .block();
+    let car = car_v1(
+        &[root_cid],
+        &[(root_cid, root), (b_cid, b.clone()), (a_cid, a.clone())],
+    );
+    match read_seek(&car, &write_ahead()).await {
+        Err(ReadSingleFileError::DataNodesNotSorted) => {}
+        x => panic!("other result {:?}", x),
+    }
+
+    // Blocksizes not matching the leaves
+    let (root_cid, root) = PbNode::file_branch(&[(a_cid, 3), (b_cid, 2)]).block();
+    let car = car_v1(&[root_cid], &[(root_cid, root), (b_cid, b), (a_cid, a)]);
+    match read_seek(&car, &write_ahead()).await {
+        Err(ReadSingleFileError::BlocksizeMismatch {
+            cid,
+            declared,
+            actual,
+        }) => assert_eq!((cid, declared, actual), (a_cid, 3, 4)),
+        x => panic!("other result {:?}", x),
+    }
+}
@@ -0,0 +1,122 @@
+//! `write_limit` is checked before each write, `out` never holds bytes past it
+
+mod common;
+
+use common::{car_v1, PbNode};
+use futures::{io::Cursor, AsyncReadExt};
+use rs_car::Cid;
+use rs_car_ipfs::single_file::{
+    read_single_file_buffer_with_options, read_single_file_seek_with_options, CarFileReader,
+    ReadSingleFileError, ReadSingleFileOptions,
+};
+
+/// CAR of a root without `filesize` linking `leaves` in order, with each distinct leaf once
+fn undeclared_size(leaves: &[&[u8]]) -> Vec<u8> {
+    let children: Vec<_> = leaves
+        .iter()
+        .map(|data| (PbNode::file_leaf(data).block().0, data.len() as u64))
+        .collect();
+    let leaves: Vec<(Cid, Vec<u8>)> = leaves
+        .iter()
+        .map(|data| PbNode::file_leaf(data).block())
+        .collect();
+    let (root_cid, root) = PbNode {
+        filesize: None,
+        ..PbNode::file_branch(&children)
+    }
+    .block();
+    let mut blocks = vec![(root_cid, root)];
+    for leaf in leaves {
+        if !blocks.contains(&leaf) {
+            blocks.push(leaf);
+        }
+    }
+    car_v1(&[root_cid], &blocks)
+}
+
+/// Results of the seek and buffer readers and of [`CarFileReader`] with `write_limit`, with their
+/// output
+async fn read_limited(
+    car: &[u8],
+    write_limit: usize,
+) -> Vec<(Result<u64, ReadSingleFileError>, Vec<u8>)> {
+    let options = ReadSingleFileOptions {
+        write_limit: Some(write_limit),
+        ..Default::default()
+    };
+    let mut results = vec![];
+
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_seek_with_options(&mut &car[..], &mut out, None, &options).await;
+    results.push((res.map(|summary| summary.bytes_written), out.into_inner()));
+
+    let mut out = Cursor::new(Vec::new());
+    let res = read_single_file_buffer_with_options(&mut &car[..], &mut out, None, &options).await;
+    results.push((res.map(|summary| summary.bytes_written), out.into_inner()));
+
+    let mut car_input = car;
+    let mut reader = CarFileReader::new(&mut car_input, None, &options);
+    let mut out = vec![];
+    let res = reader.read_to_end(&mut out).await.map_err(|err| {
+        *err.into_inner()
+            .unwrap()
+            .downcast::<ReadSingleFileError>()
+            .unwrap()
+    });
+    results.push((res.map(|len| len as u64), out));
+
+    results
+}
+
+/// The write of the leaf `leaf` crosses `limit`, read from the CAR or copied from `out`
+async fn assert_exceeded(car: &[u8], limit: usize, attempted: usize, leaf: &[u8], written: &[u8]) {
+    let leaf_cid = PbNode::file_leaf(leaf).block().0;
+    for (res, out) in read_limited(car, limit).await {
+        match res {
+            Err(ReadSingleFileError::WriteLimitExceeded {
+                limit: actual_limit,
+                attempted: actual_attempted,
+                cid,
+            }) => assert_eq!(
+                (actual_limit, actual_attempted, cid),
+                (limit, attempted, Some(leaf_cid))
+            ),
+            x => panic!("limit {}: other result {:?}", limit, x),
+        }
+        assert!(out.len() <= limit, "limit {}: wrote {}", limit, out.len());
+        assert_eq!(out, written, "limit {}", limit);
+    }
+}
+
+#[async_std::test]
+async fn write_limit_boundaries() {
+    // The second `a` is copied from the first one by the seek reader
+    let car = undeclared_size(&[b"aaaa", b"bb", b"aaaa"]);
+
+    for (res, out) in read_limited(&car, 10).await {
+        assert_eq!(res.unwrap(), 10);
+        assert_eq!(out, b"aaaabbaaaa");
+    }
+    // On a leaf boundary, mid-leaf and mid-copy
+    assert_exceeded(&car, 4, 6, b"bb", b"aaaa").await;
+    assert_exceeded(&car, 5, 6, b"bb", b"aaaa").await;
+    assert_exceeded(&car, 6, 10, b"aaaa", b"aaaabb").await;
+    assert_exceeded(&car, 9, 10, b"aaaa", b"aaaabb").await;
+    assert_exceeded(&car, 0, 4, b"aaaa", b"").await;
+}
+
+#[async_std::test]
+async fn write_limit_sparse() {
+    // Runs of zeros are written as a hole and a last byte by the seek reader, still within the
+    // limit, also when copied
+    let zeros = [0; 64];
+    let car = undeclared_size(&[&zeros, &zeros, b"c"]);
+
+    assert_exceeded(&car, 63, 64, &zeros, b"").await;
+    assert_exceeded(&car, 100, 128, &zeros, &zeros).await;
+    assert_exceeded(&car, 128, 129, b"c", &[0; 128]).await;
+    for (res, out) in read_limited(&car, 129).await {
+        assert_eq!(res.unwrap(), 129);
+        assert_eq!(out, [&[0; 128][..], b"c"].concat());
+    }
+}